@@ -0,0 +1,50 @@
+//! Deriving a test case that changes a few variables from a large fixture,
+//! by cloning a `FakeEnvironment` versus branching a `CowEnvironment`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use env_wrapper::{CowEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+
+fn fixture(size: usize) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    for index in 0..size {
+        env.set_var(format!("FIXTURE_KEY_{index}"), format!("value_{index}"));
+    }
+    env
+}
+
+fn apply_case(env: &mut impl Environment) {
+    env.set_var("FIXTURE_KEY_0", "changed");
+    env.set_var("CASE_ONLY", "1");
+    env.remove_var("FIXTURE_KEY_1");
+}
+
+fn derive_case(c: &mut Criterion) {
+    let mut group = c.benchmark_group("derive_case");
+    for size in [10, 100, 1000] {
+        let fake_env = fixture(size);
+        group.bench_with_input(
+            BenchmarkId::new("clone_fake", size),
+            &fake_env,
+            |b, fake_env| {
+                b.iter(|| {
+                    let mut case = fake_env.clone();
+                    apply_case(&mut case);
+                    black_box(case.var_os("FIXTURE_KEY_2"))
+                })
+            },
+        );
+
+        let cow_env = CowEnvironment::new(fake_env);
+        group.bench_with_input(BenchmarkId::new("cow", size), &cow_env, |b, cow_env| {
+            b.iter(|| {
+                let mut case = cow_env.clone();
+                apply_case(&mut case);
+                black_box(case.var_os("FIXTURE_KEY_2"))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, derive_case);
+criterion_main!(benches);
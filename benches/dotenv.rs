@@ -0,0 +1,64 @@
+//! Loading a large generated dotenv file, as test fixtures do: into a
+//! `DotenvEnvironment`, into a `FakeEnvironment` with `load_dotenv`, and
+//! into one directly with `FakeEnvironment::from_dotenv_reader`.
+
+use std::{fmt::Write, fs, path::PathBuf};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use env_wrapper::{load_dotenv, DotenvEnvironment, FakeEnvironment, Override};
+
+const LINES: usize = 50_000;
+
+/// A file mixing every form of value, with a comment every tenth line.
+fn synthetic_file() -> String {
+    let mut file = String::new();
+    for index in 0..LINES {
+        let _ = match index % 10 {
+            0 => writeln!(file, "# section {index}"),
+            1..=4 => writeln!(file, "PLAIN_KEY_{index}=value_{index}_with_some_length"),
+            5 => writeln!(
+                file,
+                "export EXPORTED_{index}=value_{index} # trailing comment"
+            ),
+            6 | 7 => writeln!(file, "SINGLE_{index}='literal value {index} with $HOME'"),
+            8 => writeln!(
+                file,
+                "DOUBLE_{index}=\"quoted value {index} without escapes\""
+            ),
+            _ => writeln!(
+                file,
+                "ESCAPED_{index}=\"line\\nbreak {index} \\\"quoted\\\"\""
+            ),
+        };
+    }
+    file
+}
+
+fn load(c: &mut Criterion) {
+    let contents = synthetic_file();
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("env_wrapper_bench_{}.env", std::process::id()));
+    fs::write(&path, &contents).unwrap();
+
+    let mut group = c.benchmark_group("dotenv");
+    group.throughput(Throughput::Bytes(contents.len() as u64));
+    group.bench_function("open", |b| {
+        b.iter(|| DotenvEnvironment::open(black_box(&path)).unwrap())
+    });
+    group.bench_function("load_dotenv", |b| {
+        b.iter(|| {
+            let mut env = FakeEnvironment::new();
+            load_dotenv(black_box(&path), &mut env, Override::Yes).unwrap();
+            env
+        })
+    });
+    group.bench_function("from_dotenv_reader", |b| {
+        b.iter(|| FakeEnvironment::from_dotenv_reader(black_box(contents.as_bytes())).unwrap())
+    });
+    group.finish();
+
+    let _ = fs::remove_file(&path);
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Reading, listing, and cloning environments of several sizes, to catch
+//! regressions in the common operations. `RealEnvironment` is only measured
+//! for lookups, since the size of the process environment is not controlled.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use env_wrapper::{
+    EnumerableEnvironment, Environment, FakeEnvironment, ReadEnvironment, RealEnvironment,
+};
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+fn fixture(size: usize) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    for index in 0..size {
+        env.set_var(format!("FIXTURE_KEY_{index}"), format!("value_{index}"));
+    }
+    env
+}
+
+fn fake_lookups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fake");
+    for size in SIZES {
+        let env = fixture(size);
+        let key = format!("FIXTURE_KEY_{}", size / 2);
+        group.bench_with_input(BenchmarkId::new("var", size), &env, |b, env| {
+            b.iter(|| env.var(black_box(&key)))
+        });
+        group.bench_with_input(BenchmarkId::new("var_os", size), &env, |b, env| {
+            b.iter(|| env.var_os(black_box(&key)))
+        });
+        group.bench_with_input(BenchmarkId::new("var_miss", size), &env, |b, env| {
+            b.iter(|| env.var(black_box("MISSING_KEY")))
+        });
+        group.bench_with_input(BenchmarkId::new("set_var", size), &env, |b, env| {
+            let mut env = env.clone();
+            b.iter(|| env.set_var(black_box(&key), black_box("changed")))
+        });
+        group.bench_with_input(BenchmarkId::new("vars_os", size), &env, |b, env| {
+            b.iter(|| env.vars_os())
+        });
+        group.bench_with_input(BenchmarkId::new("clone", size), &env, |b, env| {
+            b.iter(|| env.clone())
+        });
+    }
+    group.finish();
+}
+
+fn real_lookups(c: &mut Criterion) {
+    let key = "ENV_WRAPPER_BENCH_KEY";
+    RealEnvironment.set_var(key, "value");
+    let mut group = c.benchmark_group("real");
+    group.bench_function("var", |b| b.iter(|| RealEnvironment.var(black_box(key))));
+    group.bench_function("var_os", |b| {
+        b.iter(|| RealEnvironment.var_os(black_box(key)))
+    });
+    group.bench_function("var_miss", |b| {
+        b.iter(|| RealEnvironment.var(black_box("ENV_WRAPPER_BENCH_MISSING")))
+    });
+    group.finish();
+    RealEnvironment.remove_var(key);
+}
+
+criterion_group!(benches, fake_lookups, real_lookups);
+criterion_main!(benches);
@@ -0,0 +1,112 @@
+//! Reading and writing variables of a `FakeEnvironment` with a large fixture,
+//! to compare its hashers. Run it once with the default SipHash and once with
+//! the `fxhash` feature, comparing against the first run:
+//!
+//! ```text
+//! cargo bench --bench fake_lookup -- --save-baseline siphash
+//! cargo bench --bench fake_lookup --features fxhash -- --baseline siphash
+//! ```
+//!
+//! The `read` group compares the ways of reading a variable, from `var`,
+//! which allocates a `String`, to `get_os`, which borrows the value. The
+//! `read_large` group compares them for a large value, where `var_os_arc`
+//! shares the stored buffer instead of copying it.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+
+fn keys(size: usize) -> Vec<String> {
+    (0..size)
+        .map(|index| format!("FIXTURE_KEY_{index}"))
+        .collect()
+}
+
+fn fixture(keys: &[String]) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    for key in keys {
+        env.set_var(key, "value");
+    }
+    env
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for size in [10, 100, 1000] {
+        let keys = keys(size);
+        let env = fixture(&keys);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &keys, |b, keys| {
+            b.iter(|| {
+                for key in keys {
+                    black_box(env.var_os(key));
+                }
+                black_box(env.var_os("MISSING_KEY"))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    let keys = keys(1000);
+    let env = fixture(&keys);
+    group.bench_function("var", |b| {
+        b.iter(|| {
+            for key in &keys {
+                let _ = black_box(env.var(key));
+            }
+        })
+    });
+    group.bench_function("var_os", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(env.var_os(key));
+            }
+        })
+    });
+    group.bench_function("get", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(env.get(key));
+            }
+        })
+    });
+    group.bench_function("get_os", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(env.get_os(key));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn read_large(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_large");
+    let mut env = FakeEnvironment::new();
+    env.set_var("CERTIFICATE", "x".repeat(64 * 1024));
+    group.bench_function("var_os", |b| {
+        b.iter(|| black_box(env.var_os(black_box("CERTIFICATE"))))
+    });
+    group.bench_function("var_os_arc", |b| {
+        b.iter(|| black_box(env.var_os_arc(black_box("CERTIFICATE"))))
+    });
+    group.bench_function("get_os", |b| {
+        b.iter(|| black_box(env.get_os(black_box("CERTIFICATE"))))
+    });
+    group.finish();
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for size in [10, 100, 1000] {
+        let keys = keys(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &keys, |b, keys| {
+            b.iter(|| black_box(fixture(keys)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lookup, read, read_large, insert);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! Reading many variables from `RealEnvironment` one at a time and with one
+//! pass over `vars_os`, to find the number of keys where the pass becomes
+//! cheaper. `read_all` should track the faster of the two.
+//!
+//! Each lookup scans the process environment, and so does the pass, so the
+//! crossover depends on the number of keys more than on the size of the
+//! environment. Both are measured with the environment padded to two sizes;
+//! the pass catches up at about 48 keys with 200 variables and 32 with 1000.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use env_wrapper::{EnumerableEnvironment, Environment, ReadEnvironment, RealEnvironment};
+
+const KEY_COUNTS: [usize; 7] = [1, 8, 16, 32, 48, 64, 128];
+
+fn per_key(keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+    keys.iter()
+        .filter_map(|&key| Some((key.to_os_string(), RealEnvironment.var_os(key)?)))
+        .collect()
+}
+
+fn one_pass(keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+    let wanted: HashSet<&OsStr> = keys.iter().copied().collect();
+    RealEnvironment
+        .vars_os()
+        .into_iter()
+        .filter(|(key, _)| wanted.contains(key.as_os_str()))
+        .collect()
+}
+
+fn read_all(c: &mut Criterion) {
+    let mut padded = 0;
+    for size in [200, 1000] {
+        while padded < KEY_COUNTS[KEY_COUNTS.len() - 1] || std::env::vars_os().count() < size {
+            RealEnvironment.set_var(format!("ENV_WRAPPER_BENCH_PAD_{padded}"), "padding");
+            padded += 1;
+        }
+        let mut group = c.benchmark_group(format!("read_all/{size}_vars"));
+        for count in KEY_COUNTS {
+            // Read the padding last set, which a scan reaches last.
+            let names: Vec<String> = (padded - count..padded)
+                .map(|index| format!("ENV_WRAPPER_BENCH_PAD_{index}"))
+                .collect();
+            let keys: Vec<&OsStr> = names.iter().map(OsStr::new).collect();
+            group.bench_with_input(BenchmarkId::new("per_key", count), &keys, |b, keys| {
+                b.iter(|| per_key(black_box(keys)))
+            });
+            group.bench_with_input(BenchmarkId::new("one_pass", count), &keys, |b, keys| {
+                b.iter(|| one_pass(black_box(keys)))
+            });
+            group.bench_with_input(BenchmarkId::new("read_all", count), &keys, |b, keys| {
+                b.iter(|| RealEnvironment.read_all(black_box(keys)))
+            });
+        }
+        group.finish();
+    }
+    for index in 0..padded {
+        RealEnvironment.remove_var(format!("ENV_WRAPPER_BENCH_PAD_{index}"));
+    }
+}
+
+criterion_group!(benches, read_all);
+criterion_main!(benches);
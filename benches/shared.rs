@@ -0,0 +1,75 @@
+//! Reading a shared fake environment from several threads at once, to
+//! compare the lock-free `SnapshotSwapEnvironment` with the `RwLock` in
+//! `SharedFakeEnvironment`. Requires the `arc-swap` feature:
+//!
+//! ```text
+//! cargo bench --bench shared --features arc-swap
+//! ```
+//!
+//! Each iteration is one read on every thread, so the throughput is in reads
+//! per second across all threads.
+
+use std::{
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use env_wrapper::{Environment, ReadEnvironment, SharedFakeEnvironment, SnapshotSwapEnvironment};
+
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+
+fn fixture<E: Environment + Default>() -> E {
+    let mut env = E::default();
+    for index in 0..100 {
+        env.set_var(format!("FIXTURE_KEY_{index}"), format!("value_{index}"));
+    }
+    env
+}
+
+/// Time `iters` reads on each of `threads` threads, started together.
+fn concurrent_reads<E>(env: &E, threads: usize, iters: u64) -> Duration
+where
+    E: ReadEnvironment + Sync,
+{
+    let barrier = Barrier::new(threads + 1);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                barrier.wait();
+                for _ in 0..iters {
+                    black_box(env.var_os(black_box("FIXTURE_KEY_50")));
+                }
+                barrier.wait();
+            });
+        }
+        barrier.wait();
+        let start = Instant::now();
+        barrier.wait();
+        start.elapsed()
+    })
+}
+
+fn reads(c: &mut Criterion) {
+    let rw_lock: SharedFakeEnvironment = fixture();
+    let snapshot_swap: SnapshotSwapEnvironment = fixture();
+    let mut group = c.benchmark_group("concurrent_reads");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new("rw_lock", threads),
+            &threads,
+            |b, &threads| b.iter_custom(|iters| concurrent_reads(&rw_lock, threads, iters)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("snapshot_swap", threads),
+            &threads,
+            |b, &threads| b.iter_custom(|iters| concurrent_reads(&snapshot_swap, threads, iters)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reads);
+criterion_main!(benches);
@@ -0,0 +1,210 @@
+//! Derive and attribute macros for [`env_wrapper`](https://docs.rs/env_wrapper).
+//! Use them through `env_wrapper`'s `derive` and `isolated-env` features
+//! rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Field, Fields,
+    ItemFn, LitStr, PathArguments, Token, Type,
+};
+
+/// Derive `env_wrapper::FromEnvironment` for a struct with named fields. See
+/// the trait's documentation for the supported `#[env(...)]` attributes.
+#[proc_macro_derive(FromEnvironment, attributes(env))]
+pub fn derive_from_environment(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Run a test that changes the real environment with exclusive use of it,
+/// restoring every variable when it ends, even if it panics. See
+/// `env_wrapper::IsolatedEnvGuard`.
+///
+/// Optionally takes the names of variables to remove before the test runs,
+/// as in `#[isolated_env("HOME", "XDG_CONFIG_HOME")]`.
+#[proc_macro_attribute]
+pub fn isolated_env(args: TokenStream, item: TokenStream) -> TokenStream {
+    let test = parse_macro_input!(item as ItemFn);
+    expand_isolated_env(args.into(), test)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_isolated_env(args: TokenStream2, test: ItemFn) -> syn::Result<TokenStream2> {
+    let clear = Punctuated::<LitStr, Token![,]>::parse_terminated
+        .parse2(args)
+        .map_err(|error| {
+            syn::Error::new(
+                error.span(),
+                "expected the names of variables to clear, as string literals",
+            )
+        })?
+        .into_iter();
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = test;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let _isolated_env = ::env_wrapper::IsolatedEnvGuard::acquire(&[#(#clear),*]);
+            #block
+        }
+    })
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(not_a_struct(input)),
+        },
+        _ => return Err(not_a_struct(input)),
+    };
+    let field_values = fields
+        .iter()
+        .map(field_value)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::env_wrapper::FromEnvironment for #name #ty_generics #where_clause {
+            fn from_environment_prefixed(
+                env: &impl ::env_wrapper::ReadEnvironment,
+                prefix: &str,
+            ) -> ::core::result::Result<Self, ::env_wrapper::EnvStructError> {
+                ::core::result::Result::Ok(Self {
+                    #(#field_values,)*
+                })
+            }
+        }
+    })
+}
+
+fn not_a_struct(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(
+        &input.ident,
+        "FromEnvironment can only be derived for structs with named fields",
+    )
+}
+
+/// The settings from a field's `#[env(...)]` attributes.
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<LitStr>,
+    default: Option<LitStr>,
+    /// `Some(None)` for a bare `prefix`.
+    prefix: Option<Option<LitStr>>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("env"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let name = meta
+                    .path
+                    .get_ident()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                let slot_is_taken = match name.as_str() {
+                    "rename" => attrs.rename.replace(meta.value()?.parse()?).is_some(),
+                    "default" => attrs.default.replace(meta.value()?.parse()?).is_some(),
+                    "prefix" => {
+                        let prefix = if meta.input.peek(syn::Token![=]) {
+                            Some(meta.value()?.parse()?)
+                        } else {
+                            None
+                        };
+                        attrs.prefix.replace(prefix).is_some()
+                    }
+                    _ => {
+                        return Err(meta.error(
+                            "unknown env attribute; expected `rename`, `default`, or `prefix`",
+                        ))
+                    }
+                };
+                if slot_is_taken {
+                    return Err(meta.error(format!("duplicate `{name}` attribute")));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// The `field: value` initializer for one field.
+fn field_value(field: &Field) -> syn::Result<TokenStream2> {
+    let attrs = FieldAttrs::parse(field)?;
+    let ident = field.ident.as_ref().expect("named fields have identifiers");
+    let ty = &field.ty;
+    let upper_name = ident
+        .to_string()
+        .trim_start_matches("r#")
+        .to_ascii_uppercase();
+
+    if let Some(prefix) = &attrs.prefix {
+        if attrs.rename.is_some() || attrs.default.is_some() {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "`prefix` cannot be combined with `rename` or `default`",
+            ));
+        }
+        let nested_prefix = match prefix {
+            Some(prefix) => prefix.value(),
+            None => format!("{upper_name}_"),
+        };
+        return Ok(quote! {
+            #ident: <#ty as ::env_wrapper::FromEnvironment>::from_environment_prefixed(
+                env,
+                &::std::format!("{}{}", prefix, #nested_prefix),
+            )?
+        });
+    }
+
+    let name = attrs.rename.as_ref().map_or(upper_name, LitStr::value);
+    let key = quote! { &::std::format!("{}{}", prefix, #name) };
+    if is_option(ty) {
+        if let Some(default) = &attrs.default {
+            return Err(syn::Error::new_spanned(
+                default,
+                "`default` cannot be used on an `Option` field",
+            ));
+        }
+        return Ok(quote! {
+            #ident: ::env_wrapper::__private::optional(env, #key)?
+        });
+    }
+    let default = match &attrs.default {
+        Some(default) => quote! { ::core::option::Option::Some(#default) },
+        None => quote! { ::core::option::Option::None },
+    };
+    Ok(quote! {
+        #ident: ::env_wrapper::__private::required(env, #key, #default)?
+    })
+}
+
+/// Whether `ty` is spelled as an `Option<T>`.
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && matches!(
+            path.path.segments.last(),
+            Some(segment) if segment.ident == "Option"
+                && matches!(segment.arguments, PathArguments::AngleBracketed(_))
+        )
+}
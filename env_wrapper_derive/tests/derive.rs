@@ -0,0 +1,162 @@
+use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+use env_wrapper::{EnvStructError, Environment, FakeEnvironment, FromEnvironment};
+
+const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+fn fake_env(vars: &[(&str, &str)]) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    for (key, value) in vars {
+        env.set_var(key, value);
+    }
+    env
+}
+
+#[derive(Debug, FromEnvironment, PartialEq)]
+struct Config {
+    #[env(rename = "DATABASE_URL")]
+    database: String,
+    #[env(default = "8080")]
+    port: u16,
+    workers: Option<u32>,
+    debug: bool,
+    #[env(prefix)]
+    tls: TlsConfig,
+    #[env(prefix = "CACHE_")]
+    cache: CacheConfig,
+}
+
+#[derive(Debug, FromEnvironment, PartialEq)]
+struct TlsConfig {
+    cert_path: String,
+    #[env(default = "false")]
+    required: bool,
+}
+
+#[derive(Debug, FromEnvironment, PartialEq)]
+struct CacheConfig {
+    #[env(rename = "TTL")]
+    ttl_secs: u64,
+}
+
+fn complete_config_env() -> FakeEnvironment {
+    fake_env(&[
+        ("DATABASE_URL", "postgres://localhost/app"),
+        ("WORKERS", "4"),
+        ("DEBUG", "true"),
+        ("TLS_CERT_PATH", "/etc/tls/cert.pem"),
+        ("CACHE_TTL", "60"),
+    ])
+}
+
+#[test]
+fn given_a_complete_environment_when_building_then_every_field_is_read() {
+    // Arrange
+    let env = complete_config_env();
+
+    // Act
+    let config = Config::from_environment(&env).unwrap();
+
+    // Assert
+    assert_eq!(
+        config,
+        Config {
+            database: "postgres://localhost/app".into(),
+            port: 8080,
+            workers: Some(4),
+            debug: true,
+            tls: TlsConfig {
+                cert_path: "/etc/tls/cert.pem".into(),
+                required: false,
+            },
+            cache: CacheConfig { ttl_secs: 60 },
+        }
+    );
+}
+
+#[test]
+fn given_set_variables_when_building_then_they_override_defaults() {
+    // Arrange
+    let mut env = complete_config_env();
+    env.set_var("PORT", "9090");
+    env.remove_var("WORKERS");
+
+    // Act
+    let config = Config::from_environment(&env).unwrap();
+
+    // Assert
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.workers, None);
+}
+
+#[test]
+fn given_a_prefix_when_building_then_every_variable_name_is_prefixed() {
+    // Arrange
+    let env = fake_env(&[
+        ("MYAPP_DATABASE_URL", "postgres://localhost/app"),
+        ("MYAPP_DEBUG", "false"),
+        ("MYAPP_TLS_CERT_PATH", "/etc/tls/cert.pem"),
+        ("MYAPP_CACHE_TTL", "60"),
+    ]);
+
+    // Act
+    let config = Config::from_environment_prefixed(&env, "MYAPP_").unwrap();
+
+    // Assert
+    assert_eq!(config.database, "postgres://localhost/app");
+    assert_eq!(config.tls.cert_path, "/etc/tls/cert.pem");
+    assert_eq!(config.cache.ttl_secs, 60);
+}
+
+#[test]
+fn given_a_missing_nested_variable_when_building_then_the_error_names_it() {
+    // Arrange
+    let mut env = complete_config_env();
+    env.remove_var("TLS_CERT_PATH");
+
+    // Act
+    let result = Config::from_environment(&env);
+
+    // Assert
+    let error = result.unwrap_err();
+    assert_eq!(error, EnvStructError::Missing("TLS_CERT_PATH".into()));
+    assert_eq!(
+        error.to_string(),
+        "environment variable \"TLS_CERT_PATH\" is not set"
+    );
+}
+
+#[test]
+fn given_an_unparsable_value_when_building_then_the_error_names_the_variable() {
+    // Arrange
+    let mut env = complete_config_env();
+    env.set_var("WORKERS", "many");
+
+    // Act
+    let result = Config::from_environment(&env);
+
+    // Assert
+    assert_eq!(
+        result.unwrap_err(),
+        EnvStructError::Invalid {
+            variable: "WORKERS".into(),
+            message: "cannot parse \"many\": invalid digit found in string".into(),
+        }
+    );
+}
+
+#[test]
+fn given_a_non_unicode_value_when_building_then_it_is_a_not_unicode_error() {
+    // Arrange
+    let mut env = complete_config_env();
+    env.set_var("DATABASE_URL", OsStr::from_bytes(&INVALID_UTF8));
+
+    // Act
+    let result = Config::from_environment(&env);
+
+    // Assert
+    assert_eq!(
+        result.unwrap_err(),
+        EnvStructError::NotUnicode("DATABASE_URL".into())
+    );
+}
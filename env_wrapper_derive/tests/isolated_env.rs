@@ -0,0 +1,65 @@
+use std::{panic, thread, time::Duration};
+
+use env_wrapper::{isolated_env, Environment, ReadEnvironment, RealEnvironment};
+
+/// The variable both conflicting tests set, to different values.
+const SHARED: &str = "ENV_WRAPPER_ISOLATED_SHARED";
+
+/// Set the shared variable, give the other test time to run, and check that
+/// the value is still this test's.
+fn set_and_keep(value: &str) {
+    assert!(RealEnvironment.var_os(SHARED).is_none(), "a test leaked");
+    RealEnvironment.set_var(SHARED, value);
+    for _ in 0..10 {
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(RealEnvironment.var(SHARED).unwrap(), value);
+    }
+}
+
+#[test]
+#[isolated_env]
+fn given_two_isolated_tests_when_both_set_a_variable_then_the_first_keeps_its_value() {
+    set_and_keep("first");
+}
+
+#[test]
+#[isolated_env]
+fn given_two_isolated_tests_when_both_set_a_variable_then_the_second_keeps_its_value() {
+    set_and_keep("second");
+}
+
+#[test]
+#[isolated_env("ENV_WRAPPER_ISOLATED_CLEARED", "HOME")]
+fn given_variables_to_clear_when_the_test_runs_then_they_are_unset() {
+    assert!(RealEnvironment
+        .var_os("ENV_WRAPPER_ISOLATED_CLEARED")
+        .is_none());
+    assert!(RealEnvironment.var_os("HOME").is_none());
+}
+
+#[isolated_env]
+fn set_and_panic() {
+    RealEnvironment.set_var("ENV_WRAPPER_ISOLATED_PANICKED", "1");
+    panic!("the test failed");
+}
+
+#[test]
+fn given_an_isolated_test_that_panics_when_it_unwinds_then_the_environment_is_restored() {
+    // Act
+    let result = panic::catch_unwind(set_and_panic);
+
+    // Assert
+    assert!(result.is_err());
+    assert!(RealEnvironment
+        .var_os("ENV_WRAPPER_ISOLATED_PANICKED")
+        .is_none());
+}
+
+#[test]
+#[isolated_env]
+fn given_an_isolated_test_returning_a_result_when_it_runs_then_it_can_use_the_question_mark(
+) -> Result<(), std::env::VarError> {
+    RealEnvironment.set_var(SHARED, "result");
+    assert_eq!(RealEnvironment.var(SHARED)?, "result");
+    Ok(())
+}
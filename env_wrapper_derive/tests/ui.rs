@@ -0,0 +1,5 @@
+#[test]
+fn attribute_misuse_is_a_compile_error() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,9 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+struct Config {
+    #[env(default = "4")]
+    workers: Option<u32>,
+}
+
+fn main() {}
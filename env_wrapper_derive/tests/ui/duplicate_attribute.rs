@@ -0,0 +1,9 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+struct Config {
+    #[env(rename = "PORT", rename = "HTTP_PORT")]
+    port: u16,
+}
+
+fn main() {}
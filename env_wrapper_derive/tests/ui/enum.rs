@@ -0,0 +1,9 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+enum Mode {
+    Fast,
+    Slow,
+}
+
+fn main() {}
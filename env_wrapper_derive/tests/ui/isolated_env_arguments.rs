@@ -0,0 +1,6 @@
+use env_wrapper::isolated_env;
+
+#[isolated_env(HOME)]
+fn reads_home() {}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+struct Config {
+    #[env(prefix, rename = "DB")]
+    database: DatabaseConfig,
+}
+
+#[derive(FromEnvironment)]
+struct DatabaseConfig {
+    url: String,
+}
+
+fn main() {}
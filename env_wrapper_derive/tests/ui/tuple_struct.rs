@@ -0,0 +1,6 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+struct Port(u16);
+
+fn main() {}
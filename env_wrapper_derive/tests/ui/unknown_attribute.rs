@@ -0,0 +1,9 @@
+use env_wrapper::FromEnvironment;
+
+#[derive(FromEnvironment)]
+struct Config {
+    #[env(name = "PORT")]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,314 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt,
+    sync::Arc,
+};
+
+use crate::{EnumerableEnvironment, EnvError, Environment, ReadEnvironment};
+
+type DeprecationCallback = Arc<dyn Fn(&OsStr, &OsStr) + Send + Sync>;
+
+/// A wrapper for renaming variables: reads of a variable's new, canonical
+/// name fall back to its old names (aliases) while the canonical one is
+/// unset.
+///
+/// When both are set, the canonical value wins. Keys are resolved to their
+/// canonical name before use, so reading an alias reads the canonical
+/// variable too, and writes only ever set the canonical variable. Removing a
+/// variable removes the canonical variable and its aliases, so it reads as
+/// not present afterwards. Listings include each canonical variable that is
+/// only set through an alias.
+///
+/// The first time a read falls back to each alias, the callback registered
+/// with [`on_deprecated`](AliasEnvironment::on_deprecated) is called with the
+/// alias and canonical names, e.g. to log a warning.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{AliasEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_DB", "postgres://localhost/db");
+///
+/// let env = AliasEnvironment::new(fake_env)
+///     .alias("MYAPP_DB", "MYAPP_DATABASE_URL")
+///     .on_deprecated(|alias, canonical| {
+///         eprintln!("warning: {alias:?} is deprecated, use {canonical:?}")
+///     });
+///
+/// assert_eq!(
+///     env.var("MYAPP_DATABASE_URL").unwrap(),
+///     "postgres://localhost/db"
+/// );
+/// ```
+#[derive(Clone)]
+pub struct AliasEnvironment<E> {
+    inner: E,
+    canonical_names: HashMap<OsString, OsString>,
+    aliases: HashMap<OsString, Vec<OsString>>,
+    on_deprecated: Option<DeprecationCallback>,
+    reported_aliases: RefCell<HashSet<OsString>>,
+}
+
+impl<E: ReadEnvironment> AliasEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        AliasEnvironment {
+            inner,
+            canonical_names: HashMap::new(),
+            aliases: HashMap::new(),
+            on_deprecated: None,
+            reported_aliases: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Register `alias` as an old name for `canonical`. Aliases of the same
+    /// canonical name are tried in the order they were registered.
+    pub fn alias(mut self, alias: impl AsRef<OsStr>, canonical: impl AsRef<OsStr>) -> Self {
+        let alias: OsString = alias.as_ref().into();
+        let canonical: OsString = canonical.as_ref().into();
+        self.aliases
+            .entry(canonical.clone())
+            .or_default()
+            .push(alias.clone());
+        self.canonical_names.insert(alias, canonical);
+        self
+    }
+
+    /// Call `callback` with the alias and canonical names the first time a
+    /// read falls back to each alias.
+    pub fn on_deprecated(
+        mut self,
+        callback: impl Fn(&OsStr, &OsStr) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_deprecated = Some(Arc::new(callback));
+        self
+    }
+
+    /// The canonical name for `key`, which is `key` itself unless it is an
+    /// alias.
+    pub fn canonical_name<'a>(&'a self, key: &'a OsStr) -> &'a OsStr {
+        self.canonical_names
+            .get(key)
+            .map_or(key, OsString::as_os_str)
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn aliases_of(&self, canonical: &OsStr) -> &[OsString] {
+        self.aliases.get(canonical).map_or(&[], Vec::as_slice)
+    }
+
+    fn report(&self, alias: &OsStr, canonical: &OsStr) {
+        if let Some(on_deprecated) = &self.on_deprecated {
+            if self.reported_aliases.borrow_mut().insert(alias.into()) {
+                on_deprecated(alias, canonical);
+            }
+        }
+    }
+}
+
+impl<E> fmt::Debug for AliasEnvironment<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AliasEnvironment")
+            .field("inner", &self.inner)
+            .field("aliases", &self.aliases)
+            .field("on_deprecated", &self.on_deprecated.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for AliasEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let canonical = self.canonical_name(key.as_ref());
+        if let Some(value) = self.inner.var_os(canonical) {
+            return Some(value);
+        }
+        self.aliases_of(canonical).iter().find_map(|alias| {
+            let value = self.inner.var_os(alias)?;
+            self.report(alias, canonical);
+            Some(value)
+        })
+    }
+}
+
+impl<E: Environment> Environment for AliasEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let canonical = self.canonical_name(key.as_ref()).to_owned();
+        self.inner.set_var(canonical, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let canonical = self.canonical_name(key.as_ref()).to_owned();
+        for alias in self.aliases_of(&canonical).to_vec() {
+            self.inner.remove_var(alias);
+        }
+        self.inner.remove_var(canonical)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let canonical = self.canonical_name(key.as_ref()).to_owned();
+        self.inner.try_set_var(canonical, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        let canonical = self.canonical_name(key.as_ref()).to_owned();
+        for alias in self.aliases_of(&canonical).to_vec() {
+            self.inner.try_remove_var(alias)?;
+        }
+        self.inner.try_remove_var(canonical)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for AliasEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut vars: HashMap<_, _> = self.inner.vars_os().into_iter().collect();
+        for (canonical, aliases) in &self.aliases {
+            if vars.contains_key(canonical) {
+                continue;
+            }
+            if let Some(value) = aliases.iter().find_map(|alias| vars.get(alias)) {
+                vars.insert(canonical.clone(), value.clone());
+            }
+        }
+        vars.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        sync::{Arc, Mutex},
+    };
+
+    use super::AliasEnvironment;
+    use crate::{
+        test_helpers::fake_env, EnumerableEnvironment, Environment, FakeEnvironment,
+        ReadEnvironment,
+    };
+
+    type Reported = Arc<Mutex<Vec<(OsString, OsString)>>>;
+
+    fn recording_aliases(inner: FakeEnvironment) -> (AliasEnvironment<FakeEnvironment>, Reported) {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reported);
+        let env = AliasEnvironment::new(inner)
+            .alias("MYAPP_DB", "MYAPP_DATABASE_URL")
+            .on_deprecated(move |alias, canonical| {
+                sink.lock().unwrap().push((alias.into(), canonical.into()))
+            });
+        (env, reported)
+    }
+
+    #[test]
+    fn given_only_the_alias_set_when_reading_the_canonical_key_then_the_alias_value_is_used() {
+        // Arrange
+        let (env, _) = recording_aliases(fake_env(&[("MYAPP_DB", "old")]));
+
+        // Act
+        let result = env.var("MYAPP_DATABASE_URL");
+
+        // Assert
+        assert_eq!(result.unwrap(), "old");
+    }
+
+    #[test]
+    fn given_both_names_set_when_reading_either_then_the_canonical_value_wins() {
+        // Arrange
+        let (env, reported) = recording_aliases(fake_env(&[
+            ("MYAPP_DB", "old"),
+            ("MYAPP_DATABASE_URL", "new"),
+        ]));
+
+        // Act
+        let canonical = env.var("MYAPP_DATABASE_URL");
+        let alias = env.var("MYAPP_DB");
+
+        // Assert
+        assert_eq!(canonical.unwrap(), "new");
+        assert_eq!(alias.unwrap(), "new");
+        assert!(reported.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_repeated_fallbacks_when_reading_then_the_callback_is_called_once_per_alias() {
+        // Arrange
+        let (env, reported) = recording_aliases(fake_env(&[("MYAPP_DB", "old")]));
+
+        // Act
+        for _ in 0..3 {
+            let _ = env.var("MYAPP_DATABASE_URL");
+            let _ = env.var_os("MYAPP_DB");
+        }
+
+        // Assert
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![("MYAPP_DB".into(), "MYAPP_DATABASE_URL".into())]
+        );
+    }
+
+    #[test]
+    fn when_writing_through_either_name_then_only_the_canonical_variable_is_set() {
+        // Arrange
+        let (mut env, _) = recording_aliases(fake_env(&[("MYAPP_DB", "old")]));
+
+        // Act
+        env.set_var("MYAPP_DB", "via alias");
+        let via_alias = env.var("MYAPP_DATABASE_URL");
+        env.set_var("MYAPP_DATABASE_URL", "via canonical");
+
+        // Assert
+        assert_eq!(via_alias.unwrap(), "via alias");
+        let inner = env.into_inner();
+        assert_eq!(inner.var("MYAPP_DATABASE_URL").unwrap(), "via canonical");
+        assert_eq!(inner.var("MYAPP_DB").unwrap(), "old");
+    }
+
+    #[test]
+    fn given_both_names_set_when_removing_then_neither_is_readable() {
+        // Arrange
+        let (mut env, _) = recording_aliases(fake_env(&[
+            ("MYAPP_DB", "old"),
+            ("MYAPP_DATABASE_URL", "new"),
+        ]));
+
+        // Act
+        env.remove_var("MYAPP_DATABASE_URL");
+
+        // Assert
+        assert!(env.var_os("MYAPP_DATABASE_URL").is_none());
+        assert!(env.var_os("MYAPP_DB").is_none());
+    }
+
+    #[test]
+    fn given_only_the_alias_set_when_listing_then_the_canonical_name_is_included() {
+        // Arrange
+        let (env, reported) = recording_aliases(fake_env(&[("MYAPP_DB", "old")]));
+
+        // Act
+        let mut vars = env.vars_os();
+        vars.sort();
+
+        // Assert
+        assert_eq!(
+            vars,
+            vec![
+                ("MYAPP_DATABASE_URL".into(), "old".into()),
+                ("MYAPP_DB".into(), "old".into()),
+            ]
+        );
+        assert!(reported.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    cell::RefCell,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    marker::PhantomData,
+};
+
+use crate::{DynEnvironment, EnvError, Environment, ReadEnvironment, RealEnvironment};
+
+thread_local! {
+    static OVERRIDES: RefCell<Vec<Box<dyn DynEnvironment>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The current thread's ambient environment, for code that cannot take an
+/// [`Environment`](Environment) parameter.
+///
+/// This is [`RealEnvironment`](RealEnvironment), unless overridden on this
+/// thread with [`with_ambient`](with_ambient). Prefer passing an environment
+/// explicitly where possible.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{ambient, with_ambient, Environment, FakeEnvironment, ReadEnvironment};
+/// fn legacy_config_location() -> String {
+///     ambient()
+///         .var("CONFIG_LOCATION")
+///         .unwrap_or_else(|_| "/etc/my_app/service.conf".into())
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("CONFIG_LOCATION", "/tmp/test.conf");
+///
+/// let location = with_ambient(fake_env, legacy_config_location);
+///
+/// assert_eq!(location, "/tmp/test.conf");
+/// ```
+pub fn ambient() -> AmbientEnvironment {
+    AmbientEnvironment {
+        _thread_bound: PhantomData,
+    }
+}
+
+/// Run `f` with `env` as the current thread's ambient environment, restoring
+/// the previous one afterwards, even if `f` panics.
+///
+/// Overrides nest: the innermost one is used. While `env` handles a call,
+/// e.g. in an [`ObservableEnvironment`](crate::ObservableEnvironment)
+/// callback, the ambient environment is the one it overrides. Other threads
+/// are unaffected.
+/// `env` is dropped when `f` returns; to inspect what `f` wrote, pass a
+/// [`SharedFakeEnvironment`](crate::SharedFakeEnvironment) and keep a clone.
+pub fn with_ambient<R>(env: impl Environment + 'static, f: impl FnOnce() -> R) -> R {
+    OVERRIDES.with(|overrides| overrides.borrow_mut().push(Box::new(env)));
+    let _restore = PopOverride;
+    f()
+}
+
+struct PopOverride;
+
+impl Drop for PopOverride {
+    fn drop(&mut self) {
+        // The thread-local may already be gone if the thread is exiting.
+        let _ = OVERRIDES.try_with(|overrides| overrides.borrow_mut().pop());
+    }
+}
+
+/// A handle to the current thread's ambient environment, returned by
+/// [`ambient`](ambient).
+///
+/// Every call goes to whichever environment is ambient at the time of the
+/// call, so a handle can be kept across overrides. It cannot be sent to
+/// other threads, which have their own ambient environments.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AmbientEnvironment {
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl AmbientEnvironment {
+    fn with_current<R>(&self, f: impl FnOnce(&dyn DynEnvironment) -> R) -> R {
+        with_current(|env| f(env))
+    }
+
+    fn with_current_mut<R>(&mut self, f: impl FnOnce(&mut dyn DynEnvironment) -> R) -> R {
+        with_current(f)
+    }
+}
+
+/// Run `f` on the innermost override, or on the real environment if there is
+/// none. The override is taken off the stack while `f` runs, so that calls it
+/// makes to the ambient environment, e.g. from an observer's callback, go to
+/// the environment it overrides instead of finding the stack borrowed.
+fn with_current<R>(f: impl FnOnce(&mut dyn DynEnvironment) -> R) -> R {
+    let Some(env) = OVERRIDES.with(|overrides| overrides.borrow_mut().pop()) else {
+        return f(&mut RealEnvironment);
+    };
+    let mut current = RestoreOverride(Some(env));
+    f(current
+        .0
+        .as_deref_mut()
+        .expect("the override is only restored on drop"))
+}
+
+/// Puts an override taken off the stack back on top, even if the call using
+/// it panics.
+struct RestoreOverride(Option<Box<dyn DynEnvironment>>);
+
+impl Drop for RestoreOverride {
+    fn drop(&mut self) {
+        if let Some(env) = self.0.take() {
+            // The thread-local may already be gone if the thread is exiting.
+            let _ = OVERRIDES.try_with(|overrides| overrides.borrow_mut().push(env));
+        }
+    }
+}
+
+impl ReadEnvironment for AmbientEnvironment {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.with_current(|env| env.dyn_var(key.as_ref()))
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.with_current(|env| env.dyn_var_os(key.as_ref()))
+    }
+}
+
+impl Environment for AmbientEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.with_current_mut(|env| env.dyn_set_var(key.as_ref(), value.as_ref()))
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.with_current_mut(|env| env.dyn_remove_var(key.as_ref()))
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.with_current_mut(|env| env.dyn_try_set_var(key.as_ref(), value.as_ref()))
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.with_current_mut(|env| env.dyn_try_remove_var(key.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::{ambient, with_ambient};
+    use crate::{
+        test_helpers::fake_env, test_helpers::random_upper, Environment, ObservableEnvironment,
+        ReadEnvironment, SharedFakeEnvironment,
+    };
+
+    #[test]
+    fn given_no_override_when_reading_the_ambient_environment_then_it_is_the_real_environment() {
+        // Arrange
+        let key = random_upper();
+        let value = random_upper();
+        std::env::set_var(&key, &value);
+
+        // Act
+        let result = ambient().var(&key);
+
+        // Assert
+        assert_eq!(result.unwrap(), value);
+    }
+
+    #[test]
+    fn given_nested_overrides_when_reading_then_the_inner_one_shadows_and_the_outer_is_restored() {
+        // Arrange
+        let outer = fake_env(&[("MODE", "outer"), ("OUTER_ONLY", "1")]);
+        let inner = fake_env(&[("MODE", "inner")]);
+
+        // Act
+        let (before_inner, during_inner, outer_only_during_inner, after_inner) =
+            with_ambient(outer, || {
+                let before_inner = ambient().var("MODE").unwrap();
+                let (during_inner, outer_only_during_inner) = with_ambient(inner, || {
+                    (
+                        ambient().var("MODE").unwrap(),
+                        ambient().var_os("OUTER_ONLY"),
+                    )
+                });
+                let after_inner = ambient().var("MODE").unwrap();
+                (
+                    before_inner,
+                    during_inner,
+                    outer_only_during_inner,
+                    after_inner,
+                )
+            });
+
+        // Assert
+        assert_eq!(before_inner, "outer");
+        assert_eq!(during_inner, "inner");
+        assert!(outer_only_during_inner.is_none());
+        assert_eq!(after_inner, "outer");
+        assert!(ambient().var_os("OUTER_ONLY").is_none());
+    }
+
+    #[test]
+    fn given_an_override_when_the_closure_panics_then_the_previous_environment_is_restored() {
+        // Arrange
+        let outer = fake_env(&[("MODE", "outer")]);
+
+        // Act
+        let mode_after_panic = with_ambient(outer, || {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                with_ambient(fake_env(&[("MODE", "inner")]), || panic!("test panic"))
+            }));
+            assert!(result.is_err());
+            ambient().var("MODE").unwrap()
+        });
+
+        // Assert
+        assert_eq!(mode_after_panic, "outer");
+    }
+
+    #[test]
+    fn given_an_override_on_one_thread_when_reading_on_another_then_it_is_unaffected() {
+        // Arrange
+        let key = random_upper();
+
+        // Act
+        let other_thread_value = with_ambient(fake_env(&[(&key, "fake")]), || {
+            let key = key.clone();
+            thread::spawn(move || ambient().var_os(key)).join().unwrap()
+        });
+
+        // Assert
+        assert!(other_thread_value.is_none());
+    }
+
+    #[test]
+    fn given_an_override_that_uses_the_ambient_environment_when_called_then_it_sees_the_outer_one()
+    {
+        // Arrange
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut observed = ObservableEnvironment::new(fake_env(&[]));
+        let log = Arc::clone(&seen);
+        observed.on_set(move |key, _old, _new| {
+            let log_level = ambient().var("LOG_LEVEL").unwrap();
+            log.lock()
+                .unwrap()
+                .push(format!("{key:?} with {log_level}"));
+        });
+
+        // Act
+        let mode = with_ambient(fake_env(&[("LOG_LEVEL", "debug")]), || {
+            with_ambient(observed, || {
+                ambient().set_var("MODE", "fast");
+                ambient().var("MODE").unwrap()
+            })
+        });
+
+        // Assert
+        assert_eq!(mode, "fast");
+        assert_eq!(*seen.lock().unwrap(), ["\"MODE\" with debug"]);
+    }
+
+    #[test]
+    fn given_an_override_when_writing_through_the_ambient_environment_then_the_override_changes() {
+        // Arrange
+        let shared_env = SharedFakeEnvironment::new();
+
+        // Act
+        with_ambient(shared_env.clone(), || ambient().set_var("STATE", "done"));
+
+        // Assert
+        assert_eq!(shared_env.var("STATE").unwrap(), "done");
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::ReadEnvironment;
+
+/// Assert that a variable is set to a value, as
+/// [`var_os`](crate::ReadEnvironment::var_os) reads it.
+///
+/// On failure, the panic message names the variable, its value, or that it
+/// is unset or not valid Unicode, and the expected value, after the optional
+/// message. Works with any [`ReadEnvironment`](crate::ReadEnvironment) or a
+/// reference to one.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{assert_var_eq, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MODE", "fast");
+///
+/// assert_var_eq!(fake_env, "MODE", "fast");
+/// assert_var_eq!(&fake_env, "MODE", "fast", "after {}", "setup");
+/// ```
+#[macro_export]
+macro_rules! assert_var_eq {
+    ($env:expr, $key:expr, $expected:expr $(,)?) => {
+        $crate::__assert_var_eq(&$env, $key, $expected, ::std::option::Option::None)
+    };
+    ($env:expr, $key:expr, $expected:expr, $($arg:tt)+) => {
+        $crate::__assert_var_eq(
+            &$env,
+            $key,
+            $expected,
+            ::std::option::Option::Some(::std::format_args!($($arg)+)),
+        )
+    };
+}
+
+/// Assert that a variable is not set.
+///
+/// On failure, the panic message names the variable and its value, after the
+/// optional message. Works with any
+/// [`ReadEnvironment`](crate::ReadEnvironment) or a reference to one.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{assert_var_unset, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("TMP", "/tmp");
+/// fake_env.remove_var("TMP");
+///
+/// assert_var_unset!(fake_env, "TMP");
+/// ```
+#[macro_export]
+macro_rules! assert_var_unset {
+    ($env:expr, $key:expr $(,)?) => {
+        $crate::__assert_var_unset(&$env, $key, ::std::option::Option::None)
+    };
+    ($env:expr, $key:expr, $($arg:tt)+) => {
+        $crate::__assert_var_unset(
+            &$env,
+            $key,
+            ::std::option::Option::Some(::std::format_args!($($arg)+)),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_var_eq(
+    env: &impl ReadEnvironment,
+    key: impl AsRef<OsStr>,
+    expected: impl AsRef<OsStr>,
+    message: Option<fmt::Arguments<'_>>,
+) {
+    let (key, expected) = (key.as_ref(), expected.as_ref());
+    let actual = env.var_os(key);
+    if actual.as_deref() != Some(expected) {
+        fail(
+            message,
+            format_args!(
+                "environment variable {key:?} {}, expected {expected:?}",
+                State(actual)
+            ),
+        );
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_var_unset(
+    env: &impl ReadEnvironment,
+    key: impl AsRef<OsStr>,
+    message: Option<fmt::Arguments<'_>>,
+) {
+    let key = key.as_ref();
+    let actual = env.var_os(key);
+    if actual.is_some() {
+        fail(
+            message,
+            format_args!(
+                "environment variable {key:?} {}, expected it to be unset",
+                State(actual)
+            ),
+        );
+    }
+}
+
+#[track_caller]
+fn fail(message: Option<fmt::Arguments<'_>>, failure: fmt::Arguments<'_>) -> ! {
+    match message {
+        Some(message) => panic!("assertion failed: {message}\n{failure}"),
+        None => panic!("assertion failed: {failure}"),
+    }
+}
+
+/// How a variable is set, as `is unset`, `is "value"`, or
+/// `is not valid Unicode: "fo\x80o"`.
+struct State(Option<OsString>);
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            None => write!(f, "is unset"),
+            Some(value) => match value.to_str() {
+                Some(value) => write!(f, "is {value:?}"),
+                None => write!(f, "is not valid Unicode: {value:?}"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, panic};
+
+    use crate::{test_helpers::fake_env, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn given_matching_variables_when_asserting_then_nothing_panics() {
+        // Arrange
+        let env = fake_env(&[("MODE", "fast")]);
+
+        // Act/Assert
+        assert_var_eq!(env, "MODE", "fast");
+        assert_var_eq!(&env, "MODE", "fast");
+        assert_var_eq!(
+            env,
+            String::from("MODE"),
+            OsStr::new("fast"),
+            "with a message"
+        );
+        assert_var_unset!(env, "MISSING");
+        assert_var_unset!(&env, "MISSING", "with a message");
+    }
+
+    #[test]
+    fn given_a_different_value_when_asserting_equality_then_the_message_shows_both() {
+        // Arrange
+        let env = fake_env(&[("MODE", "slow")]);
+
+        // Act
+        let message = panic_message(|| assert_var_eq!(env, "MODE", "fast"));
+
+        // Assert
+        assert_eq!(
+            message,
+            "assertion failed: environment variable \"MODE\" is \"slow\", expected \"fast\""
+        );
+    }
+
+    #[test]
+    fn given_an_unset_variable_when_asserting_equality_then_the_message_says_it_is_unset() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let message = panic_message(|| assert_var_eq!(env, "MODE", "fast", "in case {}", 2));
+
+        // Assert
+        assert_eq!(
+            message,
+            "assertion failed: in case 2\nenvironment variable \"MODE\" is unset, expected \"fast\""
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_asserting_equality_then_the_message_shows_it_escaped() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let message = panic_message(|| assert_var_eq!(env, "BINARY", "foo"));
+
+        // Assert
+        assert_eq!(
+            message,
+            "assertion failed: environment variable \"BINARY\" is not valid Unicode: \
+             \"fo\\x80o\", expected \"foo\""
+        );
+    }
+
+    #[test]
+    fn given_a_set_variable_when_asserting_it_is_unset_then_the_message_shows_its_value() {
+        // Arrange
+        let env = fake_env(&[("TMP", "/tmp")]);
+
+        // Act
+        let message = panic_message(|| assert_var_unset!(env, "TMP"));
+
+        // Assert
+        assert_eq!(
+            message,
+            "assertion failed: environment variable \"TMP\" is \"/tmp\", expected it to be unset"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+    future::{self, Future},
+    pin::Pin,
+};
+
+use crate::Environment;
+#[cfg(feature = "tokio")]
+use crate::ReadEnvironment;
+
+/// An environment whose variables are read and written asynchronously, such
+/// as one backed by a remote parameter store.
+///
+/// This mirrors [`Environment`](Environment). Wrap a synchronous
+/// environment in an [`AsyncAdapter`](AsyncAdapter) to use it where an
+/// `AsyncEnvironment` is expected, and, with the `tokio` feature, an
+/// `AsyncEnvironment` in a [`BlockingAdapter`](BlockingAdapter) to use it
+/// where an `Environment` is expected.
+///
+/// Each method returns a boxed [`EnvFuture`](EnvFuture), so implementations
+/// return their `async` blocks with `Box::pin`.
+///
+/// # Example
+/// ```rust
+/// # use std::{collections::HashMap, ffi::{OsStr, OsString}};
+/// # use env_wrapper::{AsyncEnvironment, EnvFuture};
+/// /// A stand-in for a client of a remote parameter store.
+/// struct ParameterStore {
+///     parameters: HashMap<OsString, OsString>,
+/// }
+///
+/// impl AsyncEnvironment for ParameterStore {
+///     fn var_os<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Option<OsString>> {
+///         Box::pin(async move { self.parameters.get(key).cloned() })
+///     }
+///
+///     fn set_var<'a>(&'a mut self, key: &'a OsStr, value: &'a OsStr) -> EnvFuture<'a, ()> {
+///         Box::pin(async move {
+///             self.parameters.insert(key.into(), value.into());
+///         })
+///     }
+///
+///     fn remove_var<'a>(&'a mut self, key: &'a OsStr) -> EnvFuture<'a, ()> {
+///         Box::pin(async move {
+///             self.parameters.remove(key);
+///         })
+///     }
+/// }
+/// ```
+pub trait AsyncEnvironment {
+    /// Get an environment variable, checking for valid UTF-8.
+    ///
+    /// The default implementation calls `var_os`.
+    ///
+    /// # Errors
+    /// * If a key doesn't exist, it should return a `VarError::NotPresent`.
+    /// * If the environment variable value contains invalid UTF-8, it
+    ///   should return `VarError::NotUnicode(OsString)`.
+    fn var<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Result<String, VarError>> {
+        let value = self.var_os(key);
+        Box::pin(async move {
+            value
+                .await
+                .ok_or(VarError::NotPresent)?
+                .into_string()
+                .map_err(VarError::NotUnicode)
+        })
+    }
+
+    /// Get an environment variable. This does not check for valid UTF-8.
+    fn var_os<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Option<OsString>>;
+
+    /// Set an environment variable.
+    fn set_var<'a>(&'a mut self, key: &'a OsStr, value: &'a OsStr) -> EnvFuture<'a, ()>;
+
+    /// Remove an environment variable.
+    fn remove_var<'a>(&'a mut self, key: &'a OsStr) -> EnvFuture<'a, ()>;
+}
+
+/// The future an [`AsyncEnvironment`](AsyncEnvironment) operation returns,
+/// which may borrow the environment and the key and value for `'a`.
+pub type EnvFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An adapter that makes a synchronous [`Environment`](Environment) an
+/// [`AsyncEnvironment`](AsyncEnvironment).
+///
+/// Each operation runs on the wrapped environment when it is called, and
+/// returns a future that is already complete, so the environment need not
+/// be `Sync` or `Send`.
+///
+/// # Example
+/// ```rust
+/// # use std::ffi::OsStr;
+/// # use env_wrapper::{AsyncAdapter, AsyncEnvironment, Environment, FakeEnvironment};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("CONFIG_LOCATION", "/tmp/test.conf");
+///
+/// let env = AsyncAdapter::new(fake_env);
+///
+/// let location = env.var(OsStr::new("CONFIG_LOCATION")).await;
+/// assert_eq!(location.unwrap(), "/tmp/test.conf");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AsyncAdapter<E> {
+    inner: E,
+}
+
+impl<E: Environment> AsyncAdapter<E> {
+    /// Wrap `inner`.
+    pub fn new(inner: E) -> Self {
+        AsyncAdapter { inner }
+    }
+
+    /// Unwrap the adapter, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: Environment> AsyncEnvironment for AsyncAdapter<E> {
+    fn var<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Result<String, VarError>> {
+        Box::pin(future::ready(self.inner.var(key)))
+    }
+
+    fn var_os<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Option<OsString>> {
+        Box::pin(future::ready(self.inner.var_os(key)))
+    }
+
+    fn set_var<'a>(&'a mut self, key: &'a OsStr, value: &'a OsStr) -> EnvFuture<'a, ()> {
+        self.inner.set_var(key, value);
+        Box::pin(future::ready(()))
+    }
+
+    fn remove_var<'a>(&'a mut self, key: &'a OsStr) -> EnvFuture<'a, ()> {
+        self.inner.remove_var(key);
+        Box::pin(future::ready(()))
+    }
+}
+
+/// An adapter that makes an [`AsyncEnvironment`](AsyncEnvironment) a
+/// synchronous [`Environment`](Environment), by blocking the calling thread
+/// on each operation with a tokio runtime. Requires the `tokio` feature.
+///
+/// # Panics
+/// Each operation panics if it is called from within an asynchronous
+/// context, as [`Handle::block_on`](tokio::runtime::Handle::block_on)
+/// does. Use it at synchronous call sites, such as code that runs on a
+/// [`spawn_blocking`](tokio::task::spawn_blocking) thread.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{AsyncAdapter, BlockingAdapter, Environment, FakeEnvironment, ReadEnvironment};
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let mut env = BlockingAdapter::new(
+///     AsyncAdapter::new(FakeEnvironment::new()),
+///     runtime.handle().clone(),
+/// );
+///
+/// env.set_var("CONFIG_LOCATION", "/tmp/test.conf");
+///
+/// assert_eq!(env.var("CONFIG_LOCATION").unwrap(), "/tmp/test.conf");
+/// ```
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug)]
+pub struct BlockingAdapter<A> {
+    inner: A,
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl<A: AsyncEnvironment> BlockingAdapter<A> {
+    /// Wrap `inner`, running its operations on the runtime of `handle`.
+    pub fn new(inner: A, handle: tokio::runtime::Handle) -> Self {
+        BlockingAdapter { inner, handle }
+    }
+
+    /// Unwrap the adapter, returning the underlying environment.
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<A: AsyncEnvironment> ReadEnvironment for BlockingAdapter<A> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.handle.block_on(self.inner.var(key.as_ref()))
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.handle.block_on(self.inner.var_os(key.as_ref()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<A: AsyncEnvironment> Environment for BlockingAdapter<A> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.handle
+            .block_on(self.inner.set_var(key.as_ref(), value.as_ref()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.handle.block_on(self.inner.remove_var(key.as_ref()));
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod remote_store_conformance {
+    crate::conformance_tests!(super::tests::blocking(super::tests::RemoteStore::default()));
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod async_adapter_conformance {
+    crate::conformance_tests!(super::tests::blocking(super::AsyncAdapter::new(
+        crate::FakeEnvironment::new()
+    )));
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::VarError,
+        ffi::{OsStr, OsString},
+        os::unix::ffi::OsStrExt,
+        sync::{Mutex, OnceLock},
+    };
+
+    use tokio::runtime::{Builder, Runtime};
+
+    use super::{AsyncAdapter, AsyncEnvironment, BlockingAdapter, EnvFuture};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    /// A toy remote parameter store, which yields to the runtime before each
+    /// operation as a network call would.
+    #[derive(Default)]
+    pub(super) struct RemoteStore {
+        parameters: Mutex<HashMap<OsString, OsString>>,
+    }
+
+    impl AsyncEnvironment for RemoteStore {
+        fn var_os<'a>(&'a self, key: &'a OsStr) -> EnvFuture<'a, Option<OsString>> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.parameters.lock().unwrap().get(key).cloned()
+            })
+        }
+
+        fn set_var<'a>(&'a mut self, key: &'a OsStr, value: &'a OsStr) -> EnvFuture<'a, ()> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.parameters
+                    .lock()
+                    .unwrap()
+                    .insert(key.into(), value.into());
+            })
+        }
+
+        fn remove_var<'a>(&'a mut self, key: &'a OsStr) -> EnvFuture<'a, ()> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.parameters.lock().unwrap().remove(key);
+            })
+        }
+    }
+
+    fn runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| Builder::new_current_thread().build().unwrap())
+    }
+
+    pub(super) fn blocking<A: AsyncEnvironment>(inner: A) -> BlockingAdapter<A> {
+        BlockingAdapter::new(inner, runtime().handle().clone())
+    }
+
+    #[tokio::test]
+    async fn given_an_async_provider_when_reading_then_var_is_derived_from_var_os() {
+        // Arrange
+        let mut store = RemoteStore::default();
+        store
+            .set_var(OsStr::new("BINARY"), OsStr::from_bytes(&INVALID_UTF8))
+            .await;
+        store
+            .set_var(OsStr::new("REGION"), OsStr::new("eu-west-1"))
+            .await;
+
+        // Act
+        let region = store.var(OsStr::new("REGION")).await;
+        let binary = store.var(OsStr::new("BINARY")).await;
+        let missing = store.var(OsStr::new("MISSING")).await;
+
+        // Assert
+        assert_eq!(region.unwrap(), "eu-west-1");
+        assert_eq!(
+            binary.unwrap_err(),
+            VarError::NotUnicode(OsStr::from_bytes(&INVALID_UTF8).into())
+        );
+        assert_eq!(missing.unwrap_err(), VarError::NotPresent);
+    }
+
+    #[tokio::test]
+    async fn given_a_sync_environment_when_adapted_then_changes_reach_it() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("STALE", "1");
+        let mut env = AsyncAdapter::new(fake_env);
+
+        // Act
+        env.set_var(OsStr::new("REGION"), OsStr::new("eu-west-1"))
+            .await;
+        env.remove_var(OsStr::new("STALE")).await;
+
+        // Assert
+        assert_eq!(env.var(OsStr::new("REGION")).await.unwrap(), "eu-west-1");
+        let fake_env = env.into_inner();
+        assert_eq!(fake_env.var("REGION").unwrap(), "eu-west-1");
+        assert!(fake_env.var_os("STALE").is_none());
+    }
+
+    #[test]
+    fn given_an_async_provider_when_blocking_on_it_then_it_reads_as_an_environment() {
+        // Arrange
+        let mut env = blocking(RemoteStore::default());
+        env.set_var("REGION", "eu-west-1");
+
+        // Act
+        let region = env.var("REGION");
+
+        // Assert
+        assert_eq!(region.unwrap(), "eu-west-1");
+        let store = env.into_inner();
+        assert_eq!(
+            store.parameters.lock().unwrap()[OsStr::new("REGION")],
+            "eu-west-1"
+        );
+    }
+}
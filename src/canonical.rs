@@ -0,0 +1,246 @@
+use std::{ffi::OsStr, fmt::Write};
+
+use crate::{redaction::MASK, EnumerableEnvironment, KeyPattern, RedactionPolicy};
+
+/// Which variables [`to_canonical_string`](CanonicalStringExt::to_canonical_string)
+/// leaves out or masks, for values that change from run to run, such as
+/// timestamps and temporary paths.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::CanonicalOptions;
+/// let options = CanonicalOptions::new()
+///     .exclude("STARTED_AT")
+///     .redact("*_DIR")
+///     .mask("[redacted]");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CanonicalOptions {
+    excluded: Vec<KeyPattern>,
+    redacted: Vec<KeyPattern>,
+    redaction_policy: RedactionPolicy,
+    mask: String,
+}
+
+impl CanonicalOptions {
+    /// Write every variable as it is.
+    pub fn new() -> Self {
+        CanonicalOptions {
+            excluded: Vec::new(),
+            redacted: Vec::new(),
+            redaction_policy: RedactionPolicy::new(),
+            mask: MASK.into(),
+        }
+    }
+
+    /// Leave out the variables matching `pattern`, which may be an exact
+    /// name or use `*` wildcards.
+    pub fn exclude(mut self, pattern: impl Into<KeyPattern>) -> Self {
+        self.excluded.push(pattern.into());
+        self
+    }
+
+    /// Write the variables matching `pattern` with the mask instead of their
+    /// value, so the output shows they are set without what to.
+    pub fn redact(mut self, pattern: impl Into<KeyPattern>) -> Self {
+        self.redacted.push(pattern.into());
+        self
+    }
+
+    /// Also write the values `policy` covers with the mask.
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = policy;
+        self
+    }
+
+    /// Write redacted values as `mask` instead of `***`.
+    pub fn mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A deterministic, readable dump of an environment, for snapshot tests,
+/// such as with [`insta`](https://docs.rs/insta).
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{CanonicalOptions, CanonicalStringExt, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+/// fake_env.set_var("MOTD", "hello\nworld");
+/// fake_env.set_var("STARTED_AT", "2024-05-01T12:00:00Z");
+///
+/// let dump = fake_env.to_canonical_string(CanonicalOptions::new().exclude("STARTED_AT"));
+///
+/// assert_eq!(dump, "MOTD=hello\\nworld\nPORT=8080\n");
+/// ```
+pub trait CanonicalStringExt: EnumerableEnvironment {
+    /// Write every variable not excluded by `options` as a `KEY=value` line,
+    /// sorted by key.
+    ///
+    /// The output is always valid UTF-8, with one line for each variable:
+    /// * `\` is written as `\\`, and a newline, carriage return, or tab as
+    ///   `\n`, `\r`, or `\t`.
+    /// * Other control characters are written as `\u{..}`, with their code
+    ///   point in hexadecimal.
+    /// * Bytes that are not valid UTF-8 are written as `\x..`, with the byte
+    ///   in hexadecimal. On Windows, where variables are UTF-16, these are
+    ///   the bytes of their WTF-8 encoding.
+    fn to_canonical_string(&self, options: CanonicalOptions) -> String {
+        let mut vars: Vec<_> = self
+            .vars_os()
+            .into_iter()
+            .filter(|(key, _)| !options.excluded.iter().any(|pattern| pattern.matches(key)))
+            .collect();
+        vars.sort();
+
+        let mut dump = String::new();
+        for (key, value) in vars {
+            push_escaped(&mut dump, &key);
+            dump.push('=');
+            if options.redacted.iter().any(|pattern| pattern.matches(&key))
+                || options.redaction_policy.redacts(&key, &value)
+            {
+                dump.push_str(&options.mask);
+            } else {
+                push_escaped(&mut dump, &value);
+            }
+            dump.push('\n');
+        }
+        dump
+    }
+}
+
+impl<E: EnumerableEnvironment> CanonicalStringExt for E {}
+
+pub(crate) fn push_escaped(dump: &mut String, text: &OsStr) {
+    let mut bytes = text.as_encoded_bytes();
+    loop {
+        let (valid, invalid, rest) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..], &[][..]),
+            Err(error) => {
+                let (valid, after) = bytes.split_at(error.valid_up_to());
+                let (invalid, rest) = after.split_at(error.error_len().unwrap_or(after.len()));
+                // Everything before `valid_up_to` is valid UTF-8.
+                (
+                    std::str::from_utf8(valid).unwrap_or_default(),
+                    invalid,
+                    rest,
+                )
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => dump.push_str("\\\\"),
+                '\n' => dump.push_str("\\n"),
+                '\r' => dump.push_str("\\r"),
+                '\t' => dump.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(dump, "\\u{{{:x}}}", u32::from(c));
+                }
+                c => dump.push(c),
+            }
+        }
+        for byte in invalid {
+            let _ = write!(dump, "\\x{byte:02x}");
+        }
+        if rest.is_empty() {
+            break;
+        }
+        bytes = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{CanonicalOptions, CanonicalStringExt};
+    use crate::{test_helpers::fake_env, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_a_varied_environment_when_dumping_then_the_output_matches_the_golden_string() {
+        // Arrange
+        let mut env = fake_env(&[
+            ("PORT", "8080"),
+            ("EMPTY", ""),
+            ("MULTILINE", "one\ntwo\r\n\tthree"),
+            ("WINDOWS_PATH", "C:\\temp\\new"),
+            ("CONTROL", "bell\u{7}"),
+            ("UNICODE", "héllo wörld"),
+            ("STARTED_AT", "2024-05-01T12:00:00Z"),
+            ("TMP_DIR", "/tmp/run-4f2a"),
+        ]);
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        let options = CanonicalOptions::new()
+            .exclude("STARTED_AT")
+            .redact("*_DIR");
+
+        // Act
+        let dump = env.to_canonical_string(options);
+
+        // Assert
+        assert_eq!(
+            dump,
+            concat!(
+                "BINARY=fo\\x80o\n",
+                "CONTROL=bell\\u{7}\n",
+                "EMPTY=\n",
+                "MULTILINE=one\\ntwo\\r\\n\\tthree\n",
+                "PORT=8080\n",
+                "TMP_DIR=***\n",
+                "UNICODE=héllo wörld\n",
+                "WINDOWS_PATH=C:\\\\temp\\\\new\n",
+            )
+        );
+    }
+
+    #[test]
+    fn given_the_same_variables_set_in_different_orders_when_dumping_then_the_output_is_identical()
+    {
+        // Arrange
+        let first = fake_env(&[("A", "1"), ("B", "2"), ("C", "3")]);
+        let second = fake_env(&[("C", "3"), ("A", "1"), ("B", "2")]);
+
+        // Act
+        let first_dump = first.to_canonical_string(CanonicalOptions::new());
+        let second_dump = second.to_canonical_string(CanonicalOptions::default());
+
+        // Assert
+        assert_eq!(first_dump, second_dump);
+        assert_eq!(first_dump, "A=1\nB=2\nC=3\n");
+    }
+
+    #[test]
+    fn given_a_non_unicode_key_and_a_custom_mask_when_dumping_then_both_are_escaped_or_masked() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var(OsStr::from_bytes(&INVALID_UTF8), OsStr::from_bytes(&[0xff]));
+        env.set_var("API_TOKEN", "secret");
+        let options = CanonicalOptions::new().redact("API_TOKEN").mask("<token>");
+
+        // Act
+        let dump = env.to_canonical_string(options);
+
+        // Assert
+        assert_eq!(dump, "API_TOKEN=<token>\nfo\\x80o=\\xff\n");
+    }
+
+    #[test]
+    fn given_an_empty_environment_when_dumping_then_the_output_is_empty() {
+        // Act
+        let dump = FakeEnvironment::new().to_canonical_string(CanonicalOptions::new());
+
+        // Assert
+        assert_eq!(dump, "");
+    }
+}
@@ -0,0 +1,220 @@
+use std::ffi::{OsStr, OsString};
+
+use crate::{
+    DynEnvironment, EnvError, Environment, LayerId, LayerOutcome, ReadEnvironment, Resolution,
+};
+
+/// An ordered list of environments, read with fallback: the first source that
+/// has a variable supplies its value.
+///
+/// Writes and removals go to a single writable source, which is the first one
+/// unless another is chosen with
+/// [`writable_source`](ChainEnvironment::writable_source). Removing a variable
+/// only removes it from the writable source, so a later source may still
+/// supply a value for it.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{
+/// #     ChainEnvironment, Environment, FakeEnvironment, ReadEnvironment, RealEnvironment,
+/// # };
+/// let mut defaults = FakeEnvironment::new();
+/// defaults.set_var("LOG_LEVEL", "info");
+///
+/// // Process environment first, then the defaults.
+/// let env = ChainEnvironment::new(vec![Box::new(RealEnvironment), Box::new(defaults)]);
+/// # assert!(env.var("LOG_LEVEL").is_ok());
+/// ```
+pub struct ChainEnvironment {
+    sources: Vec<Box<dyn DynEnvironment>>,
+    writable: usize,
+}
+
+impl ChainEnvironment {
+    /// Chain the sources in order of precedence. The first source is writable.
+    ///
+    /// # Panics
+    /// Panics if `sources` is empty.
+    pub fn new(sources: Vec<Box<dyn DynEnvironment>>) -> Self {
+        assert!(
+            !sources.is_empty(),
+            "a ChainEnvironment needs at least one source"
+        );
+        ChainEnvironment {
+            sources,
+            writable: 0,
+        }
+    }
+
+    /// Route writes and removals to the source at `index` instead of the first.
+    ///
+    /// # Panics
+    /// Panics if there is no source at `index`.
+    pub fn writable_source(mut self, index: usize) -> Self {
+        assert!(
+            index < self.sources.len(),
+            "writable source index {index} is out of range for {} sources",
+            self.sources.len()
+        );
+        self.writable = index;
+        self
+    }
+
+    /// How `key` resolves: what each source holds for it, in order of
+    /// precedence, and which one supplies the value. Sources are numbered
+    /// from 0.
+    pub fn explain(&self, key: impl AsRef<OsStr>) -> Resolution {
+        let key = key.as_ref();
+        let sources = self.sources.iter().enumerate().map(|(index, source)| {
+            let outcome = source
+                .dyn_var_os(key)
+                .map_or(LayerOutcome::NotSet, LayerOutcome::Supplied);
+            (LayerId::Source(index), outcome)
+        });
+        Resolution::from_layers(key, sources)
+    }
+
+    /// Take the sources back out of the chain, in order of precedence.
+    pub fn into_sources(self) -> Vec<Box<dyn DynEnvironment>> {
+        self.sources
+    }
+}
+
+impl ReadEnvironment for ChainEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.sources
+            .iter()
+            .find_map(|source| source.dyn_var_os(key.as_ref()))
+    }
+}
+
+impl Environment for ChainEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.sources[self.writable].dyn_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.sources[self.writable].dyn_remove_var(key.as_ref())
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.sources[self.writable].dyn_try_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.sources[self.writable].dyn_try_remove_var(key.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::VarError;
+
+    use super::ChainEnvironment;
+    use crate::{test_helpers::fake_env, DynEnvironment, Environment, LayerId, ReadEnvironment};
+
+    fn boxed_env(vars: &[(&str, &str)]) -> Box<dyn DynEnvironment> {
+        Box::new(fake_env(vars))
+    }
+
+    #[test]
+    fn given_a_key_in_several_sources_when_reading_then_the_first_source_wins() {
+        // Arrange
+        let env = ChainEnvironment::new(vec![
+            boxed_env(&[("A", "process")]),
+            boxed_env(&[("A", "dotenv"), ("B", "dotenv")]),
+            boxed_env(&[("A", "default"), ("B", "default"), ("C", "default")]),
+        ]);
+
+        // Act/Assert
+        assert_eq!(env.var("A").unwrap(), "process");
+        assert_eq!(env.var("B").unwrap(), "dotenv");
+        assert_eq!(env.var("C").unwrap(), "default");
+    }
+
+    #[test]
+    fn given_a_key_in_several_sources_when_explaining_then_later_sources_are_shadowed() {
+        // Arrange
+        let env = ChainEnvironment::new(vec![
+            boxed_env(&[]),
+            boxed_env(&[("A", "dotenv")]),
+            boxed_env(&[("A", "default")]),
+        ]);
+
+        // Act
+        let found = env.explain("A");
+        let missing = env.explain("MISSING");
+
+        // Assert
+        assert_eq!(found.winner(), Some(LayerId::Source(1)));
+        assert_eq!(
+            found.to_string(),
+            concat!(
+                "\"A\" = \"dotenv\", from source 1\n",
+                "  source 0: not set\n",
+                "  source 1: \"dotenv\"\n",
+                "  source 2: \"default\" (shadowed)",
+            )
+        );
+        assert_eq!(missing.winner(), None);
+        assert_eq!(missing.steps.len(), 3);
+    }
+
+    #[test]
+    fn given_a_key_in_no_source_when_reading_then_it_is_not_present() {
+        // Arrange
+        let env = ChainEnvironment::new(vec![boxed_env(&[]), boxed_env(&[("OTHER", "x")])]);
+
+        // Act
+        let result = env.var("MISSING");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), VarError::NotPresent);
+        assert!(env.var_os("MISSING").is_none());
+    }
+
+    #[test]
+    fn when_writing_then_the_write_goes_to_the_first_source() {
+        // Arrange
+        let mut env = ChainEnvironment::new(vec![boxed_env(&[]), boxed_env(&[("A", "default")])]);
+
+        // Act
+        env.set_var("A", "written");
+
+        // Assert
+        let sources = env.into_sources();
+        assert_eq!(sources[0].dyn_var("A".as_ref()).unwrap(), "written");
+        assert_eq!(sources[1].dyn_var("A".as_ref()).unwrap(), "default");
+    }
+
+    #[test]
+    fn given_a_chosen_writable_source_when_writing_and_removing_then_only_that_source_changes() {
+        // Arrange
+        let mut env = ChainEnvironment::new(vec![
+            boxed_env(&[("A", "process")]),
+            boxed_env(&[("A", "dotenv")]),
+        ])
+        .writable_source(1);
+
+        // Act
+        env.set_var("B", "written");
+        env.remove_var("A");
+
+        // Assert
+        assert_eq!(env.var("A").unwrap(), "process");
+        let sources = env.into_sources();
+        assert!(sources[0].dyn_var_os("B".as_ref()).is_none());
+        assert_eq!(sources[1].dyn_var("B".as_ref()).unwrap(), "written");
+        assert!(sources[1].dyn_var_os("A".as_ref()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn when_choosing_a_writable_source_that_does_not_exist_then_panic() {
+        ChainEnvironment::new(vec![boxed_env(&[])]).writable_source(1);
+    }
+}
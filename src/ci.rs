@@ -0,0 +1,290 @@
+use crate::ReadEnvironment;
+
+/// The continuous integration service a program runs under, and what it
+/// says about the build, from [`CiInfo::detect`](CiInfo::detect).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CiInfo {
+    pub provider: CiProvider,
+    /// The branch being built. For a pull request, this is its source
+    /// branch, where the service provides it.
+    pub branch: Option<String>,
+    /// The number of the pull request or merge request being built, if any.
+    pub pull_request: Option<u64>,
+}
+
+/// A continuous integration service.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CiProvider {
+    AzurePipelines,
+    Buildkite,
+    CircleCi,
+    GitHubActions,
+    GitLab,
+    Jenkins,
+    TeamCity,
+    TravisCi,
+    /// A service not listed here that sets `CI`, as most do.
+    Other,
+}
+
+impl CiInfo {
+    /// Detect the continuous integration service from the variables it
+    /// sets in `env`, or `None` if the program does not seem to run under
+    /// one.
+    ///
+    /// Empty variables count as unset, and so do variables marking a service
+    /// that are `false` or `0`, so `CI=false` turns detection off unless a
+    /// specific service is recognized.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{CiInfo, CiProvider, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("GITHUB_ACTIONS", "true");
+    /// fake_env.set_var("GITHUB_REF", "refs/pull/42/merge");
+    /// fake_env.set_var("GITHUB_HEAD_REF", "fix-typo");
+    ///
+    /// let ci = CiInfo::detect(&fake_env).unwrap();
+    ///
+    /// assert_eq!(ci.provider, CiProvider::GitHubActions);
+    /// assert_eq!(ci.branch.as_deref(), Some("fix-typo"));
+    /// assert_eq!(ci.pull_request, Some(42));
+    /// ```
+    pub fn detect(env: &impl ReadEnvironment) -> Option<Self> {
+        let read = |name: &str| env.var(name).ok().filter(|value| !value.is_empty());
+        let flag = |name: &str| read(name).is_some_and(|value| is_true(&value));
+        let first = |names: &[&str]| names.iter().find_map(|name| read(name));
+        let number = |name: &str| read(name)?.parse().ok();
+
+        let info = if flag("GITHUB_ACTIONS") {
+            let pull_request = read("GITHUB_REF").and_then(|git_ref| {
+                git_ref
+                    .strip_prefix("refs/pull/")?
+                    .split('/')
+                    .next()?
+                    .parse()
+                    .ok()
+            });
+            CiInfo {
+                provider: CiProvider::GitHubActions,
+                branch: first(&["GITHUB_HEAD_REF", "GITHUB_REF_NAME"]),
+                pull_request,
+            }
+        } else if flag("GITLAB_CI") {
+            CiInfo {
+                provider: CiProvider::GitLab,
+                branch: first(&["CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "CI_COMMIT_BRANCH"]),
+                pull_request: number("CI_MERGE_REQUEST_IID"),
+            }
+        } else if flag("BUILDKITE") {
+            CiInfo {
+                provider: CiProvider::Buildkite,
+                branch: read("BUILDKITE_BRANCH"),
+                pull_request: number("BUILDKITE_PULL_REQUEST"),
+            }
+        } else if flag("CIRCLECI") {
+            let pull_request = number("CIRCLE_PR_NUMBER").or_else(|| {
+                read("CIRCLE_PULL_REQUEST")?
+                    .rsplit('/')
+                    .next()?
+                    .parse()
+                    .ok()
+            });
+            CiInfo {
+                provider: CiProvider::CircleCi,
+                branch: read("CIRCLE_BRANCH"),
+                pull_request,
+            }
+        } else if flag("TRAVIS") {
+            CiInfo {
+                provider: CiProvider::TravisCi,
+                branch: first(&["TRAVIS_PULL_REQUEST_BRANCH", "TRAVIS_BRANCH"]),
+                pull_request: number("TRAVIS_PULL_REQUEST"),
+            }
+        } else if flag("TF_BUILD") {
+            CiInfo {
+                provider: CiProvider::AzurePipelines,
+                branch: first(&["SYSTEM_PULLREQUEST_SOURCEBRANCH", "BUILD_SOURCEBRANCHNAME"]),
+                pull_request: number("SYSTEM_PULLREQUEST_PULLREQUESTNUMBER"),
+            }
+        } else if flag("JENKINS_URL") {
+            CiInfo {
+                provider: CiProvider::Jenkins,
+                branch: first(&["CHANGE_BRANCH", "BRANCH_NAME"]),
+                pull_request: number("CHANGE_ID"),
+            }
+        } else if flag("TEAMCITY_VERSION") {
+            CiInfo {
+                provider: CiProvider::TeamCity,
+                branch: None,
+                pull_request: None,
+            }
+        } else if flag("CI") {
+            CiInfo {
+                provider: CiProvider::Other,
+                branch: None,
+                pull_request: None,
+            }
+        } else {
+            return None;
+        };
+        Some(info)
+    }
+}
+
+/// Whether a variable set to `value` counts as set: not `false` or `0`.
+fn is_true(value: &str) -> bool {
+    !(value.eq_ignore_ascii_case("false") || value == "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CiInfo, CiProvider};
+    use crate::test_helpers::fake_env;
+
+    fn info(provider: CiProvider, branch: Option<&str>, pull_request: Option<u64>) -> CiInfo {
+        CiInfo {
+            provider,
+            branch: branch.map(Into::into),
+            pull_request,
+        }
+    }
+
+    #[test]
+    fn given_github_actions_when_detecting_then_push_and_pull_request_builds_are_described() {
+        // Arrange
+        let push = fake_env(&[
+            ("CI", "true"),
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REF", "refs/heads/main"),
+            ("GITHUB_REF_NAME", "main"),
+            ("GITHUB_HEAD_REF", ""),
+        ]);
+        let pull_request = fake_env(&[
+            ("CI", "true"),
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REF", "refs/pull/42/merge"),
+            ("GITHUB_REF_NAME", "42/merge"),
+            ("GITHUB_HEAD_REF", "fix-typo"),
+        ]);
+
+        // Act/Assert
+        assert_eq!(
+            CiInfo::detect(&push),
+            Some(info(CiProvider::GitHubActions, Some("main"), None))
+        );
+        assert_eq!(
+            CiInfo::detect(&pull_request),
+            Some(info(CiProvider::GitHubActions, Some("fix-typo"), Some(42)))
+        );
+    }
+
+    #[test]
+    fn given_gitlab_ci_when_detecting_then_the_merge_request_is_described() {
+        // Arrange
+        let env = fake_env(&[
+            ("CI", "true"),
+            ("GITLAB_CI", "true"),
+            ("CI_MERGE_REQUEST_IID", "7"),
+            ("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature/login"),
+        ]);
+
+        // Act
+        let ci = CiInfo::detect(&env);
+
+        // Assert
+        assert_eq!(
+            ci,
+            Some(info(CiProvider::GitLab, Some("feature/login"), Some(7)))
+        );
+    }
+
+    #[test]
+    fn given_buildkite_when_detecting_then_a_non_pull_request_build_has_no_number() {
+        // Arrange
+        let env = fake_env(&[
+            ("CI", "true"),
+            ("BUILDKITE", "true"),
+            ("BUILDKITE_BRANCH", "release-1.2"),
+            ("BUILDKITE_PULL_REQUEST", "false"),
+        ]);
+
+        // Act
+        let ci = CiInfo::detect(&env);
+
+        // Assert
+        assert_eq!(
+            ci,
+            Some(info(CiProvider::Buildkite, Some("release-1.2"), None))
+        );
+    }
+
+    #[test]
+    fn given_circleci_when_detecting_then_the_number_is_taken_from_the_pull_request_url() {
+        // Arrange
+        let env = fake_env(&[
+            ("CI", "true"),
+            ("CIRCLECI", "true"),
+            ("CIRCLE_BRANCH", "pull/99"),
+            (
+                "CIRCLE_PULL_REQUEST",
+                "https://github.com/example/repo/pull/99",
+            ),
+        ]);
+
+        // Act
+        let ci = CiInfo::detect(&env);
+
+        // Assert
+        assert_eq!(
+            ci,
+            Some(info(CiProvider::CircleCi, Some("pull/99"), Some(99)))
+        );
+    }
+
+    #[test]
+    fn given_teamcity_or_jenkins_when_detecting_then_they_are_recognized_without_ci_set() {
+        // Arrange
+        let teamcity = fake_env(&[("TEAMCITY_VERSION", "2024.03")]);
+        let jenkins = fake_env(&[
+            ("JENKINS_URL", "https://jenkins.example.com/"),
+            ("BRANCH_NAME", "PR-12"),
+            ("CHANGE_BRANCH", "fix-build"),
+            ("CHANGE_ID", "12"),
+        ]);
+
+        // Act/Assert
+        assert_eq!(
+            CiInfo::detect(&teamcity),
+            Some(info(CiProvider::TeamCity, None, None))
+        );
+        assert_eq!(
+            CiInfo::detect(&jenkins),
+            Some(info(CiProvider::Jenkins, Some("fix-build"), Some(12)))
+        );
+    }
+
+    #[test]
+    fn given_only_ci_when_detecting_then_an_unknown_provider_is_reported() {
+        // Arrange
+        let env = fake_env(&[("CI", "1")]);
+
+        // Act
+        let ci = CiInfo::detect(&env);
+
+        // Assert
+        assert_eq!(ci, Some(info(CiProvider::Other, None, None)));
+    }
+
+    #[test]
+    fn given_no_ci_variables_when_detecting_then_there_is_no_ci() {
+        // Arrange
+        let developer_machine = fake_env(&[("HOME", "/home/me"), ("TERM", "xterm-256color")]);
+        let disabled = fake_env(&[("CI", "false"), ("GITHUB_ACTIONS", "")]);
+
+        // Act/Assert
+        assert_eq!(CiInfo::detect(&developer_machine), None);
+        assert_eq!(CiInfo::detect(&disabled), None);
+    }
+}
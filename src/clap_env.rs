@@ -0,0 +1,200 @@
+use clap::{Command, Parser};
+
+use crate::ReadEnvironment;
+
+/// Make the arguments of `command` and its subcommands that fall back to an
+/// environment variable, through clap's `env` attribute, read it from `env`
+/// instead of the process environment. Requires the `clap` feature.
+///
+/// Clap looks up the variable when the argument is defined, so this replaces
+/// the lookup: for each such argument, the value of its variable in `env`, if
+/// set, becomes the argument's default and makes it no longer required, and
+/// the process environment is no longer consulted. Arguments given on the
+/// command line still take precedence, as they would over a variable.
+///
+/// Since the value is a default, the help text shows it as one rather than
+/// naming the variable,
+/// [`ArgMatches::value_source`](clap::ArgMatches::value_source) reports it as
+/// a default value, and it does not count towards conflicts between
+/// arguments.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{inject_env, Environment, FakeEnvironment};
+/// # use clap::{Arg, Command};
+/// let command = Command::new("app").arg(Arg::new("port").long("port").env("APP_PORT"));
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("APP_PORT", "8080");
+///
+/// let matches = inject_env(command, &fake_env).get_matches_from(["app"]);
+///
+/// assert_eq!(matches.get_one::<String>("port").unwrap(), "8080");
+/// ```
+pub fn inject_env(command: Command, env: &impl ReadEnvironment) -> Command {
+    command
+        .mut_args(|arg| {
+            let Some(name) = arg.get_env().map(ToOwned::to_owned) else {
+                return arg;
+            };
+            let arg = arg.env(None);
+            match env.var_os(name) {
+                Some(value) => arg.required(false).default_value(value),
+                None => arg,
+            }
+        })
+        .mut_subcommands(|subcommand| inject_env(subcommand, env))
+}
+
+/// Parse `P` from `args` like [`Parser::try_parse_from`], with the
+/// arguments that fall back to environment variables reading them from
+/// `env`. See [`inject_env`](inject_env). Requires the `clap` feature.
+///
+/// # Errors
+/// If the arguments, or the values taken from `env`, are invalid for `P`,
+/// it returns the error clap would have.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{try_parse_from_env, Environment, FakeEnvironment};
+/// #[derive(clap::Parser)]
+/// struct Cli {
+///     #[arg(long, env = "APP_VERBOSE")]
+///     verbose: bool,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("APP_VERBOSE", "true");
+///
+/// let cli: Cli = try_parse_from_env(["app"], &fake_env).unwrap();
+///
+/// assert!(cli.verbose);
+/// ```
+pub fn try_parse_from_env<P, I, T>(args: I, env: &impl ReadEnvironment) -> Result<P, clap::Error>
+where
+    P: Parser,
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let mut matches = inject_env(P::command(), env).try_get_matches_from(args)?;
+    P::from_arg_matches_mut(&mut matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{error::ErrorKind, Parser, Subcommand};
+
+    use super::try_parse_from_env;
+    use crate::{test_helpers::fake_env, FakeEnvironment};
+
+    #[derive(Debug, Parser)]
+    struct Cli {
+        #[arg(long, env = "ENV_WRAPPER_TEST_VERBOSE")]
+        verbose: bool,
+        #[arg(long, env = "ENV_WRAPPER_TEST_PORT", default_value_t = 80)]
+        port: u16,
+        #[arg(long, env = "ENV_WRAPPER_TEST_NAME")]
+        name: String,
+        #[command(subcommand)]
+        command: Option<Commands>,
+    }
+
+    #[derive(Debug, PartialEq, Subcommand)]
+    enum Commands {
+        Serve {
+            #[arg(long, env = "ENV_WRAPPER_TEST_WORKERS")]
+            workers: u32,
+        },
+    }
+
+    #[test]
+    fn given_fake_variables_when_parsing_then_arguments_fall_back_to_them() {
+        // Arrange
+        let env = fake_env(&[
+            ("ENV_WRAPPER_TEST_VERBOSE", "true"),
+            ("ENV_WRAPPER_TEST_PORT", "8080"),
+            ("ENV_WRAPPER_TEST_NAME", "api"),
+        ]);
+
+        // Act
+        let cli: Cli = try_parse_from_env(["app"], &env).unwrap();
+
+        // Assert
+        assert!(cli.verbose);
+        assert_eq!(cli.port, 8080);
+        assert_eq!(cli.name, "api");
+    }
+
+    #[test]
+    fn given_an_explicit_argument_when_parsing_then_it_beats_the_fake_variable() {
+        // Arrange
+        let env = fake_env(&[
+            ("ENV_WRAPPER_TEST_PORT", "8080"),
+            ("ENV_WRAPPER_TEST_NAME", "api"),
+        ]);
+
+        // Act
+        let cli: Cli = try_parse_from_env(["app", "--port", "9090"], &env).unwrap();
+
+        // Assert
+        assert_eq!(cli.port, 9090);
+        assert_eq!(cli.name, "api");
+    }
+
+    #[test]
+    fn given_no_fake_variable_when_parsing_then_the_default_applies() {
+        // Arrange
+        let env = fake_env(&[("ENV_WRAPPER_TEST_NAME", "api")]);
+
+        // Act
+        let cli: Cli = try_parse_from_env(["app"], &env).unwrap();
+
+        // Assert
+        assert!(!cli.verbose);
+        assert_eq!(cli.port, 80);
+    }
+
+    #[test]
+    fn given_a_required_argument_without_a_variable_when_parsing_then_it_is_missing() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let result = try_parse_from_env::<Cli, _, _>(["app"], &env);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn given_a_subcommand_when_parsing_then_its_arguments_fall_back_to_the_fake() {
+        // Arrange
+        let env = fake_env(&[
+            ("ENV_WRAPPER_TEST_NAME", "api"),
+            ("ENV_WRAPPER_TEST_WORKERS", "4"),
+        ]);
+
+        // Act
+        let cli: Cli = try_parse_from_env(["app", "serve"], &env).unwrap();
+
+        // Assert
+        assert_eq!(cli.command, Some(Commands::Serve { workers: 4 }));
+    }
+
+    #[test]
+    fn given_an_invalid_fake_value_when_parsing_then_it_is_a_clap_error() {
+        // Arrange
+        let env = fake_env(&[
+            ("ENV_WRAPPER_TEST_NAME", "api"),
+            ("ENV_WRAPPER_TEST_PORT", "eighty"),
+        ]);
+
+        // Act
+        let result = try_parse_from_env::<Cli, _, _>(["app"], &env);
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ValueValidation);
+    }
+}
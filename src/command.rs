@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::{OsStr, OsString},
+    process::Command,
+};
+
+use crate::EnumerableEnvironment;
+
+/// The variables some platforms need to launch a process at all, for
+/// [`with_exact_env_passing`](CommandExt::with_exact_env_passing): `PATH`,
+/// and on Windows also `SystemRoot`.
+#[cfg(windows)]
+pub const DEFAULT_PASSTHROUGH: &[&str] = &["PATH", "SystemRoot"];
+
+/// The variables some platforms need to launch a process at all, for
+/// [`with_exact_env_passing`](CommandExt::with_exact_env_passing): `PATH`,
+/// and on Windows also `SystemRoot`.
+#[cfg(not(windows))]
+pub const DEFAULT_PASSTHROUGH: &[&str] = &["PATH"];
+
+/// Applying an environment to a [`Command`](std::process::Command), or with
+/// the `tokio` feature a
+/// [`tokio::process::Command`](https://docs.rs/tokio/latest/tokio/process/struct.Command.html),
+/// before spawning it.
+pub trait CommandExt {
+    /// Set every variable in `env` for the child process, as
+    /// [`Command::envs`](std::process::Command::envs) does, keeping values
+    /// that are not valid Unicode as they are.
+    ///
+    /// The child also inherits the variables of this process that `env` does
+    /// not set. For the child to see only `env`, call
+    /// [`Command::env_clear`](std::process::Command::env_clear) first.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use std::process::Command;
+    /// # use env_wrapper::{CommandExt, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("RUST_LOG", "debug");
+    ///
+    /// let status = Command::new("my-server")
+    ///     .env_clear()
+    ///     .envs_from(&fake_env)
+    ///     .status()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn envs_from(&mut self, env: &impl EnumerableEnvironment) -> &mut Self;
+
+    /// Make the child process see exactly the variables in `env`, inheriting
+    /// nothing from this process, for hermetic tests of subprocesses.
+    ///
+    /// Without `PATH`, and on Windows `SystemRoot`, some programs, or the
+    /// launch itself, can fail. To keep those, use
+    /// [`with_exact_env_passing`](CommandExt::with_exact_env_passing) with
+    /// [`DEFAULT_PASSTHROUGH`](DEFAULT_PASSTHROUGH).
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use std::process::Command;
+    /// # use env_wrapper::{CommandExt, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("RUST_LOG", "debug");
+    ///
+    /// let output = Command::new("/usr/bin/env").with_exact_env(&fake_env).output()?;
+    ///
+    /// assert_eq!(output.stdout, b"RUST_LOG=debug\n");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn with_exact_env(&mut self, env: &impl EnumerableEnvironment) -> &mut Self;
+
+    /// Like [`with_exact_env`](CommandExt::with_exact_env), but also pass
+    /// each variable named in `passthrough` from this process's environment,
+    /// if it is set there and not in `env`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use std::process::Command;
+    /// # use env_wrapper::{CommandExt, FakeEnvironment, DEFAULT_PASSTHROUGH};
+    /// let fake_env = FakeEnvironment::new();
+    ///
+    /// let status = Command::new("my-tool")
+    ///     .with_exact_env_passing(&fake_env, DEFAULT_PASSTHROUGH)
+    ///     .status()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn with_exact_env_passing(
+        &mut self,
+        env: &impl EnumerableEnvironment,
+        passthrough: &[impl AsRef<OsStr>],
+    ) -> &mut Self;
+
+    /// Make the child process see exactly the variables in `desired`, by
+    /// applying only how they differ from this process's environment: each
+    /// variable that `desired` adds or changes is set, each it lacks is
+    /// removed, and the rest are left for the child to inherit.
+    ///
+    /// This process's environment is read when this is called, so variables
+    /// it changes afterwards reach the child as they are at the spawn.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use std::process::Command;
+    /// # use env_wrapper::{CommandExt, Environment, FakeEnvironment};
+    /// let mut desired = FakeEnvironment::new();
+    /// for (key, value) in std::env::vars_os() {
+    ///     desired.set_var(key, value);
+    /// }
+    /// desired.set_var("RUST_LOG", "debug");
+    /// desired.remove_var("HTTP_PROXY");
+    ///
+    /// let status = Command::new("my-server").envs_diff_from(&desired).status()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    fn envs_diff_from(&mut self, desired: &impl EnumerableEnvironment) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn envs_from(&mut self, env: &impl EnumerableEnvironment) -> &mut Self {
+        self.envs(env.vars_os())
+    }
+
+    fn with_exact_env(&mut self, env: &impl EnumerableEnvironment) -> &mut Self {
+        self.env_clear().envs_from(env)
+    }
+
+    fn with_exact_env_passing(
+        &mut self,
+        env: &impl EnumerableEnvironment,
+        passthrough: &[impl AsRef<OsStr>],
+    ) -> &mut Self {
+        self.env_clear()
+            .envs(passed_through(passthrough))
+            .envs_from(env)
+    }
+
+    fn envs_diff_from(&mut self, desired: &impl EnumerableEnvironment) -> &mut Self {
+        let (changed, removed) = diff_from_current(desired);
+        for key in removed {
+            self.env_remove(key);
+        }
+        self.envs(changed)
+    }
+}
+
+/// Requires the `tokio` feature.
+#[cfg(feature = "tokio")]
+impl CommandExt for tokio::process::Command {
+    fn envs_from(&mut self, env: &impl EnumerableEnvironment) -> &mut Self {
+        self.envs(env.vars_os())
+    }
+
+    fn with_exact_env(&mut self, env: &impl EnumerableEnvironment) -> &mut Self {
+        self.env_clear().envs_from(env)
+    }
+
+    fn with_exact_env_passing(
+        &mut self,
+        env: &impl EnumerableEnvironment,
+        passthrough: &[impl AsRef<OsStr>],
+    ) -> &mut Self {
+        self.env_clear()
+            .envs(passed_through(passthrough))
+            .envs_from(env)
+    }
+
+    fn envs_diff_from(&mut self, desired: &impl EnumerableEnvironment) -> &mut Self {
+        let (changed, removed) = diff_from_current(desired);
+        for key in removed {
+            self.env_remove(key);
+        }
+        self.envs(changed)
+    }
+}
+
+/// The variables named in `passthrough` that are set in this process.
+fn passed_through(passthrough: &[impl AsRef<OsStr>]) -> Vec<(OsString, OsString)> {
+    passthrough
+        .iter()
+        .filter_map(|key| Some((key.as_ref().to_owned(), env::var_os(key)?)))
+        .collect()
+}
+
+/// The variables of `desired` that are added or changed from this process's
+/// environment, and the keys of this process's variables that it lacks.
+fn diff_from_current(
+    desired: &impl EnumerableEnvironment,
+) -> (Vec<(OsString, OsString)>, Vec<OsString>) {
+    let mut current: HashMap<OsString, OsString> = env::vars_os().collect();
+    let changed = desired
+        .vars_os()
+        .into_iter()
+        .filter(|(key, value)| current.remove(key).as_ref() != Some(value))
+        .collect();
+    (changed, current.into_keys().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        os::unix::ffi::{OsStrExt, OsStringExt},
+        process::Command,
+    };
+
+    use super::{CommandExt, DEFAULT_PASSTHROUGH};
+    use crate::{test_helpers::random_upper, Environment, FakeEnvironment, RealEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    /// The `NAME=VALUE` lines `env` prints in a child given `command`'s
+    /// environment.
+    fn child_env(command: &mut Command) -> Vec<OsString> {
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        output
+            .stdout
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| OsString::from_vec(line.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn given_a_fake_when_spawning_a_child_then_it_sees_exactly_those_variables() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello world");
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        let mut command = Command::new("env");
+
+        // Act
+        command.env_clear().envs_from(&env);
+
+        // Assert
+        let mut seen = child_env(&mut command);
+        seen.sort();
+        let mut binary = b"BINARY=".to_vec();
+        binary.extend(INVALID_UTF8);
+        assert_eq!(
+            seen,
+            vec![
+                OsString::from_vec(binary),
+                OsString::from("GREETING=hello world"),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_fake_without_clearing_when_spawning_then_the_fake_overrides_inherited_variables() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("ENV_WRAPPER_TEST_INJECTED", "from the fake");
+        let mut command = Command::new("env");
+        command.env("ENV_WRAPPER_TEST_INJECTED", "from the parent");
+
+        // Act
+        command.envs_from(&env);
+
+        // Assert
+        let seen = child_env(&mut command);
+        assert!(seen.contains(&"ENV_WRAPPER_TEST_INJECTED=from the fake".into()));
+        assert!(!seen.contains(&"ENV_WRAPPER_TEST_INJECTED=from the parent".into()));
+    }
+
+    #[test]
+    fn given_an_inherited_variable_when_spawning_with_the_exact_env_then_it_is_absent() {
+        // Arrange
+        let inherited = random_upper();
+        RealEnvironment.set_var(&inherited, "from the parent");
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello");
+        let mut command = Command::new("env");
+
+        // Act
+        command.with_exact_env(&env);
+
+        // Assert
+        let seen = child_env(&mut command);
+        RealEnvironment.remove_var(&inherited);
+        assert_eq!(seen, vec![OsString::from("GREETING=hello")]);
+    }
+
+    #[test]
+    fn given_a_passthrough_list_when_spawning_then_only_those_variables_are_inherited() {
+        // Arrange
+        let passed = random_upper();
+        let overridden = random_upper();
+        let inherited = random_upper();
+        for key in [&passed, &overridden, &inherited] {
+            RealEnvironment.set_var(key, "from the parent");
+        }
+        let mut env = FakeEnvironment::new();
+        env.set_var(&overridden, "from the fake");
+        let mut command = Command::new("env");
+
+        // Act
+        command.with_exact_env_passing(&env, &[&passed, &overridden, &"PATH".to_owned()]);
+
+        // Assert
+        let seen = child_env(&mut command);
+        for key in [&passed, &overridden, &inherited] {
+            RealEnvironment.remove_var(key);
+        }
+        assert!(seen.contains(&format!("{passed}=from the parent").into()));
+        assert!(seen.contains(&format!("{overridden}=from the fake").into()));
+        assert!(!seen
+            .iter()
+            .any(|line| line.to_string_lossy().starts_with(&inherited)));
+        assert!(seen
+            .iter()
+            .any(|line| line.to_string_lossy().starts_with("PATH=")));
+    }
+
+    #[test]
+    fn given_the_default_passthrough_when_spawning_then_path_is_kept() {
+        // Arrange
+        let env = FakeEnvironment::new();
+        let mut command = Command::new("env");
+
+        // Act
+        command.with_exact_env_passing(&env, DEFAULT_PASSTHROUGH);
+
+        // Assert
+        let seen = child_env(&mut command);
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].to_string_lossy().starts_with("PATH="));
+    }
+
+    #[test]
+    fn given_a_desired_environment_when_spawning_with_the_diff_then_only_the_delta_is_applied() {
+        // Arrange
+        let unchanged = random_upper();
+        let changed = random_upper();
+        let removed = random_upper();
+        let added = random_upper();
+        for key in [&unchanged, &changed, &removed] {
+            RealEnvironment.set_var(key, "from the parent");
+        }
+        let mut desired = FakeEnvironment::new();
+        for (key, value) in std::env::vars_os() {
+            desired.set_var(key, value);
+        }
+        desired.set_var(&changed, "from the fake");
+        desired.set_var(&added, "from the fake");
+        desired.remove_var(&removed);
+        let mut command = Command::new("env");
+
+        // Act
+        command.envs_diff_from(&desired);
+
+        // Assert
+        let seen = child_env(&mut command);
+        for key in [&unchanged, &changed, &removed] {
+            RealEnvironment.remove_var(key);
+        }
+        assert!(seen.contains(&format!("{unchanged}=from the parent").into()));
+        assert!(seen.contains(&format!("{changed}=from the fake").into()));
+        assert!(seen.contains(&format!("{added}=from the fake").into()));
+        assert!(!seen
+            .iter()
+            .any(|line| line.to_string_lossy().starts_with(&format!("{removed}="))));
+        let applied: Vec<_> = command
+            .get_envs()
+            .filter(|(key, _)| {
+                [&unchanged, &changed, &removed, &added]
+                    .iter()
+                    .any(|ours| *key == OsStr::new(ours))
+            })
+            .collect();
+        assert_eq!(applied.len(), 3);
+        assert!(applied.contains(&(OsStr::new(&changed), Some(OsStr::new("from the fake")))));
+        assert!(applied.contains(&(OsStr::new(&added), Some(OsStr::new("from the fake")))));
+        assert!(applied.contains(&(OsStr::new(&removed), None)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn given_a_tokio_command_when_spawning_with_the_exact_env_then_the_child_sees_the_fake() {
+        // Arrange
+        let inherited = random_upper();
+        RealEnvironment.set_var(&inherited, "from the parent");
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello");
+        let mut command = tokio::process::Command::new("env");
+
+        // Act
+        command.with_exact_env(&env);
+
+        // Assert
+        let output = command.output().await.unwrap();
+        RealEnvironment.remove_var(&inherited);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"GREETING=hello\n");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn given_a_tokio_command_and_a_passthrough_list_when_spawning_then_path_is_kept() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello");
+        let mut command = tokio::process::Command::new("env");
+
+        // Act
+        command.with_exact_env_passing(&env, DEFAULT_PASSTHROUGH);
+
+        // Assert
+        let output = command.output().await.unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines: Vec<_> = stdout.lines().collect();
+        lines.sort_unstable();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "GREETING=hello");
+        assert!(lines[1].starts_with("PATH="));
+    }
+}
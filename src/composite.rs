@@ -0,0 +1,202 @@
+use std::ffi::{OsStr, OsString};
+
+use crate::{DynEnvironment, EnvError, Environment, ReadEnvironment};
+
+/// An environment that routes each key to one of several backends by its
+/// prefix, falling through to a default backend.
+///
+/// Reads, writes, and removals all go to the backend of the first route whose
+/// prefix the key starts with, so when prefixes overlap, the route registered
+/// first wins. Keys are passed to the backend unchanged, prefix included.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{
+/// #     CompositeEnvironment, Environment, FakeEnvironment, ReadEnvironment, RealEnvironment,
+/// # };
+/// let mut secrets = FakeEnvironment::new();
+/// secrets.set_var("SECRET_DB_PASSWORD", "hunter2");
+/// let mut config = FakeEnvironment::new();
+/// config.set_var("MYAPP_LOG_LEVEL", "debug");
+///
+/// let env = CompositeEnvironment::new(Box::new(RealEnvironment))
+///     .route("SECRET_", Box::new(secrets))
+///     .route("MYAPP_", Box::new(config));
+///
+/// assert_eq!(env.var("SECRET_DB_PASSWORD").unwrap(), "hunter2");
+/// assert_eq!(env.var("MYAPP_LOG_LEVEL").unwrap(), "debug");
+/// # assert!(env.var_os("MYAPP_UNSET").is_none());
+/// ```
+pub struct CompositeEnvironment {
+    routes: Vec<(OsString, Box<dyn DynEnvironment>)>,
+    default: Box<dyn DynEnvironment>,
+}
+
+impl CompositeEnvironment {
+    /// Route every key to `default` until routes are added.
+    pub fn new(default: Box<dyn DynEnvironment>) -> Self {
+        CompositeEnvironment {
+            routes: Vec::new(),
+            default,
+        }
+    }
+
+    /// Route keys starting with `prefix` to `backend`, unless an earlier
+    /// route already matches them.
+    pub fn route(mut self, prefix: impl AsRef<OsStr>, backend: Box<dyn DynEnvironment>) -> Self {
+        self.routes.push((prefix.as_ref().into(), backend));
+        self
+    }
+
+    fn backend(&self, key: &OsStr) -> &dyn DynEnvironment {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| has_prefix(key, prefix))
+            .map_or(self.default.as_ref(), |(_, backend)| backend.as_ref())
+    }
+
+    fn backend_mut(&mut self, key: &OsStr) -> &mut dyn DynEnvironment {
+        match self
+            .routes
+            .iter_mut()
+            .find(|(prefix, _)| has_prefix(key, prefix))
+        {
+            Some((_, backend)) => backend.as_mut(),
+            None => self.default.as_mut(),
+        }
+    }
+}
+
+fn has_prefix(key: &OsStr, prefix: &OsStr) -> bool {
+    key.as_encoded_bytes()
+        .starts_with(prefix.as_encoded_bytes())
+}
+
+impl ReadEnvironment for CompositeEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.backend(key.as_ref()).dyn_var_os(key.as_ref())
+    }
+}
+
+impl Environment for CompositeEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.backend_mut(key.as_ref())
+            .dyn_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.backend_mut(key.as_ref()).dyn_remove_var(key.as_ref())
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.backend_mut(key.as_ref())
+            .dyn_try_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.backend_mut(key.as_ref())
+            .dyn_try_remove_var(key.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompositeEnvironment;
+    use crate::{Environment, ReadEnvironment, SharedFakeEnvironment};
+
+    fn shared_env(vars: &[(&str, &str)]) -> SharedFakeEnvironment {
+        let mut env = SharedFakeEnvironment::new();
+        for (key, value) in vars {
+            env.set_var(key, value);
+        }
+        env
+    }
+
+    struct Backends {
+        secrets: SharedFakeEnvironment,
+        config: SharedFakeEnvironment,
+        default: SharedFakeEnvironment,
+    }
+
+    fn composite() -> (CompositeEnvironment, Backends) {
+        let backends = Backends {
+            secrets: shared_env(&[("SECRET_TOKEN", "from secrets")]),
+            config: shared_env(&[("MYAPP_MODE", "from config"), ("HOME", "from config")]),
+            default: shared_env(&[("HOME", "from default"), ("MYAPP_MODE", "from default")]),
+        };
+        let env = CompositeEnvironment::new(Box::new(backends.default.clone()))
+            .route("SECRET_", Box::new(backends.secrets.clone()))
+            .route("MYAPP_", Box::new(backends.config.clone()));
+        (env, backends)
+    }
+
+    #[test]
+    fn given_routes_when_reading_then_each_key_is_read_from_its_backend() {
+        // Arrange
+        let (env, _) = composite();
+
+        // Act/Assert
+        assert_eq!(env.var("SECRET_TOKEN").unwrap(), "from secrets");
+        assert_eq!(env.var("MYAPP_MODE").unwrap(), "from config");
+        assert_eq!(env.var("HOME").unwrap(), "from default");
+        assert!(env.var_os("SECRET_MISSING").is_none());
+    }
+
+    #[test]
+    fn given_routes_when_writing_then_each_key_is_written_to_its_backend_only() {
+        // Arrange
+        let (mut env, backends) = composite();
+
+        // Act
+        env.set_var("SECRET_NEW", "s");
+        env.set_var("MYAPP_NEW", "c");
+        env.set_var("OTHER_NEW", "d");
+
+        // Assert
+        assert_eq!(backends.secrets.var("SECRET_NEW").unwrap(), "s");
+        assert_eq!(backends.config.var("MYAPP_NEW").unwrap(), "c");
+        assert_eq!(backends.default.var("OTHER_NEW").unwrap(), "d");
+        assert!(backends.default.var_os("SECRET_NEW").is_none());
+        assert!(backends.default.var_os("MYAPP_NEW").is_none());
+        assert!(backends.config.var_os("OTHER_NEW").is_none());
+    }
+
+    #[test]
+    fn given_routes_when_removing_then_each_key_is_removed_from_its_backend_only() {
+        // Arrange
+        let (mut env, backends) = composite();
+
+        // Act
+        env.remove_var("MYAPP_MODE");
+        env.remove_var("HOME");
+
+        // Assert
+        assert!(backends.config.var_os("MYAPP_MODE").is_none());
+        assert_eq!(backends.default.var("MYAPP_MODE").unwrap(), "from default");
+        assert!(backends.default.var_os("HOME").is_none());
+        assert_eq!(backends.config.var("HOME").unwrap(), "from config");
+    }
+
+    #[test]
+    fn given_overlapping_prefixes_when_routing_then_the_first_registered_route_wins() {
+        // Arrange
+        let broad = shared_env(&[("MYAPP_SECRET_KEY", "broad")]);
+        let narrow = shared_env(&[("MYAPP_SECRET_KEY", "narrow")]);
+        let mut env = CompositeEnvironment::new(Box::new(SharedFakeEnvironment::new()))
+            .route("MYAPP_", Box::new(broad.clone()))
+            .route("MYAPP_SECRET_", Box::new(narrow.clone()));
+
+        // Act
+        let read = env.var("MYAPP_SECRET_KEY");
+        env.set_var("MYAPP_SECRET_OTHER", "written");
+
+        // Assert
+        assert_eq!(read.unwrap(), "broad");
+        assert_eq!(broad.var("MYAPP_SECRET_OTHER").unwrap(), "written");
+        assert!(narrow.var_os("MYAPP_SECRET_OTHER").is_none());
+    }
+}
@@ -0,0 +1,351 @@
+use config::{ConfigError, Map, Source, Value, ValueKind};
+
+use crate::EnumerableEnvironment;
+
+/// Where [`EnvWrapperSource`] says its values came from.
+const ORIGIN: &str = "the environment";
+
+/// A [`config`](https://docs.rs/config) [`Source`] over a snapshot of any
+/// enumerable environment, so fakes can take part in layered configuration.
+/// Requires the `config` feature.
+///
+/// It behaves like config's own `Environment` source, which only reads the
+/// real process environment:
+/// * Names are lower-cased.
+/// * [`prefix`](EnvWrapperSource::prefix) keeps only the variables whose
+///   names start with the prefix and the prefix separator, compared
+///   case-insensitively, and strips both.
+/// * [`separator`](EnvWrapperSource::separator) nests values at each
+///   occurrence of a pattern in the name, so with `__` the variable
+///   `DB__HOST` provides `db.host`.
+/// * [`list_separator`](EnvWrapperSource::list_separator) splits values into
+///   lists, either all of them or only those for the keys added with
+///   [`with_list_parse_key`](EnvWrapperSource::with_list_parse_key).
+///
+/// The variables are copied when the source is created, since config needs
+/// sources it can clone and keep. Names and values that are not valid Unicode
+/// are converted lossily.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, EnvWrapperSource, FakeEnvironment};
+/// #[derive(serde::Deserialize)]
+/// struct Settings {
+///     db: Database,
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Database {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_DB__HOST", "localhost");
+/// fake_env.set_var("MYAPP_DB__PORT", "5432");
+///
+/// let settings: Settings = config::Config::builder()
+///     .add_source(
+///         EnvWrapperSource::new(&fake_env)
+///             .prefix("MYAPP")
+///             .prefix_separator("_")
+///             .separator("__"),
+///     )
+///     .build()
+///     .unwrap()
+///     .try_deserialize()
+///     .unwrap();
+///
+/// assert_eq!(settings.db.host, "localhost");
+/// assert_eq!(settings.db.port, 5432);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EnvWrapperSource {
+    vars: Vec<(String, String)>,
+    prefix: Option<String>,
+    prefix_separator: Option<String>,
+    separator: Option<String>,
+    list_separator: Option<String>,
+    list_parse_keys: Option<Vec<String>>,
+    try_parsing: bool,
+}
+
+impl EnvWrapperSource {
+    /// Provide a copy of every variable currently in `env`, lower-cased and
+    /// unnested.
+    pub fn new(env: &impl EnumerableEnvironment) -> Self {
+        let vars = env
+            .vars_os()
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        EnvWrapperSource {
+            vars,
+            prefix: None,
+            prefix_separator: None,
+            separator: None,
+            list_separator: None,
+            list_parse_keys: None,
+            try_parsing: false,
+        }
+    }
+
+    /// Only provide the variables whose names start with `prefix` followed
+    /// by the prefix separator, with both removed.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// What separates the prefix from the rest of the name. Defaults to the
+    /// separator if one is set, or `_` otherwise.
+    pub fn prefix_separator(mut self, separator: &str) -> Self {
+        self.prefix_separator = Some(separator.into());
+        self
+    }
+
+    /// Nest values at each occurrence of `separator` in their names.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = Some(separator.into());
+        self
+    }
+
+    /// Split values into lists at each occurrence of `separator`.
+    pub fn list_separator(mut self, separator: &str) -> Self {
+        self.list_separator = Some(separator.into());
+        self
+    }
+
+    /// Only split the value for `key` into a list, along with any other keys
+    /// added this way. `key` is compared after the prefix is stripped and the
+    /// name is nested, so it is lower-case and dotted, like `db.hosts`.
+    pub fn with_list_parse_key(mut self, key: &str) -> Self {
+        self.list_parse_keys
+            .get_or_insert_with(Vec::new)
+            .push(key.to_lowercase());
+        self
+    }
+
+    /// Whether to provide values that look like `bool`s and numbers as such,
+    /// rather than as strings. Defaults to `false`.
+    pub fn try_parsing(mut self, try_parsing: bool) -> Self {
+        self.try_parsing = try_parsing;
+        self
+    }
+
+    /// The key for a variable, or `None` if it is not provided.
+    fn key(&self, name: &str) -> Option<String> {
+        let mut key = name.to_lowercase();
+        if let Some(prefix) = &self.prefix {
+            let prefix_separator = self
+                .prefix_separator
+                .as_deref()
+                .or(self.separator.as_deref())
+                .unwrap_or("_");
+            let pattern = format!("{prefix}{prefix_separator}").to_lowercase();
+            key = key.strip_prefix(&pattern)?.to_owned();
+        }
+        match self.separator.as_deref() {
+            Some(separator) if !separator.is_empty() => Some(key.replace(separator, ".")),
+            _ => Some(key),
+        }
+    }
+
+    fn value(&self, key: &str, value: &str) -> ValueKind {
+        let origin = ORIGIN.to_owned();
+        if let Some(separator) = &self.list_separator {
+            let listed = match &self.list_parse_keys {
+                Some(keys) => keys.iter().any(|listed| listed == key),
+                None => true,
+            };
+            if listed {
+                let elements = value
+                    .split(separator.as_str())
+                    .map(|element| Value::new(Some(&origin), self.scalar(element)))
+                    .collect();
+                return ValueKind::Array(elements);
+            }
+        }
+        self.scalar(value)
+    }
+
+    fn scalar(&self, value: &str) -> ValueKind {
+        if self.try_parsing {
+            if let Ok(parsed) = value.to_lowercase().parse() {
+                return ValueKind::Boolean(parsed);
+            }
+            if let Ok(parsed) = value.parse() {
+                return ValueKind::I64(parsed);
+            }
+            if let Ok(parsed) = value.parse() {
+                return ValueKind::Float(parsed);
+            }
+        }
+        ValueKind::String(value.into())
+    }
+}
+
+impl Source for EnvWrapperSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let origin = ORIGIN.to_owned();
+        Ok(self
+            .vars
+            .iter()
+            .filter_map(|(name, value)| {
+                let key = self.key(name)?;
+                let value = Value::new(Some(&origin), self.value(&key, value));
+                Some((key, value))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, FileFormat, Source, ValueKind};
+    use serde::Deserialize;
+
+    use super::EnvWrapperSource;
+    use crate::{test_helpers::fake_env, Environment};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        name: String,
+        debug: bool,
+        db: Database,
+        hosts: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Database {
+        host: String,
+        port: u16,
+    }
+
+    #[test]
+    fn given_a_fake_when_building_a_config_then_it_deserializes_nested_settings() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_NAME", "api"),
+            ("MYAPP_DEBUG", "true"),
+            ("MYAPP_DB__HOST", "db.internal"),
+            ("MYAPP_DB__PORT", "5432"),
+            ("MYAPP_HOSTS", "a.example,b.example"),
+            ("OTHER_NAME", "ignored"),
+        ]);
+        let source = EnvWrapperSource::new(&env)
+            .prefix("MYAPP")
+            .prefix_separator("_")
+            .separator("__")
+            .list_separator(",")
+            .with_list_parse_key("hosts");
+
+        // Act
+        let settings: Settings = Config::builder()
+            .add_source(source)
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            settings,
+            Settings {
+                name: "api".into(),
+                debug: true,
+                db: Database {
+                    host: "db.internal".into(),
+                    port: 5432,
+                },
+                hosts: vec!["a.example".into(), "b.example".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn given_a_fake_over_a_file_when_building_a_config_then_the_fake_overrides_it() {
+        // Arrange
+        let file = r#"
+            name = "api"
+            debug = false
+            hosts = []
+
+            [db]
+            host = "localhost"
+            port = 5432
+        "#;
+        let env = fake_env(&[("APP__DB__PORT", "6543")]);
+
+        // Act
+        let settings: Settings = Config::builder()
+            .add_source(config::File::from_str(file, FileFormat::Toml))
+            .add_source(EnvWrapperSource::new(&env).prefix("app").separator("__"))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        // Assert
+        assert_eq!(settings.db.host, "localhost");
+        assert_eq!(settings.db.port, 6543);
+    }
+
+    #[test]
+    fn given_a_source_when_the_fake_changes_then_the_source_keeps_its_snapshot() {
+        // Arrange
+        let mut env = fake_env(&[("PORT", "8080")]);
+        let source = EnvWrapperSource::new(&env);
+
+        // Act
+        env.set_var("PORT", "9090");
+
+        // Assert
+        let values = source.collect().unwrap();
+        assert_eq!(values["port"].clone().into_string().unwrap(), "8080");
+    }
+
+    #[test]
+    fn given_try_parsing_when_collecting_then_values_are_typed() {
+        // Arrange
+        let env = fake_env(&[
+            ("FLAG", "TRUE"),
+            ("COUNT", "-3"),
+            ("RATIO", "0.5"),
+            ("NAME", "x"),
+        ]);
+        let source = EnvWrapperSource::new(&env).try_parsing(true);
+
+        // Act
+        let values = source.collect().unwrap();
+
+        // Assert
+        assert_eq!(values["flag"].kind, ValueKind::Boolean(true));
+        assert_eq!(values["count"].kind, ValueKind::I64(-3));
+        assert_eq!(values["ratio"].kind, ValueKind::Float(0.5));
+        assert_eq!(values["name"].kind, ValueKind::String("x".into()));
+    }
+
+    #[test]
+    fn given_a_list_separator_without_keys_when_collecting_then_every_value_is_a_list() {
+        // Arrange
+        let env = fake_env(&[("A", "1;2"), ("B", "3")]);
+        let source = EnvWrapperSource::new(&env).list_separator(";");
+
+        // Act
+        let values = source.collect().unwrap();
+
+        // Assert
+        assert_eq!(values["a"].clone().into_array().unwrap().len(), 2);
+        assert_eq!(values["b"].clone().into_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! The behavior every [`Environment`](crate::Environment) shares with the
+//! process environment, as checks to run against an implementation outside
+//! this crate. Requires the `conformance` feature.
+//!
+//! Each function checks one behavior of the environment it is given, and
+//! panics if the environment does not behave that way. The
+//! [`conformance_tests!`](crate::conformance_tests) macro generates a test
+//! for each, and is the easiest way to run them all. This crate runs the same
+//! suite against [`RealEnvironment`](crate::RealEnvironment) and
+//! [`FakeEnvironment`](crate::FakeEnvironment).
+//!
+//! The checks use keys unlikely to be set already, and leave the variables
+//! they set behind, so give each one a fresh environment.
+
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{test_util, EnumerableEnvironment, Environment};
+
+/// A key no other check uses, in this process or another.
+fn unique_key() -> String {
+    test_util::unique_key("ENV_WRAPPER_CONFORMANCE")
+}
+
+/// A value that is not valid Unicode, on platforms that have one.
+#[cfg(unix)]
+fn not_unicode() -> Option<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(OsStr::from_bytes(&[0x66, 0x6f, 0x80, 0x6f]).to_owned())
+}
+
+/// A value that is not valid Unicode, on platforms that have one.
+#[cfg(windows)]
+fn not_unicode() -> Option<OsString> {
+    use std::os::windows::ffi::OsStringExt;
+
+    Some(OsString::from_wide(&[0x66, 0x6f, 0xd800, 0x6f]))
+}
+
+/// A value that is not valid Unicode, on platforms that have one.
+#[cfg(not(any(unix, windows)))]
+fn not_unicode() -> Option<OsString> {
+    None
+}
+
+/// A variable that was set can be read.
+pub fn when_adding_an_environment_variable_then_it_can_be_read(mut env: impl Environment) {
+    // Arrange
+    let key = unique_key();
+    let value = unique_key();
+    env.set_var(&key, &value);
+
+    // Act
+    let result = env.var(&key);
+
+    // Assert
+    assert_eq!(result.unwrap(), value);
+}
+
+/// Reading a variable that was never set with `var` is a
+/// `VarError::NotPresent` error.
+pub fn given_a_nonexistent_env_var_when_getting_the_env_var_with_var_then_it_is_a_not_present_error(
+    env: impl Environment,
+) {
+    // Arrange
+    let nonexistent_key = unique_key();
+
+    // Act
+    let result = env.var(nonexistent_key);
+
+    // Assert
+    assert_eq!(result.unwrap_err(), VarError::NotPresent);
+}
+
+/// Keys and values can be given as any type that is `AsRef<OsStr>`.
+pub fn when_setting_env_vars_then_multiple_data_types_can_be_used_on_the_same_environment_instance(
+    mut env: impl Environment,
+) {
+    // Act
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    env.set_var(&unique_key(), &unique_key());
+    env.set_var(unique_key(), unique_key());
+    env.set_var(OsStr::new(&unique_key()), OsStr::new(&unique_key()));
+    env.set_var(OsString::from(unique_key()), OsString::from(unique_key()));
+
+    // Assert - none. This is strictly for type enforcement.
+}
+
+/// Reading a value that is not valid Unicode with `var` is a
+/// `VarError::NotUnicode` error.
+pub fn when_using_var_getter_with_an_invalid_utf8_value_then_it_is_a_not_unicode_error(
+    mut env: impl Environment,
+) {
+    let Some(not_unicode) = not_unicode() else {
+        return;
+    };
+    // Arrange
+    let key = unique_key();
+    env.set_var(&key, &not_unicode);
+
+    // Act
+    let result = env.var(&key);
+
+    // Assert
+    assert_eq!(result.unwrap_err(), VarError::NotUnicode(not_unicode));
+}
+
+/// Reading a variable that was never set with `var_os` is `None`.
+pub fn given_a_nonexistent_env_var_when_getting_the_env_var_with_var_os_then_it_is_none(
+    env: impl Environment,
+) {
+    // Arrange
+    let key = unique_key();
+
+    // Act
+    let result = env.var_os(key);
+
+    // Assert
+    assert!(result.is_none());
+}
+
+/// Reading a value that is not valid Unicode with `var_os` returns it as it
+/// is.
+pub fn given_an_env_var_with_invalid_utf8_when_getting_the_env_var_with_var_os_then_it_is_some(
+    mut env: impl Environment,
+) {
+    let Some(not_unicode) = not_unicode() else {
+        return;
+    };
+    // Arrange
+    let key = unique_key();
+    env.set_var(&key, &not_unicode);
+
+    // Act
+    let result = env.var_os(&key);
+
+    // Assert
+    assert_eq!(result, Some(not_unicode));
+}
+
+/// Setting a variable again replaces its value.
+pub fn given_an_existing_environment_variable_when_setting_the_same_environment_variable_then_the_value_is_overwritten(
+    mut env: impl Environment,
+) {
+    // Arrange
+    let key = unique_key();
+    let val_1 = unique_key();
+    let val_2 = unique_key();
+    env.set_var(&key, &val_1);
+
+    // Act
+    env.set_var(&key, &val_2);
+
+    // Assert
+    assert_eq!(env.var(&key).unwrap(), val_2);
+}
+
+/// A variable that was removed is no longer present.
+pub fn given_an_existing_environment_variable_when_removing_the_same_environment_variable_then_the_variable_no_longer_exists(
+    mut env: impl Environment,
+) {
+    // Arrange
+    let key = unique_key();
+    let value = unique_key();
+    env.set_var(&key, &value);
+
+    // Act
+    env.remove_var(&key);
+
+    // Assert
+    assert_eq!(env.var(&key).unwrap_err(), VarError::NotPresent);
+}
+
+/// Removing a variable that was never set does nothing.
+pub fn when_removing_a_nonexistent_environment_variable_then_do_not_panic(
+    mut env: impl Environment,
+) {
+    // Arrange
+    let key = unique_key();
+
+    // Act
+    env.remove_var(&key);
+
+    // Assert - no assertion
+}
+
+/// The fallible setters succeed, and change the environment as `set_var` and
+/// `remove_var` do.
+pub fn when_using_the_fallible_setters_then_they_succeed_and_behave_like_the_infallible_ones(
+    mut env: impl Environment,
+) {
+    // Arrange
+    let key = unique_key();
+    let value = unique_key();
+
+    // Act/Assert
+    env.try_set_var(&key, &value).unwrap();
+    assert_eq!(env.var(&key).unwrap(), value);
+    env.try_remove_var(&key).unwrap();
+    assert_eq!(env.var(&key).unwrap_err(), VarError::NotPresent);
+}
+
+/// `contains` is true for a variable that was set, whatever its value, and
+/// false for one that was not.
+pub fn given_set_and_unset_environment_variables_when_checking_if_they_are_contained_then_only_the_set_one_is(
+    mut env: impl Environment,
+) {
+    // Arrange
+    let set_key = unique_key();
+    let unset_key = unique_key();
+    env.set_var(&set_key, not_unicode().unwrap_or_default());
+
+    // Act/Assert
+    assert!(env.contains(&set_key));
+    assert!(!env.contains(&unset_key));
+}
+
+/// Keys that are empty or contain `=` or NUL are never present.
+pub fn given_malformed_keys_when_getting_them_then_they_are_not_present(env: impl Environment) {
+    for key in malformed_keys() {
+        // Act/Assert
+        assert_eq!(env.var(&key), Err(VarError::NotPresent), "{key:?}");
+        assert_eq!(env.var_os(&key), None, "{key:?}");
+        assert!(!env.contains(&key), "{key:?}");
+    }
+}
+
+/// `read_all` returns the value of each key that is set, and leaves out
+/// those that are not, for a few keys and for many.
+pub fn given_set_and_unset_environment_variables_when_reading_them_all_then_only_the_set_ones_are_returned(
+    mut env: impl Environment,
+) {
+    for count in [3, 40] {
+        // Arrange
+        let set: Vec<(OsString, OsString)> = (0..count)
+            .map(|_| (unique_key().into(), unique_key().into()))
+            .collect();
+        for (key, value) in &set {
+            env.set_var(key, value);
+        }
+        let unset = unique_key();
+        let mut keys: Vec<&OsStr> = set.iter().map(|(key, _)| key.as_os_str()).collect();
+        keys.push(OsStr::new(&unset));
+
+        // Act
+        let vars = env.read_all(&keys);
+
+        // Assert
+        assert_eq!(vars, set.into_iter().collect(), "{count} keys");
+    }
+}
+
+/// A variable that was set is listed by `vars_os`.
+pub fn given_an_existing_environment_variable_when_listing_all_variables_then_it_is_included(
+    mut env: impl Environment + EnumerableEnvironment,
+) {
+    // Arrange
+    let key = unique_key();
+    let value = unique_key();
+    env.set_var(&key, &value);
+
+    // Act
+    let vars = env.vars_os();
+
+    // Assert
+    assert!(vars.contains(&(key.into(), value.into())));
+}
+
+/// A variable that was removed is not listed by `vars_os`.
+pub fn given_a_removed_environment_variable_when_listing_all_variables_then_it_is_not_included(
+    mut env: impl Environment + EnumerableEnvironment,
+) {
+    // Arrange
+    let key = unique_key();
+    env.set_var(&key, unique_key());
+    env.remove_var(&key);
+
+    // Act
+    let vars = env.vars_os();
+
+    // Assert
+    assert!(vars.iter().all(|(k, _)| k != key.as_str()));
+}
+
+/// Setting a key that is empty or contains `=` or NUL never makes it listed
+/// by `vars_os`. The process environment panics instead of setting it; an
+/// implementation may do either.
+pub fn given_malformed_keys_when_setting_them_then_they_are_not_listed(
+    mut env: impl Environment + EnumerableEnvironment,
+) {
+    for key in malformed_keys() {
+        // Act
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| env.set_var(&key, "value")));
+
+        // Assert
+        assert!(!env.contains(&key), "{key:?}");
+        assert!(
+            env.vars_os().iter().all(|(k, _)| k != key.as_str()),
+            "{key:?}"
+        );
+    }
+}
+
+/// Keys the platform never stores: empty, or containing `=` or NUL.
+fn malformed_keys() -> [String; 4] {
+    [
+        String::new(),
+        format!("{}=SUFFIX", unique_key()),
+        format!("{}\0SUFFIX", unique_key()),
+        format!("={}", unique_key()),
+    ]
+}
+
+/// Generate a `#[test]` for each check in [`conformance`](crate::conformance),
+/// each given a new environment from `$env`. Requires the `conformance`
+/// feature.
+///
+/// Add `enumerable` to also check
+/// [`vars_os`](crate::EnumerableEnvironment::vars_os). Since the tests are
+/// named after the checks, invoke it in a module of its own.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::FakeEnvironment as MyEnvironment;
+/// #[cfg(test)]
+/// mod my_environment_conformance {
+///     use super::MyEnvironment;
+///
+///     env_wrapper::conformance_tests!(MyEnvironment::new(), enumerable);
+/// }
+/// ```
+#[macro_export]
+macro_rules! conformance_tests {
+    ($env:expr, enumerable $(,)?) => {
+        $crate::conformance_tests!($env);
+        $crate::conformance_tests!(@tests $env;
+            given_an_existing_environment_variable_when_listing_all_variables_then_it_is_included,
+            given_a_removed_environment_variable_when_listing_all_variables_then_it_is_not_included,
+            given_malformed_keys_when_setting_them_then_they_are_not_listed,
+        );
+    };
+    ($env:expr $(,)?) => {
+        $crate::conformance_tests!(@tests $env;
+            when_adding_an_environment_variable_then_it_can_be_read,
+            given_a_nonexistent_env_var_when_getting_the_env_var_with_var_then_it_is_a_not_present_error,
+            when_setting_env_vars_then_multiple_data_types_can_be_used_on_the_same_environment_instance,
+            when_using_var_getter_with_an_invalid_utf8_value_then_it_is_a_not_unicode_error,
+            given_a_nonexistent_env_var_when_getting_the_env_var_with_var_os_then_it_is_none,
+            given_an_env_var_with_invalid_utf8_when_getting_the_env_var_with_var_os_then_it_is_some,
+            given_an_existing_environment_variable_when_setting_the_same_environment_variable_then_the_value_is_overwritten,
+            given_an_existing_environment_variable_when_removing_the_same_environment_variable_then_the_variable_no_longer_exists,
+            when_removing_a_nonexistent_environment_variable_then_do_not_panic,
+            when_using_the_fallible_setters_then_they_succeed_and_behave_like_the_infallible_ones,
+            given_set_and_unset_environment_variables_when_checking_if_they_are_contained_then_only_the_set_one_is,
+            given_malformed_keys_when_getting_them_then_they_are_not_present,
+            given_set_and_unset_environment_variables_when_reading_them_all_then_only_the_set_ones_are_returned,
+        );
+    };
+    (@tests $env:expr; $($check:ident),+ $(,)?) => {
+        $(
+            #[test]
+            fn $check() {
+                $crate::conformance::$check($env);
+            }
+        )+
+    };
+}
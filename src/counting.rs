@@ -0,0 +1,242 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+/// A wrapper that counts the reads and writes of each variable made through
+/// it, for assertions like "`DATABASE_URL` was read exactly once".
+///
+/// * Every `var`, `var_os`, and `contains` call is a read, whether or not the
+///   variable was set. Reads of variables that were not set are also counted
+///   as misses.
+/// * Every `set_var` and `remove_var` call, or their `try_` versions, is a
+///   write, even if it fails.
+///
+/// Listing variables is not counted.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{CountingEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("DATABASE_URL", "postgres://localhost");
+/// fake_env.set_var("LEGACY_DB", "mysql://localhost");
+///
+/// let env = CountingEnvironment::new(fake_env);
+/// let _ = env.var("DATABASE_URL");
+///
+/// assert_eq!(env.read_count("DATABASE_URL"), 1);
+/// assert_eq!(env.read_count("LEGACY_DB"), 0);
+/// assert_eq!(env.unread_keys(), vec!["LEGACY_DB"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CountingEnvironment<E> {
+    inner: E,
+    counts: RefCell<HashMap<OsString, Counts>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Counts {
+    reads: usize,
+    misses: usize,
+    writes: usize,
+}
+
+impl<E: ReadEnvironment> CountingEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        CountingEnvironment {
+            inner,
+            counts: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// How many times `key` was read, including reads that found it unset.
+    pub fn read_count(&self, key: impl AsRef<OsStr>) -> usize {
+        self.counts_for(key).reads
+    }
+
+    /// How many times `key` was read while it was not set.
+    pub fn miss_count(&self, key: impl AsRef<OsStr>) -> usize {
+        self.counts_for(key).misses
+    }
+
+    /// How many times `key` was set or removed.
+    pub fn write_count(&self, key: impl AsRef<OsStr>) -> usize {
+        self.counts_for(key).writes
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn counts_for(&self, key: impl AsRef<OsStr>) -> Counts {
+        self.counts
+            .borrow()
+            .get(key.as_ref())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn count_read(&self, key: &OsStr, hit: bool) {
+        let mut counts = self.counts.borrow_mut();
+        let counts = counts.entry(key.into()).or_default();
+        counts.reads += 1;
+        if !hit {
+            counts.misses += 1;
+        }
+    }
+
+    fn count_write(&self, key: &OsStr) {
+        self.counts
+            .borrow_mut()
+            .entry(key.into())
+            .or_default()
+            .writes += 1;
+    }
+}
+
+impl<E: EnumerableEnvironment> CountingEnvironment<E> {
+    /// The variables currently set in the wrapped environment that have never
+    /// been read, sorted.
+    pub fn unread_keys(&self) -> Vec<OsString> {
+        let counts = self.counts.borrow();
+        let mut keys: Vec<_> = self
+            .inner
+            .vars_os()
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| counts.get(key).map_or(0, |counts| counts.reads) == 0)
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for CountingEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        let result = self.inner.var(&key);
+        self.count_read(key.as_ref(), !matches!(result, Err(VarError::NotPresent)));
+        result
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key);
+        self.count_read(key.as_ref(), value.is_some());
+        value
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for CountingEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let result = self.inner.var_with_source(&key);
+        self.count_read(key.as_ref(), !matches!(result, Err(VarError::NotPresent)));
+        result
+    }
+}
+
+impl<E: Environment> Environment for CountingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.count_write(key.as_ref());
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.count_write(key.as_ref());
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.count_write(key.as_ref());
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.count_write(key.as_ref());
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for CountingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CountingEnvironment;
+    use crate::{test_helpers::fake_env, Environment, FakeEnvironment, ReadEnvironment};
+
+    #[test]
+    fn given_reads_through_var_and_var_os_when_counting_then_both_are_counted() {
+        // Arrange
+        let env = CountingEnvironment::new(fake_env(&[("DATABASE_URL", "postgres://")]));
+
+        // Act
+        let _ = env.var("DATABASE_URL");
+        let _ = env.var_os("DATABASE_URL");
+        env.contains("DATABASE_URL");
+
+        // Assert
+        assert_eq!(env.read_count("DATABASE_URL"), 3);
+        assert_eq!(env.miss_count("DATABASE_URL"), 0);
+        assert_eq!(env.read_count("LEGACY_DB"), 0);
+    }
+
+    #[test]
+    fn given_reads_of_unset_keys_when_counting_then_they_are_reads_and_misses() {
+        // Arrange
+        let mut env = CountingEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        let _ = env.var("MODE");
+        env.set_var("MODE", "fast");
+        let _ = env.var("MODE");
+
+        // Assert
+        assert_eq!(env.read_count("MODE"), 2);
+        assert_eq!(env.miss_count("MODE"), 1);
+    }
+
+    #[test]
+    fn given_sets_and_removals_when_counting_then_both_are_writes() {
+        // Arrange
+        let mut env = CountingEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        env.set_var("TMP", "/tmp");
+        env.try_set_var("TMP", "/var/tmp").unwrap();
+        env.remove_var("TMP");
+
+        // Assert
+        assert_eq!(env.write_count("TMP"), 3);
+        assert_eq!(env.read_count("TMP"), 0);
+    }
+
+    #[test]
+    fn given_seeded_variables_when_listing_unread_keys_then_only_never_read_keys_are_listed() {
+        // Arrange
+        let env = CountingEnvironment::new(fake_env(&[
+            ("DATABASE_URL", "postgres://"),
+            ("LEGACY_DB", "mysql://"),
+            ("LOG_LEVEL", "debug"),
+        ]));
+
+        // Act
+        let _ = env.var("DATABASE_URL");
+        let _ = env.var("NOT_SEEDED");
+
+        // Assert
+        assert_eq!(env.unread_keys(), vec!["LEGACY_DB", "LOG_LEVEL"]);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::Arc,
+};
+
+use crate::{
+    var_from_os, EnumerableEnvironment, Environment, FakeEnvironment, Provenance, ReadEnvironment,
+    SourcedEnvironment,
+};
+
+/// A copy-on-write environment: a base environment shared between variants,
+/// plus a small overlay of this variant's own changes.
+///
+/// Reads check the overlay, then the base. Writes and removals only change
+/// the overlay, so the base is never modified; removing a variable hides any
+/// value for it in the base. Cloning copies only the overlay, so deriving
+/// many test cases from one large fixture costs as much as their changes.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{CowEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fixture = FakeEnvironment::new();
+/// fixture.set_var("LOG_LEVEL", "info");
+/// fixture.set_var("REGION", "us-east-1");
+/// let base = CowEnvironment::new(fixture);
+///
+/// let mut debug_case = base.clone();
+/// debug_case.set_var("LOG_LEVEL", "debug");
+///
+/// assert_eq!(debug_case.var("LOG_LEVEL").unwrap(), "debug");
+/// assert_eq!(debug_case.var("REGION").unwrap(), "us-east-1");
+/// assert_eq!(base.var("LOG_LEVEL").unwrap(), "info");
+/// ```
+#[derive(Debug)]
+pub struct CowEnvironment<E = FakeEnvironment> {
+    base: Arc<E>,
+    // `None` marks a variable removed in this variant.
+    overlay: HashMap<OsString, Option<OsString>>,
+}
+
+impl<E: ReadEnvironment> CowEnvironment<E> {
+    /// A variant of `base` with no changes yet. Clone it to derive further
+    /// variants sharing the same base.
+    pub fn new(base: E) -> Self {
+        Self::from_shared(Arc::new(base))
+    }
+
+    /// A variant of a base that is already shared, with no changes yet.
+    pub fn from_shared(base: Arc<E>) -> Self {
+        CowEnvironment {
+            base,
+            overlay: HashMap::new(),
+        }
+    }
+
+    /// The shared base environment, without this variant's changes.
+    pub fn base(&self) -> &Arc<E> {
+        &self.base
+    }
+
+    /// The number of variables this variant has set or removed.
+    pub fn change_count(&self) -> usize {
+        self.overlay.len()
+    }
+}
+
+impl<E> Clone for CowEnvironment<E> {
+    fn clone(&self) -> Self {
+        CowEnvironment {
+            base: Arc::clone(&self.base),
+            overlay: self.overlay.clone(),
+        }
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for CowEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.overlay.get(key.as_ref()) {
+            Some(value) => value.clone(),
+            None => self.base.var_os(key),
+        }
+    }
+}
+
+/// Values this variant changed come from [`Provenance::Fake`], and the
+/// rest from the shared base.
+impl<E: SourcedEnvironment> SourcedEnvironment for CowEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        match self.overlay.get(key.as_ref()) {
+            Some(value) => var_from_os(value.clone()).map(|value| (value, Provenance::Fake)),
+            None => self.base.var_with_source(key),
+        }
+    }
+}
+
+impl<E: ReadEnvironment> Environment for CowEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.overlay
+            .insert(key.as_ref().into(), Some(value.as_ref().into()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.overlay.insert(key.as_ref().into(), None);
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for CowEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged: HashMap<_, _> = self.base.vars_os().into_iter().collect();
+        for (key, value) in &self.overlay {
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+        merged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::CowEnvironment;
+    use crate::{test_helpers::fake_env, EnumerableEnvironment, Environment, ReadEnvironment};
+
+    #[test]
+    fn given_an_overlay_value_when_reading_then_it_shadows_the_base() {
+        // Arrange
+        let mut env = CowEnvironment::new(fake_env(&[("KEY", "base"), ("BASE_ONLY", "b")]));
+
+        // Act
+        env.set_var("KEY", "overlay");
+
+        // Assert
+        assert_eq!(env.var("KEY").unwrap(), "overlay");
+        assert_eq!(env.var("BASE_ONLY").unwrap(), "b");
+        assert_eq!(env.base().var("KEY").unwrap(), "base");
+    }
+
+    #[test]
+    fn given_a_removed_base_key_when_reading_and_listing_then_it_is_hidden_until_set_again() {
+        // Arrange
+        let mut env = CowEnvironment::new(fake_env(&[("KEY", "base"), ("OTHER", "o")]));
+
+        // Act
+        env.remove_var("KEY");
+        let after_removal = env.var_os("KEY");
+        let listed = env.vars_os();
+        env.set_var("KEY", "again");
+
+        // Assert
+        assert!(after_removal.is_none());
+        assert_eq!(listed, vec![("OTHER".into(), "o".into())]);
+        assert_eq!(env.var("KEY").unwrap(), "again");
+        assert_eq!(env.base().var("KEY").unwrap(), "base");
+    }
+
+    #[test]
+    fn given_sibling_variants_when_one_is_changed_then_the_others_are_unaffected() {
+        // Arrange
+        let base = Arc::new(fake_env(&[("KEY", "base"), ("SHARED", "s")]));
+        let mut first = CowEnvironment::from_shared(Arc::clone(&base));
+        let mut second = first.clone();
+        let third = CowEnvironment::from_shared(base);
+
+        // Act
+        first.set_var("KEY", "first");
+        first.remove_var("SHARED");
+        second.set_var("NEW", "second");
+
+        // Assert
+        assert_eq!(first.var("KEY").unwrap(), "first");
+        assert!(first.var_os("SHARED").is_none());
+        assert!(first.var_os("NEW").is_none());
+        assert_eq!(second.var("KEY").unwrap(), "base");
+        assert_eq!(second.var("SHARED").unwrap(), "s");
+        assert_eq!(third.var("KEY").unwrap(), "base");
+        assert!(third.var_os("NEW").is_none());
+    }
+
+    #[test]
+    fn given_a_variant_when_cloning_then_only_the_changes_are_copied() {
+        // Arrange
+        let keys: Vec<_> = (0..100).map(|index| format!("KEY_{index}")).collect();
+        let base_vars: Vec<_> = keys.iter().map(|key| (key.as_str(), "v")).collect();
+        let mut env = CowEnvironment::new(fake_env(&base_vars));
+        env.set_var("KEY_0", "changed");
+
+        // Act
+        let variant = env.clone();
+
+        // Assert
+        assert_eq!(variant.change_count(), 1);
+        assert!(Arc::ptr_eq(variant.base(), env.base()));
+    }
+}
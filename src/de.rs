@@ -0,0 +1,892 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    error::Error,
+    fmt, slice,
+    str::FromStr,
+};
+
+use serde::de::{
+    self,
+    value::{SeqDeserializer, StrDeserializer, StringDeserializer},
+    DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, Visitor,
+};
+
+use crate::{EnvError, NestedValue, ReadEnvironment};
+
+/// Deserialize a configuration struct from `env`. Requires the `serde`
+/// feature.
+///
+/// Each field is read from the variable named after it in upper case, so
+/// `database_url` is read from `DATABASE_URL`; `#[serde(rename)]` changes the
+/// name as usual. Values are parsed according to the field's type:
+/// * numbers and `bool`s are parsed from their text,
+/// * `Option` fields are `None` when their variable is not set,
+/// * `Vec` fields are split on commas, with each element trimmed and parsed
+///   in turn, and
+/// * unit enum variants are matched by name.
+///
+/// Fields with `#[serde(default)]` take their default when their variable is
+/// not set. Nested structs and maps are not supported here; see
+/// [`from_nested`](from_nested) for those, and [`to_env`](crate::to_env) for
+/// the reverse.
+///
+/// Variables are read with
+/// [`var_redacted`](ReadEnvironment::var_redacted), so a variable the
+/// environment cannot read, such as a secret whose backend is unreachable,
+/// is reported as unavailable rather than as not set.
+///
+/// # Errors
+/// If a required variable is not set, or a value is unavailable, not valid
+/// Unicode, or cannot be parsed, it returns a [`DeError`](DeError) naming
+/// the variable.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{from_env, Environment, FakeEnvironment};
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///     port: u16,
+///     log_level: Option<String>,
+///     allowed_hosts: Vec<String>,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+/// fake_env.set_var("ALLOWED_HOSTS", "example.com,example.org");
+///
+/// let config: Config = from_env(&fake_env).unwrap();
+///
+/// assert_eq!(config.port, 8080);
+/// assert_eq!(config.log_level, None);
+/// assert_eq!(config.allowed_hosts, ["example.com", "example.org"]);
+/// ```
+pub fn from_env<T: DeserializeOwned>(env: &impl ReadEnvironment) -> Result<T, DeError> {
+    T::deserialize(EnvDeserializer { env })
+}
+
+/// Deserialize a configuration struct from a tree built by
+/// [`to_nested_map`](crate::to_nested_map), so nested structs can be read
+/// from names like `MYAPP_DB__HOST`. Requires the `serde` feature.
+///
+/// Fields are looked up by their names in upper case, and values are parsed
+/// as by [`from_env`](from_env). Nested structs are read from the subtree
+/// under their field's name, and maps from every entry of theirs, with the
+/// keys as they are.
+///
+/// # Errors
+/// If a required field is missing, a value cannot be parsed, or a leaf is
+/// found where a nested struct is expected or the reverse, it returns a
+/// [`DeError`](DeError). Errors name the field, without its parents.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{from_nested, to_nested_map, Environment, FakeEnvironment};
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///     db: Database,
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Database {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_DB__HOST", "localhost");
+/// fake_env.set_var("MYAPP_DB__PORT", "5432");
+///
+/// let tree = to_nested_map(&fake_env, "MYAPP_", "__").unwrap();
+/// let config: Config = from_nested(&tree).unwrap();
+///
+/// assert_eq!(config.db.host, "localhost");
+/// assert_eq!(config.db.port, 5432);
+/// ```
+pub fn from_nested<T: DeserializeOwned>(value: &NestedValue) -> Result<T, DeError> {
+    T::deserialize(NestedDeserializer { value })
+}
+
+/// An error from deserializing a struct with [`from_env`](from_env).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeError {
+    /// The named variable is required but not set.
+    Missing(String),
+    /// The value of the named variable is not valid Unicode.
+    NotUnicode(String),
+    /// The named variable could not be read, for the reason in `message`.
+    Unavailable { variable: String, message: String },
+    /// The value of the named variable could not be parsed.
+    Invalid { variable: String, message: String },
+    /// Any other error, such as trying to deserialize a type that is not a
+    /// struct.
+    Message(String),
+}
+
+impl DeError {
+    /// The same error, naming variables with `prefix` in front, for values
+    /// read through a view with the prefix removed.
+    pub(crate) fn prefixed(self, prefix: &str) -> Self {
+        match self {
+            DeError::Missing(name) => DeError::Missing(format!("{prefix}{name}")),
+            DeError::NotUnicode(name) => DeError::NotUnicode(format!("{prefix}{name}")),
+            DeError::Unavailable { variable, message } => DeError::Unavailable {
+                variable: format!("{prefix}{variable}"),
+                message,
+            },
+            DeError::Invalid { variable, message } => DeError::Invalid {
+                variable: format!("{prefix}{variable}"),
+                message,
+            },
+            error => error,
+        }
+    }
+
+    /// Attribute an error from parsing a value to the variable it came from.
+    fn in_variable(self, variable: &str) -> Self {
+        match self {
+            DeError::Message(message) => DeError::Invalid {
+                variable: variable.into(),
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeError::Missing(name) => write!(f, "environment variable {name:?} is not set"),
+            DeError::NotUnicode(name) => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            DeError::Unavailable { variable, message } => {
+                write!(
+                    f,
+                    "environment variable {variable:?} is unavailable: {message}"
+                )
+            }
+            DeError::Invalid { variable, message } => {
+                write!(f, "environment variable {variable:?} is invalid: {message}")
+            }
+            DeError::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeError::Message(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        DeError::Missing(variable_name(field))
+    }
+}
+
+fn variable_name(field: &str) -> String {
+    field.to_ascii_uppercase()
+}
+
+/// Deserializes a struct from the variables named after its fields.
+struct EnvDeserializer<'a, E> {
+    env: &'a E,
+}
+
+impl<'de, E: ReadEnvironment> Deserializer<'de> for EnvDeserializer<'_, E> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DeError> {
+        Err(DeError::Message(
+            "only structs can be deserialized from the environment".into(),
+        ))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_map(FieldAccess {
+            env: self.env,
+            fields: fields.iter(),
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// Visits each field whose variable is set, so that unset ones are treated
+/// as missing.
+struct FieldAccess<'a, E> {
+    env: &'a E,
+    fields: slice::Iter<'static, &'static str>,
+    /// The variable name and value for the field just visited.
+    value: Option<(String, String)>,
+}
+
+impl<'de, E: ReadEnvironment> MapAccess<'de> for FieldAccess<'_, E> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        for field in self.fields.by_ref() {
+            let variable = variable_name(field);
+            match self.env.var_redacted(&variable) {
+                Ok(value) => {
+                    self.value = Some((variable, value));
+                    let field: StrDeserializer<'_, DeError> = field.into_deserializer();
+                    return seed.deserialize(field).map(Some);
+                }
+                Err(EnvError::NotPresent(_)) => continue,
+                Err(EnvError::NotUnicode { .. }) => return Err(DeError::NotUnicode(variable)),
+                Err(EnvError::Unavailable { message, .. }) => {
+                    return Err(DeError::Unavailable { variable, message })
+                }
+                Err(error) => return Err(DeError::Message(error.to_string())),
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let (variable, value) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer { value: &value })
+            .map_err(|error| error.in_variable(&variable))
+    }
+}
+
+/// Deserializes a single value from its text.
+struct ValueDeserializer<'a> {
+    value: &'a str,
+}
+
+impl ValueDeserializer<'_> {
+    fn parse<T>(&self) -> Result<T, DeError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value
+            .trim()
+            .parse()
+            .map_err(|error| DeError::Message(format!("cannot parse {:?}: {error}", self.value)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($deserialize:ident => $visit:ident,)*) => {
+        $(
+            fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_str(self.value)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let elements = self
+            .value
+            .split(',')
+            .filter(|_| !self.value.trim().is_empty())
+            .map(|value| ValueDeserializer {
+                value: value.trim(),
+            });
+        let mut seq = SeqDeserializer::new(elements);
+        let result = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let variant: StringDeserializer<DeError> =
+            self.value.trim().to_string().into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// Deserializes a leaf with [`ValueDeserializer`](ValueDeserializer), or a
+/// map as a struct or a map.
+struct NestedDeserializer<'a> {
+    value: &'a NestedValue,
+}
+
+impl<'a> NestedDeserializer<'a> {
+    fn leaf(&self) -> Result<ValueDeserializer<'a>, DeError> {
+        match self.value {
+            NestedValue::Leaf(value) => Ok(ValueDeserializer { value }),
+            NestedValue::Map(_) => Err(DeError::Message(
+                "expected a single value, found nested variables".into(),
+            )),
+        }
+    }
+
+    fn map(&self) -> Result<&'a BTreeMap<String, NestedValue>, DeError> {
+        match self.value {
+            NestedValue::Leaf(_) => Err(DeError::Message(
+                "expected nested variables, found a single value".into(),
+            )),
+            NestedValue::Map(map) => Ok(map),
+        }
+    }
+}
+
+macro_rules! deserialize_leaf {
+    ($($deserialize:ident,)*) => {
+        $(
+            fn $deserialize<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                self.leaf()?.$deserialize(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for NestedDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.value {
+            NestedValue::Leaf(value) => visitor.visit_str(value),
+            NestedValue::Map(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    deserialize_leaf! {
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_seq,
+        deserialize_identifier,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.leaf()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.leaf()?.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.leaf()?.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(EntryAccess {
+            entries: self.map()?.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_map(NestedFieldAccess {
+            map: self.map()?,
+            fields: fields.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.leaf()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+/// Visits each field present in a map, so that absent ones are treated as
+/// missing.
+struct NestedFieldAccess<'a> {
+    map: &'a BTreeMap<String, NestedValue>,
+    fields: slice::Iter<'static, &'static str>,
+    /// The key and value for the field just visited.
+    value: Option<(String, &'a NestedValue)>,
+}
+
+impl<'de> MapAccess<'de> for NestedFieldAccess<'_> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        for field in self.fields.by_ref() {
+            let key = variable_name(field);
+            if let Some(value) = self.map.get(&key) {
+                self.value = Some((key, value));
+                let field: StrDeserializer<'_, DeError> = field.into_deserializer();
+                return seed.deserialize(field).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(NestedDeserializer { value })
+            .map_err(|error| error.in_variable(&key))
+    }
+}
+
+/// Visits every entry of a map.
+struct EntryAccess<'a> {
+    entries: btree_map::Iter<'a, String, NestedValue>,
+    /// The key and value for the entry just visited.
+    value: Option<(&'a String, &'a NestedValue)>,
+}
+
+impl<'de> MapAccess<'de> for EntryAccess<'_> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((key, value));
+        let key: StrDeserializer<'_, DeError> = key.as_str().into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(NestedDeserializer { value })
+            .map_err(|error| error.in_variable(key))
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, DeError> for ValueDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use serde::Deserialize;
+
+    use super::{from_env, from_nested, DeError};
+    use crate::{
+        test_helpers::fake_env, to_nested_map, Environment, FakeEnvironment, InMemorySecretSource,
+        SecretsOverlayEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum LogLevel {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Port(u16);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        database_url: String,
+        port: Port,
+        workers: Option<u32>,
+        timeout_secs: f64,
+        debug: bool,
+        log_level: LogLevel,
+        allowed_hosts: Vec<String>,
+        retry_delays_ms: Vec<u64>,
+        #[serde(default)]
+        feature_flags: Vec<String>,
+        #[serde(rename = "MYAPP_REGION")]
+        region: Option<String>,
+    }
+
+    fn complete_config_env() -> FakeEnvironment {
+        fake_env(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("PORT", "8080"),
+            ("TIMEOUT_SECS", "2.5"),
+            ("DEBUG", "true"),
+            ("LOG_LEVEL", "info"),
+            ("ALLOWED_HOSTS", "example.com, example.org"),
+            ("RETRY_DELAYS_MS", "100,200,400"),
+            ("MYAPP_REGION", "eu-west-1"),
+        ])
+    }
+
+    #[test]
+    fn given_a_realistic_environment_when_deserializing_then_every_field_is_parsed() {
+        // Arrange
+        let env = complete_config_env();
+
+        // Act
+        let config: Config = from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(
+            config,
+            Config {
+                database_url: "postgres://localhost/app".into(),
+                port: Port(8080),
+                workers: None,
+                timeout_secs: 2.5,
+                debug: true,
+                log_level: LogLevel::Info,
+                allowed_hosts: vec!["example.com".into(), "example.org".into()],
+                retry_delays_ms: vec![100, 200, 400],
+                feature_flags: vec![],
+                region: Some("eu-west-1".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn given_an_empty_list_variable_when_deserializing_then_the_list_is_empty() {
+        // Arrange
+        let mut env = complete_config_env();
+        env.set_var("ALLOWED_HOSTS", "");
+        env.set_var("WORKERS", "4");
+
+        // Act
+        let config: Config = from_env(&env).unwrap();
+
+        // Assert
+        assert!(config.allowed_hosts.is_empty());
+        assert_eq!(config.workers, Some(4));
+    }
+
+    #[test]
+    fn given_a_required_variable_not_set_when_deserializing_then_the_error_names_it() {
+        // Arrange
+        let mut env = complete_config_env();
+        env.remove_var("DATABASE_URL");
+
+        // Act
+        let result = from_env::<Config>(&env);
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error, DeError::Missing("DATABASE_URL".into()));
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"DATABASE_URL\" is not set"
+        );
+    }
+
+    #[test]
+    fn given_an_unparsable_number_when_deserializing_then_the_error_names_the_variable() {
+        // Arrange
+        let mut env = complete_config_env();
+        env.set_var("PORT", "eighty");
+
+        // Act
+        let result = from_env::<Config>(&env);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "environment variable \"PORT\" is invalid: cannot parse \"eighty\": invalid digit \
+             found in string"
+        );
+    }
+
+    #[test]
+    fn given_an_unparsable_list_element_when_deserializing_then_the_error_names_the_variable() {
+        // Arrange
+        let mut env = complete_config_env();
+        env.set_var("RETRY_DELAYS_MS", "100,soon");
+
+        // Act
+        let result = from_env::<Config>(&env);
+
+        // Assert
+        assert!(matches!(
+            result.unwrap_err(),
+            DeError::Invalid { variable, .. } if variable == "RETRY_DELAYS_MS"
+        ));
+    }
+
+    #[test]
+    fn given_invalid_bool_and_enum_values_when_deserializing_then_the_errors_name_the_variables() {
+        // Arrange
+        let mut bad_bool = complete_config_env();
+        bad_bool.set_var("DEBUG", "yes please");
+        let mut bad_enum = complete_config_env();
+        bad_enum.set_var("LOG_LEVEL", "verbose");
+
+        // Act
+        let bool_result = from_env::<Config>(&bad_bool);
+        let enum_result = from_env::<Config>(&bad_enum);
+
+        // Assert
+        assert!(matches!(
+            bool_result.unwrap_err(),
+            DeError::Invalid { variable, .. } if variable == "DEBUG"
+        ));
+        assert!(matches!(
+            enum_result.unwrap_err(),
+            DeError::Invalid { variable, .. } if variable == "LOG_LEVEL"
+        ));
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_deserializing_then_it_is_a_not_unicode_error() {
+        // Arrange
+        let mut env = complete_config_env();
+        env.set_var("DATABASE_URL", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = from_env::<Config>(&env);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DeError::NotUnicode("DATABASE_URL".into())
+        );
+    }
+
+    #[test]
+    fn given_a_secret_that_cannot_be_fetched_when_deserializing_then_it_is_an_unavailable_error() {
+        // Arrange
+        let source = InMemorySecretSource::new().with_failure("DATABASE_URL", "timed out");
+        let env =
+            SecretsOverlayEnvironment::new(complete_config_env(), source).secret("DATABASE_URL");
+
+        // Act
+        let result = from_env::<Config>(&env);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DeError::Unavailable {
+                variable: "DATABASE_URL".into(),
+                message: "timed out".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn when_deserializing_something_other_than_a_struct_then_it_is_an_error() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let result = from_env::<u32>(&env);
+
+        // Assert
+        assert!(matches!(result.unwrap_err(), DeError::Message(_)));
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Service {
+        name: String,
+        db: Database,
+        cache: Option<Cache>,
+        labels: HashMap<String, String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Database {
+        host: String,
+        port: u16,
+        replicas: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Cache {
+        ttl_secs: u64,
+    }
+
+    #[test]
+    fn given_a_nested_tree_when_deserializing_then_nested_structs_and_maps_are_read() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_NAME", "api"),
+            ("MYAPP_DB__HOST", "localhost"),
+            ("MYAPP_DB__PORT", "5432"),
+            ("MYAPP_DB__REPLICAS", "r1,r2"),
+            ("MYAPP_LABELS__team", "core"),
+        ]);
+        let tree = to_nested_map(&env, "MYAPP_", "__").unwrap();
+
+        // Act
+        let service: Service = from_nested(&tree).unwrap();
+
+        // Assert
+        assert_eq!(
+            service,
+            Service {
+                name: "api".into(),
+                db: Database {
+                    host: "localhost".into(),
+                    port: 5432,
+                    replicas: vec!["r1".into(), "r2".into()],
+                },
+                cache: None,
+                labels: HashMap::from([("team".into(), "core".into())]),
+            }
+        );
+    }
+
+    #[test]
+    fn given_a_bad_nested_value_when_deserializing_then_the_error_names_the_field() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_NAME", "api"),
+            ("MYAPP_DB__HOST", "localhost"),
+            ("MYAPP_DB__PORT", "many"),
+            ("MYAPP_DB__REPLICAS", ""),
+            ("MYAPP_LABELS__team", "core"),
+        ]);
+        let tree = to_nested_map(&env, "MYAPP_", "__").unwrap();
+
+        // Act
+        let result = from_nested::<Service>(&tree);
+
+        // Assert
+        assert!(matches!(
+            result.unwrap_err(),
+            DeError::Invalid { variable, .. } if variable == "PORT"
+        ));
+    }
+
+    #[test]
+    fn given_a_leaf_where_a_struct_is_expected_when_deserializing_then_it_is_an_error() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_NAME", "api"), ("MYAPP_DB", "postgres://db")]);
+        let tree = to_nested_map(&env, "MYAPP_", "__").unwrap();
+
+        // Act
+        let result = from_nested::<Service>(&tree);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DeError::Invalid {
+                variable: "DB".into(),
+                message: "expected nested variables, found a single value".into(),
+            }
+        );
+    }
+}
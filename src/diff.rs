@@ -0,0 +1,324 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fmt::{self, Display},
+};
+
+use crate::{redaction::MASK, EnumerableEnvironment, RedactionPolicy};
+
+/// How two environments differ, from [`env_diff`](env_diff).
+///
+/// Its `Display` lists each difference on its own line, sorted by key, with
+/// values that are not valid Unicode escaped.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnvDiff {
+    /// The variables only set in the right environment.
+    pub added: Vec<(OsString, OsString)>,
+    /// The variables only set in the left environment.
+    pub removed: Vec<(OsString, OsString)>,
+    /// The variables set in both to different values, as the key, the left
+    /// value, and the right value.
+    pub changed: Vec<(OsString, OsString, OsString)>,
+}
+
+impl EnvDiff {
+    /// Whether the environments hold the same variables with the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The same differences with the values `policy` covers masked, to show
+    /// or log them.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{env_diff, Environment, FakeEnvironment, RedactionPolicy};
+    /// let left = FakeEnvironment::new();
+    /// let mut right = FakeEnvironment::new();
+    /// right.set_var("API_TOKEN", "abc123");
+    ///
+    /// let diff = env_diff(&left, &right).redacted(&RedactionPolicy::new().secret("*_TOKEN"));
+    ///
+    /// assert_eq!(diff.to_string(), "environments differ:\n  + \"API_TOKEN\"=\"***\"");
+    /// ```
+    pub fn redacted(mut self, policy: &RedactionPolicy) -> Self {
+        let redact = |key: &OsString, value: &mut OsString| {
+            if policy.redacts(key, &*value) {
+                *value = MASK.into();
+            }
+        };
+        for (key, value) in self.added.iter_mut().chain(&mut self.removed) {
+            redact(key, value);
+        }
+        for (key, left, right) in &mut self.changed {
+            redact(key, left);
+            redact(key, right);
+        }
+        self
+    }
+}
+
+impl Display for EnvDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "environments are equal");
+        }
+        write!(f, "environments differ:")?;
+        for (key, value) in &self.removed {
+            write!(f, "\n  - {key:?}={value:?}")?;
+        }
+        for (key, value) in &self.added {
+            write!(f, "\n  + {key:?}={value:?}")?;
+        }
+        for (key, left, right) in &self.changed {
+            write!(f, "\n  ~ {key:?}: {left:?} -> {right:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How `right` differs from `left`, comparing every variable, including
+/// those whose keys or values are not valid Unicode.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{env_diff, Environment, FakeEnvironment};
+/// let mut left = FakeEnvironment::new();
+/// left.set_var("HOST", "localhost");
+/// left.set_var("PORT", "8080");
+/// let mut right = left.clone();
+/// right.set_var("PORT", "9090");
+///
+/// let diff = env_diff(&left, &right);
+///
+/// assert_eq!(
+///     diff.to_string(),
+///     "environments differ:\n  ~ \"PORT\": \"8080\" -> \"9090\""
+/// );
+/// ```
+pub fn env_diff(left: &impl EnumerableEnvironment, right: &impl EnumerableEnvironment) -> EnvDiff {
+    let mut left: HashMap<OsString, OsString> = left.vars_os().into_iter().collect();
+    let mut diff = EnvDiff::default();
+    for (key, right_value) in right.vars_os() {
+        match left.remove(&key) {
+            None => diff.added.push((key, right_value)),
+            Some(left_value) if left_value != right_value => {
+                diff.changed.push((key, left_value, right_value))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed.extend(left);
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+    diff
+}
+
+/// Whether `left` and `right` hold the same variables with the same values,
+/// such as a [`FakeEnvironment`](crate::FakeEnvironment) and the
+/// [`RealEnvironment`](crate::RealEnvironment) it was meant to match.
+///
+/// To see how they differ, use [`env_diff`](env_diff), or
+/// [`assert_env_eq!`](crate::assert_env_eq) in tests.
+pub fn env_eq(left: &impl EnumerableEnvironment, right: &impl EnumerableEnvironment) -> bool {
+    env_diff(left, right).is_empty()
+}
+
+/// Assert that two environments hold the same variables with the same
+/// values, as [`env_eq`](crate::env_eq) checks.
+///
+/// On failure, the panic message lists only the variables that differ, as
+/// [`EnvDiff`](crate::EnvDiff) displays them, after the optional message.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{assert_env_eq, Environment, FakeEnvironment};
+/// let mut expected = FakeEnvironment::new();
+/// expected.set_var("MODE", "fast");
+/// let mut actual = FakeEnvironment::new();
+/// actual.set_var("MODE", "fast");
+///
+/// assert_env_eq!(actual, expected);
+/// assert_env_eq!(actual, expected, "after setting {}", "MODE");
+/// ```
+#[macro_export]
+macro_rules! assert_env_eq {
+    ($left:expr, $right:expr $(,)?) => {{
+        let diff = $crate::env_diff(&$left, &$right);
+        if !diff.is_empty() {
+            ::std::panic!("assertion `left == right` failed: {}", diff);
+        }
+    }};
+    ($left:expr, $right:expr, $($arg:tt)+) => {{
+        let diff = $crate::env_diff(&$left, &$right);
+        if !diff.is_empty() {
+            ::std::panic!(
+                "assertion `left == right` failed: {}\n{}",
+                ::std::format_args!($($arg)+),
+                diff
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        os::unix::ffi::OsStrExt,
+        panic,
+    };
+
+    use super::{env_diff, env_eq};
+    use crate::{
+        test_helpers::fake_env, test_helpers::random_upper, EnumerableEnvironment, Environment,
+        FakeEnvironment, RealEnvironment, RedactionPolicy,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn given_environments_built_differently_with_the_same_variables_when_comparing_then_they_are_equal(
+    ) {
+        // Arrange
+        let left = fake_env(&[("A", "1"), ("B", "2")]);
+        let mut right = fake_env(&[("B", "2"), ("C", "3")]);
+        right.set_var("A", "1");
+        right.remove_var("C");
+
+        // Act
+        let equal = env_eq(&left, &right);
+
+        // Assert
+        assert!(equal);
+        assert!(env_diff(&left, &right).is_empty());
+        assert_env_eq!(left, right);
+    }
+
+    #[test]
+    fn given_a_copy_of_the_real_environment_when_diffing_then_the_copied_variables_match() {
+        // Arrange
+        let key = random_upper();
+        RealEnvironment.set_var(&key, OsStr::from_bytes(&INVALID_UTF8));
+        let mut fake = FakeEnvironment::new();
+        for (key, value) in RealEnvironment.vars_os() {
+            fake.set_var(key, value);
+        }
+
+        // Act
+        let diff = env_diff(&fake, &RealEnvironment);
+        RealEnvironment.remove_var(&key);
+
+        // Assert
+        assert!(diff.added.iter().all(|(added, _)| *added != *key));
+        assert!(diff.changed.iter().all(|(changed, ..)| *changed != *key));
+        assert!(diff.removed.iter().all(|(removed, _)| *removed != *key));
+    }
+
+    #[test]
+    fn given_one_differing_key_when_diffing_then_only_it_is_reported() {
+        // Arrange
+        let left = fake_env(&[("SAME", "x"), ("GONE", "old"), ("MODE", "fast")]);
+        let right = fake_env(&[("SAME", "x"), ("NEW", "new"), ("MODE", "slow")]);
+
+        // Act
+        let diff = env_diff(&left, &right);
+
+        // Assert
+        assert!(!env_eq(&left, &right));
+        assert_eq!(diff.added, [("NEW".into(), "new".into())]);
+        assert_eq!(diff.removed, [("GONE".into(), "old".into())]);
+        assert_eq!(
+            diff.changed,
+            [("MODE".into(), "fast".into(), "slow".into())]
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_diffing_then_it_is_compared_and_shown_escaped() {
+        // Arrange
+        let mut left = FakeEnvironment::new();
+        left.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        let mut right = FakeEnvironment::new();
+        right.set_var("BINARY", "foo");
+
+        // Act
+        let diff = env_diff(&left, &right);
+
+        // Assert
+        assert_eq!(
+            diff.changed,
+            [(
+                OsString::from("BINARY"),
+                OsStr::from_bytes(&INVALID_UTF8).to_owned(),
+                OsString::from("foo")
+            )]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "environments differ:\n  ~ \"BINARY\": \"fo\\x80o\" -> \"foo\""
+        );
+    }
+
+    #[test]
+    fn given_a_redaction_policy_when_rendering_a_diff_then_secret_values_are_masked() {
+        // Arrange
+        let left = fake_env(&[
+            ("API_TOKEN", "old-token"),
+            ("GONE_SECRET", "s1"),
+            ("MODE", "a"),
+        ]);
+        let right = fake_env(&[
+            ("API_TOKEN", "new-token"),
+            ("NEW_SECRET", "s2"),
+            ("MODE", "b"),
+        ]);
+        let policy = RedactionPolicy::new().secret("*_TOKEN").secret("*_SECRET");
+
+        // Act
+        let rendered = env_diff(&left, &right).redacted(&policy).to_string();
+
+        // Assert
+        assert_eq!(
+            rendered,
+            concat!(
+                "environments differ:\n",
+                "  - \"GONE_SECRET\"=\"***\"\n",
+                "  + \"NEW_SECRET\"=\"***\"\n",
+                "  ~ \"API_TOKEN\": \"***\" -> \"***\"\n",
+                "  ~ \"MODE\": \"a\" -> \"b\"",
+            )
+        );
+    }
+
+    #[test]
+    fn given_differing_environments_when_asserting_equality_then_the_message_lists_only_the_differences(
+    ) {
+        // Arrange
+        let left = fake_env(&[("SAME", "x"), ("GONE", "old"), ("MODE", "fast")]);
+        let right = fake_env(&[("SAME", "x"), ("NEW", "new"), ("MODE", "slow")]);
+
+        // Act
+        let message = panic_message(|| assert_env_eq!(left, right));
+        let with_context = panic_message(|| assert_env_eq!(left, right, "in case {}", 3));
+
+        // Assert
+        assert_eq!(
+            message,
+            concat!(
+                "assertion `left == right` failed: environments differ:\n",
+                "  - \"GONE\"=\"old\"\n",
+                "  + \"NEW\"=\"new\"\n",
+                "  ~ \"MODE\": \"fast\" -> \"slow\"",
+            )
+        );
+        assert!(with_context.starts_with("assertion `left == right` failed: in case 3\n"));
+        assert!(!with_context.contains("SAME"));
+    }
+}
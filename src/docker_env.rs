@@ -0,0 +1,201 @@
+use std::io::{self, ErrorKind};
+
+use crate::EnumerableEnvironment;
+
+/// Export of an environment as a file for `docker run --env-file`.
+///
+/// Docker reads each line of such a file as `KEY=VALUE`, taking the value
+/// literally up to the end of the line: there is no quoting or escaping, and
+/// no way to continue a value onto the next line.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{DockerEnvFileExt, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+/// fake_env.set_var("GREETING", "hello \"world\"");
+///
+/// let file = fake_env.to_docker_env_file().unwrap();
+///
+/// assert_eq!(file, "GREETING=hello \"world\"\nPORT=8080\n");
+/// ```
+pub trait DockerEnvFileExt: EnumerableEnvironment {
+    /// Write every variable as a `KEY=VALUE` line, sorted by key. Values are
+    /// written exactly as they are, including any quotes and surrounding
+    /// whitespace, since Docker keeps them.
+    ///
+    /// # Errors
+    /// * If a value contains a newline, carriage return, or NUL, or a key is
+    ///   empty, contains `=` or whitespace, or starts with `#`, so Docker
+    ///   would read the line differently, it returns an
+    ///   `ErrorKind::InvalidInput` error.
+    /// * If a key or value is not valid Unicode, it returns an
+    ///   `ErrorKind::InvalidData` error.
+    fn to_docker_env_file(&self) -> io::Result<String> {
+        let mut vars = self
+            .vars_os()
+            .into_iter()
+            .map(
+                |(key, value)| match (key.into_string(), value.into_string()) {
+                    (Ok(key), Ok(value)) => Ok((key, value)),
+                    (key, _) => {
+                        let key = key.unwrap_or_else(|key| key.to_string_lossy().into_owned());
+                        Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("environment variable {key:?} is not valid Unicode"),
+                        ))
+                    }
+                },
+            )
+            .collect::<io::Result<Vec<_>>>()?;
+        vars.sort();
+
+        let mut file = String::new();
+        for (key, value) in vars {
+            if !is_docker_key(&key) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{key:?} cannot be a key in a Docker env file"),
+                ));
+            }
+            if value.contains(['\n', '\r', '\0']) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "environment variable {key:?} contains a newline or NUL, which a Docker \
+                         env file cannot hold"
+                    ),
+                ));
+            }
+            file.push_str(&key);
+            file.push('=');
+            file.push_str(&value);
+            file.push('\n');
+        }
+        Ok(file)
+    }
+}
+
+impl<E: EnumerableEnvironment> DockerEnvFileExt for E {}
+
+/// Whether Docker reads `key=` back with the same key, rather than as a
+/// comment or a malformed line.
+fn is_docker_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('#')
+        && !key.contains(|c: char| c == '=' || c == '\0' || c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, io::ErrorKind, os::unix::ffi::OsStrExt};
+
+    use super::DockerEnvFileExt;
+    use crate::{test_helpers::fake_env, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    /// Parse a Docker env file the way `docker run --env-file` does.
+    fn parse_docker_env_file(file: &str) -> Vec<(String, String)> {
+        file.lines()
+            .map(str::trim_start)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (key, value) = line.split_once('=').expect("a value is given");
+                (key.to_owned(), value.to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn given_variables_when_exporting_then_lines_are_sorted_by_key() {
+        // Arrange
+        let env = fake_env(&[
+            ("PORT", "8080"),
+            ("DATABASE_URL", "postgres://user:p@ss=word@db/app"),
+            ("EMPTY", ""),
+        ]);
+
+        // Act
+        let file = env.to_docker_env_file().unwrap();
+
+        // Assert
+        assert_eq!(
+            file,
+            "DATABASE_URL=postgres://user:p@ss=word@db/app\nEMPTY=\nPORT=8080\n"
+        );
+    }
+
+    #[test]
+    fn given_values_with_spaces_and_quotes_when_parsed_back_then_they_are_unchanged() {
+        // Arrange
+        let env = fake_env(&[
+            ("GREETING", "  hello   world  "),
+            ("QUOTED", "'single' and \"double\""),
+            ("HASH", "# not a comment"),
+        ]);
+
+        // Act
+        let file = env.to_docker_env_file().unwrap();
+
+        // Assert
+        assert_eq!(
+            parse_docker_env_file(&file),
+            vec![
+                ("GREETING".to_owned(), "  hello   world  ".to_owned()),
+                ("HASH".to_owned(), "# not a comment".to_owned()),
+                ("QUOTED".to_owned(), "'single' and \"double\"".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_value_with_a_newline_when_exporting_then_it_is_refused() {
+        // Arrange
+        let env = fake_env(&[("CERT", "-----BEGIN-----\nabc")]);
+
+        // Act
+        let result = env.to_docker_env_file();
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"CERT\" contains a newline or NUL, which a Docker env file \
+             cannot hold"
+        );
+    }
+
+    #[test]
+    fn given_a_key_docker_would_misread_when_exporting_then_it_is_refused() {
+        for key in ["#COMMENT", "WITH SPACE"] {
+            // Arrange
+            let env = fake_env(&[(key, "x")]);
+
+            // Act
+            let result = env.to_docker_env_file();
+
+            // Assert
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput, "{key}");
+        }
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_exporting_then_it_is_invalid_data() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = env.to_docker_env_file();
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"BINARY\" is not valid Unicode"
+        );
+    }
+}
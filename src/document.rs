@@ -0,0 +1,131 @@
+use std::{error::Error, ffi::OsString, fmt};
+
+use crate::encoding::{base64_decode, base64_encode, os_string_from_bytes};
+
+/// The key of the object that holds a base64-encoded value, with
+/// [`NonUnicode::Base64`](NonUnicode::Base64).
+pub(crate) const BASE64_TAG: &str = "base64";
+
+/// How converting an environment to and from a JSON or YAML document treats
+/// names and values that are not valid Unicode.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum NonUnicode {
+    /// Exporting fails with [`DocumentError::NotUnicode`], and importing
+    /// accepts only string values.
+    Error,
+    /// Exporting replaces invalid sequences with `U+FFFD`, and importing
+    /// accepts only string values. Round trips lose the original bytes.
+    Lossy,
+    /// Exporting writes a value that is not valid Unicode as an object
+    /// holding its bytes in base64, like `{"base64": "Zm+Abw=="}`, and
+    /// importing decodes such objects. Names cannot be encoded this way, so
+    /// a name that is not valid Unicode is still an error.
+    ///
+    /// Decoding bytes that are not valid Unicode is only supported on Unix,
+    /// where variables can hold any bytes.
+    Base64,
+}
+
+/// An error from converting an environment to or from a JSON or YAML
+/// document.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DocumentError {
+    /// The top level of the document is not an object or map.
+    NotAnObject,
+    /// A key of the top-level map is not a string. Holds the key as written
+    /// in the document.
+    InvalidKey(String),
+    /// The value of the named variable is neither a string nor, with
+    /// [`NonUnicode::Base64`](NonUnicode::Base64), a base64-encoded object.
+    InvalidValue(String),
+    /// The name or value of the named variable is not valid Unicode.
+    NotUnicode(String),
+    /// The base64-encoded value of the named variable cannot be decoded.
+    InvalidBase64(String),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::NotAnObject => {
+                write!(f, "the document is not an object of environment variables")
+            }
+            DocumentError::InvalidKey(key) => {
+                write!(f, "the key {key} is not an environment variable name")
+            }
+            DocumentError::InvalidValue(name) => {
+                write!(
+                    f,
+                    "environment variable {name:?} does not have a string value"
+                )
+            }
+            DocumentError::NotUnicode(name) => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            DocumentError::InvalidBase64(name) => {
+                write!(f, "environment variable {name:?} is not valid base64")
+            }
+        }
+    }
+}
+
+impl Error for DocumentError {}
+
+/// A value as written in a document.
+pub(crate) enum Encoded {
+    Text(String),
+    Base64(String),
+}
+
+/// Encode every variable for a document, sorted by name.
+pub(crate) fn encode_vars(
+    vars: Vec<(OsString, OsString)>,
+    policy: NonUnicode,
+) -> Result<Vec<(String, Encoded)>, DocumentError> {
+    let mut encoded = vars
+        .into_iter()
+        .map(|(name, value)| {
+            let name = match (name.into_string(), policy) {
+                (Ok(name), _) => name,
+                (Err(name), NonUnicode::Lossy) => name.to_string_lossy().into_owned(),
+                (Err(name), _) => {
+                    return Err(DocumentError::NotUnicode(
+                        name.to_string_lossy().into_owned(),
+                    ))
+                }
+            };
+            let value = match (value.into_string(), policy) {
+                (Ok(value), _) => Encoded::Text(value),
+                (Err(value), NonUnicode::Lossy) => {
+                    Encoded::Text(value.to_string_lossy().into_owned())
+                }
+                (Err(value), NonUnicode::Base64) => {
+                    Encoded::Base64(base64_encode(value.as_encoded_bytes()))
+                }
+                (Err(_), NonUnicode::Error) => return Err(DocumentError::NotUnicode(name)),
+            };
+            Ok((name, value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    encoded.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(encoded)
+}
+
+/// Decode the value of the variable `name` from a document.
+pub(crate) fn decode_value(
+    name: &str,
+    value: Encoded,
+    policy: NonUnicode,
+) -> Result<OsString, DocumentError> {
+    match (value, policy) {
+        (Encoded::Text(text), _) => Ok(text.into()),
+        (Encoded::Base64(encoded), NonUnicode::Base64) => {
+            let bytes =
+                base64_decode(&encoded).ok_or_else(|| DocumentError::InvalidBase64(name.into()))?;
+            os_string_from_bytes(bytes).ok_or_else(|| DocumentError::NotUnicode(name.into()))
+        }
+        (Encoded::Base64(_), _) => Err(DocumentError::InvalidValue(name.into())),
+    }
+}
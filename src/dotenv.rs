@@ -0,0 +1,812 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    env::VarError,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    EnumerableEnvironment, Environment, FakeEnvironment, Provenance, ReadEnvironment,
+    SourcedEnvironment,
+};
+
+/// An environment read from a dotenv (`.env`) file, which can be reloaded
+/// when the file changes.
+///
+/// Writes and removals are kept in an in-memory overlay that shadows the
+/// file. The overlay survives [`reload`](DotenvEnvironment::reload), and the
+/// file is only written by [`persist`](DotenvEnvironment::persist).
+///
+/// The file holds one `KEY=VALUE` assignment per line, optionally preceded by
+/// `export`. Blank lines and lines starting with `#` are ignored. Values may
+/// be:
+/// * unquoted, with surrounding whitespace and any ` #` comment removed,
+/// * single-quoted, taken literally, or
+/// * double-quoted, with `\n`, `\r`, `\t`, `\"`, and `\\` escapes.
+///
+/// Values are not expanded, and may not span several lines.
+///
+/// # Example
+/// ```rust,no_run
+/// # use env_wrapper::{DotenvEnvironment, Environment, ReadEnvironment};
+/// let mut env = DotenvEnvironment::open(".env")?;
+/// env.set_var("LOG_LEVEL", "debug");
+///
+/// // Pick up edits to the file, keeping LOG_LEVEL=debug.
+/// env.reload()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DotenvEnvironment {
+    path: PathBuf,
+    file_vars: HashMap<OsString, OsString>,
+    // `None` marks a variable removed in memory.
+    overlay: HashMap<OsString, Option<OsString>>,
+}
+
+impl DotenvEnvironment {
+    /// Read and parse the dotenv file at `path`.
+    ///
+    /// # Errors
+    /// * If the file cannot be read, it returns the I/O error.
+    /// * If the file is not valid UTF-8 or a line cannot be parsed, it
+    ///   returns an `ErrorKind::InvalidData` error naming the line.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file_vars = read_dotenv(&path)?;
+        Ok(DotenvEnvironment {
+            path,
+            file_vars,
+            overlay: HashMap::new(),
+        })
+    }
+
+    /// The path of the dotenv file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-read the dotenv file, keeping the in-memory changes on top of it.
+    ///
+    /// # Errors
+    /// As for [`open`](DotenvEnvironment::open). On error, the previously
+    /// read variables are kept.
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.file_vars = read_dotenv(&self.path)?;
+        Ok(())
+    }
+
+    /// The path, file variables, and in-memory overlay of the environment.
+    #[cfg(feature = "watch")]
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        PathBuf,
+        HashMap<OsString, OsString>,
+        HashMap<OsString, Option<OsString>>,
+    ) {
+        (self.path, self.file_vars, self.overlay)
+    }
+
+    /// Write the current variables, including in-memory changes, back to the
+    /// dotenv file, sorted by key. Comments and formatting in the file are
+    /// not kept.
+    ///
+    /// # Errors
+    /// * If a key or value is not valid Unicode, or a key contains characters
+    ///   other than ASCII letters, digits, `_`, `.`, and `-`, it returns an
+    ///   `ErrorKind::InvalidData` error.
+    /// * If the file cannot be written, it returns the I/O error.
+    pub fn persist(&mut self) -> io::Result<()> {
+        let vars: BTreeMap<_, _> = self.vars_os().into_iter().collect();
+        let mut contents = String::new();
+        for (key, value) in &vars {
+            let (Some(key), Some(value)) = (key.to_str(), value.to_str()) else {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("cannot write {key:?} to a dotenv file: it is not valid Unicode"),
+                ));
+            };
+            if !is_valid_key(key) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("cannot write {key:?} to a dotenv file: it is not a valid key"),
+                ));
+            }
+            contents.push_str(&format!("{key}=\"{}\"\n", escape(value)));
+        }
+        fs::write(&self.path, contents)?;
+        self.file_vars = vars.into_iter().collect();
+        self.overlay.clear();
+        Ok(())
+    }
+}
+
+/// Whether [`load_dotenv`](load_dotenv) replaces variables that are already
+/// set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Override {
+    /// Keep the existing values, skipping those in the file.
+    No,
+    /// Replace the existing values with those in the file.
+    Yes,
+}
+
+/// Which variables [`load_dotenv`](load_dotenv) set, and which it skipped
+/// because they were already set. Both are sorted.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LoadSummary {
+    pub set: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// Read the dotenv file at `path` and apply its variables to `env`, keeping
+/// or replacing those already set according to `policy`. The file format is
+/// the one [`DotenvEnvironment`](DotenvEnvironment) reads.
+///
+/// # Errors
+/// As for [`DotenvEnvironment::open`](DotenvEnvironment::open). On error,
+/// `env` is left unchanged.
+///
+/// # Example
+/// ```rust,no_run
+/// # use env_wrapper::{load_dotenv, Override, RealEnvironment};
+/// let summary = load_dotenv(".env", &mut RealEnvironment, Override::No)?;
+/// println!("loaded {:?}, kept existing {:?}", summary.set, summary.skipped);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn load_dotenv(
+    path: impl AsRef<Path>,
+    env: &mut impl Environment,
+    policy: Override,
+) -> io::Result<LoadSummary> {
+    let vars: BTreeMap<OsString, OsString> = read_dotenv(path.as_ref())?;
+    let mut summary = LoadSummary::default();
+    for (key, value) in vars {
+        let name = key.to_string_lossy().into_owned();
+        if policy == Override::No && env.contains(&key) {
+            summary.skipped.push(name);
+        } else {
+            env.set_var(&key, value);
+            summary.set.push(name);
+        }
+    }
+    Ok(summary)
+}
+
+impl FakeEnvironment {
+    /// A fake environment with the variables of the dotenv file read from
+    /// `reader`, in the format [`DotenvEnvironment`](DotenvEnvironment)
+    /// reads.
+    ///
+    /// The file is read a line at a time into the same buffer, and each key
+    /// and value is only copied to store it, so large generated fixtures load
+    /// quickly.
+    ///
+    /// # Errors
+    /// * If `reader` fails, it returns the I/O error.
+    /// * If a line is not valid UTF-8 or cannot be parsed, it returns an
+    ///   `ErrorKind::InvalidData` error naming the line.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{FakeEnvironment, ReadEnvironment};
+    /// let file = b"# generated\nPORT=8080\nGREETING=\"hello\\nworld\"\n";
+    ///
+    /// let fake_env = FakeEnvironment::from_dotenv_reader(&file[..])?;
+    ///
+    /// assert_eq!(fake_env.var("GREETING").unwrap(), "hello\nworld");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_dotenv_reader(reader: impl Read) -> io::Result<Self> {
+        let mut env = FakeEnvironment::new();
+        parse_dotenv(BufReader::new(reader), |key, value| env.set_var(key, value))?;
+        Ok(env)
+    }
+}
+
+/// Read the dotenv file at `path` into a map of its variables.
+pub(crate) fn read_dotenv<M>(path: &Path) -> io::Result<M>
+where
+    M: Default + Extend<(OsString, OsString)>,
+{
+    let mut vars = M::default();
+    parse_dotenv(BufReader::new(File::open(path)?), |key, value| {
+        vars.extend([(key.into(), value.into())]);
+    })
+    .map_err(|error| match error.kind() {
+        ErrorKind::InvalidData => io::Error::new(error.kind(), format!("{path:?}: {error}")),
+        _ => error,
+    })?;
+    Ok(vars)
+}
+
+/// Parse dotenv file contents from `reader`, passing each assignment to
+/// `assign` in order, so later assignments to the same key win.
+///
+/// Each line is read into the same buffer, and the key and value are
+/// borrowed from it, unless the value has escapes to replace.
+fn parse_dotenv(mut reader: impl BufRead, mut assign: impl FnMut(&str, &str)) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        number += 1;
+        let parsed = std::str::from_utf8(&line)
+            .map_err(|_| "not valid UTF-8".to_string())
+            .and_then(parse_line);
+        match parsed {
+            Ok(Some((key, value))) => assign(key, &value),
+            Ok(None) => {}
+            Err(message) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {number}: {message}"),
+                ))
+            }
+        }
+    }
+}
+
+/// Parse one line, which may end with its newline, into an assignment, or
+/// `None` if it is blank or a comment.
+fn parse_line(line: &str) -> Result<Option<(&str, Cow<'_, str>)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line
+        .strip_prefix("export")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map_or(line, str::trim_start);
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=VALUE".to_string())?;
+    let key = key.trim_end();
+    if !is_valid_key(key) {
+        return Err(format!("{key:?} is not a valid key"));
+    }
+    Ok(Some((key, parse_value(value.trim_start())?)))
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.' | b'-'))
+}
+
+fn parse_value(value: &str) -> Result<Cow<'_, str>, String> {
+    let (parsed, rest) = match value.as_bytes().first() {
+        Some(b'\'') => {
+            let end = value[1..]
+                .find('\'')
+                .ok_or("unterminated single-quoted value")?;
+            (Cow::Borrowed(&value[1..=end]), &value[end + 2..])
+        }
+        Some(b'"') => parse_double_quoted(&value[1..])?,
+        _ => {
+            // A comment starts at a `#` after whitespace.
+            let end = value
+                .match_indices('#')
+                .find(|&(index, _)| value[..index].ends_with(char::is_whitespace))
+                .map_or(value.len(), |(index, _)| index);
+            return Ok(Cow::Borrowed(value[..end].trim_end()));
+        }
+    };
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(parsed)
+    } else {
+        Err(format!("unexpected {rest:?} after the closing quote"))
+    }
+}
+
+/// Parse a double-quoted value after its opening quote, returning the value
+/// and the text after its closing quote. The value is borrowed unless it has
+/// escapes.
+fn parse_double_quoted(value: &str) -> Result<(Cow<'_, str>, &str), String> {
+    const UNTERMINATED: &str = "unterminated double-quoted value";
+    let end = value.find(['"', '\\']).ok_or(UNTERMINATED)?;
+    if value[end..].starts_with('"') {
+        return Ok((Cow::Borrowed(&value[..end]), &value[end + 1..]));
+    }
+    let mut parsed = String::from(&value[..end]);
+    let mut rest = &value[end..];
+    loop {
+        // `rest` starts with a `\` or the closing quote.
+        let mut chars = rest.chars();
+        if chars.next() == Some('"') {
+            return Ok((Cow::Owned(parsed), chars.as_str()));
+        }
+        match chars.next().ok_or(UNTERMINATED)? {
+            'n' => parsed.push('\n'),
+            'r' => parsed.push('\r'),
+            't' => parsed.push('\t'),
+            escaped @ ('"' | '\\') => parsed.push(escaped),
+            other => {
+                parsed.push('\\');
+                parsed.push(other);
+            }
+        }
+        let after = chars.as_str();
+        let end = after.find(['"', '\\']).ok_or(UNTERMINATED)?;
+        parsed.push_str(&after[..end]);
+        rest = &after[end..];
+    }
+}
+
+/// Write `value` so it parses back unchanged: as it is if it has nothing the
+/// parser would treat specially, or double-quoted otherwise.
+pub(crate) fn quote(value: &str) -> String {
+    let is_plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+%".contains(c));
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("\"{}\"", escape(value))
+    }
+}
+
+/// Escape `value` for writing inside double quotes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl ReadEnvironment for DotenvEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.overlay.get(key.as_ref()) {
+            Some(value) => value.clone(),
+            None => self.file_vars.get(key.as_ref()).cloned(),
+        }
+    }
+}
+
+/// Values changed in memory come from [`Provenance::Fake`], and the rest
+/// from [`Provenance::DotenvFile`].
+impl SourcedEnvironment for DotenvEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let provenance = if self.overlay.contains_key(key.as_ref()) {
+            Provenance::Fake
+        } else {
+            Provenance::DotenvFile(self.path.clone())
+        };
+        self.var(key).map(|value| (value, provenance))
+    }
+}
+
+impl Environment for DotenvEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.overlay
+            .insert(key.as_ref().into(), Some(value.as_ref().into()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.overlay.insert(key.as_ref().into(), None);
+    }
+}
+
+impl EnumerableEnvironment for DotenvEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged = self.file_vars.clone();
+        for (key, value) in &self.overlay {
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+        merged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        ffi::{OsStr, OsString},
+        fs,
+        io::ErrorKind,
+    };
+
+    use super::{
+        is_valid_key, load_dotenv, parse_dotenv, DotenvEnvironment, LoadSummary, Override,
+    };
+    use crate::{
+        test_helpers::TempFile, EnumerableEnvironment, Environment, FakeEnvironment,
+        ReadEnvironment,
+    };
+
+    fn parse(contents: &str) -> Result<HashMap<OsString, OsString>, String> {
+        let mut vars = HashMap::new();
+        parse_dotenv(contents.as_bytes(), |key, value| {
+            vars.insert(key.into(), value.into());
+        })
+        .map_err(|error| error.to_string())?;
+        Ok(vars)
+    }
+
+    /// The simple line-by-line parser the streaming one replaced, which
+    /// allocates each key and value as it goes, as a reference for what the
+    /// streaming one must produce.
+    mod reference {
+        use std::{collections::HashMap, ffi::OsString};
+
+        use super::is_valid_key;
+
+        pub fn parse_dotenv(contents: &str) -> Result<HashMap<OsString, OsString>, String> {
+            let mut vars = HashMap::new();
+            for (index, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (key, value) =
+                    parse_line(line).map_err(|message| format!("line {}: {message}", index + 1))?;
+                vars.insert(key.into(), value.into());
+            }
+            Ok(vars)
+        }
+
+        fn parse_line(line: &str) -> Result<(&str, String), String> {
+            let line = line
+                .strip_prefix("export")
+                .filter(|rest| rest.starts_with(char::is_whitespace))
+                .map_or(line, str::trim_start);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| "expected KEY=VALUE".to_string())?;
+            let key = key.trim_end();
+            if !is_valid_key(key) {
+                return Err(format!("{key:?} is not a valid key"));
+            }
+            Ok((key, parse_value(value.trim_start())?))
+        }
+
+        fn parse_value(value: &str) -> Result<String, String> {
+            let (parsed, rest) = match value.chars().next() {
+                Some('\'') => {
+                    let end = value[1..]
+                        .find('\'')
+                        .ok_or("unterminated single-quoted value")?;
+                    (value[1..=end].to_string(), &value[end + 2..])
+                }
+                Some('"') => parse_double_quoted(&value[1..])?,
+                _ => {
+                    let end = value
+                        .char_indices()
+                        .find(|&(index, c)| {
+                            c == '#' && value[..index].ends_with(char::is_whitespace)
+                        })
+                        .map_or(value.len(), |(index, _)| index);
+                    return Ok(value[..end].trim_end().to_string());
+                }
+            };
+            let rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                Ok(parsed)
+            } else {
+                Err(format!("unexpected {rest:?} after the closing quote"))
+            }
+        }
+
+        fn parse_double_quoted(value: &str) -> Result<(String, &str), String> {
+            let mut parsed = String::new();
+            let mut chars = value.char_indices();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '"' => return Ok((parsed, &value[index + 1..])),
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => parsed.push('\n'),
+                        Some((_, 'r')) => parsed.push('\r'),
+                        Some((_, 't')) => parsed.push('\t'),
+                        Some((_, escaped @ ('"' | '\\'))) => parsed.push(escaped),
+                        Some((_, other)) => {
+                            parsed.push('\\');
+                            parsed.push(other);
+                        }
+                        None => break,
+                    },
+                    c => parsed.push(c),
+                }
+            }
+            Err("unterminated double-quoted value".into())
+        }
+    }
+
+    #[test]
+    fn given_the_shared_corpus_when_parsing_then_the_result_matches_the_reference_parser() {
+        let corpus = [
+            "",
+            "\n\n",
+            "A=1",
+            "A=1\nA=2\n",
+            "A=1\r\nB=2\r\n",
+            "  # indented comment\n\tA = 1 \n",
+            "A=a#b # c\nB=x\t#y\nC=x\u{a0}#y\nD=#x\nE= #x\n",
+            "export A=1\nexport\tB=2\nexportC=3\n",
+            "export=1\n",
+            "A='single \"x\" \\n' # c\nB=''\n",
+            "A=\"\"\nB=\"plain\"\nC=\"a\\nb\\rc\\td\\\"e\\\\f\\qg\" #c\n",
+            "A=\"\\\\\"\nB=\"ünï\\ncödé\"\nC=ünï cödé\n",
+            "A=\"x\"  \n",
+            "a.b-c_D9=1\n",
+            "NO_EQUALS\n",
+            "=value\n",
+            "BAD KEY=1\n",
+            "KEY!=1\n",
+            "A='open\n",
+            "A=\"open\n",
+            "A=\"open\\\n",
+            "A=\"esc\\\"\n",
+            "A=\"x\"junk\n",
+            "A='x' junk\n",
+            "A=1\nB=\"x\" y\nC=3\n",
+        ];
+        for contents in corpus {
+            // Act
+            let streaming = parse(contents);
+            let reference = reference::parse_dotenv(contents);
+
+            // Assert
+            assert_eq!(streaming, reference, "{contents:?}");
+        }
+    }
+
+    #[test]
+    fn given_a_line_that_is_not_valid_utf8_when_parsing_then_the_line_is_reported() {
+        // Arrange
+        let contents = b"A=1\nB=fo\x80o\n";
+
+        // Act
+        let result = parse_dotenv(&contents[..], |_, _| {});
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 2: not valid UTF-8");
+    }
+
+    #[test]
+    fn given_a_reader_when_building_a_fake_environment_then_it_holds_the_variables() {
+        // Arrange
+        let contents = "# generated\nPORT=8080\nPORT=9090\nGREETING=\"hello\\nworld\"\n";
+
+        // Act
+        let env = FakeEnvironment::from_dotenv_reader(contents.as_bytes()).unwrap();
+
+        // Assert
+        let mut vars = env.vars_os();
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                ("GREETING".into(), "hello\nworld".into()),
+                ("PORT".into(), "9090".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_an_unparsable_reader_when_building_a_fake_environment_then_it_names_the_line() {
+        // Act
+        let result = FakeEnvironment::from_dotenv_reader(&b"A=1\nB\n"[..]);
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 2: expected KEY=VALUE");
+    }
+
+    #[test]
+    fn given_a_dotenv_file_when_parsing_then_each_value_form_is_understood() {
+        // Arrange
+        let contents = "\
+            # a comment\n\
+            \n\
+            PLAIN=value\n\
+            SPACED = spaced value  # trailing comment\n\
+            HASH=a#b\n\
+            export EXPORTED=1\n\
+            SINGLE='literal \\n $HOME'\n\
+            DOUBLE=\"line\\nbreak \\\"quoted\\\"\" # comment\n\
+            EMPTY=\n";
+
+        // Act
+        let vars = parse(contents).unwrap();
+
+        // Assert
+        let var = |key: &str| vars.get(OsStr::new(key)).unwrap().clone();
+        assert_eq!(var("PLAIN"), "value");
+        assert_eq!(var("SPACED"), "spaced value");
+        assert_eq!(var("HASH"), "a#b");
+        assert_eq!(var("EXPORTED"), "1");
+        assert_eq!(var("SINGLE"), "literal \\n $HOME");
+        assert_eq!(var("DOUBLE"), "line\nbreak \"quoted\"");
+        assert_eq!(var("EMPTY"), "");
+        assert_eq!(vars.len(), 7);
+    }
+
+    #[test]
+    fn given_malformed_lines_when_parsing_then_the_line_is_reported() {
+        // Arrange/Act/Assert
+        assert_eq!(
+            parse("A=1\nNO_EQUALS\n").unwrap_err(),
+            "line 2: expected KEY=VALUE"
+        );
+        assert_eq!(
+            parse("A=\"open\n").unwrap_err(),
+            "line 1: unterminated double-quoted value"
+        );
+        assert_eq!(
+            parse("BAD KEY=1\n").unwrap_err(),
+            "line 1: \"BAD KEY\" is not a valid key"
+        );
+    }
+
+    #[test]
+    fn given_a_changed_file_when_reloading_then_added_and_removed_keys_are_picked_up() {
+        // Arrange
+        let file = TempFile::with_contents("KEPT=1\nREMOVED=2\n");
+        let mut env = DotenvEnvironment::open(file.path()).unwrap();
+        fs::write(file.path(), "KEPT=changed\nADDED=3\n").unwrap();
+
+        // Act
+        env.reload().unwrap();
+
+        // Assert
+        assert_eq!(env.var("KEPT").unwrap(), "changed");
+        assert_eq!(env.var("ADDED").unwrap(), "3");
+        assert!(env.var_os("REMOVED").is_none());
+    }
+
+    #[test]
+    fn given_in_memory_changes_when_reloading_then_they_still_shadow_the_file() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=file\nDROPPED=file\n");
+        let mut env = DotenvEnvironment::open(file.path()).unwrap();
+        env.set_var("MODE", "memory");
+        env.set_var("NEW", "memory");
+        env.remove_var("DROPPED");
+        fs::write(file.path(), "MODE=edited\nDROPPED=edited\nOTHER=edited\n").unwrap();
+
+        // Act
+        env.reload().unwrap();
+
+        // Assert
+        let mut vars = env.vars_os();
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                ("MODE".into(), "memory".into()),
+                ("NEW".into(), "memory".into()),
+                ("OTHER".into(), "edited".into()),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(file.path()).unwrap(),
+            "MODE=edited\nDROPPED=edited\nOTHER=edited\n"
+        );
+    }
+
+    #[test]
+    fn given_in_memory_changes_when_persisting_then_the_file_holds_the_merged_variables() {
+        // Arrange
+        let file = TempFile::with_contents("# comment\nMODE=file\nDROPPED=file\n");
+        let mut env = DotenvEnvironment::open(file.path()).unwrap();
+        env.set_var("MODE", "say \"hi\"\n");
+        env.remove_var("DROPPED");
+
+        // Act
+        env.persist().unwrap();
+
+        // Assert
+        assert_eq!(
+            fs::read_to_string(file.path()).unwrap(),
+            "MODE=\"say \\\"hi\\\"\\n\"\n"
+        );
+        let reopened = DotenvEnvironment::open(file.path()).unwrap();
+        assert_eq!(reopened.vars_os(), env.vars_os());
+    }
+
+    #[test]
+    fn given_a_missing_file_when_opening_then_it_is_a_not_found_error() {
+        // Arrange
+        let file = TempFile::new();
+
+        // Act
+        let result = DotenvEnvironment::open(file.path());
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn given_an_unparsable_file_when_reloading_then_it_is_an_error_and_the_old_variables_remain() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=file\n");
+        let mut env = DotenvEnvironment::open(file.path()).unwrap();
+        fs::write(file.path(), "MODE=\"unterminated\n").unwrap();
+
+        // Act
+        let result = env.reload();
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(env.var("MODE").unwrap(), "file");
+    }
+
+    fn existing_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("MODE", "existing");
+        env
+    }
+
+    #[test]
+    fn given_no_override_when_loading_then_existing_variables_are_kept() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=file\nPORT=8080\n");
+        let mut env = existing_env();
+
+        // Act
+        let summary = load_dotenv(file.path(), &mut env, Override::No).unwrap();
+
+        // Assert
+        assert_eq!(
+            summary,
+            LoadSummary {
+                set: vec!["PORT".into()],
+                skipped: vec!["MODE".into()],
+            }
+        );
+        assert_eq!(env.var("MODE").unwrap(), "existing");
+        assert_eq!(env.var("PORT").unwrap(), "8080");
+    }
+
+    #[test]
+    fn given_override_when_loading_then_existing_variables_are_replaced() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=file\nPORT=8080\n");
+        let mut env = existing_env();
+
+        // Act
+        let summary = load_dotenv(file.path(), &mut env, Override::Yes).unwrap();
+
+        // Assert
+        assert_eq!(summary.set, ["MODE", "PORT"]);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(env.var("MODE").unwrap(), "file");
+    }
+
+    #[test]
+    fn given_a_missing_file_when_loading_then_it_is_a_not_found_error_and_nothing_changes() {
+        // Arrange
+        let file = TempFile::new();
+        let mut env = existing_env();
+
+        // Act
+        let result = load_dotenv(file.path(), &mut env, Override::Yes);
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(env.vars_os(), existing_env().vars_os());
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex, PoisonError, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    dotenv::read_dotenv, DotenvEnvironment, EnumerableEnvironment, Environment, Provenance,
+    ReadEnvironment, SourcedEnvironment,
+};
+
+/// How long the file must go without changing before it is re-read.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+type ErrorCallback = Box<dyn FnMut(&io::Error) + Send>;
+
+impl DotenvEnvironment {
+    /// Keep the environment up to date with its dotenv file, re-reading it
+    /// whenever it changes. Requires the `watch` feature.
+    ///
+    /// See [`WatchedDotenvEnvironment`](WatchedDotenvEnvironment).
+    ///
+    /// # Errors
+    /// If the file's directory cannot be watched, it returns the error as an
+    /// I/O error.
+    pub fn watch(self) -> io::Result<WatchedDotenvEnvironment> {
+        let (path, file_vars, overlay) = self.into_parts();
+        let file_vars = Arc::new(RwLock::new(file_vars));
+        let on_error = Arc::new(Mutex::new(Vec::new()));
+
+        let (events, received) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(events).map_err(io::Error::other)?;
+        // Watching the directory, rather than the file, sees the file when an
+        // editor replaces it instead of writing to it.
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+
+        let reloader = Reloader {
+            path: path.clone(),
+            file_vars: Arc::clone(&file_vars),
+            on_error: Arc::clone(&on_error),
+        };
+        thread::spawn(move || reloader.run(&received));
+
+        Ok(WatchedDotenvEnvironment {
+            path,
+            file_vars,
+            overlay,
+            on_error,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// A [`DotenvEnvironment`](DotenvEnvironment) that re-reads its file
+/// whenever it changes, e.g. so a development server picks up edits to
+/// `.env`. Created by [`DotenvEnvironment::watch`](DotenvEnvironment::watch).
+/// Requires the `watch` feature.
+///
+/// The file is re-read on a background thread once it has not changed for
+/// 50 ms, so a burst of writes causes a single reload. The new variables
+/// replace the old ones all at once, so reads never see a file half
+/// applied. If the file cannot be read or parsed, the previous variables are
+/// kept and the error is passed to the callbacks registered with
+/// [`on_error`](WatchedDotenvEnvironment::on_error).
+///
+/// As with [`DotenvEnvironment`](DotenvEnvironment), writes and removals
+/// are kept in an in-memory overlay that shadows the file, and survives
+/// reloads. Watching stops when the environment is dropped.
+///
+/// # Example
+/// ```rust,no_run
+/// # use env_wrapper::{DotenvEnvironment, ReadEnvironment};
+/// let mut env = DotenvEnvironment::open(".env")?.watch()?;
+/// env.on_error(|error| eprintln!("keeping the previous .env: {error}"));
+///
+/// // Reads see the latest valid version of the file.
+/// let log_level = env.var("LOG_LEVEL");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct WatchedDotenvEnvironment {
+    path: PathBuf,
+    file_vars: Arc<RwLock<HashMap<OsString, OsString>>>,
+    // `None` marks a variable removed in memory.
+    overlay: HashMap<OsString, Option<OsString>>,
+    on_error: Arc<Mutex<Vec<ErrorCallback>>>,
+    // Dropping the watcher ends the reloading thread.
+    _watcher: RecommendedWatcher,
+}
+
+impl WatchedDotenvEnvironment {
+    /// The path of the dotenv file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Call `callback` with the error whenever re-reading the file fails.
+    /// It runs on the reloading thread.
+    pub fn on_error(&mut self, callback: impl FnMut(&io::Error) + Send + 'static) {
+        self.on_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(callback));
+    }
+
+    fn file_var(&self, key: &OsStr) -> Option<OsString> {
+        self.file_vars
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+}
+
+impl fmt::Debug for WatchedDotenvEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchedDotenvEnvironment")
+            .field("path", &self.path)
+            .field("file_vars", &self.file_vars)
+            .field("overlay", &self.overlay)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadEnvironment for WatchedDotenvEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.overlay.get(key.as_ref()) {
+            Some(value) => value.clone(),
+            None => self.file_var(key.as_ref()),
+        }
+    }
+}
+
+/// Values changed in memory come from [`Provenance::Fake`], and the rest
+/// from [`Provenance::DotenvFile`].
+impl SourcedEnvironment for WatchedDotenvEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let provenance = if self.overlay.contains_key(key.as_ref()) {
+            Provenance::Fake
+        } else {
+            Provenance::DotenvFile(self.path.clone())
+        };
+        self.var(key).map(|value| (value, provenance))
+    }
+}
+
+impl Environment for WatchedDotenvEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.overlay
+            .insert(key.as_ref().into(), Some(value.as_ref().into()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.overlay.insert(key.as_ref().into(), None);
+    }
+}
+
+impl EnumerableEnvironment for WatchedDotenvEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged = self
+            .file_vars
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for (key, value) in &self.overlay {
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+        merged.into_iter().collect()
+    }
+}
+
+/// What the reloading thread shares with the environment.
+struct Reloader {
+    path: PathBuf,
+    file_vars: Arc<RwLock<HashMap<OsString, OsString>>>,
+    on_error: Arc<Mutex<Vec<ErrorCallback>>>,
+}
+
+impl Reloader {
+    /// Reload the file after each burst of changes to it, until the watcher
+    /// is dropped.
+    fn run(&self, events: &Receiver<notify::Result<Event>>) {
+        while let Ok(event) = events.recv() {
+            if !self.concerns_file(&event) {
+                continue;
+            }
+            loop {
+                match events.recv_timeout(DEBOUNCE) {
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            self.reload();
+        }
+    }
+
+    /// Whether `event` may have changed the file. Reading the file is an
+    /// access event, so the thread's own reloads are ignored.
+    fn concerns_file(&self, event: &notify::Result<Event>) -> bool {
+        match event {
+            Ok(event) => {
+                !event.kind.is_access()
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == self.path.file_name())
+            }
+            // The watcher may have missed changes, so read the file again.
+            Err(_) => true,
+        }
+    }
+
+    fn reload(&self) {
+        match read_dotenv(&self.path) {
+            Ok(vars) => {
+                *self
+                    .file_vars
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner) = vars;
+            }
+            Err(error) => {
+                let mut callbacks = self.on_error.lock().unwrap_or_else(PoisonError::into_inner);
+                for callback in callbacks.iter_mut() {
+                    callback(&error);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::ErrorKind,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        test_helpers::TempFile, DotenvEnvironment, EnumerableEnvironment, Environment,
+        ReadEnvironment,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Poll `condition` until it holds, failing the test after a while.
+    fn eventually(mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < TIMEOUT, "timed out waiting for a reload");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn given_a_watched_file_when_it_is_rewritten_then_the_new_values_are_read() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=first\nREMOVED=1\n");
+        let env = DotenvEnvironment::open(file.path())
+            .unwrap()
+            .watch()
+            .unwrap();
+
+        // Act
+        fs::write(file.path(), "MODE=second\nADDED=1\n").unwrap();
+
+        // Assert
+        eventually(|| env.var("MODE").as_deref() == Ok("second"));
+        assert_eq!(env.var("ADDED").unwrap(), "1");
+        assert!(env.var_os("REMOVED").is_none());
+        assert_eq!(env.vars_os().len(), 2);
+    }
+
+    #[test]
+    fn given_a_watched_file_when_it_is_replaced_then_the_new_values_are_read() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=first\n");
+        let replacement = TempFile::with_contents("MODE=replaced\n");
+        let env = DotenvEnvironment::open(file.path())
+            .unwrap()
+            .watch()
+            .unwrap();
+
+        // Act
+        fs::rename(replacement.path(), file.path()).unwrap();
+
+        // Assert
+        eventually(|| env.var("MODE").as_deref() == Ok("replaced"));
+    }
+
+    #[test]
+    fn given_in_memory_changes_when_the_file_is_rewritten_then_they_still_shadow_it() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=first\nLOG_LEVEL=info\n");
+        let mut env = DotenvEnvironment::open(file.path())
+            .unwrap()
+            .watch()
+            .unwrap();
+        env.set_var("MODE", "memory");
+
+        // Act
+        fs::write(file.path(), "MODE=second\nLOG_LEVEL=debug\n").unwrap();
+
+        // Assert
+        eventually(|| env.var("LOG_LEVEL").as_deref() == Ok("debug"));
+        assert_eq!(env.var("MODE").unwrap(), "memory");
+    }
+
+    #[test]
+    fn given_an_invalid_rewrite_when_reloading_then_the_error_is_reported_and_values_are_kept() {
+        // Arrange
+        let file = TempFile::with_contents("MODE=first\n");
+        let mut env = DotenvEnvironment::open(file.path())
+            .unwrap()
+            .watch()
+            .unwrap();
+        let (errors, reported) = mpsc::channel();
+        env.on_error(move |error| {
+            let _ = errors.send((error.kind(), error.to_string()));
+        });
+
+        // Act
+        fs::write(file.path(), "MODE=second\nnot an assignment\n").unwrap();
+
+        // Assert
+        let (kind, message) = reported.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(kind, ErrorKind::InvalidData);
+        assert!(message.contains("line 2"), "{message}");
+        assert_eq!(env.var("MODE").unwrap(), "first");
+        fs::write(file.path(), "MODE=fixed\n").unwrap();
+        eventually(|| env.var("MODE").as_deref() == Ok("fixed"));
+    }
+}
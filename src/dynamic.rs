@@ -0,0 +1,222 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{EnvError, Environment, ReadEnvironment};
+
+/// An object-safe counterpart to [`Environment`](Environment), taking
+/// `&OsStr` keys and values instead of generic parameters, for when different
+/// environment implementations need to be stored together, e.g. as
+/// `Box<dyn DynEnvironment>`.
+///
+/// Every [`Environment`](Environment) implements this trait, and
+/// `Box<dyn DynEnvironment>` implements [`Environment`](Environment), so a
+/// boxed environment can be used anywhere an `impl Environment` is accepted.
+///
+/// With the `mockall` feature, [`mockall`](https://docs.rs/mockall)
+/// generates `MockDynEnvironment` from this trait, for interaction-style
+/// tests. Set expectations on its `dyn_` methods, and box it to pass it
+/// where an `Environment` is expected.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{DynEnvironment, Environment, FakeEnvironment, RealEnvironment};
+/// let envs: Vec<Box<dyn DynEnvironment>> =
+///     vec![Box::new(RealEnvironment), Box::new(FakeEnvironment::new())];
+///
+/// for env in envs {
+///     print_home(env);
+/// }
+///
+/// fn print_home(env: impl Environment) {
+///     println!("{:?}", env.var_os("HOME"));
+/// }
+/// ```
+#[cfg_attr(feature = "mockall", mockall::automock)]
+pub trait DynEnvironment {
+    /// See [`Environment::set_var`](Environment::set_var).
+    fn dyn_set_var(&mut self, key: &OsStr, value: &OsStr);
+
+    /// See [`ReadEnvironment::var`](crate::ReadEnvironment::var).
+    fn dyn_var(&self, key: &OsStr) -> Result<String, VarError>;
+
+    /// See [`ReadEnvironment::var_os`](crate::ReadEnvironment::var_os).
+    fn dyn_var_os(&self, key: &OsStr) -> Option<OsString>;
+
+    /// See [`Environment::remove_var`](Environment::remove_var).
+    fn dyn_remove_var(&mut self, key: &OsStr);
+
+    /// See [`Environment::try_set_var`](Environment::try_set_var).
+    fn dyn_try_set_var(&mut self, key: &OsStr, value: &OsStr) -> Result<(), EnvError>;
+
+    /// See [`Environment::try_remove_var`](Environment::try_remove_var).
+    fn dyn_try_remove_var(&mut self, key: &OsStr) -> Result<(), EnvError>;
+}
+
+impl<E: Environment> DynEnvironment for E {
+    fn dyn_set_var(&mut self, key: &OsStr, value: &OsStr) {
+        self.set_var(key, value)
+    }
+
+    fn dyn_var(&self, key: &OsStr) -> Result<String, VarError> {
+        self.var(key)
+    }
+
+    fn dyn_var_os(&self, key: &OsStr) -> Option<OsString> {
+        self.var_os(key)
+    }
+
+    fn dyn_remove_var(&mut self, key: &OsStr) {
+        self.remove_var(key)
+    }
+
+    fn dyn_try_set_var(&mut self, key: &OsStr, value: &OsStr) -> Result<(), EnvError> {
+        self.try_set_var(key, value)
+    }
+
+    fn dyn_try_remove_var(&mut self, key: &OsStr) -> Result<(), EnvError> {
+        self.try_remove_var(key)
+    }
+}
+
+impl<E: DynEnvironment + ?Sized> ReadEnvironment for Box<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        (**self).dyn_var(key.as_ref())
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        (**self).dyn_var_os(key.as_ref())
+    }
+}
+
+impl<E: DynEnvironment + ?Sized> Environment for Box<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        (**self).dyn_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        (**self).dyn_remove_var(key.as_ref())
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        (**self).dyn_try_set_var(key.as_ref(), value.as_ref())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        (**self).dyn_try_remove_var(key.as_ref())
+    }
+}
+
+#[cfg(all(test, feature = "mockall"))]
+mod mock_tests {
+    use std::{env::VarError, ffi::OsStr};
+
+    use super::MockDynEnvironment;
+    use crate::{EnvError, Environment, ReadEnvironment};
+
+    /// Code under test, which marks a start in fast mode.
+    fn start(env: &mut impl Environment) -> Result<(), EnvError> {
+        match env.var("MODE") {
+            Ok(mode) if mode == "fast" => env.try_set_var("STATE", "started-fast"),
+            Ok(_) | Err(VarError::NotUnicode(_)) => env.try_set_var("STATE", "started"),
+            Err(VarError::NotPresent) => Err(EnvError::NotPresent("MODE".into())),
+        }
+    }
+
+    #[test]
+    fn given_a_mock_when_driven_through_environment_then_its_expectations_are_met() {
+        // Arrange
+        let mut mock_env = MockDynEnvironment::new();
+        mock_env
+            .expect_dyn_var()
+            .withf(|key| key == OsStr::new("MODE"))
+            .times(1)
+            .returning(|_| Ok("fast".into()));
+        mock_env
+            .expect_dyn_try_set_var()
+            .withf(|key, value| key == OsStr::new("STATE") && value == OsStr::new("started-fast"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut env = Box::new(mock_env);
+
+        // Act
+        let result = start(&mut env);
+
+        // Assert
+        assert_eq!(result, Ok(()));
+        env.checkpoint();
+    }
+
+    #[test]
+    fn given_a_mock_when_setting_a_variable_then_set_var_reaches_the_expectation() {
+        // Arrange
+        let mut mock_env = MockDynEnvironment::new();
+        mock_env
+            .expect_dyn_set_var()
+            .withf(|key, value| key == OsStr::new("LOG_LEVEL") && value == OsStr::new("debug"))
+            .times(1)
+            .return_const(());
+        mock_env
+            .expect_dyn_var_os()
+            .returning(|_| Some("debug".into()));
+        let mut env = Box::new(mock_env);
+
+        // Act
+        env.set_var("LOG_LEVEL", "debug");
+
+        // Assert
+        assert!(env.contains("LOG_LEVEL"));
+        env.checkpoint();
+    }
+
+    #[test]
+    fn given_a_failing_mock_when_driven_through_environment_then_the_error_is_returned() {
+        // Arrange
+        let mut mock_env = MockDynEnvironment::new();
+        mock_env
+            .expect_dyn_var()
+            .returning(|_| Err(VarError::NotPresent));
+        mock_env.expect_dyn_try_set_var().never();
+        let mut env = Box::new(mock_env);
+
+        // Act
+        let result = start(&mut env);
+
+        // Assert
+        assert_eq!(result, Err(EnvError::NotPresent("MODE".into())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynEnvironment;
+    use crate::{test_helpers::random_upper, Environment, FakeEnvironment, RealEnvironment};
+
+    #[test]
+    fn given_boxed_environments_when_using_them_as_environments_then_calls_reach_the_inner_environment(
+    ) {
+        fn test(mut env: impl Environment) {
+            // Arrange
+            let key = random_upper();
+            let value = random_upper();
+
+            // Act
+            env.set_var(&key, &value);
+
+            // Assert
+            assert_eq!(env.var(&key).unwrap(), value);
+            env.remove_var(&key);
+            assert!(env.var_os(&key).is_none());
+        }
+
+        let real: Box<dyn DynEnvironment> = Box::new(RealEnvironment);
+        let fake: Box<dyn DynEnvironment> = Box::new(FakeEnvironment::new());
+        test(real);
+        test(fake);
+    }
+}
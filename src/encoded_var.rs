@@ -0,0 +1,317 @@
+use std::{
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{
+    encoding::{base64_decode, base64_encode, os_string_from_bytes},
+    EnumerableEnvironment, Environment, FakeEnvironment,
+};
+
+/// Starts every encoded environment, naming the version of the format.
+const FORMAT_V1: &str = "ew1:";
+
+/// The most a child's variable can hold. On Linux, `NAME=value` and its
+/// terminating NUL must fit in `MAX_ARG_STRLEN`, which other Unix platforms
+/// allow at least.
+#[cfg(not(windows))]
+const MAX_VAR_LEN: usize = 128 * 1024;
+
+/// The most a child's variable can hold. On Windows, the value must fit in
+/// 32,767 characters.
+#[cfg(windows)]
+const MAX_VAR_LEN: usize = 32_767;
+
+/// An error from passing a [`FakeEnvironment`](FakeEnvironment) through a
+/// single encoded variable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EncodedVarError {
+    /// The encoded environment is too large for a variable of a child
+    /// process on this platform.
+    TooLarge {
+        name: String,
+        size: usize,
+        limit: usize,
+    },
+    /// The variable that should hold the encoded environment is not set.
+    NotPresent(String),
+    /// The variable does not hold an environment encoded by
+    /// [`to_encoded_var`](FakeEnvironment::to_encoded_var), or holds one that
+    /// cannot be represented on this platform.
+    Malformed(String),
+}
+
+impl fmt::Display for EncodedVarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodedVarError::TooLarge { name, size, limit } => write!(
+                f,
+                "environment variable {name:?} would hold {size} bytes of encoded environment, \
+                 over the limit of {limit}"
+            ),
+            EncodedVarError::NotPresent(name) => {
+                write!(f, "environment variable {name:?} is not set")
+            }
+            EncodedVarError::Malformed(name) => write!(
+                f,
+                "environment variable {name:?} does not hold an encoded environment"
+            ),
+        }
+    }
+}
+
+impl Error for EncodedVarError {}
+
+impl FakeEnvironment {
+    /// Encode every variable into the value of one variable named `name`,
+    /// to set for a child process, which can rebuild this environment with
+    /// [`from_encoded_var`](FakeEnvironment::from_encoded_var).
+    ///
+    /// The value is `ew1:` followed by base64, so it is plain ASCII whatever
+    /// the variables hold, including bytes that are not valid Unicode. The
+    /// format is stable: later versions of this crate read it.
+    ///
+    /// # Errors
+    /// If the value would be too large for a variable of a child process on
+    /// this platform, it returns
+    /// [`EncodedVarError::TooLarge`](EncodedVarError::TooLarge). On Linux,
+    /// the variable's name, value, and two more bytes must fit in 128 KiB,
+    /// and on Windows, the value in 32,767 characters.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use std::process::Command;
+    /// # use env_wrapper::{Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("API_URL", "http://localhost:8080");
+    ///
+    /// let state = fake_env.to_encoded_var("ENV_WRAPPER_STATE").unwrap();
+    /// Command::new("my-test-helper").env("ENV_WRAPPER_STATE", state).status()?;
+    ///
+    /// // In the child:
+    /// let fake_env = FakeEnvironment::from_encoded_var("ENV_WRAPPER_STATE").unwrap();
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn to_encoded_var(&self, name: &str) -> Result<String, EncodedVarError> {
+        let mut vars = self.vars_os();
+        vars.sort();
+        let mut payload = Vec::new();
+        for (key, value) in &vars {
+            for part in [key, value] {
+                let bytes = part.as_encoded_bytes();
+                let len = u32::try_from(bytes.len()).map_err(|_| too_large(name, usize::MAX))?;
+                payload.extend(len.to_be_bytes());
+                payload.extend(bytes);
+            }
+        }
+        let encoded = format!("{FORMAT_V1}{}", base64_encode(&payload));
+
+        let size = var_size(name, &encoded);
+        if size > MAX_VAR_LEN {
+            return Err(too_large(name, size));
+        }
+        Ok(encoded)
+    }
+
+    /// A fake environment rebuilt from the variable `name` of this process,
+    /// as set to the value from
+    /// [`to_encoded_var`](FakeEnvironment::to_encoded_var).
+    ///
+    /// # Errors
+    /// * [`EncodedVarError::NotPresent`](EncodedVarError::NotPresent) if the
+    ///   variable is not set.
+    /// * [`EncodedVarError::Malformed`](EncodedVarError::Malformed) if it does
+    ///   not hold an encoded environment, or holds values that are not valid
+    ///   Unicode on a platform other than Unix.
+    pub fn from_encoded_var(name: &str) -> Result<Self, EncodedVarError> {
+        let encoded = env::var_os(name).ok_or_else(|| EncodedVarError::NotPresent(name.into()))?;
+        decode(&encoded).ok_or_else(|| EncodedVarError::Malformed(name.into()))
+    }
+}
+
+fn too_large(name: &str, size: usize) -> EncodedVarError {
+    EncodedVarError::TooLarge {
+        name: name.into(),
+        size,
+        limit: MAX_VAR_LEN,
+    }
+}
+
+/// How much of the platform's limit the variable uses.
+#[cfg(not(windows))]
+fn var_size(name: &str, value: &str) -> usize {
+    name.len() + value.len() + "=\0".len()
+}
+
+/// How much of the platform's limit the variable uses.
+#[cfg(windows)]
+fn var_size(_name: &str, value: &str) -> usize {
+    value.len()
+}
+
+fn decode(encoded: &OsStr) -> Option<FakeEnvironment> {
+    let payload = base64_decode(encoded.to_str()?.strip_prefix(FORMAT_V1)?)?;
+    let mut rest = payload.as_slice();
+    let mut env = FakeEnvironment::new();
+    while !rest.is_empty() {
+        let key = take_part(&mut rest)?;
+        let value = take_part(&mut rest)?;
+        env.set_var(key, value);
+    }
+    Some(env)
+}
+
+/// Take one length-prefixed part off the front of `rest`.
+fn take_part(rest: &mut &[u8]) -> Option<OsString> {
+    if rest.len() < 4 {
+        return None;
+    }
+    let (len, after_len) = rest.split_at(4);
+    let len = usize::try_from(u32::from_be_bytes(len.try_into().ok()?)).ok()?;
+    if after_len.len() < len {
+        return None;
+    }
+    let (part, after_part) = after_len.split_at(len);
+    *rest = after_part;
+    os_string_from_bytes(part.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{EncodedVarError, MAX_VAR_LEN};
+    use crate::{
+        run_in_subprocess, test_helpers::random_upper, Environment, FakeEnvironment,
+        RealEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+    const STATE_VAR: &str = "ENV_WRAPPER_TEST_STATE";
+
+    fn tricky_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello world");
+        env.set_var("EMPTY", "");
+        env.set_var("MULTILINE", "one\ntwo");
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        env.set_var(OsStr::from_bytes(&INVALID_UTF8), "binary key");
+        env
+    }
+
+    #[test]
+    fn given_a_fake_when_round_tripped_through_a_variable_then_it_is_unchanged() {
+        // Arrange
+        let env = tricky_env();
+        let name = random_upper();
+
+        // Act
+        let encoded = env.to_encoded_var(&name).unwrap();
+        RealEnvironment.set_var(&name, &encoded);
+        let decoded = FakeEnvironment::from_encoded_var(&name);
+        RealEnvironment.remove_var(&name);
+
+        // Assert
+        assert!(encoded.starts_with("ew1:"));
+        assert!(encoded.is_ascii());
+        assert_eq!(decoded.unwrap(), env);
+    }
+
+    #[test]
+    fn given_a_small_fake_when_encoding_then_the_format_is_stable() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("A", "b");
+
+        // Act
+        let encoded = env.to_encoded_var("STATE").unwrap();
+
+        // Assert
+        assert_eq!(encoded, "ew1:AAAAAUEAAAABYg==");
+        assert_eq!(
+            FakeEnvironment::new().to_encoded_var("STATE").unwrap(),
+            "ew1:"
+        );
+    }
+
+    #[test]
+    fn given_a_fake_too_large_for_a_variable_when_encoding_then_it_is_refused() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BIG", "x".repeat(MAX_VAR_LEN));
+
+        // Act
+        let result = env.to_encoded_var("STATE");
+
+        // Assert
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error,
+            EncodedVarError::TooLarge { ref name, size, limit }
+                if name == "STATE" && size > limit && limit == MAX_VAR_LEN
+        ));
+        assert!(error.to_string().contains("over the limit of 131072"));
+    }
+
+    #[test]
+    fn given_a_missing_or_malformed_variable_when_decoding_then_it_is_an_error() {
+        // Arrange
+        let missing = random_upper();
+        let malformed = random_upper();
+        let truncated = random_upper();
+        RealEnvironment.set_var(&malformed, "not an environment");
+        RealEnvironment.set_var(&truncated, "ew1:AAAAAUEAAAAC");
+
+        // Act
+        let missing_result = FakeEnvironment::from_encoded_var(&missing);
+        let malformed_result = FakeEnvironment::from_encoded_var(&malformed);
+        let truncated_result = FakeEnvironment::from_encoded_var(&truncated);
+        RealEnvironment.remove_var(&malformed);
+        RealEnvironment.remove_var(&truncated);
+
+        // Assert
+        assert_eq!(
+            missing_result.unwrap_err(),
+            EncodedVarError::NotPresent(missing)
+        );
+        assert_eq!(
+            malformed_result.unwrap_err(),
+            EncodedVarError::Malformed(malformed)
+        );
+        assert_eq!(
+            truncated_result.unwrap_err(),
+            EncodedVarError::Malformed(truncated)
+        );
+    }
+
+    fn check_decoded_state() -> i32 {
+        match FakeEnvironment::from_encoded_var(STATE_VAR) {
+            Ok(env) if env == tricky_env() => 0,
+            Ok(env) => {
+                eprint!("{env:?}");
+                1
+            }
+            Err(error) => {
+                eprint!("{error}");
+                2
+            }
+        }
+    }
+
+    #[test]
+    fn given_an_encoded_fake_when_passed_to_a_child_then_the_child_rebuilds_it() {
+        // Arrange
+        let mut child_env = FakeEnvironment::new();
+        let encoded = tricky_env().to_encoded_var(STATE_VAR).unwrap();
+        child_env.set_var(STATE_VAR, encoded);
+
+        // Act
+        let result = run_in_subprocess(&child_env, check_decoded_state);
+
+        // Assert
+        assert_eq!(result.status.code(), Some(0), "{}", result.stderr);
+    }
+}
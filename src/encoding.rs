@@ -0,0 +1,96 @@
+use std::ffi::OsString;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The variable value with the bytes `bytes`, if it can have them on this
+/// platform.
+#[cfg(unix)]
+pub(crate) fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Some(OsString::from_vec(bytes))
+}
+
+/// The variable value with the bytes `bytes`, if it can have them on this
+/// platform.
+#[cfg(not(unix))]
+pub(crate) fn os_string_from_bytes(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(Into::into)
+}
+
+/// Standard, padded base64.
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .chain([0, 0].iter())
+            .take(3)
+            .fold(0u32, |group, &byte| group << 8 | u32::from(byte));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize].into());
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let chunks = encoded.as_bytes().chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    let groups = chunks.len();
+    let mut decoded = Vec::with_capacity(groups * 3);
+    for (index, chunk) in chunks.enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 < groups) {
+            return None;
+        }
+        let mut group = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let sextet = ALPHABET.iter().position(|&letter| letter == c)?;
+            group = group << 6 | sextet as u32;
+        }
+        group <<= 6 * padding;
+        decoded.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64_decode, base64_encode};
+
+    #[test]
+    fn given_each_padding_length_when_encoding_then_it_matches_standard_base64() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+        ] {
+            // Act
+            let actual = base64_encode(bytes.as_bytes());
+
+            // Assert
+            assert_eq!(actual, encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), bytes.as_bytes());
+        }
+    }
+
+    #[test]
+    fn given_malformed_base64_when_decoding_then_it_is_rejected() {
+        for encoded in ["Zg=", "Z===", "Zg==Zg==", "Zm9*"] {
+            // Act
+            let decoded = base64_decode(encoded);
+
+            // Assert
+            assert_eq!(decoded, None, "{encoded}");
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::{env::VarError, error::Error, fmt, str::FromStr};
+
+use crate::ReadEnvironment;
+
+/// A type that can be built from environment variables.
+///
+/// With the `derive` feature, this can be derived for structs with named
+/// fields. Each field is parsed with [`FromStr`](FromStr) from the variable
+/// named after it in upper case, and `Option` fields are `None` when their
+/// variable is not set. Fields accept these attributes:
+/// * `#[env(rename = "NAME")]` reads the variable `NAME` instead.
+/// * `#[env(default = "value")]` parses `value` when the variable is not set.
+/// * `#[env(prefix)]` builds a nested struct from the variables starting with
+///   the field's name in upper case and `_`, and `#[env(prefix = "P_")]` from
+///   those starting with `P_`.
+///
+/// The derived implementation only reads through the
+/// [`ReadEnvironment`](ReadEnvironment) it is given, so fakes work.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # {
+/// # use env_wrapper::{Environment, FakeEnvironment, FromEnvironment};
+/// #[derive(FromEnvironment)]
+/// struct Config {
+///     #[env(rename = "DATABASE_URL")]
+///     database: String,
+///     #[env(default = "8080")]
+///     port: u16,
+///     #[env(prefix)]
+///     tls: TlsConfig,
+/// }
+///
+/// #[derive(FromEnvironment)]
+/// struct TlsConfig {
+///     cert_path: Option<String>,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("DATABASE_URL", "postgres://localhost/app");
+/// fake_env.set_var("TLS_CERT_PATH", "/etc/tls/cert.pem");
+///
+/// let config = Config::from_environment(&fake_env).unwrap();
+///
+/// assert_eq!(config.database, "postgres://localhost/app");
+/// assert_eq!(config.port, 8080);
+/// assert_eq!(config.tls.cert_path.as_deref(), Some("/etc/tls/cert.pem"));
+/// # }
+/// ```
+pub trait FromEnvironment: Sized {
+    /// Build the value from `env`.
+    ///
+    /// # Errors
+    /// If a required variable is not set, or a value is not valid Unicode or
+    /// cannot be parsed, it returns an [`EnvStructError`](EnvStructError)
+    /// naming the variable.
+    fn from_environment(env: &impl ReadEnvironment) -> Result<Self, EnvStructError> {
+        Self::from_environment_prefixed(env, "")
+    }
+
+    /// Build the value from the variables in `env` whose names start with
+    /// `prefix`.
+    ///
+    /// # Errors
+    /// As for [`from_environment`](FromEnvironment::from_environment).
+    fn from_environment_prefixed(
+        env: &impl ReadEnvironment,
+        prefix: &str,
+    ) -> Result<Self, EnvStructError>;
+}
+
+/// An error from building a value with
+/// [`FromEnvironment`](FromEnvironment).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EnvStructError {
+    /// The named variable is required but not set.
+    Missing(String),
+    /// The value of the named variable is not valid Unicode.
+    NotUnicode(String),
+    /// The value of the named variable could not be parsed.
+    Invalid { variable: String, message: String },
+}
+
+impl fmt::Display for EnvStructError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvStructError::Missing(name) => write!(f, "environment variable {name:?} is not set"),
+            EnvStructError::NotUnicode(name) => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            EnvStructError::Invalid { variable, message } => {
+                write!(f, "environment variable {variable:?} is invalid: {message}")
+            }
+        }
+    }
+}
+
+impl Error for EnvStructError {}
+
+/// Support for the code generated by `#[derive(FromEnvironment)]`. Not part
+/// of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    /// Parse the variable `key`, or `default` if it is not set.
+    pub fn required<T>(
+        env: &impl ReadEnvironment,
+        key: &str,
+        default: Option<&str>,
+    ) -> Result<T, EnvStructError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match (lookup(env, key)?, default) {
+            (Some(value), _) => parse(key, &value),
+            (None, Some(default)) => parse(key, default),
+            (None, None) => Err(EnvStructError::Missing(key.into())),
+        }
+    }
+
+    /// Parse the variable `key`, if it is set.
+    pub fn optional<T>(env: &impl ReadEnvironment, key: &str) -> Result<Option<T>, EnvStructError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        lookup(env, key)?
+            .map(|value| parse(key, &value))
+            .transpose()
+    }
+
+    fn lookup(env: &impl ReadEnvironment, key: &str) -> Result<Option<String>, EnvStructError> {
+        match env.var(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(VarError::NotPresent) => Ok(None),
+            Err(VarError::NotUnicode(_)) => Err(EnvStructError::NotUnicode(key.into())),
+        }
+    }
+
+    fn parse<T>(key: &str, value: &str) -> Result<T, EnvStructError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        value.parse().map_err(|error| EnvStructError::Invalid {
+            variable: key.into(),
+            message: format!("cannot parse {value:?}: {error}"),
+        })
+    }
+}
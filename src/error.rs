@@ -0,0 +1,144 @@
+use std::{
+    env::VarError,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+/// An error from a fallible environment operation, such as
+/// [`Environment::try_set_var`](crate::Environment::try_set_var) or
+/// [`ReadEnvironment::var_redacted`](crate::ReadEnvironment::var_redacted).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EnvError {
+    /// The environment does not allow the variable with this key to be
+    /// changed.
+    ReadOnly(OsString),
+    /// The variable with this key is not set.
+    NotPresent(OsString),
+    /// The value of the variable with this key is not valid Unicode. Only
+    /// its length and the offset of its first invalid byte are kept, never
+    /// its content, so the error is safe to log even if the value is a
+    /// secret. On Windows, both count bytes of the value's WTF-8 encoding.
+    NotUnicode {
+        key: OsString,
+        len: usize,
+        valid_up_to: usize,
+    },
+    /// The variable with this key could not be read, such as a secret whose
+    /// backend could not be reached. Holds a description of the failure,
+    /// never a value.
+    Unavailable { key: OsString, message: String },
+}
+
+impl EnvError {
+    /// The error for reading `key` that failed with `error`, without the
+    /// value a [`VarError::NotUnicode`](VarError::NotUnicode) carries.
+    pub fn from_var_error(key: impl AsRef<OsStr>, error: VarError) -> Self {
+        let key = key.as_ref().into();
+        match error {
+            VarError::NotPresent => EnvError::NotPresent(key),
+            VarError::NotUnicode(value) => {
+                let bytes = value.as_encoded_bytes();
+                let valid_up_to = match std::str::from_utf8(bytes) {
+                    Ok(valid) => valid.len(),
+                    Err(error) => error.valid_up_to(),
+                };
+                EnvError::NotUnicode {
+                    key,
+                    len: bytes.len(),
+                    valid_up_to,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::ReadOnly(key) => write!(
+                f,
+                "cannot change environment variable {key:?}: the environment is read-only"
+            ),
+            EnvError::NotPresent(key) => write!(f, "environment variable {key:?} is not set"),
+            EnvError::NotUnicode {
+                key,
+                len,
+                valid_up_to,
+            } => write!(
+                f,
+                "environment variable {key:?} is not valid Unicode: {len} bytes, the first \
+                 invalid one at offset {valid_up_to}"
+            ),
+            EnvError::Unavailable { key, message } => {
+                write!(f, "environment variable {key:?} is unavailable: {message}")
+            }
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::EnvError;
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    const SECRET_WITH_INVALID_UTF8: &[u8] = b"hunter2\xff\xfeswordfish";
+
+    fn fake_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("API_KEY", OsStr::from_bytes(SECRET_WITH_INVALID_UTF8));
+        env.set_var("MODE", "fast");
+        env
+    }
+
+    #[test]
+    fn given_a_non_unicode_secret_when_reading_it_redacted_then_the_error_holds_no_value_bytes() {
+        // Arrange
+        let env = fake_env();
+
+        // Act
+        let error = env.var_redacted("API_KEY").unwrap_err();
+
+        // Assert
+        assert_eq!(
+            error,
+            EnvError::NotUnicode {
+                key: "API_KEY".into(),
+                len: SECRET_WITH_INVALID_UTF8.len(),
+                valid_up_to: 7,
+            }
+        );
+        for rendered in [error.to_string(), format!("{error:?}")] {
+            assert!(!rendered.contains("hunter"), "{rendered}");
+            assert!(!rendered.contains("swordfish"), "{rendered}");
+            assert!(!rendered.contains("\\xff"), "{rendered}");
+        }
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"API_KEY\" is not valid Unicode: 18 bytes, the first invalid \
+             one at offset 7"
+        );
+    }
+
+    #[test]
+    fn given_set_and_unset_keys_when_reading_them_redacted_then_results_match_var() {
+        // Arrange
+        let env = fake_env();
+
+        // Act/Assert
+        assert_eq!(env.var_redacted("MODE").unwrap(), "fast");
+        assert_eq!(
+            env.var_redacted("MISSING").unwrap_err(),
+            EnvError::NotPresent("MISSING".into())
+        );
+        assert_eq!(
+            env.var("API_KEY").unwrap_err(),
+            VarError::NotUnicode(OsStr::from_bytes(SECRET_WITH_INVALID_UTF8).into())
+        );
+    }
+}
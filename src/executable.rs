@@ -0,0 +1,211 @@
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+use crate::ReadEnvironment;
+
+/// The extensions Windows tries when `PATHEXT` is not set.
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// Find the executable that running `name` would start, like the `which`
+/// command, reading `PATH`, and on Windows `PATHEXT`, from `env`.
+///
+/// If `name` has more than one component, like `./tool` or `/usr/bin/tool`,
+/// it is checked as it is, without searching. Otherwise, each directory of
+/// `PATH`, split with the platform's rules, is checked in order, and the
+/// first match wins. On Windows, each extension in `PATHEXT`, or `.COM`,
+/// `.EXE`, `.BAT`, and `.CMD` if it is not set, is appended in turn, after
+/// trying `name` as it is if it already has an extension.
+///
+/// Only the environment is read through `env`: whether a file exists, and on
+/// Unix whether it is executable, is checked on the real filesystem.
+///
+/// # Example
+/// ```rust,no_run
+/// # use env_wrapper::{find_executable, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PATH", "/opt/tools/bin:/usr/bin");
+///
+/// let git = find_executable(&fake_env, "git");
+/// ```
+pub fn find_executable(env: &impl ReadEnvironment, name: impl AsRef<OsStr>) -> Option<PathBuf> {
+    #[cfg(windows)]
+    let extensions = pathext(env.var_os("PATHEXT"));
+    #[cfg(not(windows))]
+    let extensions = Vec::new();
+    search(env.var_os("PATH").as_deref(), Path::new(&name), &extensions)
+}
+
+/// The extensions listed in `pathext`, or the default ones if it is not set.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn pathext(pathext: Option<OsString>) -> Vec<OsString> {
+    let pathext = pathext.unwrap_or_else(|| DEFAULT_PATHEXT.into());
+    pathext
+        .to_string_lossy()
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(OsString::from)
+        .collect()
+}
+
+fn search(path: Option<&OsStr>, name: &Path, extensions: &[OsString]) -> Option<PathBuf> {
+    match name.components().count() {
+        0 => None,
+        1 => env::split_paths(path?)
+            .flat_map(|dir| candidates(dir.join(name), extensions))
+            .find(|candidate| is_executable(candidate)),
+        _ => candidates(name.to_owned(), extensions).find(|candidate| is_executable(candidate)),
+    }
+}
+
+/// The paths to try for `path`, with each of `extensions` appended.
+fn candidates(path: PathBuf, extensions: &[OsString]) -> impl Iterator<Item = PathBuf> + '_ {
+    let as_is = (extensions.is_empty() || path.extension().is_some()).then(|| path.clone());
+    let extended = extensions.iter().map(move |extension| {
+        let mut extended = path.clone().into_os_string();
+        extended.push(extension);
+        PathBuf::from(extended)
+    });
+    as_is.into_iter().chain(extended)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        ffi::OsString,
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use super::{find_executable, pathext, search};
+    use crate::{test_helpers::TempDir, Environment, FakeEnvironment};
+
+    fn create_file(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    fn fake_path(dirs: &[&Path]) -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("PATH", env::join_paths(dirs).unwrap());
+        env
+    }
+
+    #[test]
+    fn given_the_executable_in_several_directories_when_finding_then_the_first_wins() {
+        // Arrange
+        let first = TempDir::new();
+        let second = TempDir::new();
+        create_file(second.path(), "tool", 0o755);
+        create_file(first.path(), "tool", 0o755);
+        let env = fake_path(&[first.path(), second.path()]);
+
+        // Act
+        let found = find_executable(&env, "tool");
+
+        // Assert
+        assert_eq!(found, Some(first.path().join("tool")));
+    }
+
+    #[test]
+    fn given_a_non_executable_file_earlier_on_the_path_when_finding_then_it_is_skipped() {
+        // Arrange
+        let first = TempDir::new();
+        let second = TempDir::new();
+        create_file(first.path(), "tool", 0o644);
+        fs::create_dir(second.path().join("dir")).unwrap();
+        let expected = create_file(second.path(), "tool", 0o755);
+        let env = fake_path(&[first.path(), second.path()]);
+
+        // Act
+        let found = find_executable(&env, "tool");
+        let directory = find_executable(&env, "dir");
+
+        // Assert
+        assert_eq!(found, Some(expected));
+        assert_eq!(directory, None);
+    }
+
+    #[test]
+    fn given_a_missing_executable_or_path_when_finding_then_it_is_none() {
+        // Arrange
+        let dir = TempDir::new();
+        let env = fake_path(&[dir.path()]);
+
+        // Act
+        let missing = find_executable(&env, "no-such-tool");
+        let without_path = find_executable(&FakeEnvironment::new(), "sh");
+
+        // Assert
+        assert_eq!(missing, None);
+        assert_eq!(without_path, None);
+    }
+
+    #[test]
+    fn given_a_name_with_a_separator_when_finding_then_the_path_is_not_searched() {
+        // Arrange
+        let on_path = TempDir::new();
+        let elsewhere = TempDir::new();
+        create_file(on_path.path(), "tool", 0o755);
+        let direct = create_file(elsewhere.path(), "tool", 0o755);
+        let env = fake_path(&[on_path.path()]);
+
+        // Act
+        let found = find_executable(&env, &direct);
+        let relative = find_executable(&env, Path::new(".").join("tool"));
+
+        // Assert
+        assert_eq!(found, Some(direct));
+        assert_eq!(relative, None);
+    }
+
+    #[test]
+    fn given_windows_extensions_when_searching_then_each_is_tried_in_order() {
+        // Arrange
+        let dir = TempDir::new();
+        create_file(dir.path(), "tool.BAT", 0o755);
+        create_file(dir.path(), "tool.EXE", 0o755);
+        create_file(dir.path(), "script.ps1", 0o755);
+        let path = env::join_paths([dir.path()]).unwrap();
+        let extensions = pathext(Some(".COM;.EXE;.BAT".into()));
+
+        // Act
+        let tool = search(Some(&path), Path::new("tool"), &extensions);
+        let with_extension = search(Some(&path), Path::new("script.ps1"), &extensions);
+
+        // Assert
+        assert_eq!(tool, Some(dir.path().join("tool.EXE")));
+        assert_eq!(with_extension, Some(dir.path().join("script.ps1")));
+    }
+
+    #[test]
+    fn given_no_pathext_when_listing_extensions_then_the_windows_defaults_apply() {
+        // Act
+        let extensions = pathext(None);
+
+        // Assert
+        assert_eq!(
+            extensions,
+            [".COM", ".EXE", ".BAT", ".CMD"].map(OsString::from)
+        );
+    }
+}
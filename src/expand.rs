@@ -0,0 +1,786 @@
+use std::{error::Error, ffi::OsStr, fmt, path::PathBuf};
+
+use crate::{expand_tilde, ReadEnvironment};
+
+const DEFAULT_MAX_DEPTH: usize = 16;
+
+/// What to do with a `${NAME}` or `$NAME` reference to a variable that is not
+/// set.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownVariable {
+    /// Fail with [`ExpandError::Unknown`](ExpandError::Unknown).
+    #[default]
+    Error,
+    /// Substitute an empty string, as POSIX shells do.
+    Empty,
+    /// Keep the reference as written.
+    Literal,
+}
+
+/// Settings for `${NAME}` expansion.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{ExpandOptions, UnknownVariable};
+/// let options = ExpandOptions::new()
+///     .max_depth(4)
+///     .unknown_variables(UnknownVariable::Empty);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpandOptions {
+    max_depth: usize,
+    unknown: UnknownVariable,
+}
+
+impl ExpandOptions {
+    /// Allow references nested 16 deep and fail on unknown variables.
+    pub fn new() -> Self {
+        ExpandOptions {
+            max_depth: DEFAULT_MAX_DEPTH,
+            unknown: UnknownVariable::Error,
+        }
+    }
+
+    /// How deep references may nest. With a depth of 1, substituted values
+    /// may not contain references of their own.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// How to handle references to variables that are not set.
+    pub fn unknown_variables(mut self, policy: UnknownVariable) -> Self {
+        self.unknown = policy;
+        self
+    }
+}
+
+impl Default for ExpandOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An error from expanding `${NAME}` references.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ExpandError {
+    /// A referenced variable is not set, and unknown variables are errors.
+    Unknown(String),
+    /// The value of the named variable is not valid Unicode, so it cannot be
+    /// expanded.
+    NotUnicode(String),
+    /// Variables reference each other in a loop. Holds the names along the
+    /// loop, starting and ending with the same name.
+    Cycle(Vec<String>),
+    /// Expanding the named variable would nest references deeper than the
+    /// configured maximum.
+    TooDeep(String),
+    /// A `${` has no closing `}`. Holds the text from the `${` onwards.
+    Unterminated(String),
+    /// A `${NAME:?message}` or `${NAME?message}` reference to a variable
+    /// that is not set, or empty for the `:?` form. Holds the name and the
+    /// expanded message, which may be empty.
+    Required { name: String, message: String },
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandError::Unknown(name) => {
+                write!(f, "environment variable {name:?} is referenced but not set")
+            }
+            ExpandError::NotUnicode(name) => write!(
+                f,
+                "environment variable {name:?} cannot be expanded: its value is not valid Unicode"
+            ),
+            ExpandError::Cycle(names) => write!(
+                f,
+                "environment variables reference each other in a cycle: {}",
+                names.join(" -> ")
+            ),
+            ExpandError::TooDeep(name) => write!(
+                f,
+                "expanding environment variable {name:?} nests references too deeply"
+            ),
+            ExpandError::Unterminated(text) => write!(f, "unterminated reference in {text:?}"),
+            ExpandError::Required { name, message } if message.is_empty() => {
+                write!(f, "environment variable {name:?} is required but not set")
+            }
+            ExpandError::Required { name, message } => {
+                write!(f, "environment variable {name:?} is required: {message}")
+            }
+        }
+    }
+}
+
+impl Error for ExpandError {}
+
+/// Expansion of variable references embedded in text, resolved against an
+/// [`ReadEnvironment`](ReadEnvironment) rather than the process environment.
+pub trait ExpandExt: ReadEnvironment {
+    /// Expand `${NAME}` and `$NAME` references, using the default
+    /// [`ExpandOptions`](ExpandOptions). See
+    /// [`expand_with`](ExpandExt::expand_with).
+    ///
+    /// # Errors
+    /// See [`expand_with`](ExpandExt::expand_with).
+    fn expand(&self, input: &str) -> Result<String, ExpandError> {
+        self.expand_with(input, &ExpandOptions::new())
+    }
+
+    /// Expand `${NAME}` and `$NAME` references the way POSIX shells do:
+    /// * `$NAME` takes the longest name made of ASCII letters, digits, and
+    ///   `_`, not starting with a digit.
+    /// * `$$` is an escaped, literal `$`. A `$` not followed by a name, `{`,
+    ///   or `$` is kept literally.
+    /// * Substituted values are expanded too, up to the configured depth.
+    /// * `${NAME:-word}` is `word` if `NAME` is unset or empty, and
+    ///   `${NAME-word}` only if it is unset.
+    /// * `${NAME:+word}` is `word` if `NAME` is set and not empty, and
+    ///   `${NAME+word}` if it is set at all. Otherwise, they are empty.
+    /// * `${NAME:?message}` fails with `message` if `NAME` is unset or empty,
+    ///   and `${NAME?message}` only if it is unset.
+    /// * The `word` or `message` is only expanded when it is used, and may
+    ///   contain references of its own, such as `${A:-${B:-b}}`. These forms
+    ///   decide what an unset variable means, so the policy for unknown
+    ///   variables does not apply to them.
+    ///
+    /// # Errors
+    /// * [`ExpandError::Unknown`](ExpandError::Unknown) for a reference to a
+    ///   variable that is not set, if unknown variables are errors.
+    /// * [`ExpandError::NotUnicode`](ExpandError::NotUnicode) for a reference
+    ///   to a value that is not valid Unicode.
+    /// * [`ExpandError::Cycle`](ExpandError::Cycle) if values reference each
+    ///   other in a loop.
+    /// * [`ExpandError::TooDeep`](ExpandError::TooDeep) if references nest
+    ///   deeper than the configured depth.
+    /// * [`ExpandError::Unterminated`](ExpandError::Unterminated) for a `${`
+    ///   without a closing `}`.
+    /// * [`ExpandError::Required`](ExpandError::Required) for a
+    ///   `${NAME:?message}` or `${NAME?message}` reference that fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, ExpandExt, ExpandOptions, FakeEnvironment, UnknownVariable};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("HOME", "/home/me");
+    /// fake_env.set_var("DATA_DIR", "${HOME}/data");
+    ///
+    /// let options = ExpandOptions::new().unknown_variables(UnknownVariable::Empty);
+    /// let expanded = fake_env.expand_with("$DATA_DIR/$UNSET/cost: $$5", &options);
+    ///
+    /// assert_eq!(expanded.unwrap(), "/home/me/data//cost: $5");
+    ///
+    /// let port = fake_env.expand("${PORT:-${DEFAULT_PORT:-8080}}");
+    /// assert_eq!(port.unwrap(), "8080");
+    /// ```
+    fn expand_with(&self, input: &str, options: &ExpandOptions) -> Result<String, ExpandError> {
+        Expander::new(self, options, 0).expand(input)
+    }
+
+    /// Expand `%NAME%` references the way Windows'
+    /// [`ExpandEnvironmentStrings`](https://learn.microsoft.com/en-us/windows/win32/api/processenv/nf-processenv-expandenvironmentstringsw)
+    /// does:
+    /// * References to unknown variables are left as-is.
+    /// * `%%` is not an escape; it is an empty (and so unknown) reference.
+    /// * A `%` without a closing `%` is kept literally.
+    /// * Expansion is not recursive: substituted values are not expanded again.
+    ///
+    /// Unlike Windows, names are looked up with the environment's own key
+    /// semantics, so [`FakeEnvironment`](crate::FakeEnvironment) lookups are
+    /// case-sensitive. Values that are not valid Unicode are substituted
+    /// lossily.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, ExpandExt, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("APPDATA", "C:\\Users\\me\\AppData\\Roaming");
+    ///
+    /// let expanded = fake_env.expand_windows("%APPDATA%\\myapp");
+    ///
+    /// assert_eq!(expanded, "C:\\Users\\me\\AppData\\Roaming\\myapp");
+    /// ```
+    fn expand_windows(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find('%') {
+            output.push_str(&rest[..start]);
+            let after_open = &rest[start + 1..];
+            let Some(end) = after_open.find('%') else {
+                output.push_str(&rest[start..]);
+                return output;
+            };
+            let name = &after_open[..end];
+            let value = if name.is_empty() {
+                None
+            } else {
+                self.var_os(name)
+            };
+            match value {
+                Some(value) => {
+                    output.push_str(&value.to_string_lossy());
+                    rest = &after_open[end + 1..];
+                }
+                None => {
+                    // The closing `%` may open the next reference, as on Windows.
+                    output.push('%');
+                    output.push_str(name);
+                    rest = &after_open[end..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+
+    /// Read `key` as a path, expanding a leading `~` to the home directory
+    /// of this environment with [`expand_tilde`](crate::expand_tilde).
+    /// Returns `None` if it is not set.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::path::Path;
+    /// # use env_wrapper::{Environment, ExpandExt, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("HOME", "/home/me");
+    /// fake_env.set_var("MYAPP_DATA_DIR", "~/data");
+    ///
+    /// let data_dir = fake_env.var_path_expanded("MYAPP_DATA_DIR").unwrap();
+    ///
+    /// assert_eq!(data_dir, Path::new("/home/me/data"));
+    /// ```
+    fn var_path_expanded(&self, key: impl AsRef<OsStr>) -> Option<PathBuf> {
+        let value = self.var_os(key)?;
+        Some(expand_tilde(self, &value).into())
+    }
+}
+
+impl<E: ReadEnvironment> ExpandExt for E {}
+
+/// Read `key` and expand the references in its value, counting the variable
+/// itself as part of any cycle. Returns `None` for keys that are not valid
+/// Unicode, which references cannot name.
+pub(crate) fn expand_var<E: ReadEnvironment + ?Sized>(
+    env: &E,
+    key: &OsStr,
+    options: &ExpandOptions,
+) -> Result<Option<String>, ExpandError> {
+    match key.to_str() {
+        Some(key) => Expander::new(env, options, 1).resolve(key),
+        None => Ok(None),
+    }
+}
+
+struct Expander<'a, E: ?Sized> {
+    env: &'a E,
+    unknown: UnknownVariable,
+    /// The variables whose values are being expanded, outermost first.
+    resolving: Vec<String>,
+    max_resolving: usize,
+}
+
+impl<'a, E: ReadEnvironment + ?Sized> Expander<'a, E> {
+    /// `roots` is how many of the resolved variables are not themselves
+    /// references, and so do not count towards the depth.
+    fn new(env: &'a E, options: &ExpandOptions, roots: usize) -> Self {
+        Expander {
+            env,
+            unknown: options.unknown,
+            resolving: Vec::new(),
+            max_resolving: options.max_depth.saturating_add(roots),
+        }
+    }
+
+    fn expand(&mut self, input: &str) -> Result<String, ExpandError> {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find('$') {
+            output.push_str(&rest[..start]);
+            let reference = &rest[start..];
+            let after_dollar = &reference[1..];
+            if let Some(after_escape) = after_dollar.strip_prefix('$') {
+                output.push('$');
+                rest = after_escape;
+            } else if let Some(braced) = after_dollar.strip_prefix('{') {
+                let end = closing_brace(braced)
+                    .ok_or_else(|| ExpandError::Unterminated(reference.to_owned()))?;
+                // `${` + contents + `}`
+                let reference_len = end + 3;
+                self.substitute_braced(&braced[..end], &reference[..reference_len], &mut output)?;
+                rest = &reference[reference_len..];
+            } else {
+                let name_len = name_len(after_dollar);
+                if name_len == 0 {
+                    output.push('$');
+                } else {
+                    let name = &after_dollar[..name_len];
+                    self.substitute(name, &reference[..name_len + 1], &mut output)?;
+                }
+                rest = &after_dollar[name_len..];
+            }
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn substitute(
+        &mut self,
+        name: &str,
+        reference: &str,
+        output: &mut String,
+    ) -> Result<(), ExpandError> {
+        match self.resolve(name)? {
+            Some(value) => output.push_str(&value),
+            None => match self.unknown {
+                UnknownVariable::Error => return Err(ExpandError::Unknown(name.to_owned())),
+                UnknownVariable::Empty => {}
+                UnknownVariable::Literal => output.push_str(reference),
+            },
+        }
+        Ok(())
+    }
+
+    /// Substitute a `${...}` reference, given what is between the braces.
+    fn substitute_braced(
+        &mut self,
+        contents: &str,
+        reference: &str,
+        output: &mut String,
+    ) -> Result<(), ExpandError> {
+        let Some((name, operator, word)) = split_operator(contents) else {
+            return self.substitute(contents, reference, output);
+        };
+        let value = self
+            .resolve(name)?
+            .filter(|value| !(operator.colon && value.is_empty()));
+        match (operator.kind, value) {
+            (OperatorKind::Default | OperatorKind::Required, Some(value)) => {
+                output.push_str(&value);
+            }
+            (OperatorKind::Default, None) | (OperatorKind::Alternate, Some(_)) => {
+                let word = self.expand(word)?;
+                output.push_str(&word);
+            }
+            (OperatorKind::Required, None) => {
+                return Err(ExpandError::Required {
+                    name: name.to_owned(),
+                    message: self.expand(word)?,
+                });
+            }
+            (OperatorKind::Alternate, None) => {}
+        }
+        Ok(())
+    }
+
+    /// The expanded value of `name`, or `None` if it is not set.
+    fn resolve(&mut self, name: &str) -> Result<Option<String>, ExpandError> {
+        if let Some(position) = self.resolving.iter().position(|other| other == name) {
+            let mut cycle = self.resolving[position..].to_vec();
+            cycle.push(name.to_owned());
+            return Err(ExpandError::Cycle(cycle));
+        }
+        if self.resolving.len() >= self.max_resolving {
+            return Err(ExpandError::TooDeep(name.to_owned()));
+        }
+        let Some(value) = self.env.var_os(name) else {
+            return Ok(None);
+        };
+        let value = value
+            .into_string()
+            .map_err(|_| ExpandError::NotUnicode(name.to_owned()))?;
+        self.resolving.push(name.to_owned());
+        let expanded = self.expand(&value);
+        self.resolving.pop();
+        expanded.map(Some)
+    }
+}
+
+/// The operator of a `${NAME:-word}` style reference.
+#[derive(Clone, Copy)]
+struct Operator {
+    kind: OperatorKind,
+    /// Whether an empty value counts as unset, as with `:-` but not `-`.
+    colon: bool,
+}
+
+#[derive(Clone, Copy)]
+enum OperatorKind {
+    /// `-`: use the word if the variable is unset.
+    Default,
+    /// `?`: fail with the word if the variable is unset.
+    Required,
+    /// `+`: use the word if the variable is set.
+    Alternate,
+}
+
+/// Split the contents of a `${...}` reference into its name, operator and
+/// word, or `None` if it is a plain `${NAME}`.
+fn split_operator(contents: &str) -> Option<(&str, Operator, &str)> {
+    let name_len = name_len(contents);
+    if name_len == 0 {
+        return None;
+    }
+    let (name, rest) = contents.split_at(name_len);
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let kind = match rest.chars().next()? {
+        '-' => OperatorKind::Default,
+        '?' => OperatorKind::Required,
+        '+' => OperatorKind::Alternate,
+        _ => return None,
+    };
+    Some((name, Operator { kind, colon }, &rest[1..]))
+}
+
+/// The offset of the `}` closing a `${` reference in `braced`, the text
+/// after the `${`, skipping over the references nested in it.
+fn closing_brace(braced: &str) -> Option<usize> {
+    let bytes = braced.as_bytes();
+    let mut depth = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match (bytes[index], bytes.get(index + 1)) {
+            (b'$', Some(b'{')) => {
+                depth += 1;
+                index += 1;
+            }
+            // An escaped `$` cannot open a reference.
+            (b'$', Some(b'$')) => index += 1,
+            (b'}', _) if depth == 0 => return Some(index),
+            (b'}', _) => depth -= 1,
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// The length of the `$NAME` name at the start of `input`, or 0 if there is
+/// none.
+fn name_len(input: &str) -> usize {
+    input
+        .bytes()
+        .enumerate()
+        .take_while(|&(index, byte)| {
+            byte == b'_' || byte.is_ascii_alphabetic() || (index > 0 && byte.is_ascii_digit())
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{ExpandError, ExpandExt, ExpandOptions, UnknownVariable};
+    use crate::{test_helpers::fake_env, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_a_known_variable_when_expanding_windows_references_then_it_is_substituted() {
+        // Arrange
+        let env = fake_env(&[("APPDATA", "C:\\AppData")]);
+
+        // Act
+        let result = env.expand_windows("%APPDATA%\\myapp");
+
+        // Assert
+        assert_eq!(result, "C:\\AppData\\myapp");
+    }
+
+    #[test]
+    fn given_an_unknown_variable_when_expanding_windows_references_then_it_is_left_literally() {
+        // Arrange
+        let env = fake_env(&[]);
+
+        // Act
+        let result = env.expand_windows("a %UNKNOWN% b");
+
+        // Assert
+        assert_eq!(result, "a %UNKNOWN% b");
+    }
+
+    #[test]
+    fn given_adjacent_references_when_expanding_windows_references_then_both_are_substituted() {
+        // Arrange
+        let env = fake_env(&[("A", "1"), ("B", "2")]);
+
+        // Act
+        let result = env.expand_windows("%A%%B%");
+
+        // Assert
+        assert_eq!(result, "12");
+    }
+
+    #[test]
+    fn given_an_unknown_reference_followed_by_text_when_expanding_windows_references_then_its_closing_percent_can_open_the_next_reference(
+    ) {
+        // Arrange
+        let env = fake_env(&[("B", "2")]);
+
+        // Act
+        let result = env.expand_windows("%UNKNOWN%B%");
+
+        // Assert
+        assert_eq!(result, "%UNKNOWN2");
+    }
+
+    #[test]
+    fn when_expanding_windows_references_then_a_double_percent_is_not_an_escape() {
+        // Arrange
+        let env = fake_env(&[("A", "1")]);
+
+        // Act
+        let result = env.expand_windows("100%% %A%");
+
+        // Assert
+        assert_eq!(result, "100%% 1");
+    }
+
+    #[test]
+    fn given_a_value_containing_percent_references_when_expanding_windows_references_then_the_value_is_not_expanded_again(
+    ) {
+        // Arrange
+        let env = fake_env(&[("OUTER", "%INNER%"), ("INNER", "nested")]);
+
+        // Act
+        let result = env.expand_windows("%OUTER%");
+
+        // Assert
+        assert_eq!(result, "%INNER%");
+    }
+
+    #[test]
+    fn given_an_unclosed_percent_when_expanding_windows_references_then_the_rest_is_kept_literally()
+    {
+        // Arrange
+        let env = fake_env(&[("A", "1")]);
+
+        // Act
+        let result = env.expand_windows("%A% 50%A");
+
+        // Assert
+        assert_eq!(result, "1 50%A");
+    }
+
+    #[test]
+    fn given_nested_references_when_expanding_then_substituted_values_are_expanded_too() {
+        // Arrange
+        let env = fake_env(&[
+            ("HOME", "/home/me"),
+            ("DATA_DIR", "${HOME}/data"),
+            ("CACHE_DIR", "$DATA_DIR/cache"),
+        ]);
+
+        // Act
+        let result = env.expand("cache: ${CACHE_DIR}");
+
+        // Assert
+        assert_eq!(result.unwrap(), "cache: /home/me/data/cache");
+    }
+
+    #[test]
+    fn given_a_bare_reference_when_expanding_then_the_name_ends_at_the_first_non_name_character() {
+        // Arrange
+        let env = fake_env(&[("A", "1"), ("A_2", "2")]);
+
+        // Act
+        let result = env.expand("$A_2-$A.txt $1");
+
+        // Assert
+        assert_eq!(result.unwrap(), "2-1.txt $1");
+    }
+
+    #[test]
+    fn when_expanding_then_a_double_dollar_is_a_literal_dollar() {
+        // Arrange
+        let env = fake_env(&[("PRICE", "5"), ("ESCAPED", "$${PRICE}")]);
+
+        // Act
+        let result = env.expand("$$$PRICE $$PRICE $ESCAPED $");
+
+        // Assert
+        assert_eq!(result.unwrap(), "$5 $PRICE ${PRICE} $");
+    }
+
+    #[test]
+    fn given_variables_referencing_each_other_when_expanding_then_it_is_a_cycle_error() {
+        // Arrange
+        let env = fake_env(&[("A", "${B}"), ("B", "x${C}"), ("C", "$A")]);
+
+        // Act
+        let result = env.expand("start ${A}");
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            ExpandError::Cycle(vec!["A".into(), "B".into(), "C".into(), "A".into()])
+        );
+    }
+
+    #[test]
+    fn given_the_same_variable_referenced_twice_when_expanding_then_it_is_not_a_cycle() {
+        // Arrange
+        let env = fake_env(&[("A", "$B$B"), ("B", "b")]);
+
+        // Act
+        let result = env.expand("$A$A");
+
+        // Assert
+        assert_eq!(result.unwrap(), "bbbb");
+    }
+
+    #[test]
+    fn given_references_deeper_than_the_maximum_when_expanding_then_it_is_a_too_deep_error() {
+        // Arrange
+        let env = fake_env(&[("A", "$B"), ("B", "$C"), ("C", "c")]);
+        let options = ExpandOptions::new().max_depth(2);
+
+        // Act
+        let shallow = env.expand_with("$B", &options);
+        let deep = env.expand_with("$A", &options);
+
+        // Assert
+        assert_eq!(shallow.unwrap(), "c");
+        assert_eq!(deep.unwrap_err(), ExpandError::TooDeep("C".into()));
+    }
+
+    #[test]
+    fn given_each_unknown_variable_policy_when_expanding_an_unknown_reference_then_it_is_applied() {
+        // Arrange
+        let env = fake_env(&[]);
+        let options = |policy| ExpandOptions::new().unknown_variables(policy);
+
+        // Act
+        let error = env.expand_with("a ${UNSET} $UNSET", &options(UnknownVariable::Error));
+        let empty = env.expand_with("a ${UNSET} $UNSET", &options(UnknownVariable::Empty));
+        let literal = env.expand_with("a ${UNSET} $UNSET", &options(UnknownVariable::Literal));
+
+        // Assert
+        assert_eq!(error.unwrap_err(), ExpandError::Unknown("UNSET".into()));
+        assert_eq!(empty.unwrap(), "a  ");
+        assert_eq!(literal.unwrap(), "a ${UNSET} $UNSET");
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_expanding_a_reference_to_it_then_it_is_a_not_unicode_error() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BIN", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = env.expand("$BIN");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), ExpandError::NotUnicode("BIN".into()));
+    }
+
+    #[test]
+    fn given_an_unclosed_brace_when_expanding_then_it_is_an_unterminated_error() {
+        // Arrange
+        let env = fake_env(&[("A", "1")]);
+
+        // Act
+        let result = env.expand("$A ${A");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), ExpandError::Unterminated("${A".into()));
+    }
+
+    #[test]
+    fn given_each_operator_when_expanding_a_set_empty_or_unset_variable_then_posix_rules_apply() {
+        // Arrange
+        let env = fake_env(&[("SET", "value"), ("EMPTY", ""), ("WORD", "word")]);
+        let cases = [
+            ("${SET:-$WORD}", Ok("value")),
+            ("${EMPTY:-$WORD}", Ok("word")),
+            ("${UNSET:-$WORD}", Ok("word")),
+            ("${SET-$WORD}", Ok("value")),
+            ("${EMPTY-$WORD}", Ok("")),
+            ("${UNSET-$WORD}", Ok("word")),
+            ("${SET:+$WORD}", Ok("word")),
+            ("${EMPTY:+$WORD}", Ok("")),
+            ("${UNSET:+$WORD}", Ok("")),
+            ("${SET+$WORD}", Ok("word")),
+            ("${EMPTY+$WORD}", Ok("word")),
+            ("${UNSET+$WORD}", Ok("")),
+            ("${SET:?no $WORD}", Ok("value")),
+            ("${EMPTY:?no $WORD}", Err(("EMPTY", "no word"))),
+            ("${UNSET:?no $WORD}", Err(("UNSET", "no word"))),
+            ("${SET?no $WORD}", Ok("value")),
+            ("${EMPTY?no $WORD}", Ok("")),
+            ("${UNSET?no $WORD}", Err(("UNSET", "no word"))),
+        ];
+
+        for (input, expected) in cases {
+            // Act
+            let result = env.expand(input);
+
+            // Assert
+            let expected =
+                expected
+                    .map(String::from)
+                    .map_err(|(name, message)| ExpandError::Required {
+                        name: name.into(),
+                        message: message.into(),
+                    });
+            assert_eq!(result, expected, "expanding {input}");
+        }
+    }
+
+    #[test]
+    fn given_nested_defaults_when_expanding_then_the_first_set_value_is_used() {
+        // Arrange
+        let env = fake_env(&[("HOST", "db"), ("FALLBACK", "${HOST}:5432")]);
+
+        // Act
+        let nested = env.expand("${URL:-${OTHER:-postgres://${FALLBACK}}}/app");
+        let unused = env.expand("${HOST:-${UNSET:?never expanded}}");
+        let escaped = env.expand("${UNSET:-$${HOST}}");
+
+        // Assert
+        assert_eq!(nested.unwrap(), "postgres://db:5432/app");
+        assert_eq!(unused.unwrap(), "db");
+        assert_eq!(escaped.unwrap(), "${HOST}");
+    }
+
+    #[test]
+    fn given_an_operator_when_expanding_an_unset_variable_then_the_unknown_policy_does_not_apply() {
+        // Arrange
+        let env = fake_env(&[]);
+        let options = ExpandOptions::new().unknown_variables(UnknownVariable::Literal);
+
+        // Act
+        let default = env.expand("${UNSET:-fallback}");
+        let alternate = env.expand_with("[${UNSET:+alt}]", &options);
+        let required = env.expand("${UNSET:?}");
+
+        // Assert
+        assert_eq!(default.unwrap(), "fallback");
+        assert_eq!(alternate.unwrap(), "[]");
+        let error = required.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"UNSET\" is required but not set"
+        );
+    }
+
+    #[test]
+    fn given_an_unclosed_nested_reference_when_expanding_then_it_is_an_unterminated_error() {
+        // Arrange
+        let env = fake_env(&[]);
+
+        // Act
+        let result = env.expand("${A:-${B}");
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            ExpandError::Unterminated("${A:-${B}".into())
+        );
+    }
+}
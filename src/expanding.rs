@@ -0,0 +1,316 @@
+use std::ffi::{OsStr, OsString};
+
+use crate::{
+    expand::expand_var, EnumerableEnvironment, EnvError, Environment, ExpandError, ExpandOptions,
+    ReadEnvironment, VersionedEnvironment,
+};
+
+/// A wrapper that expands `${NAME}` and `$NAME` references in values when
+/// they are read, so `DATA_DIR=${HOME}/data` reads as `/home/me/data`.
+///
+/// References are resolved against the same environment, and expanded
+/// recursively, following the rules of
+/// [`ExpandExt::expand_with`](crate::ExpandExt::expand_with).
+///
+/// A variable whose value cannot be expanded, for example because of a cycle
+/// or, with [`UnknownVariable::Error`](crate::UnknownVariable::Error), a
+/// reference to a variable that is not set, has no value to read: `var`
+/// returns `VarError::NotPresent`, `var_os` returns `None`, and listing skips
+/// it. Use [`expand_var`](ExpandingEnvironment::expand_var) to get the
+/// [`ExpandError`](ExpandError) instead. Variables whose name or value is not
+/// valid Unicode are read as stored, unexpanded. Writes pass through
+/// unexpanded.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, ExpandingEnvironment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("HOME", "/home/me");
+/// fake_env.set_var("DATA_DIR", "${HOME}/data");
+///
+/// let env = ExpandingEnvironment::new(fake_env);
+///
+/// assert_eq!(env.var("DATA_DIR").unwrap(), "/home/me/data");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExpandingEnvironment<E> {
+    inner: E,
+    options: ExpandOptions,
+}
+
+impl<E: ReadEnvironment> ExpandingEnvironment<E> {
+    /// Wrap `inner`, expanding with the default
+    /// [`ExpandOptions`](ExpandOptions).
+    pub fn new(inner: E) -> Self {
+        Self::with_options(inner, ExpandOptions::new())
+    }
+
+    /// Wrap `inner`, expanding with `options`.
+    pub fn with_options(inner: E, options: ExpandOptions) -> Self {
+        ExpandingEnvironment { inner, options }
+    }
+
+    /// Read `key` and expand the references in its value, returning `None` if
+    /// it is not set.
+    ///
+    /// # Errors
+    /// As for [`ExpandExt::expand_with`](crate::ExpandExt::expand_with). A
+    /// variable whose value references itself, directly or not, is a
+    /// [`ExpandError::Cycle`](ExpandError::Cycle).
+    pub fn expand_var(&self, key: impl AsRef<OsStr>) -> Result<Option<String>, ExpandError> {
+        expand_var(&self.inner, key.as_ref(), &self.options)
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for ExpandingEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let key = key.as_ref();
+        let Some(name) = key.to_str() else {
+            return self.inner.var_os(key);
+        };
+        match self.expand_var(key) {
+            Ok(value) => value.map(OsString::from),
+            Err(ExpandError::NotUnicode(culprit)) if culprit == name => self.inner.var_os(key),
+            Err(_) => None,
+        }
+    }
+}
+
+impl<E: Environment> Environment for ExpandingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(key)
+    }
+}
+
+/// Expanded values can only change when the variables they are expanded from
+/// do, so this is the generation of the underlying environment.
+impl<E: VersionedEnvironment> VersionedEnvironment for ExpandingEnvironment<E> {
+    fn generation(&self) -> u64 {
+        self.inner.generation()
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for ExpandingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner
+            .vars_os()
+            .into_iter()
+            .filter_map(|(key, _)| {
+                let value = self.var_os(&key)?;
+                Some((key, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::ExpandingEnvironment;
+    use crate::{
+        test_helpers::fake_env, EnumerableEnvironment, Environment, ExpandError, ExpandOptions,
+        FakeEnvironment, ReadEnvironment, UnknownVariable, VersionedEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_nested_references_when_reading_then_the_value_is_fully_expanded() {
+        // Arrange
+        let env = ExpandingEnvironment::new(fake_env(&[
+            ("HOME", "/home/me"),
+            ("DATA_DIR", "${HOME}/data"),
+            ("CACHE_DIR", "$DATA_DIR/cache"),
+        ]));
+
+        // Act/Assert
+        assert_eq!(env.var("CACHE_DIR").unwrap(), "/home/me/data/cache");
+        assert_eq!(env.var_os("DATA_DIR").unwrap(), "/home/me/data");
+        assert_eq!(env.var("MISSING").unwrap_err(), VarError::NotPresent);
+    }
+
+    #[test]
+    fn given_an_escaped_dollar_when_reading_then_it_is_a_literal_dollar() {
+        // Arrange
+        let env =
+            ExpandingEnvironment::new(fake_env(&[("PRICE", "$$5 for ${ITEM}"), ("ITEM", "tea")]));
+
+        // Act/Assert
+        assert_eq!(env.var("PRICE").unwrap(), "$5 for tea");
+    }
+
+    #[test]
+    fn given_a_cycle_when_expanding_a_variable_then_it_is_a_cycle_error() {
+        // Arrange
+        let env = ExpandingEnvironment::new(fake_env(&[
+            ("A", "${B}"),
+            ("B", "${A}"),
+            ("SELF", "x$SELF"),
+        ]));
+
+        // Act/Assert
+        assert_eq!(
+            env.expand_var("A").unwrap_err(),
+            ExpandError::Cycle(vec!["A".into(), "B".into(), "A".into()])
+        );
+        assert_eq!(
+            env.expand_var("SELF").unwrap_err(),
+            ExpandError::Cycle(vec!["SELF".into(), "SELF".into()])
+        );
+    }
+
+    #[test]
+    fn given_a_cycle_when_reading_then_it_is_not_present() {
+        // Arrange
+        let env = ExpandingEnvironment::new(fake_env(&[("A", "${B}"), ("B", "${A}"), ("C", "c")]));
+
+        // Act/Assert
+        assert_eq!(env.var("A").unwrap_err(), VarError::NotPresent);
+        assert!(env.var_os("B").is_none());
+        assert_eq!(env.vars_os(), vec![("C".into(), "c".into())]);
+    }
+
+    #[test]
+    fn given_an_unknown_reference_and_the_error_policy_when_reading_then_it_is_not_present() {
+        // Arrange
+        let inner = fake_env(&[("PATH_LIKE", "/bin:${EXTRA}")]);
+        let options = ExpandOptions::new().unknown_variables(UnknownVariable::Error);
+        let env = ExpandingEnvironment::with_options(inner, options);
+
+        // Act
+        let result = env.var("PATH_LIKE");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), VarError::NotPresent);
+    }
+
+    #[test]
+    fn given_a_non_unicode_key_when_reading_then_its_value_is_read_as_stored() {
+        // Arrange
+        let key = OsStr::from_bytes(&INVALID_UTF8);
+        let mut inner = fake_env(&[("HOME", "/home/me"), ("fo\u{fffd}o", "wrong")]);
+        inner.set_var(key, "${HOME}/data");
+        let env = ExpandingEnvironment::new(inner);
+
+        // Act/Assert
+        assert_eq!(env.expand_var(key), Ok(None));
+        assert_eq!(env.var(key).unwrap(), "${HOME}/data");
+    }
+
+    #[test]
+    fn given_a_maximum_depth_when_expanding_a_variable_then_deeper_references_are_an_error() {
+        // Arrange
+        let inner = fake_env(&[("A", "$B"), ("B", "$C"), ("C", "c")]);
+        let env = ExpandingEnvironment::with_options(inner, ExpandOptions::new().max_depth(1));
+
+        // Act/Assert
+        assert_eq!(env.expand_var("B").unwrap().unwrap(), "c");
+        assert_eq!(
+            env.expand_var("A").unwrap_err(),
+            ExpandError::TooDeep("C".into())
+        );
+    }
+
+    #[test]
+    fn given_each_unknown_variable_policy_when_expanding_an_unknown_reference_then_it_is_applied() {
+        // Arrange
+        let env = |policy| {
+            let inner = fake_env(&[("PATH_LIKE", "/bin:${EXTRA}")]);
+            let options = ExpandOptions::new().unknown_variables(policy);
+            ExpandingEnvironment::with_options(inner, options)
+        };
+
+        // Act/Assert
+        assert_eq!(
+            env(UnknownVariable::Error)
+                .expand_var("PATH_LIKE")
+                .unwrap_err(),
+            ExpandError::Unknown("EXTRA".into())
+        );
+        assert_eq!(
+            env(UnknownVariable::Empty).var("PATH_LIKE").unwrap(),
+            "/bin:"
+        );
+        assert_eq!(
+            env(UnknownVariable::Literal).var("PATH_LIKE").unwrap(),
+            "/bin:${EXTRA}"
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_reading_then_it_passes_through_unexpanded() {
+        // Arrange
+        let value = OsStr::from_bytes(&INVALID_UTF8);
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("BIN", value);
+        let env = ExpandingEnvironment::new(inner);
+
+        // Act/Assert
+        assert_eq!(env.var_os("BIN").unwrap(), value);
+        assert_eq!(
+            env.var("BIN").unwrap_err(),
+            VarError::NotUnicode(value.into())
+        );
+    }
+
+    #[test]
+    fn given_references_when_writing_and_listing_then_values_are_stored_raw_and_listed_expanded() {
+        // Arrange
+        let mut env = ExpandingEnvironment::new(fake_env(&[("HOME", "/home/me")]));
+
+        // Act
+        env.set_var("DATA_DIR", "${HOME}/data");
+        let mut vars = env.vars_os();
+
+        // Assert
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                ("DATA_DIR".into(), "/home/me/data".into()),
+                ("HOME".into(), "/home/me".into()),
+            ]
+        );
+        assert_eq!(env.into_inner().var("DATA_DIR").unwrap(), "${HOME}/data");
+    }
+
+    #[test]
+    fn given_a_change_to_a_referenced_variable_when_checking_the_generation_then_it_changed() {
+        // Arrange
+        let mut env = ExpandingEnvironment::new(fake_env(&[
+            ("HOME", "/home/me"),
+            ("DATA_DIR", "${HOME}/data"),
+        ]));
+        let seen = env.generation();
+        let _ = env.var("DATA_DIR");
+
+        // Act
+        env.set_var("HOME", "/home/you");
+
+        // Assert
+        assert!(env.changed_since(seen));
+        assert_eq!(env.var("DATA_DIR").unwrap(), "/home/you/data");
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{EnumerableEnvironment, EnvError, Environment, ReadEnvironment};
+
+/// How a read of a failing variable fails.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// The variable reads as not set: `var` returns
+    /// [`VarError::NotPresent`](VarError::NotPresent) and `var_os` returns
+    /// `None`.
+    Missing,
+    /// `var` returns this error. `var_os` is unaffected.
+    VarError(VarError),
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    fault: Fault,
+    successes_left: usize,
+}
+
+impl Rule {
+    /// Count a read, returning the fault if it should fail.
+    fn read(&mut self) -> Option<Fault> {
+        if self.successes_left > 0 {
+            self.successes_left -= 1;
+            None
+        } else {
+            Some(self.fault.clone())
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    keys: HashMap<OsString, Rule>,
+    all: Option<Rule>,
+}
+
+/// A handle for changing which reads of a
+/// [`FailingEnvironment`](FailingEnvironment) fail, even after it has been
+/// handed to the code under test.
+///
+/// A fault configured for a key takes precedence over one configured for all
+/// keys. Configuring a fault replaces the previous one and restarts its
+/// count of successful reads.
+#[derive(Clone, Debug, Default)]
+pub struct FaultHandle {
+    faults: Arc<Mutex<Faults>>,
+}
+
+impl FaultHandle {
+    /// Make every read of `key` fail with `fault`.
+    pub fn fail(&self, key: impl AsRef<OsStr>, fault: Fault) {
+        self.fail_after(key, fault, 0)
+    }
+
+    /// Let the next `successes` reads of `key` succeed, then make every read
+    /// of it fail with `fault`.
+    pub fn fail_after(&self, key: impl AsRef<OsStr>, fault: Fault, successes: usize) {
+        self.lock().keys.insert(
+            key.as_ref().into(),
+            Rule {
+                fault,
+                successes_left: successes,
+            },
+        );
+    }
+
+    /// Make every read of a key without its own fault fail with `fault`.
+    pub fn fail_all(&self, fault: Fault) {
+        self.fail_all_after(fault, 0)
+    }
+
+    /// Let the next `successes` reads of keys without their own fault
+    /// succeed, then make every such read fail with `fault`.
+    pub fn fail_all_after(&self, fault: Fault, successes: usize) {
+        self.lock().all = Some(Rule {
+            fault,
+            successes_left: successes,
+        });
+    }
+
+    /// Stop reads of `key` failing, unless a fault is configured for all
+    /// keys.
+    pub fn clear(&self, key: impl AsRef<OsStr>) {
+        self.lock().keys.remove(key.as_ref());
+    }
+
+    /// Stop all reads failing.
+    pub fn clear_all(&self) {
+        *self.lock() = Faults::default();
+    }
+
+    fn read(&self, key: &OsStr) -> Option<Fault> {
+        let mut faults = self.lock();
+        if let Some(rule) = faults.keys.get_mut(key) {
+            return rule.read();
+        }
+        faults.all.as_mut().and_then(Rule::read)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Faults> {
+        // The faults are valid even if a panic interrupted another user.
+        self.faults.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A wrapper that makes reads fail on demand, for testing how code handles
+/// unset or non-Unicode variables without setting up such values.
+///
+/// Faults are configured through a [`FaultHandle`](FaultHandle), which can be
+/// kept by the test to change them mid-scenario. Writes, removals, and
+/// listing variables pass through unchanged.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FailingEnvironment, FakeEnvironment, Fault, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("DATABASE_URL", "postgres://localhost");
+///
+/// let env = FailingEnvironment::new(fake_env);
+/// let faults = env.handle();
+/// faults.fail_after("DATABASE_URL", Fault::Missing, 1);
+///
+/// assert!(env.var("DATABASE_URL").is_ok());
+/// assert!(env.var("DATABASE_URL").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FailingEnvironment<E> {
+    inner: E,
+    faults: FaultHandle,
+}
+
+impl<E: ReadEnvironment> FailingEnvironment<E> {
+    /// Wrap `inner`, with no reads failing yet.
+    pub fn new(inner: E) -> Self {
+        FailingEnvironment {
+            inner,
+            faults: FaultHandle::default(),
+        }
+    }
+
+    /// A handle for configuring which reads fail.
+    pub fn handle(&self) -> FaultHandle {
+        self.faults.clone()
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for FailingEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        match self.faults.read(key.as_ref()) {
+            None => self.inner.var(key),
+            Some(Fault::Missing) => Err(VarError::NotPresent),
+            Some(Fault::VarError(error)) => Err(error),
+        }
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.faults.read(key.as_ref()) {
+            Some(Fault::Missing) => None,
+            None | Some(Fault::VarError(_)) => self.inner.var_os(key),
+        }
+    }
+}
+
+impl<E: Environment> Environment for FailingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for FailingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::VarError;
+
+    use super::{FailingEnvironment, Fault};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    fn fake_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("DATABASE_URL", "postgres://localhost");
+        env.set_var("HOME", "/home/me");
+        env
+    }
+
+    #[test]
+    fn given_a_missing_fault_when_reading_the_key_then_it_is_not_present() {
+        // Arrange
+        let env = FailingEnvironment::new(fake_env());
+        env.handle().fail("DATABASE_URL", Fault::Missing);
+
+        // Act/Assert
+        assert_eq!(env.var("DATABASE_URL").unwrap_err(), VarError::NotPresent);
+        assert!(env.var_os("DATABASE_URL").is_none());
+        assert_eq!(env.var("HOME").unwrap(), "/home/me");
+    }
+
+    #[test]
+    fn given_a_var_error_fault_when_reading_the_key_then_var_fails_and_var_os_does_not() {
+        // Arrange
+        let env = FailingEnvironment::new(fake_env());
+        let error = VarError::NotUnicode("bad".into());
+        env.handle().fail("HOME", Fault::VarError(error.clone()));
+
+        // Act/Assert
+        assert_eq!(env.var("HOME").unwrap_err(), error);
+        assert_eq!(env.var_os("HOME").unwrap(), "/home/me");
+    }
+
+    #[test]
+    fn given_a_fault_for_all_keys_when_reading_then_keys_with_their_own_fault_use_it() {
+        // Arrange
+        let env = FailingEnvironment::new(fake_env());
+        let faults = env.handle();
+        faults.fail_all(Fault::Missing);
+        faults.fail("HOME", Fault::VarError(VarError::NotUnicode("x".into())));
+
+        // Act/Assert
+        assert_eq!(env.var("DATABASE_URL").unwrap_err(), VarError::NotPresent);
+        assert_eq!(
+            env.var("HOME").unwrap_err(),
+            VarError::NotUnicode("x".into())
+        );
+    }
+
+    #[test]
+    fn given_a_fault_after_n_reads_when_reading_repeatedly_then_only_later_reads_fail() {
+        // Arrange
+        let env = FailingEnvironment::new(fake_env());
+        env.handle().fail_after("DATABASE_URL", Fault::Missing, 2);
+
+        // Act
+        let first = env.var("DATABASE_URL");
+        let second = env.var_os("DATABASE_URL");
+        let other = env.var("HOME");
+        let third = env.var("DATABASE_URL");
+
+        // Assert
+        assert!(first.is_ok());
+        assert!(second.is_some());
+        assert!(other.is_ok());
+        assert_eq!(third.unwrap_err(), VarError::NotPresent);
+    }
+
+    #[test]
+    fn given_a_fault_for_all_keys_after_n_reads_when_reading_then_reads_of_any_key_count() {
+        // Arrange
+        let env = FailingEnvironment::new(fake_env());
+        env.handle().fail_all_after(Fault::Missing, 1);
+
+        // Act/Assert
+        assert!(env.var("HOME").is_ok());
+        assert!(env.var_os("DATABASE_URL").is_none());
+    }
+
+    #[test]
+    fn given_a_handle_kept_by_the_test_when_changing_faults_mid_scenario_then_reads_follow() {
+        // Arrange
+        let mut env = FailingEnvironment::new(fake_env());
+        let faults = env.handle();
+
+        // Act/Assert
+        assert!(env.var("HOME").is_ok());
+        faults.fail("HOME", Fault::Missing);
+        assert!(env.var("HOME").is_err());
+        faults.clear("HOME");
+        assert!(env.var("HOME").is_ok());
+        faults.fail_all(Fault::Missing);
+        env.set_var("NEW", "value");
+        assert!(env.var("NEW").is_err());
+        faults.clear_all();
+        assert_eq!(env.var("NEW").unwrap(), "value");
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{EnvError, ReadEnvironment};
+
+/// Feature flags read from a variable listing them, such as
+/// `MYAPP_FEATURES=new_parser,-legacy_auth,beta_ui`.
+///
+/// Each entry of the comma-separated list enables the flag it names, or
+/// disables it if it starts with `-`. Names are trimmed and compared
+/// case-insensitively, empty entries are ignored, and when a flag is listed
+/// more than once, the last entry wins. Flags that are not listed take the
+/// default declared with [`flag`](FeatureFlags::flag), or are disabled.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, FeatureFlags};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_FEATURES", "new_parser, -legacy_auth, beta_uj");
+///
+/// let flags = FeatureFlags::from_env(&fake_env, "MYAPP_FEATURES")
+///     .unwrap()
+///     .flag("new_parser", false)
+///     .flag("legacy_auth", true)
+///     .flag("beta_ui", false)
+///     .flag("fast_path", true);
+///
+/// assert!(flags.is_enabled("new_parser"));
+/// assert!(!flags.is_enabled("legacy_auth"));
+/// assert!(!flags.is_enabled("beta_ui"));
+/// assert!(flags.is_enabled("fast_path"));
+/// assert_eq!(flags.unknown_flags(), ["beta_uj"]);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeatureFlags {
+    listed: BTreeMap<String, bool>,
+    defaults: HashMap<String, bool>,
+}
+
+impl FeatureFlags {
+    /// Parse the flags listed in the variable named `key` of `env`. If it
+    /// is not set, every flag takes its default.
+    ///
+    /// # Errors
+    /// If the variable's value is not valid Unicode.
+    pub fn from_env(env: &impl ReadEnvironment, key: &str) -> Result<Self, EnvError> {
+        match env.var_redacted(key) {
+            Ok(value) => Ok(Self::parse(&value)),
+            Err(EnvError::NotPresent(_)) => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Parse the flags listed in `value`.
+    pub fn parse(value: &str) -> Self {
+        let mut listed = BTreeMap::new();
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            let (name, enabled) = match entry.strip_prefix('-') {
+                Some(name) => (name.trim(), false),
+                None => (entry, true),
+            };
+            if !name.is_empty() {
+                listed.insert(name.to_lowercase(), enabled);
+            }
+        }
+        FeatureFlags {
+            listed,
+            defaults: HashMap::new(),
+        }
+    }
+
+    /// Declare the flag `name`, enabled by default if `enabled` is `true`.
+    pub fn flag(mut self, name: &str, enabled: bool) -> Self {
+        self.defaults.insert(name.to_lowercase(), enabled);
+        self
+    }
+
+    /// Whether the flag `name` is enabled: as listed, otherwise as declared,
+    /// otherwise not.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.listed
+            .get(&name)
+            .or_else(|| self.defaults.get(&name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// The flags listed that were not declared, in lower case and sorted,
+    /// to warn about as they may be typos.
+    pub fn unknown_flags(&self) -> Vec<&str> {
+        self.listed
+            .keys()
+            .filter(|name| !self.defaults.contains_key(*name))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::FeatureFlags;
+    use crate::{EnvError, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn flags(value: &str) -> FeatureFlags {
+        FeatureFlags::parse(value)
+            .flag("new_parser", false)
+            .flag("legacy_auth", true)
+            .flag("beta_ui", false)
+    }
+
+    #[test]
+    fn given_listed_flags_when_checking_them_then_they_are_enabled_whatever_their_case() {
+        // Arrange
+        let flags = flags(" New_Parser ,,BETA_UI");
+
+        // Act/Assert
+        assert!(flags.is_enabled("new_parser"));
+        assert!(flags.is_enabled("beta_ui"));
+        assert!(flags.is_enabled("Beta_UI"));
+        assert!(flags.is_enabled("legacy_auth"));
+        assert!(flags.unknown_flags().is_empty());
+    }
+
+    #[test]
+    fn given_a_disabled_flag_that_is_on_by_default_when_checking_it_then_it_is_disabled() {
+        // Arrange
+        let flags = flags("new_parser,- legacy_auth");
+
+        // Act/Assert
+        assert!(!flags.is_enabled("legacy_auth"));
+        assert!(flags.is_enabled("new_parser"));
+    }
+
+    #[test]
+    fn given_a_flag_listed_several_times_when_checking_it_then_the_last_entry_wins() {
+        // Arrange
+        let flags = flags("beta_ui,-legacy_auth,-beta_ui,legacy_auth,new_parser,new_parser");
+
+        // Act/Assert
+        assert!(!flags.is_enabled("beta_ui"));
+        assert!(flags.is_enabled("legacy_auth"));
+        assert!(flags.is_enabled("new_parser"));
+    }
+
+    #[test]
+    fn given_undeclared_flags_when_listing_unknown_ones_then_they_are_returned_sorted() {
+        // Arrange
+        let flags = flags("Zeta,new_parser,-alpha");
+
+        // Act/Assert
+        assert_eq!(flags.unknown_flags(), ["alpha", "zeta"]);
+        assert!(flags.is_enabled("zeta"));
+        assert!(!flags.is_enabled("alpha"));
+        assert!(!flags.is_enabled("never_mentioned"));
+    }
+
+    #[test]
+    fn given_an_unset_variable_when_reading_flags_then_every_flag_takes_its_default() {
+        // Arrange
+        let fake_env = FakeEnvironment::new();
+
+        // Act
+        let flags = FeatureFlags::from_env(&fake_env, "MYAPP_FEATURES")
+            .unwrap()
+            .flag("new_parser", false)
+            .flag("legacy_auth", true);
+
+        // Assert
+        assert!(!flags.is_enabled("new_parser"));
+        assert!(flags.is_enabled("legacy_auth"));
+        assert!(flags.unknown_flags().is_empty());
+    }
+
+    #[test]
+    fn given_a_non_unicode_variable_when_reading_flags_then_it_fails() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("MYAPP_FEATURES", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let error = FeatureFlags::from_env(&fake_env, "MYAPP_FEATURES").unwrap_err();
+
+        // Assert
+        assert!(matches!(error, EnvError::NotUnicode { .. }));
+    }
+}
@@ -0,0 +1,306 @@
+use figment::{
+    value::{Dict, Map, Value},
+    Error, Metadata, Profile, Provider,
+};
+
+use crate::EnumerableEnvironment;
+
+/// A [`figment`](https://docs.rs/figment) [`Provider`] that sources values
+/// from any enumerable environment, so fakes can take part in layered
+/// configuration. Requires the `figment` feature.
+///
+/// It behaves like figment's own `Env` provider, which only reads the real
+/// process environment:
+/// * [`prefix`](FigmentProvider::prefix) keeps only the variables whose names
+///   start with the prefix, compared case-insensitively, and strips it.
+/// * [`split`](FigmentProvider::split) nests values into dictionaries at each
+///   occurrence of a pattern in the name.
+/// * Names are lower-cased unless disabled with
+///   [`lowercase`](FigmentProvider::lowercase).
+/// * Values are parsed with TOML-like syntax, so `8080` is a number and
+///   `[1, 2]` an array.
+///
+/// Names and values that are not valid Unicode are converted lossily.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, FigmentProvider};
+/// # use figment::Figment;
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///     port: u16,
+///     tls: Tls,
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Tls {
+///     cert_path: String,
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("APP_PORT", "8080");
+/// fake_env.set_var("APP_TLS__CERT_PATH", "/etc/tls/cert.pem");
+///
+/// let provider = FigmentProvider::new(fake_env).prefix("APP_").split("__");
+/// let config: Config = Figment::from(provider).extract().unwrap();
+///
+/// assert_eq!(config.port, 8080);
+/// assert_eq!(config.tls.cert_path, "/etc/tls/cert.pem");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FigmentProvider<E> {
+    inner: E,
+    prefix: Option<String>,
+    split: Option<String>,
+    lowercase: bool,
+    profile: Profile,
+}
+
+impl<E: EnumerableEnvironment> FigmentProvider<E> {
+    /// Provide every variable in `inner`, lower-cased and unsplit, to the
+    /// default profile.
+    pub fn new(inner: E) -> Self {
+        FigmentProvider {
+            inner,
+            prefix: None,
+            split: None,
+            lowercase: true,
+            profile: Profile::Default,
+        }
+    }
+
+    /// Only provide the variables whose names start with `prefix`, compared
+    /// case-insensitively, with the prefix removed.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Split names at each occurrence of `pattern`, nesting the value in a
+    /// dictionary for each part, so with `__` the variable `TLS__CERT_PATH`
+    /// provides `tls.cert_path`.
+    pub fn split(mut self, pattern: impl Into<String>) -> Self {
+        self.split = Some(pattern.into());
+        self
+    }
+
+    /// Whether to lower-case names before providing them. Defaults to `true`.
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Provide the values to `profile` instead of the default profile.
+    pub fn profile(mut self, profile: impl Into<Profile>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    /// Unwrap the provider, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    /// The dotted key path for a variable, or `None` if it is not provided.
+    fn key_path(&self, name: &str) -> Option<String> {
+        let name = name.trim();
+        let name = match &self.prefix {
+            Some(prefix) => name
+                .get(..prefix.len())
+                .filter(|start| start.eq_ignore_ascii_case(prefix))
+                .map(|_| &name[prefix.len()..])?,
+            None => name,
+        };
+        let name = name.trim();
+        let path = match &self.split {
+            Some(pattern) => name.replace(pattern.as_str(), "."),
+            None => name.to_owned(),
+        };
+        if path.split('.').any(str::is_empty) {
+            return None;
+        }
+        Some(if self.lowercase {
+            path.to_ascii_lowercase()
+        } else {
+            path
+        })
+    }
+}
+
+impl<E: EnumerableEnvironment + Send + Sync> Provider for FigmentProvider<E> {
+    fn metadata(&self) -> Metadata {
+        let name = match &self.prefix {
+            Some(prefix) => format!("`{}` environment variable(s)", prefix.to_ascii_uppercase()),
+            None => "environment variable(s)".to_owned(),
+        };
+        Metadata::named(name)
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let mut dict = Dict::new();
+        for (name, value) in self.inner.vars_os() {
+            let Some(path) = self.key_path(&name.to_string_lossy()) else {
+                continue;
+            };
+            let value: Value = value
+                .to_string_lossy()
+                .parse()
+                .expect("parsing a value is infallible");
+            insert(&mut dict, &path, value);
+        }
+        Ok(self.profile.collect(dict))
+    }
+}
+
+/// Insert `value` at the dotted `path` in `dict`, creating dictionaries
+/// along the way and replacing any other value in the way.
+fn insert(dict: &mut Dict, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((key, rest)) => {
+            let entry = dict
+                .entry(key.to_owned())
+                .or_insert_with(|| Dict::new().into());
+            if !matches!(entry, Value::Dict(..)) {
+                *entry = Dict::new().into();
+            }
+            if let Value::Dict(_, nested) = entry {
+                insert(nested, rest, value);
+            }
+        }
+        None => {
+            dict.insert(path.to_owned(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        providers::{Format, Toml},
+        Figment, Profile, Provider,
+    };
+    use serde::Deserialize;
+
+    use super::FigmentProvider;
+    use crate::{test_helpers::fake_env, FakeEnvironment};
+
+    const TOML: &str = r#"
+        name = "api"
+        port = 80
+
+        [tls]
+        cert_path = "/etc/tls/default.pem"
+        required = false
+    "#;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        port: u16,
+        tls: Tls,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Tls {
+        cert_path: String,
+        required: bool,
+    }
+
+    #[test]
+    fn given_a_fake_over_toml_when_extracting_then_the_fake_overrides_the_file() {
+        // Arrange
+        let env = fake_env(&[
+            ("APP_PORT", "8080"),
+            ("APP_TLS__REQUIRED", "true"),
+            ("OTHER_PORT", "1"),
+        ]);
+        let provider = FigmentProvider::new(env).prefix("APP_").split("__");
+
+        // Act
+        let config: Config = Figment::from(Toml::string(TOML))
+            .merge(provider)
+            .extract()
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            config,
+            Config {
+                name: "api".into(),
+                port: 8080,
+                tls: Tls {
+                    cert_path: "/etc/tls/default.pem".into(),
+                    required: true,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn given_a_lower_case_prefix_when_providing_then_it_matches_case_insensitively() {
+        // Arrange
+        let env = fake_env(&[("APP_NAME", "worker"), ("APPNAME", "ignored")]);
+        let provider = FigmentProvider::new(env).prefix("app_");
+
+        // Act
+        let data = provider.data().unwrap();
+
+        // Assert
+        let dict = &data[&Profile::Default];
+        assert_eq!(dict.len(), 1);
+        assert_eq!(dict["name"].as_str(), Some("worker"));
+    }
+
+    #[test]
+    fn given_lowercase_disabled_when_providing_then_names_keep_their_case() {
+        // Arrange
+        let env = fake_env(&[("APP_Port", "8080")]);
+        let provider = FigmentProvider::new(env).prefix("APP_").lowercase(false);
+
+        // Act
+        let data = provider.data().unwrap();
+
+        // Assert
+        assert!(data[&Profile::Default].contains_key("Port"));
+    }
+
+    #[test]
+    fn given_names_with_empty_parts_when_providing_then_they_are_skipped() {
+        // Arrange
+        let env = fake_env(&[("APP_", "1"), ("APP_A__", "2"), ("APP_B", "3")]);
+        let provider = FigmentProvider::new(env).prefix("APP_").split("__");
+
+        // Act
+        let data = provider.data().unwrap();
+
+        // Assert
+        let keys: Vec<_> = data[&Profile::Default].keys().cloned().collect();
+        assert_eq!(keys, ["b"]);
+    }
+
+    #[test]
+    fn given_a_profile_when_providing_then_values_go_to_that_profile() {
+        // Arrange
+        let env = fake_env(&[("PORT", "8080")]);
+        let provider = FigmentProvider::new(env).profile("debug");
+
+        // Act
+        let data = provider.data().unwrap();
+
+        // Assert
+        assert!(!data.contains_key(&Profile::Default));
+        assert_eq!(data[&Profile::from("debug")]["port"].to_u128(), Some(8080));
+    }
+
+    #[test]
+    fn given_a_prefix_when_describing_then_the_metadata_names_it() {
+        // Arrange
+        let provider = FigmentProvider::new(FakeEnvironment::new()).prefix("app_");
+
+        // Act
+        let metadata = provider.metadata();
+
+        // Assert
+        assert_eq!(metadata.name, "`APP_` environment variable(s)");
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, KeyPattern, Provenance, ReadEnvironment,
+    SourcedEnvironment,
+};
+
+/// A view of an environment that hides variables, by either only allowing
+/// names matching an allowlist or hiding names matching a denylist. Useful
+/// for handing an environment to code that must not see secrets.
+///
+/// Hidden variables read as not present and are not listed. Writes and
+/// removals of hidden variables are silently dropped, so the wrapped
+/// environment is never changed through them.
+///
+/// On Windows, where variable names are case-insensitive, patterns match
+/// names ignoring ASCII case.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, FilteredEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("AWS_SECRET_ACCESS_KEY", "hunter2");
+/// fake_env.set_var("PLUGIN_MODE", "fast");
+///
+/// let env = FilteredEnvironment::denylist(fake_env, ["AWS_*"]);
+///
+/// assert!(env.var("AWS_SECRET_ACCESS_KEY").is_err());
+/// assert_eq!(env.var("PLUGIN_MODE").unwrap(), "fast");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FilteredEnvironment<E> {
+    inner: E,
+    mode: FilterMode,
+    patterns: Vec<KeyPattern>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FilterMode {
+    Allow,
+    Deny,
+}
+
+impl<E: ReadEnvironment> FilteredEnvironment<E> {
+    /// Only show variables whose names match one of the patterns.
+    pub fn allowlist(inner: E, patterns: impl IntoIterator<Item = impl Into<KeyPattern>>) -> Self {
+        Self::with_mode(inner, FilterMode::Allow, patterns)
+    }
+
+    /// Hide variables whose names match any of the patterns.
+    pub fn denylist(inner: E, patterns: impl IntoIterator<Item = impl Into<KeyPattern>>) -> Self {
+        Self::with_mode(inner, FilterMode::Deny, patterns)
+    }
+
+    fn with_mode(
+        inner: E,
+        mode: FilterMode,
+        patterns: impl IntoIterator<Item = impl Into<KeyPattern>>,
+    ) -> Self {
+        FilteredEnvironment {
+            inner,
+            mode,
+            patterns: patterns.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Whether the variable named `key` can be seen through this view.
+    pub fn is_visible(&self, key: impl AsRef<OsStr>) -> bool {
+        let matched = self
+            .patterns
+            .iter()
+            .any(|pattern| pattern_matches(pattern, key.as_ref()));
+        match self.mode {
+            FilterMode::Allow => matched,
+            FilterMode::Deny => !matched,
+        }
+    }
+
+    /// Unwrap the view, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+/// Whether `pattern` matches `key`. Windows looks variables up ignoring case,
+/// so there patterns do too; otherwise `var("aws_secret_access_key")` would
+/// read a variable an `AWS_*` denylist is meant to hide.
+fn pattern_matches(pattern: &KeyPattern, key: &OsStr) -> bool {
+    if cfg!(windows) {
+        pattern.matches_ignore_ascii_case(key)
+    } else {
+        pattern.matches(key)
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for FilteredEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        if !self.is_visible(&key) {
+            return Err(VarError::NotPresent);
+        }
+        self.inner.var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        if !self.is_visible(&key) {
+            return None;
+        }
+        self.inner.var_os(key)
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for FilteredEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        if !self.is_visible(&key) {
+            return Err(VarError::NotPresent);
+        }
+        self.inner.var_with_source(key)
+    }
+}
+
+impl<E: Environment> Environment for FilteredEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        if self.is_visible(&key) {
+            self.inner.set_var(key, value)
+        }
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        if self.is_visible(&key) {
+            self.inner.remove_var(key)
+        }
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        if !self.is_visible(&key) {
+            return Ok(());
+        }
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        if !self.is_visible(&key) {
+            return Ok(());
+        }
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for FilteredEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner
+            .vars_os()
+            .into_iter()
+            .filter(|(key, _)| self.is_visible(key))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::VarError;
+
+    use super::FilteredEnvironment;
+    use crate::{EnumerableEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+
+    fn fake_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        env.set_var("AWS_REGION", "us-east-1");
+        env.set_var("PLUGIN_MODE", "fast");
+        env.set_var("HOME", "/home/me");
+        env
+    }
+
+    fn sorted_keys(env: &impl EnumerableEnvironment) -> Vec<String> {
+        let mut keys: Vec<_> = env
+            .vars_os()
+            .into_iter()
+            .map(|(key, _)| key.into_string().unwrap())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn given_a_denylist_when_reading_then_matching_keys_are_not_present() {
+        // Arrange
+        let env = FilteredEnvironment::denylist(fake_env(), ["AWS_*", "HOME"]);
+
+        // Act/Assert
+        assert_eq!(
+            env.var("AWS_SECRET_ACCESS_KEY").unwrap_err(),
+            VarError::NotPresent
+        );
+        assert!(env.var_os("AWS_REGION").is_none());
+        assert!(env.var_os("HOME").is_none());
+        assert_eq!(env.var("PLUGIN_MODE").unwrap(), "fast");
+        assert_eq!(sorted_keys(&env), vec!["PLUGIN_MODE"]);
+    }
+
+    #[test]
+    fn given_a_denylist_when_reading_a_key_in_lower_case_then_it_is_hidden_only_on_windows() {
+        // Arrange
+        let mut inner = fake_env();
+        inner.set_var("aws_secret_access_key", "secret");
+        let env = FilteredEnvironment::denylist(inner, ["AWS_*", "HOME"]);
+
+        // Act
+        let wildcard = env.var("aws_secret_access_key");
+        let exact = env.is_visible("home");
+
+        // Assert
+        assert_eq!(wildcard.is_ok(), !cfg!(windows));
+        assert_eq!(exact, !cfg!(windows));
+    }
+
+    #[test]
+    fn given_an_allowlist_when_reading_then_only_matching_keys_are_present() {
+        // Arrange
+        let env = FilteredEnvironment::allowlist(fake_env(), ["PLUGIN_*", "HOME"]);
+
+        // Act/Assert
+        assert_eq!(
+            env.var("AWS_SECRET_ACCESS_KEY").unwrap_err(),
+            VarError::NotPresent
+        );
+        assert_eq!(env.var("PLUGIN_MODE").unwrap(), "fast");
+        assert_eq!(env.var("HOME").unwrap(), "/home/me");
+        assert_eq!(sorted_keys(&env), vec!["HOME", "PLUGIN_MODE"]);
+    }
+
+    #[test]
+    fn given_a_hidden_key_when_writing_or_removing_it_then_the_inner_environment_is_unchanged() {
+        // Arrange
+        let mut env = FilteredEnvironment::denylist(fake_env(), ["AWS_*"]);
+
+        // Act
+        env.set_var("AWS_SECRET_ACCESS_KEY", "overwritten");
+        env.set_var("AWS_NEW", "new");
+        env.remove_var("AWS_REGION");
+
+        // Assert
+        let inner = env.into_inner();
+        assert_eq!(inner.var("AWS_SECRET_ACCESS_KEY").unwrap(), "secret");
+        assert!(inner.var_os("AWS_NEW").is_none());
+        assert_eq!(inner.var("AWS_REGION").unwrap(), "us-east-1");
+    }
+
+    #[test]
+    fn given_a_visible_key_when_writing_or_removing_it_then_the_inner_environment_changes() {
+        // Arrange
+        let mut env = FilteredEnvironment::allowlist(fake_env(), ["PLUGIN_*"]);
+
+        // Act
+        env.set_var("PLUGIN_LEVEL", "3");
+        env.remove_var("PLUGIN_MODE");
+
+        // Assert
+        let inner = env.into_inner();
+        assert_eq!(inner.var("PLUGIN_LEVEL").unwrap(), "3");
+        assert!(inner.var_os("PLUGIN_MODE").is_none());
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    canonical::push_escaped, encoding::os_string_from_bytes, env_diff, EnumerableEnvironment,
+    Environment, FakeEnvironment,
+};
+
+/// The first line of every fixture file, naming the version of its format.
+const HEADER: &str = "# env_wrapper fixture v1";
+
+impl FakeEnvironment {
+    /// Write every variable to a fixture file at `path`, to check into a
+    /// repository as the reviewed, expected result of setup code. Compare
+    /// against it with
+    /// [`assert_matches_fixture`](FakeEnvironment::assert_matches_fixture).
+    ///
+    /// The file starts with the line `# env_wrapper fixture v1`, followed by
+    /// one `KEY=value` line for each variable, sorted by key, so changes to
+    /// it diff well. Keys and values are escaped as
+    /// [`to_canonical_string`](crate::CanonicalStringExt::to_canonical_string)
+    /// escapes them, so the file is valid UTF-8 and every variable,
+    /// including those that are not valid Unicode, loads back unchanged.
+    ///
+    /// Values are written as they are, even those the redaction policy
+    /// covers, so secrets do not belong in fixtures.
+    ///
+    /// # Errors
+    /// If the file cannot be written, it returns the I/O error.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+    /// # let path = std::env::temp_dir().join(format!("env_wrapper_doc_{}", std::process::id()));
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("PORT", "8080");
+    /// fake_env.set_var("MOTD", "hello\nworld");
+    ///
+    /// fake_env.save_fixture(&path)?;
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(&path)?,
+    ///     "# env_wrapper fixture v1\nMOTD=hello\\nworld\nPORT=8080\n"
+    /// );
+    /// assert_eq!(FakeEnvironment::load_fixture(&path)?.var("MOTD").unwrap(), "hello\nworld");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn save_fixture(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut vars = self.vars_os();
+        vars.sort();
+        let mut fixture = format!("{HEADER}\n");
+        for (key, value) in vars {
+            push_escaped(&mut fixture, &key);
+            fixture.push('=');
+            push_escaped(&mut fixture, &value);
+            fixture.push('\n');
+        }
+        fs::write(path, fixture)
+    }
+
+    /// A fake environment with the variables of the fixture file at `path`,
+    /// as written by [`save_fixture`](FakeEnvironment::save_fixture).
+    ///
+    /// Blank lines are skipped, and lines may end with `\r\n`, as they may
+    /// once checked out on Windows.
+    ///
+    /// # Errors
+    /// * If the file cannot be read, it returns the I/O error.
+    /// * If the file is not a fixture of a supported version, or a line
+    ///   cannot be parsed, it returns an `ErrorKind::InvalidData` error
+    ///   naming the file and the line.
+    pub fn load_fixture(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let invalid = |message: String| {
+            io::Error::new(ErrorKind::InvalidData, format!("{path:?}: {message}"))
+        };
+        let contents = fs::read(path)?;
+        let contents =
+            std::str::from_utf8(&contents).map_err(|_| invalid("not valid UTF-8".to_string()))?;
+
+        let mut lines = contents
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((_, header)) if header.starts_with("# env_wrapper fixture ") => {
+                return Err(invalid(format!(
+                    "unsupported fixture format {:?}, expected v1",
+                    &header["# env_wrapper fixture ".len()..]
+                )));
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "not a fixture file, expected it to start with {HEADER:?}"
+                )));
+            }
+        }
+
+        let mut env = FakeEnvironment::new();
+        for (index, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let parse = || {
+                let (key, value) = line.split_once('=').ok_or("expected KEY=value")?;
+                if key.is_empty() {
+                    return Err("empty key");
+                }
+                Ok((unescape(key)?, unescape(value)?))
+            };
+            let (key, value) =
+                parse().map_err(|message| invalid(format!("line {}: {message}", index + 1)))?;
+            env.set_var(key, value);
+        }
+        Ok(env)
+    }
+
+    /// Assert that the environment holds exactly the variables of the
+    /// fixture file at `path`, as written by
+    /// [`save_fixture`](FakeEnvironment::save_fixture).
+    ///
+    /// On failure, the panic message names the file and lists only the
+    /// variables that differ, as [`EnvDiff`](crate::EnvDiff) displays them,
+    /// with the file as the left side. Values the redaction policy covers
+    /// are masked.
+    ///
+    /// # Panics
+    /// If the environment does not match the fixture, or the fixture cannot
+    /// be loaded.
+    #[track_caller]
+    pub fn assert_matches_fixture(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let expected = match FakeEnvironment::load_fixture(path) {
+            Ok(expected) => expected,
+            Err(error) => panic!("cannot load fixture {path:?}: {error}"),
+        };
+        let mut diff = env_diff(&expected, self);
+        if let Some(policy) = self.redaction_policy() {
+            diff = diff.redacted(policy);
+        }
+        if !diff.is_empty() {
+            panic!("environment does not match fixture {path:?}: {diff}");
+        }
+    }
+}
+
+/// The key or value `escaped` stands for, undoing `push_escaped`.
+fn unescape(escaped: &str) -> Result<OsString, &'static str> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(start) = rest.find('\\') {
+        bytes.extend_from_slice(&rest.as_bytes()[..start]);
+        let after = &rest[start + 1..];
+        let (unescaped, len) = match after.chars().next() {
+            Some('\\') => ('\\', 1),
+            Some('n') => ('\n', 1),
+            Some('r') => ('\r', 1),
+            Some('t') => ('\t', 1),
+            Some('x') => {
+                let byte = after
+                    .get(1..3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or("expected two hexadecimal digits after \\x")?;
+                bytes.push(byte);
+                rest = &after[3..];
+                continue;
+            }
+            Some('u') => {
+                let end = after.find('}').ok_or("unterminated \\u{..} escape")?;
+                let c = after
+                    .strip_prefix("u{")
+                    .map(|_| &after[2..end])
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or("invalid \\u{..} escape")?;
+                (c, end + 1)
+            }
+            _ => return Err("unknown escape"),
+        };
+        let mut buffer = [0; 4];
+        bytes.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+        rest = &after[len..];
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    os_string_from_bytes(bytes).ok_or("not a valid variable on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, io::ErrorKind, os::unix::ffi::OsStrExt, panic};
+
+    use crate::{
+        env_eq, test_helpers::fake_env, test_helpers::TempFile, Environment, FakeEnvironment,
+        RedactionPolicy,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_tricky_values_when_saving_and_loading_a_fixture_then_they_round_trip() {
+        // Arrange
+        let mut env = fake_env(&[
+            ("PORT", "8080"),
+            ("MOTD", "hello\r\n\tworld"),
+            ("PATTERN", r"C:\temp\{x}=1"),
+            ("BELL", "\u{7}"),
+            ("GREETING", "héllo wörld"),
+            ("EMPTY", ""),
+        ]);
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        env.set_var(OsStr::from_bytes(&INVALID_UTF8), "key");
+        let file = TempFile::new();
+
+        // Act
+        env.save_fixture(file.path()).unwrap();
+        let loaded = FakeEnvironment::load_fixture(file.path()).unwrap();
+
+        // Assert
+        assert!(env_eq(&loaded, &env));
+        assert_eq!(
+            fs::read_to_string(file.path()).unwrap(),
+            "# env_wrapper fixture v1\n\
+             BELL=\\u{7}\n\
+             BINARY=fo\\x80o\n\
+             EMPTY=\n\
+             GREETING=héllo wörld\n\
+             MOTD=hello\\r\\n\\tworld\n\
+             PATTERN=C:\\\\temp\\\\{x}=1\n\
+             PORT=8080\n\
+             fo\\x80o=key\n"
+        );
+    }
+
+    #[test]
+    fn given_a_differing_environment_when_asserting_against_a_fixture_then_the_diff_is_shown() {
+        // Arrange
+        let file = TempFile::with_contents(
+            "# env_wrapper fixture v1\nAPI_TOKEN=old\nMODE=fast\nPORT=8080\n",
+        );
+        let mut env = fake_env(&[("API_TOKEN", "new"), ("PORT", "9090"), ("REGION", "eu")]);
+        env.set_redaction_policy(RedactionPolicy::new().secret("*_TOKEN"));
+
+        // Act
+        let panic = panic::catch_unwind(|| env.assert_matches_fixture(file.path())).unwrap_err();
+
+        // Assert
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            *message,
+            format!(
+                "environment does not match fixture {:?}: environments differ:\n  \
+                 - \"MODE\"=\"fast\"\n  \
+                 + \"REGION\"=\"eu\"\n  \
+                 ~ \"API_TOKEN\": \"***\" -> \"***\"\n  \
+                 ~ \"PORT\": \"8080\" -> \"9090\"",
+                file.path()
+            )
+        );
+    }
+
+    #[test]
+    fn given_a_matching_environment_when_asserting_against_a_fixture_then_it_passes() {
+        // Arrange
+        let env = fake_env(&[("MODE", "fast"), ("PORT", "8080")]);
+        let file = TempFile::new();
+        env.save_fixture(file.path()).unwrap();
+
+        // Act/Assert
+        env.assert_matches_fixture(file.path());
+    }
+
+    #[test]
+    fn given_another_format_version_when_loading_a_fixture_then_it_is_rejected() {
+        // Arrange
+        let newer = TempFile::with_contents("# env_wrapper fixture v2\nMODE=fast\n");
+        let unversioned = TempFile::with_contents("MODE=fast\n");
+
+        // Act
+        let newer_error = FakeEnvironment::load_fixture(newer.path()).unwrap_err();
+        let unversioned_error = FakeEnvironment::load_fixture(unversioned.path()).unwrap_err();
+
+        // Assert
+        assert_eq!(newer_error.kind(), ErrorKind::InvalidData);
+        assert!(
+            newer_error
+                .to_string()
+                .ends_with("unsupported fixture format \"v2\", expected v1"),
+            "{newer_error}"
+        );
+        assert_eq!(unversioned_error.kind(), ErrorKind::InvalidData);
+        assert!(
+            unversioned_error.to_string().contains("not a fixture file"),
+            "{unversioned_error}"
+        );
+    }
+
+    #[test]
+    fn given_an_invalid_line_when_loading_a_fixture_then_the_error_names_it() {
+        // Arrange
+        let file =
+            TempFile::with_contents("# env_wrapper fixture v1\r\nMODE=fast\r\n\r\nPORT=\\q\r\n");
+
+        // Act
+        let error = FakeEnvironment::load_fixture(file.path()).unwrap_err();
+
+        // Assert
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(
+            error.to_string().ends_with("line 4: unknown escape"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::Arc,
+};
+
+use crate::{
+    EnumerableEnvironment, FakeEnvironment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+/// An immutable snapshot of a [`FakeEnvironment`](FakeEnvironment), returned
+/// by [`FakeEnvironment::freeze`](FakeEnvironment::freeze).
+///
+/// It only implements [`ReadEnvironment`](ReadEnvironment), so passing it to
+/// code that needs an [`Environment`](crate::Environment) does not compile.
+/// Clones share the same variables, so cloning is cheap, and it can be read
+/// from several threads at once without locking.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MODE", "fast");
+/// let frozen_env = fake_env.freeze();
+///
+/// let worker_env = frozen_env.clone();
+/// let worker_mode = std::thread::spawn(move || worker_env.var("MODE"))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(worker_mode.unwrap(), "fast");
+/// ```
+///
+/// Writing does not compile:
+/// ```compile_fail
+/// # use env_wrapper::{Environment, FakeEnvironment};
+/// let mut frozen_env = FakeEnvironment::new().freeze();
+///
+/// frozen_env.set_var("MODE", "slow");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FrozenEnvironment {
+    inner: Arc<FakeEnvironment>,
+}
+
+impl FrozenEnvironment {
+    pub(crate) fn new(inner: FakeEnvironment) -> Self {
+        FrozenEnvironment {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl ReadEnvironment for FrozenEnvironment {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.inner.var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(key)
+    }
+}
+
+impl SourcedEnvironment for FrozenEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.inner.var_with_source(key)
+    }
+}
+
+impl EnumerableEnvironment for FrozenEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::FrozenEnvironment;
+    use crate::{EnumerableEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+
+    fn frozen_env() -> FrozenEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("MODE", "fast");
+        env.set_var("HOME", "/home/me");
+        env.freeze()
+    }
+
+    #[test]
+    fn given_a_frozen_environment_shared_across_threads_when_reading_then_all_reads_agree() {
+        // Arrange
+        let env = frozen_env();
+
+        // Act
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let reader_env = env.clone();
+                thread::spawn(move || (reader_env.var("MODE"), reader_env.var_os("HOME")))
+            })
+            .collect();
+        let results: Vec<_> = readers
+            .into_iter()
+            .map(|reader| reader.join().unwrap())
+            .collect();
+
+        // Assert
+        for (mode, home) in results {
+            assert_eq!(mode.unwrap(), "fast");
+            assert_eq!(home.unwrap(), "/home/me");
+        }
+    }
+
+    #[test]
+    fn given_a_frozen_environment_when_listing_then_the_snapshot_is_returned() {
+        // Arrange
+        let env = frozen_env();
+
+        // Act
+        let mut vars = env.vars_os();
+        vars.sort();
+
+        // Assert
+        assert_eq!(
+            vars,
+            vec![
+                ("HOME".into(), "/home/me".into()),
+                ("MODE".into(), "fast".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn frozen_environment_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenEnvironment>();
+    }
+}
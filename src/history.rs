@@ -0,0 +1,235 @@
+use std::ffi::OsString;
+
+use crate::{Environment, FakeEnvironment};
+
+/// A change made to a [`FakeEnvironment`](FakeEnvironment) that records its
+/// history, as listed by [`history`](FakeEnvironment::history).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvMutation {
+    /// The position of the change, counting from 1, which is also the
+    /// [`generation`](crate::VersionedEnvironment::generation) it led to.
+    pub sequence: u64,
+    pub change: EnvChange,
+}
+
+/// What an [`EnvMutation`](EnvMutation) changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EnvChange {
+    /// A write, over `old_value` if the variable was already set.
+    Set {
+        key: OsString,
+        old_value: Option<OsString>,
+        new_value: OsString,
+    },
+    /// A removal of a variable that was set.
+    Remove { key: OsString, old_value: OsString },
+    /// A [`clear`](FakeEnvironment::clear) of a non-empty environment, with
+    /// the variables it removed, sorted by key.
+    Clear { old_vars: Vec<(OsString, OsString)> },
+}
+
+impl FakeEnvironment {
+    /// A fake environment that records every change made to it, to debug
+    /// setup code that leaves it in an unexpected state. List the changes
+    /// with [`history`](FakeEnvironment::history).
+    ///
+    /// Writes are always recorded, even if they do not change the value.
+    /// Removals of variables that are not set, and clears of an empty
+    /// environment, are not.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnvChange, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new_with_history();
+    /// fake_env.set_var("MODE", "fast");
+    /// fake_env.set_var("MODE", "slow");
+    ///
+    /// let last = fake_env.history().last().unwrap();
+    /// assert_eq!(last.sequence, 2);
+    /// assert_eq!(
+    ///     last.change,
+    ///     EnvChange::Set {
+    ///         key: "MODE".into(),
+    ///         old_value: Some("fast".into()),
+    ///         new_value: "slow".into(),
+    ///     }
+    /// );
+    /// ```
+    pub fn new_with_history() -> Self {
+        FakeEnvironment {
+            history: Some(Vec::new()),
+            ..Self::new()
+        }
+    }
+
+    /// In an environment that records its history, every change made so
+    /// far, oldest first.
+    ///
+    /// # Panics
+    /// If the environment does not record its history. Create it with
+    /// [`new_with_history`](FakeEnvironment::new_with_history).
+    pub fn history(&self) -> &[EnvMutation] {
+        match &self.history {
+            Some(history) => history,
+            None => panic!(
+                "`history` needs a fake environment that records its history; create it with \
+                 `FakeEnvironment::new_with_history`"
+            ),
+        }
+    }
+
+    /// Make the changes in the [`history`](FakeEnvironment::history) again,
+    /// in order, to `target`. A clear is made again by removing the
+    /// variables it removed, so variables `target` held beforehand that this
+    /// environment never had are left alone.
+    ///
+    /// # Panics
+    /// If the environment does not record its history.
+    pub fn replay_onto(&self, target: &mut impl Environment) {
+        for mutation in self.history() {
+            match &mutation.change {
+                EnvChange::Set { key, new_value, .. } => target.set_var(key, new_value),
+                EnvChange::Remove { key, .. } => target.remove_var(key),
+                EnvChange::Clear { old_vars } => {
+                    for (key, _) in old_vars {
+                        target.remove_var(key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        EnumerableEnvironment, EnvChange, EnvMutation, Environment, FakeEnvironment,
+        VersionedEnvironment,
+    };
+
+    fn set(key: &str, old_value: Option<&str>, new_value: &str) -> EnvChange {
+        EnvChange::Set {
+            key: key.into(),
+            old_value: old_value.map(Into::into),
+            new_value: new_value.into(),
+        }
+    }
+
+    #[test]
+    fn given_a_history_when_changing_variables_then_each_change_is_recorded_in_order() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_history();
+
+        // Act
+        fake_env.set_var("MODE", "fast");
+        fake_env.set_var("PORT", "8080");
+        fake_env.set_var("MODE", "slow");
+        fake_env.remove_var("NEVER_SET");
+        fake_env.remove_var("PORT");
+        fake_env.set_var("MODE", "slow");
+
+        // Assert
+        let changes: Vec<&EnvChange> = fake_env.history().iter().map(|m| &m.change).collect();
+        assert_eq!(
+            changes,
+            [
+                &set("MODE", None, "fast"),
+                &set("PORT", None, "8080"),
+                &set("MODE", Some("fast"), "slow"),
+                &EnvChange::Remove {
+                    key: "PORT".into(),
+                    old_value: "8080".into(),
+                },
+                &set("MODE", Some("slow"), "slow"),
+            ]
+        );
+        let sequences: Vec<u64> = fake_env.history().iter().map(|m| m.sequence).collect();
+        assert_eq!(sequences, [1, 2, 3, 4, 5]);
+        assert_eq!(fake_env.generation(), 5);
+    }
+
+    #[test]
+    fn given_a_history_when_clearing_then_the_clear_is_recorded_with_the_removed_variables() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_history();
+        fake_env.set_var("PORT", "8080");
+        fake_env.set_var("MODE", "fast");
+
+        // Act
+        fake_env.clear();
+        fake_env.clear();
+        fake_env.set_var("MODE", "slow");
+
+        // Assert
+        assert_eq!(fake_env.history().len(), 4);
+        assert_eq!(
+            fake_env.history()[2],
+            EnvMutation {
+                sequence: 3,
+                change: EnvChange::Clear {
+                    old_vars: vec![
+                        ("MODE".into(), "fast".into()),
+                        ("PORT".into(), "8080".into()),
+                    ],
+                },
+            }
+        );
+        assert_eq!(fake_env.history()[3].change, set("MODE", None, "slow"));
+    }
+
+    #[test]
+    fn given_a_history_when_replaying_onto_a_fresh_fake_then_it_reaches_the_same_state() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_history();
+        fake_env.set_var("TMP", "/tmp");
+        fake_env.clear();
+        fake_env.set_var("MODE", "fast");
+        fake_env.set_var("PORT", "8080");
+        fake_env.set_var("MODE", "slow");
+        fake_env.remove_var("PORT");
+        fake_env.set_var("HOME", "/home/user");
+        let mut target = FakeEnvironment::new();
+
+        // Act
+        fake_env.replay_onto(&mut target);
+
+        // Assert
+        assert_eq!(target, fake_env);
+        let mut vars = target.vars_os();
+        vars.sort();
+        assert_eq!(
+            vars,
+            [
+                ("HOME".into(), "/home/user".into()),
+                ("MODE".into(), "slow".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_history_when_cloning_then_the_clone_keeps_the_history_so_far() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_history();
+        fake_env.set_var("MODE", "fast");
+
+        // Act
+        let mut clone = fake_env.clone();
+        clone.set_var("PORT", "8080");
+
+        // Assert
+        assert_eq!(fake_env.history().len(), 1);
+        assert_eq!(clone.history().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`history` needs a fake environment that records its history")]
+    fn given_no_history_when_listing_it_then_it_panics() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("MODE", "fast");
+
+        // Act
+        let _ = fake_env.history();
+    }
+}
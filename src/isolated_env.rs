@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{EnumerableEnvironment, Environment, ReadEnvironment, RealEnvironment};
+
+/// Held by each [`IsolatedEnvGuard`](IsolatedEnvGuard), so only one test
+/// changes the real environment through them at a time.
+static REAL_ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Exclusive use of the real environment for as long as this guard lives:
+/// it holds a lock shared by every guard in the process, and when dropped,
+/// including while a failing test unwinds, it restores the variables as they
+/// were when it was acquired. Requires the `test-util` feature.
+///
+/// Tests usually get one from the `#[isolated_env]` attribute, with the
+/// `isolated-env` feature.
+///
+/// Only tests that hold a guard wait for each other. Other tests in the same
+/// process that change the real environment at the same time may see their
+/// changes undone, so keep those in their own test binary, or give them
+/// [`unique_key`](crate::unique_key)s and a guard as well.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, IsolatedEnvGuard, ReadEnvironment, RealEnvironment};
+/// # std::env::set_var("MY_APP_MODE", "slow");
+/// {
+///     let _guard = IsolatedEnvGuard::acquire(&["MY_APP_MODE"]);
+///     assert!(RealEnvironment.var_os("MY_APP_MODE").is_none());
+///
+///     RealEnvironment.set_var("MY_APP_MODE", "fast");
+/// }
+/// assert_eq!(RealEnvironment.var("MY_APP_MODE").unwrap(), "slow");
+/// ```
+#[derive(Debug)]
+pub struct IsolatedEnvGuard {
+    snapshot: HashMap<OsString, OsString>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl IsolatedEnvGuard {
+    /// Wait for the other guards to be dropped, take a snapshot of the real
+    /// environment, and then remove the variables in `clear`.
+    pub fn acquire(clear: &[&str]) -> Self {
+        // A test that panicked while holding the lock restored the
+        // environment as it unwound, so the lock is still good to use.
+        let lock = REAL_ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let snapshot = RealEnvironment.vars_os().into_iter().collect();
+        for key in clear {
+            RealEnvironment.remove_var(key);
+        }
+        IsolatedEnvGuard {
+            snapshot,
+            _lock: lock,
+        }
+    }
+}
+
+impl Drop for IsolatedEnvGuard {
+    fn drop(&mut self) {
+        for (key, value) in RealEnvironment.vars_os() {
+            match self.snapshot.get(&key) {
+                Some(old_value) if *old_value == value => {}
+                Some(old_value) => RealEnvironment.set_var(&key, old_value),
+                None => RealEnvironment.remove_var(&key),
+            }
+        }
+        for (key, value) in &self.snapshot {
+            if RealEnvironment.var_os(key).is_none() {
+                RealEnvironment.set_var(key, value);
+            }
+        }
+    }
+}
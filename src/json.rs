@@ -0,0 +1,193 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    document::{decode_value, encode_vars, Encoded, BASE64_TAG},
+    DocumentError, EnumerableEnvironment, Environment, FakeEnvironment, NonUnicode,
+};
+
+/// Export of an environment as a JSON object. Requires the `serde_json`
+/// feature.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, JsonExt, NonUnicode};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+///
+/// let json = fake_env.to_json_value(NonUnicode::Error).unwrap();
+///
+/// assert_eq!(json, serde_json::json!({ "PORT": "8080" }));
+/// assert_eq!(FakeEnvironment::from_json_value(&json, NonUnicode::Error).unwrap(), fake_env);
+/// ```
+pub trait JsonExt: EnumerableEnvironment {
+    /// Write every variable as a member of a JSON object, with its value as a
+    /// string, treating names and values that are not valid Unicode as
+    /// `policy` says. Members are sorted by name.
+    ///
+    /// # Errors
+    /// With [`NonUnicode::Error`](NonUnicode::Error), or with
+    /// [`NonUnicode::Base64`](NonUnicode::Base64) for a name, it returns
+    /// [`DocumentError::NotUnicode`](DocumentError::NotUnicode) for a name or
+    /// value that is not valid Unicode.
+    fn to_json_value(&self, policy: NonUnicode) -> Result<Value, DocumentError> {
+        let object = encode_vars(self.vars_os(), policy)?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Encoded::Text(text) => Value::String(text),
+                    Encoded::Base64(encoded) => {
+                        Value::Object(Map::from_iter([(BASE64_TAG.into(), encoded.into())]))
+                    }
+                };
+                (name, value)
+            })
+            .collect();
+        Ok(Value::Object(object))
+    }
+}
+
+impl<E: EnumerableEnvironment> JsonExt for E {}
+
+impl FakeEnvironment {
+    /// A fake environment with a variable for each member of a JSON object,
+    /// reading values as [`JsonExt::to_json_value`](JsonExt::to_json_value)
+    /// writes them with `policy`. Requires the `serde_json` feature.
+    ///
+    /// # Errors
+    /// * [`DocumentError::NotAnObject`](DocumentError::NotAnObject) if `json`
+    ///   is not an object.
+    /// * [`DocumentError::InvalidValue`](DocumentError::InvalidValue) for a
+    ///   value that is not a string, or, with
+    ///   [`NonUnicode::Base64`](NonUnicode::Base64), a base64-encoded object.
+    /// * [`DocumentError::InvalidBase64`](DocumentError::InvalidBase64) or
+    ///   [`DocumentError::NotUnicode`](DocumentError::NotUnicode) for a
+    ///   base64-encoded value that cannot be decoded on this platform.
+    pub fn from_json_value(json: &Value, policy: NonUnicode) -> Result<Self, DocumentError> {
+        let Value::Object(object) = json else {
+            return Err(DocumentError::NotAnObject);
+        };
+        let mut env = FakeEnvironment::new();
+        for (name, value) in object {
+            let encoded = match value {
+                Value::String(text) => Encoded::Text(text.clone()),
+                Value::Object(tagged) => match (tagged.len(), tagged.get(BASE64_TAG)) {
+                    (1, Some(Value::String(encoded))) => Encoded::Base64(encoded.clone()),
+                    _ => return Err(DocumentError::InvalidValue(name.clone())),
+                },
+                _ => return Err(DocumentError::InvalidValue(name.clone())),
+            };
+            env.set_var(name, decode_value(name, encoded, policy)?);
+        }
+        Ok(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use serde_json::json;
+
+    use super::JsonExt;
+    use crate::{
+        test_helpers::fake_env, DocumentError, Environment, FakeEnvironment, NonUnicode,
+        ReadEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_an_environment_when_round_tripped_through_json_then_it_is_unchanged() {
+        // Arrange
+        let env = fake_env(&[("PORT", "8080"), ("EMPTY", ""), ("QUOTED", "say \"hi\"")]);
+
+        // Act
+        let json = env.to_json_value(NonUnicode::Error).unwrap();
+        let imported = FakeEnvironment::from_json_value(&json, NonUnicode::Error).unwrap();
+
+        // Assert
+        assert_eq!(
+            json.to_string(),
+            r#"{"EMPTY":"","PORT":"8080","QUOTED":"say \"hi\""}"#
+        );
+        assert_eq!(imported, env);
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_exporting_then_each_policy_applies() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let refused = env.to_json_value(NonUnicode::Error);
+        let lossy = env.to_json_value(NonUnicode::Lossy).unwrap();
+        let encoded = env.to_json_value(NonUnicode::Base64).unwrap();
+
+        // Assert
+        assert_eq!(
+            refused.unwrap_err(),
+            DocumentError::NotUnicode("BINARY".into())
+        );
+        assert_eq!(lossy, json!({ "BINARY": "fo\u{FFFD}o" }));
+        assert_eq!(encoded, json!({ "BINARY": { "base64": "Zm+Abw==" } }));
+    }
+
+    #[test]
+    fn given_a_base64_value_when_round_tripped_then_the_bytes_are_kept() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+        let json = env.to_json_value(NonUnicode::Base64).unwrap();
+
+        // Act
+        let imported = FakeEnvironment::from_json_value(&json, NonUnicode::Base64).unwrap();
+
+        // Assert
+        assert_eq!(
+            imported.var_os("BINARY").unwrap(),
+            OsStr::from_bytes(&INVALID_UTF8)
+        );
+    }
+
+    #[test]
+    fn given_a_base64_value_without_the_base64_policy_when_importing_then_it_is_invalid() {
+        // Arrange
+        let json = json!({ "BINARY": { "base64": "Zm+Abw==" } });
+
+        // Act
+        let result = FakeEnvironment::from_json_value(&json, NonUnicode::Error);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DocumentError::InvalidValue("BINARY".into())
+        );
+    }
+
+    #[test]
+    fn given_a_non_object_document_when_importing_then_it_is_rejected() {
+        for json in [json!(["PORT", "8080"]), json!("PORT=8080"), json!(null)] {
+            // Act
+            let result = FakeEnvironment::from_json_value(&json, NonUnicode::Error);
+
+            // Assert
+            assert_eq!(result.unwrap_err(), DocumentError::NotAnObject, "{json}");
+        }
+    }
+
+    #[test]
+    fn given_a_number_value_when_importing_then_it_is_invalid() {
+        // Arrange
+        let json = json!({ "PORT": 8080 });
+
+        // Act
+        let result = FakeEnvironment::from_json_value(&json, NonUnicode::Error);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "environment variable \"PORT\" does not have a string value"
+        );
+    }
+}
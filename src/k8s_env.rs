@@ -0,0 +1,278 @@
+use std::{
+    fmt::Write,
+    io::{self, ErrorKind},
+};
+
+use serde_yaml::Value;
+
+use crate::{EnumerableEnvironment, Environment, FakeEnvironment};
+
+/// Export of an environment as the `env:` list of a Kubernetes container,
+/// for templating manifests. Requires the `yaml` feature.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, K8sEnvExt};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+/// fake_env.set_var("DEBUG", "yes");
+///
+/// let yaml = fake_env.to_k8s_env_yaml().unwrap();
+///
+/// assert_eq!(
+///     yaml,
+///     "- name: \"DEBUG\"\n  value: \"yes\"\n- name: \"PORT\"\n  value: \"8080\"\n"
+/// );
+/// assert_eq!(FakeEnvironment::from_k8s_env_yaml(&yaml).unwrap(), fake_env);
+/// ```
+pub trait K8sEnvExt: EnumerableEnvironment {
+    /// Write every variable as a `name`/`value` entry of a YAML list, sorted
+    /// by name.
+    ///
+    /// Names and values are always double-quoted, so none are read as
+    /// anything but strings: plain YAML would turn `yes`, `0777`, or `1e3`
+    /// into a boolean or number, which Kubernetes rejects, and its YAML 1.1
+    /// parser mangles more values than YAML 1.2 libraries know to quote.
+    ///
+    /// # Errors
+    /// * If a name is not one Kubernetes accepts, of ASCII letters, digits,
+    ///   `_`, `-`, and `.`, not starting with a digit, it returns an
+    ///   `ErrorKind::InvalidInput` error.
+    /// * If a name or value is not valid Unicode, it returns an
+    ///   `ErrorKind::InvalidData` error.
+    fn to_k8s_env_yaml(&self) -> io::Result<String> {
+        let mut vars = self
+            .vars_os()
+            .into_iter()
+            .map(
+                |(name, value)| match (name.into_string(), value.into_string()) {
+                    (Ok(name), Ok(value)) => Ok((name, value)),
+                    (name, _) => {
+                        let name = name.unwrap_or_else(|name| name.to_string_lossy().into_owned());
+                        Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("environment variable {name:?} is not valid Unicode"),
+                        ))
+                    }
+                },
+            )
+            .collect::<io::Result<Vec<_>>>()?;
+        if vars.is_empty() {
+            return Ok("[]\n".into());
+        }
+        vars.sort();
+
+        let mut yaml = String::new();
+        for (name, value) in vars {
+            if !is_k8s_name(&name) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{name:?} is not a valid Kubernetes environment variable name"),
+                ));
+            }
+            yaml.push_str("- name: ");
+            push_quoted(&mut yaml, &name);
+            yaml.push_str("\n  value: ");
+            push_quoted(&mut yaml, &value);
+            yaml.push('\n');
+        }
+        Ok(yaml)
+    }
+}
+
+impl<E: EnumerableEnvironment> K8sEnvExt for E {}
+
+impl FakeEnvironment {
+    /// A fake environment with a variable for each entry of a Kubernetes
+    /// `env:` list, like [`K8sEnvExt::to_k8s_env_yaml`] writes. An entry
+    /// without a `value` is set to the empty string, as Kubernetes does.
+    /// Requires the `yaml` feature.
+    ///
+    /// # Errors
+    /// If `yaml` is not a YAML list of entries with a string `name` and an
+    /// optional string `value`, it returns an `ErrorKind::InvalidData` error.
+    /// This includes entries using `valueFrom`, since the values they refer
+    /// to cannot be resolved here.
+    pub fn from_k8s_env_yaml(yaml: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(ErrorKind::InvalidData, message);
+        let document: Value =
+            serde_yaml::from_str(yaml).map_err(|error| invalid(error.to_string()))?;
+        let Value::Sequence(entries) = document else {
+            return Err(invalid("expected a list of environment variables".into()));
+        };
+        let mut env = FakeEnvironment::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let Some(Value::String(name)) = entry.get("name") else {
+                return Err(invalid(format!("entry {index} has no string name")));
+            };
+            if entry.get("valueFrom").is_some() {
+                return Err(invalid(format!(
+                    "environment variable {name:?} uses valueFrom, which cannot be resolved"
+                )));
+            }
+            let value = match entry.get("value") {
+                Some(Value::String(value)) => value.as_str(),
+                None | Some(Value::Null) => "",
+                Some(_) => {
+                    return Err(invalid(format!(
+                        "environment variable {name:?} does not have a string value"
+                    )))
+                }
+            };
+            env.set_var(name, value);
+        }
+        Ok(env)
+    }
+}
+
+fn is_k8s_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Write `text` as a double-quoted YAML scalar, escaping anything YAML would
+/// not read back as is.
+fn push_quoted(yaml: &mut String, text: &str) {
+    yaml.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => yaml.push_str("\\\""),
+            '\\' => yaml.push_str("\\\\"),
+            '\n' => yaml.push_str("\\n"),
+            '\r' => yaml.push_str("\\r"),
+            '\t' => yaml.push_str("\\t"),
+            c if c.is_control() || matches!(c, '\u{2028}' | '\u{2029}' | '\u{feff}') => {
+                let _ = write!(yaml, "\\u{:04x}", u32::from(c));
+            }
+            c => yaml.push(c),
+        }
+    }
+    yaml.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use serde_yaml::Value;
+
+    use super::K8sEnvExt;
+    use crate::{test_helpers::fake_env, FakeEnvironment, ReadEnvironment};
+
+    #[test]
+    fn given_yaml_ambiguous_values_when_exporting_then_each_is_quoted() {
+        // Arrange
+        let env = fake_env(&[
+            ("A_BOOL", "yes"),
+            ("B_OCTAL", "0777"),
+            ("C_ZIP", "01234"),
+            ("D_FLOAT", "1e3"),
+            ("E_NULL", "~"),
+            ("F_TIME", "12:30"),
+        ]);
+
+        // Act
+        let yaml = env.to_k8s_env_yaml().unwrap();
+
+        // Assert
+        assert_eq!(
+            yaml,
+            concat!(
+                "- name: \"A_BOOL\"\n  value: \"yes\"\n",
+                "- name: \"B_OCTAL\"\n  value: \"0777\"\n",
+                "- name: \"C_ZIP\"\n  value: \"01234\"\n",
+                "- name: \"D_FLOAT\"\n  value: \"1e3\"\n",
+                "- name: \"E_NULL\"\n  value: \"~\"\n",
+                "- name: \"F_TIME\"\n  value: \"12:30\"\n",
+            )
+        );
+        let parsed: Value = serde_yaml::from_str(&yaml).unwrap();
+        assert!(parsed
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .all(|entry| entry["value"].is_string()));
+    }
+
+    #[test]
+    fn given_tricky_values_when_round_tripped_then_they_are_unchanged() {
+        // Arrange
+        let env = fake_env(&[
+            ("QUOTES", "it's \"quoted\""),
+            ("MULTILINE", "one\ntwo\r\n\tthree"),
+            ("BACKSLASH", "C:\\temp\\"),
+            ("SPACES", "  padded  "),
+            ("CONTROL", "bell\u{7}"),
+            ("EMPTY", ""),
+            ("my.dotted-name", "ok"),
+        ]);
+
+        // Act
+        let yaml = env.to_k8s_env_yaml().unwrap();
+        let read_back = FakeEnvironment::from_k8s_env_yaml(&yaml).unwrap();
+
+        // Assert
+        assert_eq!(read_back, env);
+    }
+
+    #[test]
+    fn given_a_hand_written_list_when_reading_then_missing_values_are_empty() {
+        // Arrange
+        let yaml = "
+            - name: PORT
+              value: '8080'
+            - name: EMPTY
+        ";
+
+        // Act
+        let env = FakeEnvironment::from_k8s_env_yaml(yaml).unwrap();
+
+        // Assert
+        assert_eq!(env.var("PORT").unwrap(), "8080");
+        assert_eq!(env.var("EMPTY").unwrap(), "");
+    }
+
+    #[test]
+    fn given_entries_kubernetes_would_reject_or_resolve_when_reading_then_they_are_refused() {
+        for yaml in [
+            "PORT: '8080'",
+            "- name: PORT\n  value: 8080",
+            "- value: x",
+            "- name: SECRET\n  valueFrom:\n    secretKeyRef: {name: s, key: k}",
+        ] {
+            // Act
+            let result = FakeEnvironment::from_k8s_env_yaml(yaml);
+
+            // Assert
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData, "{yaml}");
+        }
+    }
+
+    #[test]
+    fn given_an_invalid_name_when_exporting_then_it_is_refused() {
+        // Arrange
+        let env = fake_env(&[("1ST", "x")]);
+
+        // Act
+        let result = env.to_k8s_env_yaml();
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn given_an_empty_environment_when_exporting_then_it_is_an_empty_list() {
+        // Act
+        let yaml = FakeEnvironment::new().to_k8s_env_yaml().unwrap();
+
+        // Assert
+        assert_eq!(yaml, "[]\n");
+        assert_eq!(
+            FakeEnvironment::from_k8s_env_yaml(&yaml).unwrap(),
+            FakeEnvironment::new()
+        );
+    }
+}
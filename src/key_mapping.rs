@@ -0,0 +1,185 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{EnumerableEnvironment, EnvError, Environment, ReadEnvironment};
+
+/// A wrapper that maps every key through a function before using it with the
+/// wrapped environment, so differently-spelled names reach the same variable.
+///
+/// The mapping applies to reads, writes, and removals. Listing variables
+/// returns the wrapped environment's keys as they are.
+///
+/// By default, keys are mapped with [`normalize_key`](normalize_key), so
+/// `myapp.log-level` reads `MYAPP_LOG_LEVEL`.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, KeyMappingEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_LOG_LEVEL", "debug");
+///
+/// let env = KeyMappingEnvironment::new(fake_env);
+///
+/// assert_eq!(env.var("myapp.log-level").unwrap(), "debug");
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyMappingEnvironment<E, F = fn(&OsStr) -> OsString> {
+    inner: E,
+    mapping: F,
+}
+
+impl<E: ReadEnvironment> KeyMappingEnvironment<E> {
+    /// Wrap `inner`, mapping keys with [`normalize_key`](normalize_key).
+    pub fn new(inner: E) -> Self {
+        KeyMappingEnvironment {
+            inner,
+            mapping: normalize_key,
+        }
+    }
+}
+
+impl<E: ReadEnvironment, F: Fn(&OsStr) -> OsString> KeyMappingEnvironment<E, F> {
+    /// Wrap `inner`, mapping keys with `mapping`.
+    pub fn with_mapping(inner: E, mapping: F) -> Self {
+        KeyMappingEnvironment { inner, mapping }
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn map(&self, key: impl AsRef<OsStr>) -> OsString {
+        (self.mapping)(key.as_ref())
+    }
+}
+
+/// Upper-case the ASCII letters in `key` and replace `.`, `-`, and `/` with
+/// `_`. Keys that are not valid UTF-8 are returned unchanged.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::normalize_key;
+/// assert_eq!(normalize_key("myapp.log-level".as_ref()), "MYAPP_LOG_LEVEL");
+/// ```
+pub fn normalize_key(key: &OsStr) -> OsString {
+    match key.to_str() {
+        Some(key) => key
+            .chars()
+            .map(|c| match c {
+                '.' | '-' | '/' => '_',
+                c => c.to_ascii_uppercase(),
+            })
+            .collect::<String>()
+            .into(),
+        None => key.into(),
+    }
+}
+
+impl<E: ReadEnvironment, F: Fn(&OsStr) -> OsString> ReadEnvironment
+    for KeyMappingEnvironment<E, F>
+{
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.inner.var(self.map(key))
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(self.map(key))
+    }
+}
+
+impl<E: Environment, F: Fn(&OsStr) -> OsString> Environment for KeyMappingEnvironment<E, F> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = self.map(key);
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = self.map(key);
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let key = self.map(key);
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        let key = self.map(key);
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment, F: Fn(&OsStr) -> OsString> EnumerableEnvironment
+    for KeyMappingEnvironment<E, F>
+{
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use super::{normalize_key, KeyMappingEnvironment};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    #[test]
+    fn when_normalizing_keys_then_letters_are_upper_cased_and_separators_become_underscores() {
+        // Act/Assert
+        assert_eq!(normalize_key("myapp.log-level".as_ref()), "MYAPP_LOG_LEVEL");
+        assert_eq!(normalize_key("myapp/db/host".as_ref()), "MYAPP_DB_HOST");
+        assert_eq!(normalize_key("ALREADY_NORMAL".as_ref()), "ALREADY_NORMAL");
+    }
+
+    #[test]
+    fn given_a_normalized_variable_when_reading_it_by_another_spelling_then_it_is_found() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("MYAPP_LOG_LEVEL", "debug");
+        let env = KeyMappingEnvironment::new(inner);
+
+        // Act/Assert
+        assert_eq!(env.var("myapp.log-level").unwrap(), "debug");
+        assert_eq!(env.var_os("MYAPP_LOG_LEVEL").unwrap(), "debug");
+    }
+
+    #[test]
+    fn when_writing_and_removing_by_another_spelling_then_the_normalized_name_is_changed() {
+        // Arrange
+        let mut env = KeyMappingEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        env.set_var("myapp.mode", "fast");
+        env.set_var("myapp/cache-dir", "/tmp");
+        env.remove_var("MyApp.Cache-Dir");
+
+        // Assert
+        let inner = env.into_inner();
+        assert_eq!(inner.var("MYAPP_MODE").unwrap(), "fast");
+        assert!(inner.var_os("myapp.mode").is_none());
+        assert!(inner.var_os("MYAPP_CACHE_DIR").is_none());
+    }
+
+    #[test]
+    fn given_a_custom_mapping_when_reading_then_it_is_used() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("APP_PORT", "8080");
+        let env = KeyMappingEnvironment::with_mapping(inner, |key: &OsStr| {
+            let mut mapped = OsString::from("APP_");
+            mapped.push(key);
+            mapped
+        });
+
+        // Act/Assert
+        assert_eq!(env.var("PORT").unwrap(), "8080");
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, FakeEnvironment, LayerId, LayerOutcome,
+    Provenance, ReadEnvironment, Resolution, SourcedEnvironment,
+};
+
+/// A stack of environments: a base environment at the bottom with overlay
+/// layers pushed on top of it.
+///
+/// Reads search the layers from the top down, falling back to the base.
+/// Writes and removals only affect the top layer (or the base, when no layers
+/// have been pushed). Removing a variable from a layer hides any value for it
+/// in the layers and base below, until the layer is popped.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, LayeredEnvironment, ReadEnvironment};
+/// let mut base = FakeEnvironment::new();
+/// base.set_var("LOG_LEVEL", "info");
+/// let mut env = LayeredEnvironment::new(base);
+///
+/// let mut overlay = FakeEnvironment::new();
+/// overlay.set_var("LOG_LEVEL", "debug");
+/// env.push_layer(overlay);
+/// assert_eq!(env.var("LOG_LEVEL").unwrap(), "debug");
+///
+/// env.pop_layer();
+/// assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+/// ```
+#[derive(Clone, Debug)]
+pub struct LayeredEnvironment<E> {
+    base: E,
+    layers: Vec<Layer>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Layer {
+    vars: FakeEnvironment,
+    // Keys removed in this layer, hiding any value in the layers below.
+    tombstones: HashSet<OsString>,
+}
+
+impl<E: ReadEnvironment> LayeredEnvironment<E> {
+    pub fn new(base: E) -> Self {
+        LayeredEnvironment {
+            base,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Push a new top layer. Subsequent writes go to this layer.
+    pub fn push_layer(&mut self, layer: FakeEnvironment) {
+        self.layers.push(Layer {
+            vars: layer,
+            tombstones: HashSet::new(),
+        });
+    }
+
+    /// Remove the top layer, returning the variables set in it. Removals made
+    /// in the layer no longer hide values below it.
+    ///
+    /// Returns `None` if only the base remains.
+    pub fn pop_layer(&mut self) -> Option<FakeEnvironment> {
+        self.layers.pop().map(|layer| layer.vars)
+    }
+
+    /// The number of layers pushed on top of the base.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// How `key` resolves: what each layer holds for it, from the top layer
+    /// down to the base, and which one supplies the value or hides it.
+    /// Layers are numbered from 0 for the first pushed.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, LayerId, LayeredEnvironment};
+    /// let mut base = FakeEnvironment::new();
+    /// base.set_var("LOG_LEVEL", "info");
+    /// let mut env = LayeredEnvironment::new(base);
+    /// env.push_layer(FakeEnvironment::new());
+    /// env.set_var("LOG_LEVEL", "debug");
+    ///
+    /// let resolution = env.explain("LOG_LEVEL");
+    ///
+    /// assert_eq!(resolution.winner(), Some(LayerId::Layer(0)));
+    /// assert_eq!(
+    ///     resolution.to_string(),
+    ///     "\"LOG_LEVEL\" = \"debug\", from layer 0\n  \
+    ///        layer 0: \"debug\"\n  \
+    ///        base: \"info\" (shadowed)"
+    /// );
+    /// ```
+    pub fn explain(&self, key: impl AsRef<OsStr>) -> Resolution {
+        let key = key.as_ref();
+        let layers = self.layers.iter().enumerate().rev().map(|(index, layer)| {
+            let outcome = if layer.tombstones.contains(key) {
+                LayerOutcome::Tombstoned
+            } else {
+                held(layer.vars.var_os(key))
+            };
+            (LayerId::Layer(index), outcome)
+        });
+        let base = (LayerId::Base, held(self.base.var_os(key)));
+        Resolution::from_layers(key, layers.chain([base]))
+    }
+}
+
+fn held(value: Option<OsString>) -> LayerOutcome {
+    value.map_or(LayerOutcome::NotSet, LayerOutcome::Supplied)
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for LayeredEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let key = key.as_ref();
+        for layer in self.layers.iter().rev() {
+            if layer.tombstones.contains(key) {
+                return None;
+            }
+            if let Some(value) = layer.vars.var_os(key) {
+                return Some(value);
+            }
+        }
+        self.base.var_os(key)
+    }
+}
+
+/// Values from a pushed layer come from [`Provenance::Fake`], and the rest
+/// from the base.
+impl<E: SourcedEnvironment> SourcedEnvironment for LayeredEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let key = key.as_ref();
+        for layer in self.layers.iter().rev() {
+            if layer.tombstones.contains(key) {
+                return Err(VarError::NotPresent);
+            }
+            if layer.vars.contains(key) {
+                return layer.vars.var_with_source(key);
+            }
+        }
+        self.base.var_with_source(key)
+    }
+}
+
+impl<E: Environment> Environment for LayeredEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        match self.layers.last_mut() {
+            Some(top) => {
+                top.tombstones.remove(key.as_ref());
+                top.vars.set_var(key, value);
+            }
+            None => self.base.set_var(key, value),
+        }
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        match self.layers.last_mut() {
+            Some(top) => {
+                top.vars.remove_var(&key);
+                top.tombstones.insert(key.as_ref().into());
+            }
+            None => self.base.remove_var(key),
+        }
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        match self.layers.last() {
+            Some(_) => {
+                self.set_var(key, value);
+                Ok(())
+            }
+            None => self.base.try_set_var(key, value),
+        }
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        match self.layers.last() {
+            Some(_) => {
+                self.remove_var(key);
+                Ok(())
+            }
+            None => self.base.try_remove_var(key),
+        }
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for LayeredEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged: HashMap<_, _> = self.base.vars_os().into_iter().collect();
+        for layer in &self.layers {
+            for key in &layer.tombstones {
+                merged.remove(key);
+            }
+            merged.extend(layer.vars.vars_os());
+        }
+        merged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::VarError;
+
+    use super::LayeredEnvironment;
+    use crate::{
+        test_helpers::fake_env, EnumerableEnvironment, Environment, FakeEnvironment, LayerId,
+        LayerOutcome, Provenance, ReadEnvironment, RedactionPolicy, ResolutionStep,
+        SourcedEnvironment, WithDefaults,
+    };
+
+    #[test]
+    fn given_a_key_in_several_layers_when_reading_then_the_topmost_value_wins() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("KEY", "base"), ("BASE_ONLY", "b")]));
+        env.push_layer(fake_env(&[("KEY", "test")]));
+        env.push_layer(fake_env(&[("KEY", "case")]));
+
+        // Act
+        let shadowed = env.var("KEY");
+        let inherited = env.var("BASE_ONLY");
+
+        // Assert
+        assert_eq!(shadowed.unwrap(), "case");
+        assert_eq!(inherited.unwrap(), "b");
+    }
+
+    #[test]
+    fn given_a_base_with_defaults_when_reading_with_source_then_layers_report_fake_values() {
+        // Arrange
+        let base = WithDefaults::new(fake_env(&[("MODE", "base")])).default("PORT", "8080");
+        let mut env = LayeredEnvironment::new(base);
+        env.push_layer(fake_env(&[("PORT", "9090")]));
+        env.push_layer(FakeEnvironment::new());
+        env.remove_var("MODE");
+
+        // Act
+        let port = env.var_with_source("PORT");
+        let mode = env.var_with_source("MODE");
+        env.pop_layer();
+        env.pop_layer();
+        let default_port = env.var_with_source("PORT");
+
+        // Assert
+        assert_eq!(port.unwrap(), ("9090".into(), Provenance::Fake));
+        assert_eq!(mode.unwrap_err(), VarError::NotPresent);
+        assert_eq!(
+            default_port.unwrap(),
+            ("8080".into(), Provenance::Default("8080".into()))
+        );
+    }
+
+    fn step(layer: LayerId, outcome: LayerOutcome) -> ResolutionStep {
+        ResolutionStep { layer, outcome }
+    }
+
+    #[test]
+    fn given_a_shadowed_key_when_explaining_then_the_top_value_wins_over_the_others() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("MODE", "base")]));
+        env.push_layer(fake_env(&[("MODE", "suite")]));
+        env.push_layer(FakeEnvironment::new());
+        env.push_layer(fake_env(&[("MODE", "case")]));
+
+        // Act
+        let resolution = env.explain("MODE");
+
+        // Assert
+        assert_eq!(
+            resolution.steps,
+            [
+                step(LayerId::Layer(2), LayerOutcome::Supplied("case".into())),
+                step(LayerId::Layer(1), LayerOutcome::NotSet),
+                step(LayerId::Layer(0), LayerOutcome::Shadowed("suite".into())),
+                step(LayerId::Base, LayerOutcome::Shadowed("base".into())),
+            ]
+        );
+        assert_eq!(resolution.value(), env.var_os("MODE").as_deref());
+        assert_eq!(resolution.winner(), Some(LayerId::Layer(2)));
+        assert_eq!(
+            resolution.to_string(),
+            concat!(
+                "\"MODE\" = \"case\", from layer 2\n",
+                "  layer 2: \"case\"\n",
+                "  layer 1: not set\n",
+                "  layer 0: \"suite\" (shadowed)\n",
+                "  base: \"base\" (shadowed)",
+            )
+        );
+    }
+
+    #[test]
+    fn given_a_tombstoned_key_when_explaining_then_the_removal_hides_the_layers_below() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("API_TOKEN", "tok_abc123")]));
+        env.push_layer(FakeEnvironment::new());
+        env.remove_var("API_TOKEN");
+        env.push_layer(FakeEnvironment::new());
+
+        // Act
+        let resolution = env
+            .explain("API_TOKEN")
+            .redacted(&RedactionPolicy::new().secret("*_TOKEN"));
+
+        // Assert
+        assert_eq!(resolution.value(), None);
+        assert_eq!(resolution.winner(), None);
+        assert_eq!(
+            resolution.to_string(),
+            concat!(
+                "\"API_TOKEN\" is not set: it was removed in layer 0\n",
+                "  layer 1: not set\n",
+                "  layer 0: removed\n",
+                "  base: \"***\" (shadowed)",
+            )
+        );
+    }
+
+    #[test]
+    fn given_a_key_set_nowhere_when_explaining_then_every_layer_is_listed_as_not_set() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("OTHER", "x")]));
+        env.push_layer(fake_env(&[("OTHER", "y")]));
+
+        // Act
+        let resolution = env.explain("MISSING");
+
+        // Assert
+        assert_eq!(resolution.value(), None);
+        assert_eq!(
+            resolution.to_string(),
+            "\"MISSING\" is not set in any layer\n  layer 0: not set\n  base: not set"
+        );
+    }
+
+    #[test]
+    fn given_pushed_layers_when_writing_then_only_the_top_layer_changes() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("KEY", "base")]));
+        env.push_layer(FakeEnvironment::new());
+
+        // Act
+        env.set_var("KEY", "overlay");
+
+        // Assert
+        assert_eq!(env.var("KEY").unwrap(), "overlay");
+        let top = env.pop_layer().unwrap();
+        assert_eq!(top.var("KEY").unwrap(), "overlay");
+        assert_eq!(env.var("KEY").unwrap(), "base");
+    }
+
+    #[test]
+    fn given_no_pushed_layers_when_writing_then_the_base_changes() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        env.set_var("KEY", "value");
+
+        // Assert
+        assert!(env.pop_layer().is_none());
+        assert_eq!(env.var("KEY").unwrap(), "value");
+    }
+
+    #[test]
+    fn given_a_lower_layer_value_when_removing_it_in_the_top_layer_then_it_is_hidden() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("KEY", "base")]));
+        env.push_layer(fake_env(&[("KEY", "test")]));
+        env.push_layer(FakeEnvironment::new());
+
+        // Act
+        env.remove_var("KEY");
+
+        // Assert
+        assert_eq!(env.var("KEY").unwrap_err(), VarError::NotPresent);
+        assert!(env.var_os("KEY").is_none());
+        assert!(env.vars_os().is_empty());
+    }
+
+    #[test]
+    fn given_a_removed_key_when_setting_it_again_in_the_same_layer_then_it_is_visible() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("KEY", "base")]));
+        env.push_layer(FakeEnvironment::new());
+        env.remove_var("KEY");
+
+        // Act
+        env.set_var("KEY", "again");
+
+        // Assert
+        assert_eq!(env.var("KEY").unwrap(), "again");
+    }
+
+    #[test]
+    fn given_a_tombstone_in_the_top_layer_when_popping_it_then_lower_values_are_visible_again() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("KEY", "base")]));
+        env.push_layer(fake_env(&[("KEY", "test")]));
+        env.push_layer(FakeEnvironment::new());
+        env.remove_var("KEY");
+
+        // Act
+        env.pop_layer();
+
+        // Assert
+        assert_eq!(env.var("KEY").unwrap(), "test");
+        env.pop_layer();
+        assert_eq!(env.var("KEY").unwrap(), "base");
+    }
+
+    #[test]
+    fn given_layers_when_listing_all_variables_then_the_merged_view_is_returned() {
+        // Arrange
+        let mut env = LayeredEnvironment::new(fake_env(&[("A", "base"), ("B", "base")]));
+        env.push_layer(fake_env(&[("A", "test"), ("C", "test")]));
+        env.push_layer(FakeEnvironment::new());
+        env.remove_var("B");
+
+        // Act
+        let mut vars = env.vars_os();
+
+        // Assert
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![("A".into(), "test".into()), ("C".into(), "test".into())]
+        );
+    }
+}
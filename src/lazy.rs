@@ -0,0 +1,163 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{Environment, ReadEnvironment};
+
+/// An environment whose values are looked up on demand by a provider
+/// function, e.g. one that decrypts a secrets file.
+///
+/// The provider is called at most once per key: its answer, including that a
+/// variable is not set, is cached. Writes and removals are kept in local
+/// overrides that shadow the provider, which is never called for an
+/// overridden key.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, LazyEnvironment, ReadEnvironment};
+/// # use std::ffi::OsString;
+/// let mut env = LazyEnvironment::new(|key| match key.to_str() {
+///     Some("DB_PASSWORD") => Some(OsString::from("hunter2")),
+///     _ => None,
+/// });
+/// env.set_var("LOG_LEVEL", "debug");
+///
+/// assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+/// assert_eq!(env.var("LOG_LEVEL").unwrap(), "debug");
+/// ```
+pub struct LazyEnvironment<F> {
+    provider: F,
+    cache: RefCell<HashMap<OsString, Option<OsString>>>,
+    // `None` marks a variable removed locally.
+    overrides: HashMap<OsString, Option<OsString>>,
+}
+
+impl<F: Fn(&OsStr) -> Option<OsString>> LazyEnvironment<F> {
+    pub fn new(provider: F) -> Self {
+        LazyEnvironment {
+            provider,
+            cache: RefCell::new(HashMap::new()),
+            overrides: HashMap::new(),
+        }
+    }
+
+    fn provide(&self, key: &OsStr) -> Option<OsString> {
+        if let Some(value) = self.cache.borrow().get(key) {
+            return value.clone();
+        }
+        let value = (self.provider)(key);
+        self.cache.borrow_mut().insert(key.into(), value.clone());
+        value
+    }
+}
+
+impl<F> fmt::Debug for LazyEnvironment<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyEnvironment")
+            .field("cache", &self.cache)
+            .field("overrides", &self.overrides)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn(&OsStr) -> Option<OsString>> ReadEnvironment for LazyEnvironment<F> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.overrides.get(key.as_ref()) {
+            Some(value) => value.clone(),
+            None => self.provide(key.as_ref()),
+        }
+    }
+}
+
+impl<F: Fn(&OsStr) -> Option<OsString>> Environment for LazyEnvironment<F> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.overrides
+            .insert(key.as_ref().into(), Some(value.as_ref().into()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.overrides.insert(key.as_ref().into(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        ffi::{OsStr, OsString},
+    };
+
+    use super::LazyEnvironment;
+    use crate::{Environment, ReadEnvironment};
+
+    fn secrets(key: &OsStr) -> Option<OsString> {
+        match key.to_str() {
+            Some("DB_PASSWORD") => Some("hunter2".into()),
+            Some("API_KEY") => Some("abc123".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn given_repeated_reads_when_reading_then_the_provider_is_called_once_per_key() {
+        // Arrange
+        let lookups = RefCell::new(Vec::new());
+        let env = LazyEnvironment::new(|key: &OsStr| {
+            lookups.borrow_mut().push(key.to_owned());
+            secrets(key)
+        });
+
+        // Act
+        for _ in 0..3 {
+            assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+            assert!(env.var_os("MISSING").is_none());
+        }
+        let _ = env.contains("API_KEY");
+
+        // Assert
+        assert_eq!(
+            *lookups.borrow(),
+            vec![
+                OsString::from("DB_PASSWORD"),
+                OsString::from("MISSING"),
+                OsString::from("API_KEY"),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_overrides_when_reading_then_they_shadow_the_provider() {
+        // Arrange
+        let lookups = RefCell::new(0);
+        let mut env = LazyEnvironment::new(|key: &OsStr| {
+            *lookups.borrow_mut() += 1;
+            secrets(key)
+        });
+
+        // Act
+        env.set_var("DB_PASSWORD", "override");
+        env.remove_var("API_KEY");
+
+        // Assert
+        assert_eq!(env.var("DB_PASSWORD").unwrap(), "override");
+        assert!(env.var_os("API_KEY").is_none());
+        assert_eq!(*lookups.borrow(), 0);
+    }
+
+    #[test]
+    fn given_a_cached_value_when_overriding_it_then_the_override_wins() {
+        // Arrange
+        let mut env = LazyEnvironment::new(secrets);
+        let before = env.var("API_KEY");
+
+        // Act
+        env.set_var("API_KEY", "rotated");
+
+        // Assert
+        assert_eq!(before.unwrap(), "abc123");
+        assert_eq!(env.var("API_KEY").unwrap(), "rotated");
+    }
+}
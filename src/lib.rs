@@ -59,36 +59,645 @@
 //!     assert_eq!(location, user_specified_location);
 //! }
 //! ```
+//!
+//! # Features
+//! * `arc-swap`: [`SnapshotSwapEnvironment`], a shared fake environment
+//!   whose reads never wait for a lock, for concurrency tests.
+//! * `clap`: [`inject_env`] and [`try_parse_from_env`], which make
+//!   [`clap`](https://docs.rs/clap) arguments fall back to variables in any
+//!   environment instead of the process environment.
+//! * `config`: [`EnvWrapperSource`], a [`config`](https://docs.rs/config)
+//!   source over a snapshot of any enumerable environment.
+//! * `conformance`: [`conformance_tests!`], which runs the behavioral tests
+//!   this crate runs against its own implementations against any
+//!   [`Environment`].
+//! * `derive`: `#[derive(FromEnvironment)]`, which implements
+//!   [`FromEnvironment`] to build a configuration struct from environment
+//!   variables.
+//! * `figment`: [`FigmentProvider`], a [`figment`](https://docs.rs/figment)
+//!   provider backed by any enumerable environment.
+//! * `fxhash`: makes [`FakeEnvironment`] hash keys with FxHash instead of
+//!   SipHash, for faster lookups in tests with large fixtures. FxHash is not
+//!   resistant to collision attacks, which fake environments do not need.
+//! * `intern-keys`: makes clones of a [`FakeEnvironment`] share the storage
+//!   of its keys, as they always share that of its values, so cloning a
+//!   large fixture copies only pointers.
+//! * `isolated-env`: `#[isolated_env]`, an attribute for tests that must
+//!   change the real environment, which runs them one at a time and restores
+//!   the environment after each. It also enables `test-util`.
+//! * `log`: [`LoggingEnvironment`], which logs each variable accessed through
+//!   the [`log`](https://docs.rs/log) crate.
+//! * `metrics`: [`MeteredEnvironment`], which counts the variables accessed
+//!   through the [`metrics`](https://docs.rs/metrics) facade.
+//! * `mockall`: `MockDynEnvironment`, a [`mockall`](https://docs.rs/mockall)
+//!   mock of [`DynEnvironment`] that is an [`Environment`] when boxed.
+//! * `regex`: [`EnumerableEnvironment::vars_matching_regex`], which lists
+//!   the variables whose names match a [`regex`](https://docs.rs/regex).
+//! * `rstest`: [`rstest_support`], fixtures that inject fake environments
+//!   into [`rstest`](https://docs.rs/rstest) tests, and [`env_case!`], for
+//!   running a test against several environments.
+//! * `serde`: [`from_env`] and [`to_env`], which deserialize a configuration
+//!   struct from any environment and serialize one into it with
+//!   [`serde`](https://docs.rs/serde), [`from_nested`], which
+//!   deserializes one from a [`NestedValue`], and [`ConfigLoader::load`].
+//! * `serde_json`: [`JsonExt`] and [`FakeEnvironment::from_json_value`], for
+//!   converting environments to and from JSON objects.
+//! * `test-util`: [`unique_key`], [`ScopedTestVar`], and
+//!   [`IsolatedEnvGuard`], for tests that must set variables in the real
+//!   environment.
+//! * `tokio`: [`scope_env`] and [`current_env`], for giving each
+//!   [`tokio`](https://docs.rs/tokio) task its own ambient environment,
+//!   [`CommandExt`] for tokio's `Command`, and [`BlockingAdapter`], which
+//!   makes an [`AsyncEnvironment`] usable as an [`Environment`].
+//! * `tracing`: [`TracedEnvironment`], which emits a
+//!   [`tracing`](https://docs.rs/tracing) event for each variable accessed.
+//! * `watch`: [`DotenvEnvironment::watch`], which re-reads a dotenv file
+//!   whenever it changes, using [`notify`](https://docs.rs/notify).
+//! * `yaml`: [`YamlExt`] and [`FakeEnvironment::from_yaml_value`], for
+//!   converting environments to and from YAML maps, and [`K8sEnvExt`] and
+//!   [`FakeEnvironment::from_k8s_env_yaml`], for Kubernetes `env:` lists,
+//!   using [`serde_yaml`](https://docs.rs/serde_yaml).
+//! * `zeroize`: [`SecureFakeEnvironment`], a fake environment that wipes
+//!   the memory of values it no longer holds, using
+//!   [`zeroize`](https://docs.rs/zeroize).
+
+// The examples show #[test] functions as they would appear in a test
+// module, though doctests do not run them. `unknown_lints` covers
+// toolchains whose clippy predates the lint.
+#![allow(unknown_lints, clippy::test_attr_in_doctest)]
 
+mod alias;
+mod ambient;
+mod assertions;
+mod async_env;
+mod canonical;
+mod chain;
+mod ci;
+#[cfg(feature = "clap")]
+mod clap_env;
+mod command;
+mod composite;
+#[cfg(feature = "config")]
+mod config_source;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+mod counting;
+mod cow;
+#[cfg(feature = "serde")]
+mod de;
+mod diff;
+mod docker_env;
+#[cfg(any(feature = "serde_json", feature = "yaml"))]
+mod document;
+mod dotenv;
+#[cfg(feature = "watch")]
+mod dotenv_watch;
+mod dynamic;
+mod encoded_var;
+mod encoding;
+mod env_struct;
+mod error;
+mod executable;
+mod expand;
+mod expanding;
+mod failing;
+mod feature_flags;
+#[cfg(feature = "figment")]
+mod figment_provider;
+mod filtered;
+mod fixture;
+mod frozen;
+mod history;
+#[cfg(feature = "test-util")]
+mod isolated_env;
+#[cfg(feature = "serde_json")]
+mod json;
+#[cfg(feature = "yaml")]
+mod k8s_env;
+mod key_mapping;
+mod layered;
+mod lazy;
+mod loader;
+mod locale;
+#[cfg(feature = "log")]
+mod logging;
+#[cfg(feature = "metrics")]
+mod metered;
+mod mock;
+mod nested;
+mod normalizing;
+mod observable;
+mod pattern;
+mod prefixed;
+mod provenance;
+mod proxy;
+mod read_only;
+mod recording;
+mod redacting;
+mod redaction;
+mod resolution;
+#[cfg(feature = "rstest")]
+pub mod rstest_support;
+mod schema;
+mod secrets;
+#[cfg(feature = "zeroize")]
+mod secure_fake;
+mod sequence;
+#[cfg(feature = "serde")]
+mod ser;
+mod shared;
+mod shell;
+#[cfg(feature = "arc-swap")]
+mod snapshot_swap;
+mod subprocess;
+mod systemd_env;
+#[cfg(feature = "tokio")]
+mod task;
 #[cfg(test)]
 pub(crate) mod test_helpers;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
+mod tilde;
+#[cfg(feature = "tracing")]
+mod traced;
+mod transaction;
+mod watcher;
+mod windows_block;
+mod with_defaults;
+mod xdg;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+pub use alias::AliasEnvironment;
+pub use ambient::{ambient, with_ambient, AmbientEnvironment};
+#[doc(hidden)]
+pub use assertions::{__assert_var_eq, __assert_var_unset};
+#[cfg(feature = "tokio")]
+pub use async_env::BlockingAdapter;
+pub use async_env::{AsyncAdapter, AsyncEnvironment, EnvFuture};
+pub use canonical::{CanonicalOptions, CanonicalStringExt};
+pub use chain::ChainEnvironment;
+pub use ci::{CiInfo, CiProvider};
+#[cfg(feature = "clap")]
+pub use clap_env::{inject_env, try_parse_from_env};
+pub use command::{CommandExt, DEFAULT_PASSTHROUGH};
+pub use composite::CompositeEnvironment;
+#[cfg(feature = "config")]
+pub use config_source::EnvWrapperSource;
+pub use counting::CountingEnvironment;
+pub use cow::CowEnvironment;
+#[cfg(feature = "serde")]
+pub use de::{from_env, from_nested, DeError};
+pub use diff::{env_diff, env_eq, EnvDiff};
+pub use docker_env::DockerEnvFileExt;
+#[cfg(any(feature = "serde_json", feature = "yaml"))]
+pub use document::{DocumentError, NonUnicode};
+pub use dotenv::{load_dotenv, DotenvEnvironment, LoadSummary, Override};
+#[cfg(feature = "watch")]
+pub use dotenv_watch::WatchedDotenvEnvironment;
+pub use dynamic::DynEnvironment;
+#[cfg(feature = "mockall")]
+pub use dynamic::MockDynEnvironment;
+pub use encoded_var::EncodedVarError;
+#[doc(hidden)]
+pub use env_struct::__private;
+pub use env_struct::{EnvStructError, FromEnvironment};
+#[cfg(feature = "isolated-env")]
+pub use env_wrapper_derive::isolated_env;
+#[cfg(feature = "derive")]
+pub use env_wrapper_derive::FromEnvironment;
+pub use error::EnvError;
+pub use executable::find_executable;
+pub use expand::{ExpandError, ExpandExt, ExpandOptions, UnknownVariable};
+pub use expanding::ExpandingEnvironment;
+pub use failing::{FailingEnvironment, Fault, FaultHandle};
+pub use feature_flags::FeatureFlags;
+#[cfg(feature = "figment")]
+pub use figment_provider::FigmentProvider;
+pub use filtered::FilteredEnvironment;
+pub use frozen::FrozenEnvironment;
+pub use history::{EnvChange, EnvMutation};
+#[cfg(feature = "test-util")]
+pub use isolated_env::IsolatedEnvGuard;
+#[cfg(feature = "serde_json")]
+pub use json::JsonExt;
+#[cfg(feature = "yaml")]
+pub use k8s_env::K8sEnvExt;
+pub use key_mapping::{normalize_key, KeyMappingEnvironment};
+pub use layered::LayeredEnvironment;
+pub use lazy::LazyEnvironment;
+pub use loader::ConfigLoader;
+#[cfg(feature = "serde")]
+pub use loader::LoadError;
+pub use locale::{resolve_locale, LocaleCategory};
+#[cfg(feature = "log")]
+pub use logging::LoggingEnvironment;
+#[cfg(feature = "metrics")]
+pub use metered::MeteredEnvironment;
+pub use mock::{Expectation, MockEnvironment, UnexpectedCalls};
+pub use nested::{to_nested_map, NestedError, NestedValue};
+pub use normalizing::NormalizingEnvironment;
+pub use observable::{ObservableEnvironment, ObserverId};
+pub use pattern::KeyPattern;
+pub use prefixed::{PrefixedEnvironment, StripPrefixView};
+pub use provenance::{Provenance, SourcedEnvironment};
+pub use proxy::{ProxyConfig, ProxyUrl};
+pub use read_only::ReadOnlyEnvironment;
+pub use recording::{EnvCall, RecordingEnvironment};
+pub use redacting::RedactingEnvironment;
+pub use redaction::RedactionPolicy;
+pub use resolution::{LayerId, LayerOutcome, Resolution, ResolutionStep};
+pub use schema::{EnvSchema, Problem, ValidationReport, VarKind};
+pub use secrets::{InMemorySecretSource, SecretError, SecretSource, SecretsOverlayEnvironment};
+#[cfg(feature = "zeroize")]
+pub use secure_fake::SecureFakeEnvironment;
+pub use sequence::{Exhausted, SequenceEnvironment};
+#[cfg(feature = "serde")]
+pub use ser::{to_env, FieldCase, NoneValue, SerError, ToEnvOptions};
+pub use shared::SharedFakeEnvironment;
+pub use shell::{Shell, ShellExportError, ShellExportExt};
+#[cfg(feature = "arc-swap")]
+pub use snapshot_swap::SnapshotSwapEnvironment;
+pub use subprocess::{run_in_subprocess, SubprocessResult};
+pub use systemd_env::SystemdEnvFileExt;
+#[cfg(feature = "tokio")]
+pub use task::{current_env, scope_env, TaskEnvironment};
+#[cfg(feature = "test-util")]
+pub use test_util::{unique_key, ScopedTestVar};
+pub use tilde::expand_tilde;
+#[cfg(feature = "tracing")]
+pub use traced::TracedEnvironment;
+pub use transaction::Transaction;
+pub use watcher::EnvWatcher;
+pub use windows_block::WindowsEnvironmentBlockExt;
+pub use with_defaults::WithDefaults;
+pub use xdg::XdgDirs;
+#[cfg(feature = "yaml")]
+pub use yaml::YamlExt;
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     env::{self, VarError},
     ffi::{OsStr, OsString},
+    fmt, mem,
+    panic::Location,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
 
-/// Represents a process's environment.
-pub trait Environment {
-    /// Set an environment variable.
-    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>);
-
+/// Represents the read side of a process's environment.
+///
+/// Only [`var_os`](ReadEnvironment::var_os) must be implemented: the other
+/// methods have default implementations built on it.
+pub trait ReadEnvironment {
     /// Get an environment variable, checking for valid UTF-8. If valid UTF-8
     /// checks are not needed, use `var_os`.
     ///
+    /// The default implementation calls `var_os`.
+    ///
     /// # Errors
     /// * If a key doesn't exist, it should return a `VarError::NotPresent`.
     /// * If the environment variable value contains invalid UTF-8, it
-    /// should return `VarError::NotUnicode(OsString)`.
-    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError>;
+    ///   should return `VarError::NotUnicode(OsString)`.
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        var_from_os(self.var_os(key))
+    }
 
     /// Get an environment variable. This does not check for valid UTF-8.
     /// If a valid UTF-8 check is needed, use `var` instead.
     fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString>;
 
+    /// Whether an environment variable is set, regardless of its value.
+    fn contains(&self, key: impl AsRef<OsStr>) -> bool {
+        self.var_os(key).is_some()
+    }
+
+    /// Get several environment variables at once, as a map from each of
+    /// `keys` that is set to its value. This does not check for valid UTF-8.
+    ///
+    /// The default implementation calls `var_os` for each key.
+    fn read_all(&self, keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+        read_each(self, keys)
+    }
+
+    /// Get an environment variable like [`var`](ReadEnvironment::var), but
+    /// with an error that holds the key and never the value, for code that
+    /// logs errors and may read secrets.
+    ///
+    /// # Errors
+    /// * If the key doesn't exist, it returns an
+    ///   [`EnvError::NotPresent`](EnvError::NotPresent).
+    /// * If the value contains invalid UTF-8, it returns an
+    ///   [`EnvError::NotUnicode`](EnvError::NotUnicode) holding only the
+    ///   value's length and the offset of its first invalid byte.
+    /// * If the environment cannot read the variable, such as a secret whose
+    ///   backend is unreachable, it should return an
+    ///   [`EnvError::Unavailable`](EnvError::Unavailable).
+    fn var_redacted(&self, key: impl AsRef<OsStr>) -> Result<String, EnvError> {
+        self.var(&key)
+            .map_err(|error| EnvError::from_var_error(key, error))
+    }
+}
+
+/// Represents a process's environment.
+///
+/// Types that can only be read, such as views over another environment,
+/// implement [`ReadEnvironment`](ReadEnvironment) alone.
+///
+/// An implementation needs only
+/// [`var_os`](ReadEnvironment::var_os), `set_var`, and `remove_var`: the
+/// other methods have default implementations built on them.
+///
+/// # Example
+/// A minimal environment backed by a map:
+/// ```rust
+/// # use std::{collections::HashMap, env::VarError, ffi::{OsStr, OsString}};
+/// # use env_wrapper::{Environment, ReadEnvironment};
+/// #[derive(Default)]
+/// struct MapEnvironment {
+///     vars: HashMap<OsString, OsString>,
+/// }
+///
+/// impl ReadEnvironment for MapEnvironment {
+///     fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+///         self.vars.get(key.as_ref()).cloned()
+///     }
+/// }
+///
+/// impl Environment for MapEnvironment {
+///     fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+///         self.vars.insert(key.as_ref().into(), value.as_ref().into());
+///     }
+///
+///     fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+///         self.vars.remove(key.as_ref());
+///     }
+/// }
+///
+/// let mut env = MapEnvironment::default();
+/// env.set_var("MODE", "fast");
+///
+/// assert_eq!(env.var("MODE").unwrap(), "fast");
+/// assert!(env.contains("MODE"));
+/// assert_eq!(env.var("PORT"), Err(VarError::NotPresent));
+/// ```
+pub trait Environment: ReadEnvironment {
+    /// Set an environment variable.
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>);
+
     /// Remove an environment variable from the current process environment.
     fn remove_var(&mut self, key: impl AsRef<OsStr>);
+
+    /// Set an environment variable, returning an error instead of panicking if
+    /// the environment does not allow it.
+    ///
+    /// The default implementation calls `set_var` and always succeeds.
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.set_var(key, value);
+        Ok(())
+    }
+
+    /// Remove an environment variable, returning an error instead of panicking
+    /// if the environment does not allow it.
+    ///
+    /// The default implementation calls `remove_var` and always succeeds.
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.remove_var(key);
+        Ok(())
+    }
+}
+
+impl<E: ReadEnvironment + ?Sized> ReadEnvironment for &E {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        (**self).var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        (**self).var_os(key)
+    }
+
+    fn read_all(&self, keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+        (**self).read_all(keys)
+    }
+
+    fn var_redacted(&self, key: impl AsRef<OsStr>) -> Result<String, EnvError> {
+        (**self).var_redacted(key)
+    }
+}
+
+impl<E: ReadEnvironment + ?Sized> ReadEnvironment for &mut E {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        (**self).var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        (**self).var_os(key)
+    }
+
+    fn read_all(&self, keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+        (**self).read_all(keys)
+    }
+}
+
+impl<E: Environment + ?Sized> Environment for &mut E {
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        (**self).set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        (**self).remove_var(key)
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        (**self).try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        (**self).try_remove_var(key)
+    }
+}
+
+/// Read `keys` one at a time with `var_os`.
+fn read_each<E: ReadEnvironment + ?Sized>(env: &E, keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+    keys.iter()
+        .filter_map(|&key| Some((key.to_os_string(), env.var_os(key)?)))
+        .collect()
+}
+
+/// Convert the result of a `var_os` lookup into the result `var` would give.
+pub(crate) fn var_from_os(value: Option<OsString>) -> Result<String, VarError> {
+    match value {
+        Some(value) => value.into_string().map_err(VarError::NotUnicode),
+        None => Err(VarError::NotPresent),
+    }
+}
+
+/// An environment whose variables can be listed.
+pub trait EnumerableEnvironment: ReadEnvironment {
+    /// Get every environment variable as key-value pairs. This does not check
+    /// for valid UTF-8. The order of the pairs is unspecified.
+    fn vars_os(&self) -> Vec<(OsString, OsString)>;
+
+    /// Get the variables whose names match `pattern`, such as
+    /// `MYAPP_*_URL`, sorted by name. See [`KeyPattern`](KeyPattern) for
+    /// the syntax.
+    ///
+    /// Names are matched as UTF-8: variables whose names are not valid
+    /// UTF-8 are skipped. Values are not checked.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnumerableEnvironment, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("MYAPP_DB_URL", "postgres://db");
+    /// fake_env.set_var("MYAPP_CACHE_URL", "redis://cache");
+    /// fake_env.set_var("MYAPP_DB_URL_OLD", "postgres://old");
+    ///
+    /// let urls = fake_env.vars_matching("MYAPP_*_URL");
+    ///
+    /// assert_eq!(
+    ///     urls,
+    ///     [
+    ///         ("MYAPP_CACHE_URL".into(), "redis://cache".into()),
+    ///         ("MYAPP_DB_URL".into(), "postgres://db".into()),
+    ///     ]
+    /// );
+    /// ```
+    fn vars_matching(&self, pattern: impl Into<KeyPattern>) -> Vec<(String, OsString)> {
+        let pattern = pattern.into();
+        pattern::vars_where(self.vars_os(), |key| pattern.matches(key))
+    }
+
+    /// Get the variables whose names `regex` matches, sorted by name.
+    /// Requires the `regex` feature.
+    ///
+    /// The regex matches anywhere in a name unless anchored with `^` and
+    /// `$`. Names are matched as UTF-8, as by
+    /// [`vars_matching`](EnumerableEnvironment::vars_matching).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnumerableEnvironment, Environment, FakeEnvironment};
+    /// # use regex::Regex;
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("MYAPP_DB_HOST", "db");
+    /// fake_env.set_var("MYAPP_CACHE_HOST", "cache");
+    /// fake_env.set_var("MYAPP_QUEUE_HOST", "queue");
+    ///
+    /// let hosts = fake_env.vars_matching_regex(&Regex::new(r"^MYAPP_(DB|CACHE)_").unwrap());
+    ///
+    /// assert_eq!(hosts.len(), 2);
+    /// ```
+    #[cfg(feature = "regex")]
+    fn vars_matching_regex(&self, regex: &regex::Regex) -> Vec<(String, OsString)> {
+        pattern::vars_where(self.vars_os(), |key| regex.is_match(key))
+    }
+
+    /// Group the variables whose names start with `prefix` into a tree,
+    /// nesting at each `separator` in the rest of their names, with each
+    /// part of a name in lower case. So with the prefix `MYAPP_` and the
+    /// separator `_`, `MYAPP_DB_HOST` is the leaf at `db` then `host`.
+    ///
+    /// This is [`to_nested_map`](to_nested_map) for names that nest at a
+    /// single character and whose case does not matter. Variables whose
+    /// names do not start with `prefix`, or are not valid Unicode, are
+    /// ignored.
+    ///
+    /// # Errors
+    /// As [`to_nested_map`](to_nested_map), and
+    /// [`NestedError::Duplicate`](NestedError::Duplicate) for two names that
+    /// differ only in case. A variable set where others nest, such as
+    /// `MYAPP_DB` next to `MYAPP_DB_HOST`, is a
+    /// [`NestedError::Conflict`](NestedError::Conflict).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnumerableEnvironment, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("MYAPP_DB_HOST", "localhost");
+    /// fake_env.set_var("MYAPP_DB_PORT", "5432");
+    /// fake_env.set_var("MYAPP_CACHE_TTL", "60");
+    ///
+    /// let tree = fake_env.group_by_prefix("MYAPP_", '_').unwrap();
+    ///
+    /// let db = tree.get("db").unwrap();
+    /// assert_eq!(db.get("port").unwrap().as_leaf(), Some("5432"));
+    /// assert_eq!(tree.get("cache").unwrap().get("ttl").unwrap().as_leaf(), Some("60"));
+    /// ```
+    fn group_by_prefix(&self, prefix: &str, separator: char) -> Result<NestedValue, NestedError> {
+        nested::build(
+            self.vars_os(),
+            prefix,
+            separator.encode_utf8(&mut [0; 4]),
+            true,
+        )
+    }
+}
+
+impl<E: EnumerableEnvironment + ?Sized> EnumerableEnvironment for &E {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        (**self).vars_os()
+    }
+}
+
+impl<E: EnumerableEnvironment + ?Sized> EnumerableEnvironment for &mut E {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        (**self).vars_os()
+    }
+}
+
+/// An environment that counts its changes, so that code holding on to what
+/// it read can tell whether it is out of date without reading it all again.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, VersionedEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// let seen = fake_env.generation();
+///
+/// fake_env.remove_var("NEVER_SET");
+/// assert!(!fake_env.changed_since(seen));
+///
+/// fake_env.set_var("MODE", "fast");
+/// assert!(fake_env.changed_since(seen));
+/// ```
+pub trait VersionedEnvironment: ReadEnvironment {
+    /// A counter that increases by one with each change to the variables:
+    /// each variable set, each variable removed, and each time the
+    /// environment is cleared of any variables. Reads and removals of
+    /// variables that are not set leave it as it is.
+    ///
+    /// Generations are only comparable for the same environment, or clones
+    /// that share their variables.
+    fn generation(&self) -> u64;
+
+    /// Whether the variables changed since `generation` was read.
+    fn changed_since(&self, generation: u64) -> bool {
+        self.generation() != generation
+    }
+}
+
+impl<E: VersionedEnvironment + ?Sized> VersionedEnvironment for &E {
+    fn generation(&self) -> u64 {
+        (**self).generation()
+    }
+}
+
+impl<E: VersionedEnvironment + ?Sized> VersionedEnvironment for &mut E {
+    fn generation(&self) -> u64 {
+        (**self).generation()
+    }
 }
 
 /// The process's environment. Wraps the standard
@@ -112,30 +721,7 @@ pub trait Environment {
 /// ```
 pub struct RealEnvironment;
 
-impl Environment for RealEnvironment {
-    /// From [`std::env::set_var`](https://doc.rust-lang.org/std/env/fn.set_var.html):
-    /// > Sets the environment variable `key` to the value `value` for the currently running
-    /// > process.
-    /// >
-    /// > Note that while concurrent access to environment variables is safe in Rust,
-    /// > some platforms only expose inherently unsafe non-threadsafe APIs for
-    /// > inspecting the environment. As a result, extra care needs to be taken when
-    /// > auditing calls to unsafe external FFI functions to ensure that any external
-    /// > environment accesses are properly synchronized with accesses in Rust.
-    /// >
-    /// > Discussion of this unsafety on Unix may be found in:
-    /// >
-    /// >  - [Austin Group Bugzilla](https://austingroupbugs.net/view.php?id=188)
-    /// >  - [GNU C library Bugzilla](https://sourceware.org/bugzilla/show_bug.cgi?id=15607#c2)
-    /// >
-    /// > # Panics
-    /// >
-    /// > This function may panic if `key` is empty, contains an ASCII equals sign `'='`
-    /// > or the NUL character `'\0'`, or when `value` contains the NUL character.
-    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
-        env::set_var(key, value)
-    }
-
+impl ReadEnvironment for RealEnvironment {
     /// From [`std::env::var`](https://doc.rust-lang.org/std/env/fn.var.html):
     /// > Fetches the environment variable `key` from the current process.
     /// >
@@ -173,6 +759,51 @@ impl Environment for RealEnvironment {
         env::var_os(key)
     }
 
+    /// Reads a few keys one at a time, and more in one pass over the
+    /// environment, which is cheaper since each lookup scans it too. On
+    /// Windows, where keys are case-insensitive, always reads them one at a
+    /// time.
+    fn read_all(&self, keys: &[&OsStr]) -> HashMap<OsString, OsString> {
+        if cfg!(windows) || keys.len() < ONE_PASS_MIN_KEYS {
+            return read_each(self, keys);
+        }
+        let wanted: HashSet<&OsStr> = keys.iter().copied().collect();
+        env::vars_os()
+            .filter(|(key, _)| wanted.contains(key.as_os_str()))
+            .collect()
+    }
+}
+
+/// The number of keys from which [`RealEnvironment::read_all`] reads them in
+/// one pass over the environment. In the `read_all` benchmark, the pass
+/// catches up at about 48 keys with 200 variables and 32 with 1000; past
+/// that, reading one at a time falls behind quickly in large environments.
+const ONE_PASS_MIN_KEYS: usize = 32;
+
+impl Environment for RealEnvironment {
+    /// From [`std::env::set_var`](https://doc.rust-lang.org/std/env/fn.set_var.html):
+    /// > Sets the environment variable `key` to the value `value` for the currently running
+    /// > process.
+    /// >
+    /// > Note that while concurrent access to environment variables is safe in Rust,
+    /// > some platforms only expose inherently unsafe non-threadsafe APIs for
+    /// > inspecting the environment. As a result, extra care needs to be taken when
+    /// > auditing calls to unsafe external FFI functions to ensure that any external
+    /// > environment accesses are properly synchronized with accesses in Rust.
+    /// >
+    /// > Discussion of this unsafety on Unix may be found in:
+    /// >
+    /// >  - [Austin Group Bugzilla](https://austingroupbugs.net/view.php?id=188)
+    /// >  - [GNU C library Bugzilla](https://sourceware.org/bugzilla/show_bug.cgi?id=15607#c2)
+    /// >
+    /// > # Panics
+    /// >
+    /// > This function may panic if `key` is empty, contains an ASCII equals sign `'='`
+    /// > or the NUL character `'\0'`, or when `value` contains the NUL character.
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        env::set_var(key, value)
+    }
+
     /// From [`std::env::remove_var`](https://doc.rust-lang.org/std/env/fn.remove_var.html):
     /// > Removes an environment variable from the environment of the currently running process.
     /// >
@@ -197,6 +828,25 @@ impl Environment for RealEnvironment {
     }
 }
 
+impl EnumerableEnvironment for RealEnvironment {
+    /// From [`std::env::vars_os`](https://doc.rust-lang.org/std/env/fn.vars_os.html):
+    /// > Returns an iterator of (variable, value) pairs of OS strings, for all the
+    /// > environment variables of the current process.
+    /// >
+    /// > The returned iterator contains a snapshot of the process's environment
+    /// > variables at the time of this invocation. Modifications to environment
+    /// > variables afterwards will not be reflected in the returned iterator.
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        env::vars_os().collect()
+    }
+}
+
+impl SourcedEnvironment for RealEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.var(key).map(|value| (value, Provenance::Process))
+    }
+}
+
 /// A fake process environment, suitable for testing.
 ///
 /// # Notes
@@ -233,214 +883,1378 @@ impl Environment for RealEnvironment {
 ///     assert_eq!(location, user_specified_location);
 /// }
 /// ```
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Default)]
 pub struct FakeEnvironment {
-    env_vars: HashMap<OsString, OsString>,
+    /// Values are shared, between clones and with
+    /// [`var_os_arc`](FakeEnvironment::var_os_arc), until they are replaced.
+    env_vars: HashMap<StoredKey, Arc<OsStr>, KeyHasher>,
+    /// With strict reads, the keys that may be read: those allowed
+    /// explicitly and those ever set.
+    readable_keys: Option<HashSet<StoredKey, KeyHasher>>,
+    /// With access tracking, the keys read so far.
+    read_tracking: Option<ReadTracking>,
+    /// With history, every change so far, oldest first.
+    history: Option<Vec<EnvMutation>>,
+    /// With set locations, where each key was last set. Never kept in
+    /// release builds, so they pay nothing for it.
+    #[cfg(debug_assertions)]
+    set_locations: Option<HashMap<StoredKey, &'static Location<'static>, KeyHasher>>,
+    /// Which values `Debug` masks.
+    redaction_policy: Option<RedactionPolicy>,
+    /// The keys that may not be set or removed.
+    protected_keys: HashSet<StoredKey, KeyHasher>,
+    generation: u64,
 }
 
-impl FakeEnvironment {
-    pub fn new() -> Self {
-        FakeEnvironment {
-            env_vars: HashMap::new(),
-        }
+/// Lists the variables sorted by key, with values the redaction policy
+/// covers masked. The history is left out, since it holds past values.
+impl fmt::Debug for FakeEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let env_vars: BTreeMap<&OsStr, &OsStr> = self
+            .env_vars
+            .iter()
+            .map(|(key, value)| {
+                let value = match &self.redaction_policy {
+                    Some(policy) => policy.redact(key, value),
+                    None => value,
+                };
+                (key.as_ref(), value)
+            })
+            .collect();
+        f.debug_struct("FakeEnvironment")
+            .field("env_vars", &env_vars)
+            .field("readable_keys", &self.readable_keys)
+            .field("read_tracking", &self.read_tracking)
+            .field("generation", &self.generation)
+            .finish_non_exhaustive()
     }
 }
 
-impl Environment for FakeEnvironment {
-    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
-        self.env_vars
-            .insert(key.as_ref().into(), value.as_ref().into());
+/// The reads of a tracking [`FakeEnvironment`](FakeEnvironment), behind a
+/// lock since reads only borrow the environment, and the keys allowed to go
+/// unread.
+#[derive(Debug, Default)]
+struct ReadTracking {
+    reads: Mutex<Reads>,
+    allowed_unread: HashSet<StoredKey, KeyHasher>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Reads {
+    keys: HashSet<StoredKey, KeyHasher>,
+    /// The keys read while not set, in the order first read.
+    missing: Vec<StoredKey>,
+}
+
+impl ReadTracking {
+    fn reads(&self) -> MutexGuard<'_, Reads> {
+        self.reads.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
-        match self.env_vars.get(key.as_ref()) {
-            Some(val) => match val.to_str() {
-                Some(valid_utf8) => Ok(valid_utf8.into()),
-                None => Err(VarError::NotUnicode(val.into())),
-            },
-            None => Err(VarError::NotPresent),
+    fn record(&self, key: &OsStr, is_set: bool) {
+        let mut reads = self.reads();
+        let first_read = reads.keys.insert(key.into());
+        if !is_set && (first_read || !reads.missing.iter().any(|missing| **missing == *key)) {
+            reads.missing.push(key.into());
         }
     }
+}
 
-    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
-        self.env_vars.get(key.as_ref()).cloned()
+impl Clone for ReadTracking {
+    fn clone(&self) -> Self {
+        ReadTracking {
+            reads: Mutex::new(self.reads().clone()),
+            allowed_unread: self.allowed_unread.clone(),
+        }
     }
+}
 
-    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
-        self.env_vars.remove(key.as_ref());
+/// Fake environments are equal if they hold the same variables and read
+/// them as strictly, however many changes and reads it took to get there.
+impl PartialEq for FakeEnvironment {
+    fn eq(&self, other: &Self) -> bool {
+        self.env_vars == other.env_vars && self.readable_keys == other.readable_keys
     }
 }
 
-// These tests represent behavior that should be shared by fake and real
-// implementations. Both are being tested to enforce behavioral parity.
-#[cfg(test)]
-mod tests {
-    use std::{
-        env::VarError,
-        ffi::{OsStr, OsString},
-        os::unix::ffi::OsStrExt,
-    };
+impl Eq for FakeEnvironment {}
 
-    use crate::{test_helpers::random_upper, Environment, FakeEnvironment, RealEnvironment};
+/// How [`FakeEnvironment`](FakeEnvironment) stores keys: shared between
+/// clones with the `intern-keys` feature, so cloning only copies pointers.
+#[cfg(feature = "intern-keys")]
+type StoredKey = Arc<OsStr>;
 
-    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+/// How [`FakeEnvironment`](FakeEnvironment) stores keys: owned by each clone,
+/// unless the `intern-keys` feature is enabled.
+#[cfg(not(feature = "intern-keys"))]
+type StoredKey = OsString;
 
-    #[test]
-    fn when_adding_an_environment_variable_then_it_can_be_read() {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-            let value = random_upper();
-            env.set_var(&key, &value);
+/// How [`FakeEnvironment`](FakeEnvironment) hashes keys: with FxHash, which is
+/// faster but not resistant to collision attacks, with the `fxhash` feature.
+#[cfg(feature = "fxhash")]
+type KeyHasher = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
 
-            // Act
-            let result = env.var(&key);
+/// How [`FakeEnvironment`](FakeEnvironment) hashes keys: with the standard
+/// library's SipHash, unless the `fxhash` feature is enabled.
+#[cfg(not(feature = "fxhash"))]
+type KeyHasher = std::collections::hash_map::RandomState;
 
-            // Assert
-            assert_eq!(result.unwrap(), value);
+impl FakeEnvironment {
+    pub fn new() -> Self {
+        FakeEnvironment {
+            env_vars: HashMap::default(),
+            readable_keys: None,
+            read_tracking: None,
+            history: None,
+            #[cfg(debug_assertions)]
+            set_locations: None,
+            redaction_policy: None,
+            protected_keys: HashSet::default(),
+            generation: 0,
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
     }
 
-    #[test]
-    fn given_a_nonexistent_env_var_when_getting_the_env_var_with_var_then_it_is_a_not_present_error(
-    ) {
-        fn test(env: impl Environment) {
-            // Arrange
-            let nonexistent_key = random_upper();
-
-            // Act
-            let result = env.var(nonexistent_key);
-
-            // Assert
-            assert_eq!(result.unwrap_err(), VarError::NotPresent);
+    /// An empty fake environment with room for at least `capacity`
+    /// variables, to load a large fixture without growing the map as it
+    /// fills.
+    pub fn with_capacity(capacity: usize) -> Self {
+        FakeEnvironment {
+            env_vars: HashMap::with_capacity_and_hasher(capacity, KeyHasher::default()),
+            ..Self::new()
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
     }
 
-    #[test]
-    fn when_setting_env_vars_then_multiple_data_types_can_be_used_on_the_same_environment_instance()
-    {
-        fn test(mut env: impl Environment) {
-            // Act
-            env.set_var(&random_upper(), &random_upper());
-            env.set_var(random_upper(), random_upper());
-            env.set_var(OsStr::new(&random_upper()), OsStr::new(&random_upper()));
-            env.set_var(
-                OsString::from(random_upper()),
-                OsString::from(random_upper()),
-            );
-
-            // Assert - none. This is strictly for type enforcement.
+    /// A fake environment that panics on reads of variables that were never
+    /// set, to catch test fixtures that forget to set a variable the code
+    /// under test needs.
+    ///
+    /// Variables that were set and later removed read as not present, as
+    /// usual. To read a variable that is meant to be unset, allow it with
+    /// [`allow_unset`](FakeEnvironment::allow_unset).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{FakeEnvironment, ReadEnvironment};
+    /// let mut fake_env = FakeEnvironment::new_strict_reads();
+    /// fake_env.allow_unset("OPTIONAL_FLAG");
+    ///
+    /// assert!(fake_env.var("OPTIONAL_FLAG").is_err());
+    /// ```
+    pub fn new_strict_reads() -> Self {
+        FakeEnvironment {
+            readable_keys: Some(HashSet::default()),
+            ..Self::new()
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
     }
 
-    #[test]
-    fn when_using_var_getter_with_an_invalid_utf8_value_then_it_is_a_not_unicode_error() {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-            env.set_var(&key, OsStr::from_bytes(&INVALID_UTF8));
-
-            // Act
-            let result = env.var(&key);
-
-            // Assert
-            assert!(matches!(result, Err(VarError::NotUnicode(_))));
+    /// A fake environment that records which variables are read, to catch
+    /// test fixtures that set variables no code reads anymore. Check them at
+    /// the end of the test with
+    /// [`verify_all_read`](FakeEnvironment::verify_all_read).
+    ///
+    /// Reading a variable with [`var`](ReadEnvironment::var),
+    /// [`var_os`](ReadEnvironment::var_os),
+    /// [`contains`](ReadEnvironment::contains), or the borrowing getters
+    /// marks it as read. Listing every variable with
+    /// [`vars_os`](EnumerableEnvironment::vars_os) does not.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+    /// let mut fake_env = FakeEnvironment::new_tracking();
+    /// fake_env.set_var("PORT", "8080");
+    /// fake_env.set_var("LEGACY_FLAG", "1");
+    ///
+    /// let _ = fake_env.var("PORT");
+    ///
+    /// assert_eq!(fake_env.verify_all_read(), Err(vec!["LEGACY_FLAG".into()]));
+    /// ```
+    pub fn new_tracking() -> Self {
+        FakeEnvironment {
+            read_tracking: Some(ReadTracking::default()),
+            ..Self::new()
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
     }
 
-    #[test]
-    fn given_a_nonexistent_env_var_when_getting_the_env_var_with_var_os_then_it_is_none() {
-        fn test(env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-
-            // Act
-            let result = env.var_os(key);
-
-            // Assert
-            assert!(result.is_none());
+    /// With access tracking, let `key` go unread without failing
+    /// [`verify_all_read`](FakeEnvironment::verify_all_read), for variables
+    /// a fixture sets on purpose. Does nothing otherwise.
+    pub fn allow_unread(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        if let Some(read_tracking) = &mut self.read_tracking {
+            read_tracking.allowed_unread.insert(key.as_ref().into());
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
+        self
     }
 
-    #[test]
-    fn given_an_env_var_with_invalid_utf8_when_getting_the_env_var_with_var_os_then_it_is_some() {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-            env.set_var(&key, OsStr::from_bytes(&INVALID_UTF8));
+    /// Check that every variable set in a tracking environment has been
+    /// read, or allowed to go unread.
+    ///
+    /// # Errors
+    /// If any variable that is still set was never read, it returns their
+    /// keys, sorted.
+    ///
+    /// # Panics
+    /// If the environment does not track reads, since it cannot tell.
+    /// Create it with [`new_tracking`](FakeEnvironment::new_tracking).
+    pub fn verify_all_read(&self) -> Result<(), Vec<OsString>> {
+        let read_tracking = self.read_tracking("verify_all_read");
+        let reads = read_tracking.reads();
+        let mut unread: Vec<OsString> = self
+            .env_vars
+            .keys()
+            .filter(|key| {
+                !reads.keys.contains(*key) && !read_tracking.allowed_unread.contains(*key)
+            })
+            .map(|key| key.to_os_string())
+            .collect();
+        if unread.is_empty() {
+            return Ok(());
+        }
+        unread.sort();
+        Err(unread)
+    }
 
-            // Act
-            let result = env.var_os(&key);
+    /// In a tracking environment, the keys that were read while they were
+    /// not set, each once, in the order they were first read that way, to
+    /// review which fallbacks the code under test took.
+    ///
+    /// A key stays listed even if it is set and read afterwards.
+    ///
+    /// # Panics
+    /// If the environment does not track reads. Create it with
+    /// [`new_tracking`](FakeEnvironment::new_tracking).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+    /// let mut fake_env = FakeEnvironment::new_tracking();
+    /// fake_env.set_var("PORT", "8080");
+    ///
+    /// let _ = fake_env.var("PORT");
+    /// let _ = fake_env.var("LOG_LEVEL");
+    /// let _ = fake_env.var_os("TIMEOUT");
+    ///
+    /// assert_eq!(fake_env.missing_reads(), ["LOG_LEVEL", "TIMEOUT"]);
+    /// ```
+    pub fn missing_reads(&self) -> Vec<OsString> {
+        self.read_tracking("missing_reads")
+            .reads()
+            .missing
+            .iter()
+            .map(|key| key.to_os_string())
+            .collect()
+    }
 
-            // Assert
-            assert!(result.is_some());
+    fn read_tracking(&self, method: &str) -> &ReadTracking {
+        match &self.read_tracking {
+            Some(read_tracking) => read_tracking,
+            None => panic!(
+                "`{method}` needs a fake environment that tracks reads; create it with \
+                 `FakeEnvironment::new_tracking`"
+            ),
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
     }
 
-    #[test]
-    fn given_an_existing_environment_variable_when_setting_the_same_environment_variable_then_the_value_is_overwritten(
-    ) {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-            let val_1 = random_upper();
-            let val_2 = random_upper();
-            env.set_var(&key, &val_1);
+    /// A fake environment that remembers where in the source each variable
+    /// was last set, to find which helper overwrote a variable a test
+    /// relies on. Look it up with
+    /// [`last_set_location`](FakeEnvironment::last_set_location).
+    ///
+    /// Locations are only kept in builds with debug assertions, as tests
+    /// usually are. In other builds this is the same as
+    /// [`new`](FakeEnvironment::new).
+    pub fn new_with_set_locations() -> Self {
+        FakeEnvironment {
+            #[cfg(debug_assertions)]
+            set_locations: Some(HashMap::default()),
+            ..Self::new()
+        }
+    }
+
+    /// Where `key` was last set, in an environment created with
+    /// [`new_with_set_locations`](FakeEnvironment::new_with_set_locations)
+    /// in a build with debug assertions, even if it was removed since.
+    ///
+    /// The location is that of the `set_var` or `try_set_var` call, or of
+    /// the call to a helper marked `#[track_caller]` that made it.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment};
+    /// #[track_caller]
+    /// fn set_fast_mode(env: &mut impl Environment) {
+    ///     env.set_var("MYAPP_MODE", "fast");
+    /// }
+    ///
+    /// let mut fake_env = FakeEnvironment::new_with_set_locations();
+    /// set_fast_mode(&mut fake_env);
+    /// # let line = line!() - 1;
+    ///
+    /// // Always `None` in builds without debug assertions.
+    /// if let Some(location) = fake_env.last_set_location("MYAPP_MODE") {
+    ///     assert_eq!(location.line(), line);
+    /// }
+    /// ```
+    pub fn last_set_location(&self, key: impl AsRef<OsStr>) -> Option<&'static Location<'static>> {
+        #[cfg(debug_assertions)]
+        if let Some(set_locations) = &self.set_locations {
+            return set_locations.get(key.as_ref()).copied();
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = key;
+        None
+    }
+
+    /// Mask the values `policy` covers in the `Debug` output, such as in
+    /// failed assertions and logs of test fixtures.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, RedactionPolicy};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_redaction_policy(RedactionPolicy::new().secret("*_TOKEN"));
+    /// fake_env.set_var("GITHUB_TOKEN", "ghp_abc123");
+    ///
+    /// assert!(!format!("{fake_env:?}").contains("ghp_abc123"));
+    /// ```
+    pub fn set_redaction_policy(&mut self, policy: RedactionPolicy) -> &mut Self {
+        self.redaction_policy = Some(policy);
+        self
+    }
+
+    pub fn redaction_policy(&self) -> Option<&RedactionPolicy> {
+        self.redaction_policy.as_ref()
+    }
+
+    /// Forbid changes to `key`, e.g. to `HOME` or `PATH` once a fixture is
+    /// seeded, so code under test that overwrites it fails where it does.
+    ///
+    /// Setting or removing a protected key panics, naming the key and the
+    /// value it would have been set to, masked if the redaction policy
+    /// covers it. So does [`clear`](FakeEnvironment::clear) while a
+    /// protected key is set. [`try_set_var`](Environment::try_set_var) and
+    /// [`try_remove_var`](Environment::try_remove_var) return
+    /// [`EnvError::ReadOnly`](EnvError::ReadOnly) instead.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnvError, Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("HOME", "/home/test");
+    /// fake_env.protect("HOME");
+    ///
+    /// let result = fake_env.try_set_var("HOME", "/root");
+    ///
+    /// assert_eq!(result, Err(EnvError::ReadOnly("HOME".into())));
+    /// ```
+    pub fn protect(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.protected_keys.insert(key.as_ref().into());
+        self
+    }
+
+    /// Allow changes to `key` again after
+    /// [`protect`](FakeEnvironment::protect).
+    pub fn unprotect(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.protected_keys.remove(key.as_ref());
+        self
+    }
+
+    /// Whether `key` is [protected](FakeEnvironment::protect).
+    pub fn is_protected(&self, key: impl AsRef<OsStr>) -> bool {
+        self.protected_keys.contains(key.as_ref())
+    }
+
+    /// With strict reads, allow `key` to be read even though it was never
+    /// set. Does nothing otherwise.
+    pub fn allow_unset(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        if let Some(readable_keys) = &mut self.readable_keys {
+            readable_keys.insert(key.as_ref().into());
+        }
+        self
+    }
+
+    /// Remove every variable. With strict reads, they may still be read,
+    /// as not present.
+    ///
+    /// # Panics
+    /// If a [protected](FakeEnvironment::protect) key is set.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn clear(&mut self) {
+        if self.env_vars.is_empty() {
+            return;
+        }
+        if let Some(key) = self
+            .protected_keys
+            .iter()
+            .find(|key| self.env_vars.contains_key(&**key))
+        {
+            panic!("cannot clear the environment: environment variable {key:?} is protected");
+        }
+        self.generation += 1;
+        if let Some(history) = &mut self.history {
+            let mut old_vars: Vec<(OsString, OsString)> = self
+                .env_vars
+                .iter()
+                .map(|(key, value)| (key.to_os_string(), value.to_os_string()))
+                .collect();
+            old_vars.sort();
+            history.push(EnvMutation {
+                sequence: self.generation,
+                change: EnvChange::Clear { old_vars },
+            });
+        }
+        self.env_vars.clear();
+    }
+
+    /// The number of variables the environment can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.env_vars.capacity()
+    }
+
+    /// Release the capacity left over from variables that were removed, for
+    /// fakes that grow and shrink over a long run, such as in fuzzing.
+    pub fn shrink_to_fit(&mut self) {
+        self.env_vars.shrink_to_fit();
+        if let Some(readable_keys) = &mut self.readable_keys {
+            readable_keys.shrink_to_fit();
+        }
+    }
+
+    /// An estimate of the memory the environment holds on the heap: its keys
+    /// and values, and the tables that index them, including their unused
+    /// capacity.
+    ///
+    /// Values shared between clones are counted in full by each clone.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// let empty = fake_env.heap_size_bytes();
+    /// fake_env.set_var("CERTIFICATE", "x".repeat(4096));
+    ///
+    /// assert!(fake_env.heap_size_bytes() >= empty + 4096);
+    /// ```
+    pub fn heap_size_bytes(&self) -> usize {
+        // Reference-counted buffers also hold their strong and weak counts.
+        const ARC_HEADER: usize = 2 * mem::size_of::<usize>();
+        const KEY_HEADER: usize = if cfg!(feature = "intern-keys") {
+            ARC_HEADER
+        } else {
+            0
+        };
+        // Each slot of a table also has a control byte.
+        let vars_table = self.env_vars.capacity() * (mem::size_of::<(StoredKey, Arc<OsStr>)>() + 1);
+        let vars: usize = self
+            .env_vars
+            .iter()
+            .map(|(key, value)| KEY_HEADER + key.len() + ARC_HEADER + value.len())
+            .sum();
+        let readable_keys = self.readable_keys.as_ref().map_or(0, |keys| {
+            keys.capacity() * (mem::size_of::<StoredKey>() + 1)
+                + keys.iter().map(|key| KEY_HEADER + key.len()).sum::<usize>()
+        });
+        vars_table + vars + readable_keys
+    }
+
+    /// The value of `key`, borrowed from the environment: without copying
+    /// it if it is valid UTF-8, and converted lossily otherwise.
+    ///
+    /// Unlike [`var`](ReadEnvironment::var), this does not allocate for
+    /// values that are valid UTF-8, for tests that read variables in tight
+    /// loops.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::borrow::Cow;
+    /// # use env_wrapper::{Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("MODE", "fast");
+    ///
+    /// assert_eq!(fake_env.get("MODE"), Some(Cow::Borrowed("fast")));
+    /// assert_eq!(fake_env.get("MISSING"), None);
+    /// ```
+    pub fn get(&self, key: impl AsRef<OsStr>) -> Option<Cow<'_, str>> {
+        self.lookup(key.as_ref())
+            .map(|value| value.to_string_lossy())
+    }
 
-            // Act
-            env.set_var(&key, &val_2);
+    /// The value of `key`, borrowed from the environment, like
+    /// [`var_os`](ReadEnvironment::var_os) without copying it.
+    pub fn get_os(&self, key: impl AsRef<OsStr>) -> Option<&OsStr> {
+        self.lookup(key.as_ref()).map(|value| &**value)
+    }
+
+    /// The value of `key`, sharing the environment's buffer instead of
+    /// copying it like [`var_os`](ReadEnvironment::var_os), for callers
+    /// that keep large values beyond a borrow of the environment.
+    ///
+    /// Setting the variable again replaces the buffer, so the value returned
+    /// never changes.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::{ffi::OsStr, sync::Arc};
+    /// # use env_wrapper::{Environment, FakeEnvironment};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("CERTIFICATE", "-----BEGIN CERTIFICATE-----");
+    ///
+    /// let certificate = fake_env.var_os_arc("CERTIFICATE").unwrap();
+    /// fake_env.set_var("CERTIFICATE", "rotated");
+    ///
+    /// assert_eq!(&*certificate, OsStr::new("-----BEGIN CERTIFICATE-----"));
+    /// assert_eq!(fake_env.var_os_arc("MISSING"), None);
+    /// ```
+    pub fn var_os_arc(&self, key: impl AsRef<OsStr>) -> Option<Arc<OsStr>> {
+        self.lookup(key.as_ref()).cloned()
+    }
 
-            // Assert
-            assert_eq!(env.var(&key).unwrap(), val_2);
+    fn lookup(&self, key: &OsStr) -> Option<&Arc<OsStr>> {
+        if !is_valid_key(key) {
+            return None;
+        }
+        self.check_readable(key);
+        let value = self.env_vars.get(key);
+        if let Some(read_tracking) = &self.read_tracking {
+            read_tracking.record(key, value.is_some());
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
+        value
     }
 
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn check_writable(&self, key: &OsStr, value: Option<&OsStr>) {
+        if !self.is_protected(key) {
+            return;
+        }
+        match value {
+            Some(value) => {
+                let value = match &self.redaction_policy {
+                    Some(policy) => policy.redact(key, value),
+                    None => value,
+                };
+                panic!("cannot set protected environment variable {key:?} to {value:?}")
+            }
+            None => panic!("cannot remove protected environment variable {key:?}"),
+        }
+    }
+
+    fn check_readable(&self, key: &OsStr) {
+        if let Some(readable_keys) = &self.readable_keys {
+            if !readable_keys.contains(key) {
+                panic!(
+                    "environment variable {key:?} was read but never set; set it or allow it \
+                     with `FakeEnvironment::allow_unset`"
+                );
+            }
+        }
+    }
+
+    /// Lock the environment against changes, e.g. once a test's arrange phase
+    /// is done, for code that only reads. See
+    /// [`FrozenEnvironment`](FrozenEnvironment).
+    pub fn freeze(self) -> FrozenEnvironment {
+        FrozenEnvironment::new(self)
+    }
+
+    /// Lock the environment against changes, for code that insists on an
+    /// [`Environment`](Environment). See
+    /// [`ReadOnlyEnvironment`](ReadOnlyEnvironment).
+    pub fn read_only(self) -> ReadOnlyEnvironment<Self> {
+        ReadOnlyEnvironment::new(self)
+    }
+}
+
+/// Whether the platform can look up a variable named `key`. Keys that are
+/// empty or contain NUL, or `=` other than as the first character on
+/// Windows, are never found in a real environment.
+pub(crate) fn is_valid_key(key: &OsStr) -> bool {
+    let Some((&first, rest)) = key.as_encoded_bytes().split_first() else {
+        return false;
+    };
+    let first_is_valid = first != 0 && (cfg!(windows) || first != b'=');
+    // Keys are short, so one pass is faster than searching for each byte.
+    first_is_valid && !rest.iter().any(|&byte| byte == 0 || byte == b'=')
+}
+
+/// Like the platform, a key that is empty or contains `=` or NUL is never
+/// present.
+impl ReadEnvironment for FakeEnvironment {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        // This is one UTF-8 check and one copy, and the copy is needed, since
+        // `var_os` makes it too. Building the `String` from the stored bytes
+        // after `str::from_utf8` compiles to the same work.
+        match self.lookup(key.as_ref()) {
+            Some(val) => match val.to_str() {
+                Some(valid_utf8) => Ok(valid_utf8.into()),
+                None => Err(VarError::NotUnicode(val.to_os_string())),
+            },
+            None => Err(VarError::NotPresent),
+        }
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.lookup(key.as_ref()).map(|value| value.to_os_string())
+    }
+}
+
+impl Environment for FakeEnvironment {
+    /// Setting a key that is empty or contains `=` or NUL does nothing, so it
+    /// is neither present nor listed, as the platform never stores one. The
+    /// real environment panics instead.
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        self.check_writable(key, Some(value));
+        if !is_valid_key(key) {
+            return;
+        }
+        let stored_key = StoredKey::from(key);
+        if let Some(readable_keys) = &mut self.readable_keys {
+            readable_keys.insert(stored_key.clone());
+        }
+        #[cfg(debug_assertions)]
+        if let Some(set_locations) = &mut self.set_locations {
+            set_locations.insert(stored_key.clone(), Location::caller());
+        }
+        let old_value = self.env_vars.insert(stored_key, value.into());
+        self.generation += 1;
+        if let Some(history) = &mut self.history {
+            history.push(EnvMutation {
+                sequence: self.generation,
+                change: EnvChange::Set {
+                    key: key.into(),
+                    old_value: old_value.map(|old_value| old_value.to_os_string()),
+                    new_value: value.into(),
+                },
+            });
+        }
+    }
+
+    /// Removing a key that is empty or contains `=` or NUL does nothing,
+    /// since no such variable can be present.
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.check_writable(key, None);
+        if !is_valid_key(key) {
+            return;
+        }
+        let Some(old_value) = self.env_vars.remove(key) else {
+            return;
+        };
+        self.generation += 1;
+        if let Some(history) = &mut self.history {
+            history.push(EnvMutation {
+                sequence: self.generation,
+                change: EnvChange::Remove {
+                    key: key.into(),
+                    old_value: old_value.to_os_string(),
+                },
+            });
+        }
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        if self.is_protected(&key) {
+            return Err(EnvError::ReadOnly(key.as_ref().into()));
+        }
+        self.set_var(key, value);
+        Ok(())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        if self.is_protected(&key) {
+            return Err(EnvError::ReadOnly(key.as_ref().into()));
+        }
+        self.remove_var(key);
+        Ok(())
+    }
+}
+
+impl VersionedEnvironment for FakeEnvironment {
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl SourcedEnvironment for FakeEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.var(key).map(|value| (value, Provenance::Fake))
+    }
+}
+
+impl EnumerableEnvironment for FakeEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.env_vars
+            .iter()
+            .map(|(key, value)| (key.to_os_string(), value.to_os_string()))
+            .collect()
+    }
+}
+
+// These tests represent behavior that should be shared by fake and real
+// implementations. Both are being tested to enforce behavioral parity.
+#[cfg(test)]
+mod real_environment_conformance {
+    crate::conformance_tests!(crate::RealEnvironment, enumerable);
+}
+
+#[cfg(test)]
+mod fake_environment_conformance {
+    crate::conformance_tests!(crate::FakeEnvironment::new(), enumerable);
+}
+
+#[cfg(test)]
+mod minimal_environment_conformance {
+    crate::conformance_tests!(super::minimal::MinimalEnvironment::default(), enumerable);
+}
+
+/// An environment that implements only the required methods, so the
+/// conformance tests check the default implementations of the rest.
+#[cfg(test)]
+mod minimal {
+    use std::{
+        collections::HashMap,
+        ffi::{OsStr, OsString},
+    };
+
+    use crate::{EnumerableEnvironment, Environment, ReadEnvironment};
+
+    #[derive(Default)]
+    pub(super) struct MinimalEnvironment {
+        vars: HashMap<OsString, OsString>,
+    }
+
+    impl ReadEnvironment for MinimalEnvironment {
+        fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+            self.vars.get(key.as_ref()).cloned()
+        }
+    }
+
+    impl Environment for MinimalEnvironment {
+        fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+            if super::is_valid_key(key.as_ref()) {
+                self.vars.insert(key.as_ref().into(), value.as_ref().into());
+            }
+        }
+
+        fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+            self.vars.remove(key.as_ref());
+        }
+    }
+
+    impl EnumerableEnvironment for MinimalEnvironment {
+        fn vars_os(&self) -> Vec<(OsString, OsString)> {
+            self.vars.clone().into_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod fake_environment_tests {
+    use std::{borrow::Cow, env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt, sync::Arc};
+
+    use crate::{
+        EnumerableEnvironment, EnvError, Environment, FakeEnvironment, ReadEnvironment,
+        RedactionPolicy, VersionedEnvironment,
+    };
+
     #[test]
-    fn given_an_existing_environment_variable_when_removing_the_same_environment_variable_then_the_variable_no_longer_exists(
-    ) {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
-            let value = random_upper();
-            env.set_var(&key, &value);
+    #[should_panic(expected = "environment variable \"DATABASE_URL\" was read but never set")]
+    fn given_strict_reads_when_reading_a_key_never_set_then_it_panics() {
+        // Arrange
+        let fake_env = FakeEnvironment::new_strict_reads();
+
+        // Act
+        let _ = fake_env.var("DATABASE_URL");
+    }
+
+    #[test]
+    #[should_panic(expected = "\"DATABASE_URL\"")]
+    fn given_strict_reads_when_reading_a_key_never_set_with_var_os_then_it_panics() {
+        // Arrange
+        let fake_env = FakeEnvironment::new_strict_reads();
+
+        // Act
+        let _ = fake_env.var_os("DATABASE_URL");
+    }
+
+    #[test]
+    fn given_strict_reads_when_reading_set_and_allowed_keys_then_they_behave_normally() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_strict_reads();
+        fake_env.set_var("MODE", "fast");
+        fake_env.allow_unset("OPTIONAL_FLAG");
+
+        // Act/Assert
+        assert_eq!(fake_env.var("MODE").unwrap(), "fast");
+        assert!(fake_env.var_os("OPTIONAL_FLAG").is_none());
+        assert!(!fake_env.contains("OPTIONAL_FLAG"));
+    }
+
+    #[test]
+    fn given_strict_reads_when_reading_a_removed_key_then_it_is_not_present() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_strict_reads();
+        fake_env.set_var("TMP", "/tmp");
+
+        // Act
+        fake_env.remove_var("TMP");
+
+        // Assert
+        assert!(fake_env.var_os("TMP").is_none());
+    }
+
+    #[test]
+    fn given_lenient_reads_when_reading_a_key_never_set_then_it_is_not_present() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.allow_unset("IGNORED");
+
+        // Act/Assert
+        assert!(fake_env.var_os("DATABASE_URL").is_none());
+    }
+
+    #[test]
+    fn given_a_utf8_value_when_getting_it_then_it_is_borrowed() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("MODE", "fast");
+        fake_env.set_var("BINARY", OsStr::from_bytes(&[0x66, 0x6f, 0x80, 0x6f]));
+
+        // Act
+        let utf8 = fake_env.get("MODE");
+        let binary = fake_env.get("BINARY");
+        let missing = fake_env.get("MISSING");
+
+        // Assert
+        assert!(matches!(utf8, Some(Cow::Borrowed("fast"))));
+        assert!(matches!(binary, Some(Cow::Owned(ref lossy)) if lossy == "fo\u{fffd}o"));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn given_a_value_when_getting_it_as_an_os_str_then_it_is_the_stored_value() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("BINARY", OsStr::from_bytes(&[0x66, 0x6f, 0x80, 0x6f]));
+
+        // Act
+        let value = fake_env.get_os("BINARY");
+
+        // Assert
+        assert_eq!(value, Some(OsStr::from_bytes(&[0x66, 0x6f, 0x80, 0x6f])));
+        assert_eq!(fake_env.get_os("MISSING"), None);
+        assert_eq!(fake_env.get_os("BINARY=X"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "environment variable \"DATABASE_URL\" was read but never set")]
+    fn given_strict_reads_when_getting_a_key_never_set_then_it_panics() {
+        // Arrange
+        let fake_env = FakeEnvironment::new_strict_reads();
+
+        // Act
+        let _ = fake_env.get("DATABASE_URL");
+    }
+
+    #[test]
+    fn given_a_cloned_fixture_when_either_clone_is_changed_then_the_other_is_unaffected() {
+        // Arrange
+        let mut original = FakeEnvironment::new();
+        original.set_var("SHARED", "original");
+        original.set_var("REMOVED_FROM_CLONE", "original");
+        let mut clone = original.clone();
+
+        // Act
+        clone.set_var("SHARED", "clone");
+        clone.remove_var("REMOVED_FROM_CLONE");
+        clone.set_var("ONLY_IN_CLONE", "clone");
+        original.set_var("ONLY_IN_ORIGINAL", "original");
+
+        // Assert
+        assert_eq!(original.var("SHARED").unwrap(), "original");
+        assert_eq!(original.var("REMOVED_FROM_CLONE").unwrap(), "original");
+        assert!(!original.contains("ONLY_IN_CLONE"));
+        assert_eq!(clone.var("SHARED").unwrap(), "clone");
+        assert!(!clone.contains("REMOVED_FROM_CLONE"));
+        assert!(!clone.contains("ONLY_IN_ORIGINAL"));
+    }
+
+    #[test]
+    fn given_a_value_when_getting_it_as_an_arc_then_the_stored_buffer_is_shared() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("CERTIFICATE", "pem");
+        let clone = fake_env.clone();
+
+        // Act
+        let first = fake_env.var_os_arc("CERTIFICATE").unwrap();
+        let second = fake_env.var_os_arc("CERTIFICATE").unwrap();
+        let from_clone = clone.var_os_arc("CERTIFICATE").unwrap();
+        fake_env.set_var("CERTIFICATE", "rotated");
+
+        // Assert
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &from_clone));
+        assert_eq!(&*first, OsStr::new("pem"));
+        assert_eq!(
+            fake_env.var_os_arc("CERTIFICATE").unwrap(),
+            OsStr::new("rotated").into()
+        );
+        assert_eq!(fake_env.var_os_arc("MISSING"), None);
+        assert_eq!(fake_env.var_os_arc("CERTIFICATE=X"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "environment variable \"DATABASE_URL\" was read but never set")]
+    fn given_strict_reads_when_getting_a_key_never_set_as_an_arc_then_it_panics() {
+        // Arrange
+        let fake_env = FakeEnvironment::new_strict_reads();
+
+        // Act
+        let _ = fake_env.var_os_arc("DATABASE_URL");
+    }
 
-            // Act
-            env.remove_var(&key);
+    #[cfg(feature = "intern-keys")]
+    #[test]
+    fn given_interned_keys_when_cloning_a_fixture_many_times_then_the_clones_share_storage() {
+        // Arrange
+        let mut fixture = FakeEnvironment::new();
+        for index in 0..500 {
+            fixture.set_var(format!("FIXTURE_KEY_{index}"), format!("value_{index}"));
+        }
+
+        // Act
+        let clones: Vec<FakeEnvironment> = (0..100).map(|_| fixture.clone()).collect();
+
+        // Assert
+        let allocations: std::collections::HashSet<*const u8> = clones
+            .iter()
+            .flat_map(|clone| clone.env_vars.iter())
+            .flat_map(|(key, value)| {
+                [
+                    key.as_encoded_bytes().as_ptr(),
+                    value.as_encoded_bytes().as_ptr(),
+                ]
+            })
+            .collect();
+        assert_eq!(allocations.len(), 2 * 500);
+    }
 
-            // Assert
-            assert_eq!(env.var(&key).unwrap_err(), VarError::NotPresent);
+    #[test]
+    fn given_a_growing_and_shrinking_fixture_when_shrinking_to_fit_then_the_capacity_follows() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        let empty_capacity = fake_env.capacity();
+        for index in 0..1000 {
+            fake_env.set_var(format!("FIXTURE_KEY_{index}"), "value");
+        }
+        let full_capacity = fake_env.capacity();
+        for index in 1..1000 {
+            fake_env.remove_var(format!("FIXTURE_KEY_{index}"));
         }
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
+
+        // Act
+        fake_env.shrink_to_fit();
+
+        // Assert
+        assert_eq!(empty_capacity, 0);
+        assert!(full_capacity >= 1000);
+        assert!((1..full_capacity).contains(&fake_env.capacity()));
+        assert_eq!(fake_env.var("FIXTURE_KEY_0").unwrap(), "value");
     }
 
     #[test]
-    fn when_removing_a_nonexistent_environment_variable_then_do_not_panic() {
-        fn test(mut env: impl Environment) {
-            // Arrange
-            let key = random_upper();
+    fn given_a_capacity_when_loading_that_many_variables_then_the_map_does_not_grow() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::with_capacity(500);
+        let capacity = fake_env.capacity();
+
+        // Act
+        for index in 0..500 {
+            fake_env.set_var(format!("FIXTURE_KEY_{index}"), "value");
+        }
 
-            // Act
-            env.remove_var(&key);
+        // Assert
+        assert!(capacity >= 500);
+        assert_eq!(fake_env.capacity(), capacity);
+    }
 
-            // Assert - no assertion
+    #[test]
+    fn given_variables_added_one_by_one_when_measuring_the_heap_size_then_it_only_increases() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_strict_reads();
+        let mut sizes = vec![fake_env.heap_size_bytes()];
+
+        // Act
+        for index in 0..200 {
+            fake_env.set_var(format!("FIXTURE_KEY_{index}"), "x".repeat(index));
+            sizes.push(fake_env.heap_size_bytes());
         }
 
-        test(RealEnvironment);
-        test(FakeEnvironment::new());
+        // Assert
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]), "{sizes:?}");
+        let data: usize = (0..200)
+            .map(|index| format!("FIXTURE_KEY_{index}").len() + index)
+            .sum();
+        assert!(sizes[200] >= data);
+    }
+
+    #[test]
+    fn given_mutations_and_reads_when_reading_the_generation_then_only_changes_count() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_strict_reads();
+        let mut generations = vec![fake_env.generation()];
+
+        // Act
+        fake_env.set_var("MODE", "fast");
+        generations.push(fake_env.generation());
+        fake_env.set_var("MODE", "fast");
+        generations.push(fake_env.generation());
+        let _ = fake_env.var("MODE");
+        let _ = fake_env.get_os("MODE");
+        let _ = fake_env.vars_os();
+        fake_env.allow_unset("NEVER_SET");
+        fake_env.remove_var("NEVER_SET");
+        fake_env.remove_var("MODE=X");
+        generations.push(fake_env.generation());
+        fake_env.remove_var("MODE");
+        generations.push(fake_env.generation());
+        fake_env.clear();
+        generations.push(fake_env.generation());
+        fake_env.set_var("TMP", "/tmp");
+        fake_env.clear();
+        generations.push(fake_env.generation());
+
+        // Assert
+        assert_eq!(generations, [0, 1, 2, 2, 3, 3, 5]);
+        assert!(fake_env.changed_since(3));
+        assert!(!fake_env.changed_since(5));
+        assert!(fake_env.var_os("TMP").is_none());
+    }
+
+    #[test]
+    fn given_fakes_with_different_histories_when_comparing_then_only_their_variables_matter() {
+        // Arrange
+        let mut changed = FakeEnvironment::new();
+        changed.set_var("MODE", "slow");
+        changed.set_var("MODE", "fast");
+        let mut direct = FakeEnvironment::new();
+        direct.set_var("MODE", "fast");
+
+        // Act/Assert
+        assert_ne!(changed.generation(), direct.generation());
+        assert_eq!(changed, direct);
+        assert_ne!(direct, FakeEnvironment::new_strict_reads());
+    }
+
+    #[test]
+    fn given_tracking_when_every_variable_is_read_then_verification_passes() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_tracking();
+        fake_env.set_var("PORT", "8080");
+        fake_env.set_var("MODE", "fast");
+        fake_env.set_var("TMP", "/tmp");
+        fake_env.set_var("REMOVED", "1");
+
+        // Act
+        let _ = fake_env.var("PORT");
+        let _ = fake_env.var_os("MODE");
+        let _ = fake_env.contains("TMP");
+        let _ = fake_env.var("NEVER_SET");
+        fake_env.remove_var("REMOVED");
+
+        // Assert
+        assert_eq!(fake_env.verify_all_read(), Ok(()));
+    }
+
+    #[test]
+    fn given_tracking_when_variables_are_never_read_then_verification_lists_them_sorted() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_tracking();
+        for key in ["PORT", "ZONE", "LEGACY_FLAG", "MODE"] {
+            fake_env.set_var(key, "value");
+        }
+
+        // Act
+        let _ = fake_env.get("PORT");
+        let _ = fake_env.vars_os();
+        let result = fake_env.verify_all_read();
+
+        // Assert
+        assert_eq!(
+            result,
+            Err(vec!["LEGACY_FLAG".into(), "MODE".into(), "ZONE".into()])
+        );
+    }
+
+    #[test]
+    fn given_tracking_when_unread_variables_are_allowed_then_verification_passes() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_tracking();
+        fake_env.set_var("PORT", "8080");
+        fake_env.set_var("LEGACY_FLAG", "1");
+        fake_env.allow_unread("LEGACY_FLAG");
+
+        // Act
+        let _ = fake_env.var("PORT");
+
+        // Assert
+        assert_eq!(fake_env.verify_all_read(), Ok(()));
+    }
+
+    #[test]
+    fn given_code_probing_optional_variables_when_listing_missing_reads_then_each_appears_once_in_order(
+    ) {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_tracking();
+        fake_env.set_var("PORT", "8080");
+        let probe = |env: &FakeEnvironment| {
+            let port = env.var("PORT").unwrap_or_else(|_| "80".into());
+            let log_level = env.var("LOG_LEVEL").unwrap_or_else(|_| "info".into());
+            let timeout = env.var_os("TIMEOUT").is_some();
+            let verbose = env.contains("VERBOSE");
+            (port, log_level, timeout, verbose)
+        };
+
+        // Act
+        probe(&fake_env);
+        probe(&fake_env);
+        let _ = fake_env.var("ANOTHER_FALLBACK");
+
+        // Assert
+        assert_eq!(
+            fake_env.missing_reads(),
+            ["LOG_LEVEL", "TIMEOUT", "VERBOSE", "ANOTHER_FALLBACK"]
+        );
+    }
+
+    #[track_caller]
+    fn set_mode(env: &mut impl Environment, mode: &str) {
+        env.set_var("MYAPP_MODE", mode);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn given_set_locations_when_a_helper_sets_a_key_then_the_helper_call_site_is_recorded() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_set_locations();
+
+        // Act
+        set_mode(&mut fake_env, "fast");
+        let first_line = line!() - 1;
+        set_mode(&mut &mut fake_env, "slow");
+        let second_line = line!() - 1;
+
+        // Assert
+        let location = fake_env.last_set_location("MYAPP_MODE").unwrap();
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), second_line);
+        assert_ne!(first_line, second_line);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn given_set_locations_when_setting_directly_then_the_last_call_site_is_kept_after_removal() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_set_locations();
+        fake_env.set_var("PORT", "80");
+
+        // Act
+        fake_env.try_set_var("PORT", "8080").unwrap();
+        let line = line!() - 1;
+        fake_env.remove_var("PORT");
+
+        // Assert
+        assert_eq!(fake_env.last_set_location("PORT").unwrap().line(), line);
+        assert!(fake_env.last_set_location("NEVER_SET").is_none());
+    }
+
+    #[test]
+    fn given_no_set_locations_when_setting_then_no_location_is_recorded() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+
+        // Act
+        set_mode(&mut fake_env, "fast");
+
+        // Assert
+        assert!(fake_env.last_set_location("MYAPP_MODE").is_none());
+    }
+
+    #[test]
+    fn given_a_redaction_policy_when_debug_formatting_then_secret_and_long_values_are_masked() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_with_history();
+        fake_env.set_redaction_policy(
+            RedactionPolicy::new()
+                .secret("DATABASE_URL")
+                .secret("*_TOKEN")
+                .redact_values_longer_than(16),
+        );
+        fake_env.set_var("GITHUB_TOKEN", "ghp_abc123");
+        fake_env.set_var("DATABASE_URL", "postgres://user:hunter2@db");
+        fake_env.set_var("SIGNING_KEY", "0123456789abcdef0123");
+        fake_env.set_var("LOG_LEVEL", "debug");
+
+        // Act
+        let debug = format!("{fake_env:?}");
+
+        // Assert
+        assert!(debug.starts_with(
+            "FakeEnvironment { env_vars: {\"DATABASE_URL\": \"***\", \"GITHUB_TOKEN\": \"***\", \
+             \"LOG_LEVEL\": \"debug\", \"SIGNING_KEY\": \"***\"}"
+        ));
+        assert!(!debug.contains("ghp_abc123"));
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("0123456789abcdef"));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot set protected environment variable \"HOME\" to \"/root\"")]
+    fn given_a_protected_key_when_overwriting_it_then_it_panics() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("HOME", "/home/test");
+        fake_env.protect("HOME");
+
+        // Act
+        fake_env.set_var("HOME", "/root");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot set protected environment variable \"API_TOKEN\" to \"***\"")]
+    fn given_a_protected_secret_when_overwriting_it_then_the_panic_masks_the_value() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_redaction_policy(RedactionPolicy::new().secret("*_TOKEN"));
+        fake_env.protect("API_TOKEN");
+
+        // Act
+        fake_env.set_var("API_TOKEN", "hunter2");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot remove protected environment variable \"PATH\"")]
+    fn given_a_protected_key_when_removing_it_then_it_panics() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("PATH", "/usr/bin");
+        fake_env.protect("PATH");
+
+        // Act
+        fake_env.remove_var("PATH");
+    }
+
+    #[test]
+    fn given_a_protected_key_when_using_the_fallible_setters_then_they_return_an_error() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("HOME", "/home/test");
+        fake_env.protect("HOME");
+
+        // Act
+        let set = fake_env.try_set_var("HOME", "/root");
+        let removed = fake_env.try_remove_var("HOME");
+
+        // Assert
+        assert_eq!(set, Err(EnvError::ReadOnly("HOME".into())));
+        assert_eq!(removed, Err(EnvError::ReadOnly("HOME".into())));
+        assert_eq!(fake_env.var("HOME").unwrap(), "/home/test");
+        assert_eq!(fake_env.generation(), 1);
+    }
+
+    #[test]
+    fn given_protected_keys_when_changing_other_or_unprotected_keys_then_they_change() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("HOME", "/home/test");
+        fake_env.protect("HOME").protect("PATH");
+
+        // Act
+        fake_env.set_var("LOG_LEVEL", "debug");
+        fake_env.try_set_var("MODE", "fast").unwrap();
+        fake_env.unprotect("HOME");
+        fake_env.set_var("HOME", "/root");
+        fake_env.try_remove_var("LOG_LEVEL").unwrap();
+
+        // Assert
+        assert_eq!(fake_env.var("HOME").unwrap(), "/root");
+        assert_eq!(fake_env.var("MODE").unwrap(), "fast");
+        assert!(fake_env.var_os("LOG_LEVEL").is_none());
+        assert!(!fake_env.is_protected("HOME"));
+        assert!(fake_env.is_protected("PATH"));
+    }
+
+    #[test]
+    #[should_panic(expected = "environment variable \"HOME\" is protected")]
+    fn given_a_protected_key_that_is_set_when_clearing_then_it_panics() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("HOME", "/home/test");
+        fake_env.protect("HOME");
+
+        // Act
+        fake_env.clear();
+    }
+
+    #[test]
+    fn given_a_missing_read_when_the_key_is_set_and_read_again_then_it_stays_listed() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new_tracking();
+        let _ = fake_env.var("LOG_LEVEL");
+
+        // Act
+        fake_env.set_var("LOG_LEVEL", "debug");
+        let value = fake_env.var("LOG_LEVEL");
+        fake_env.remove_var("LOG_LEVEL");
+        let _ = fake_env.var("LOG_LEVEL");
+
+        // Assert
+        assert_eq!(value.unwrap(), "debug");
+        assert_eq!(fake_env.missing_reads(), ["LOG_LEVEL"]);
+        assert_eq!(fake_env.verify_all_read(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`verify_all_read` needs a fake environment that tracks reads")]
+    fn given_no_tracking_when_verifying_reads_then_it_panics() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.allow_unread("IGNORED");
+
+        // Act
+        let _ = fake_env.verify_all_read();
+    }
+
+    #[test]
+    fn given_a_key_containing_an_equals_sign_when_set_then_it_is_not_stored() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+
+        // Act
+        fake_env.set_var("KEY=SUFFIX", "value");
+
+        // Assert
+        assert_eq!(fake_env.var("KEY=SUFFIX"), Err(VarError::NotPresent));
+        assert_eq!(fake_env.var_os("KEY=SUFFIX"), None);
+        assert!(fake_env.vars_os().is_empty());
+        assert_eq!(fake_env.generation(), 0);
+    }
+
+    #[test]
+    fn given_strict_reads_when_reading_a_malformed_key_then_it_is_not_present_without_panicking() {
+        // Arrange
+        let fake_env = FakeEnvironment::new_strict_reads();
+
+        // Act/Assert
+        assert!(fake_env.var_os("").is_none());
+        assert!(fake_env.var("A=B").is_err());
     }
 }
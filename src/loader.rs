@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::{error::Error, fmt};
+
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "serde")]
+use crate::{from_env, DeError, Environment, FakeEnvironment};
+use crate::{
+    EnumerableEnvironment, EnvSchema, Problem, RedactionPolicy, StripPrefixView, ValidationReport,
+    VarKind,
+};
+
+/// Loads an application's configuration from the variables under a prefix,
+/// in one call: it checks them against the declared variables, applies
+/// defaults, and returns either every value or a report of every problem.
+///
+/// Variables are declared by their names without the prefix, as they appear
+/// in the loaded map and, with [`load`](ConfigLoader::load), as the
+/// upper-cased names of the struct's fields. Problems name them with the
+/// prefix, as they are set.
+///
+/// Variables under the prefix that are not declared are loaded as they are,
+/// unless [`deny_unknown`](ConfigLoader::deny_unknown) is set, in which case
+/// they are reported, with a suggestion if they look like a typo.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{ConfigLoader, Environment, FakeEnvironment, VarKind};
+/// let loader = ConfigLoader::new("MYAPP_")
+///     .required("PORT", VarKind::Int)
+///     .with_default("LOG_LEVEL", VarKind::one_of(["debug", "info"]), "info");
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_PORT", "8080");
+/// fake_env.set_var("MYAPP_REGION", "eu-west-1");
+///
+/// let config = loader.load_map(&fake_env).unwrap();
+///
+/// assert_eq!(config["PORT"], "8080");
+/// assert_eq!(config["LOG_LEVEL"], "info");
+/// assert_eq!(config["REGION"], "eu-west-1");
+///
+/// fake_env.set_var("MYAPP_PORT", "eighty");
+/// let report = loader.load_map(&fake_env).unwrap_err();
+///
+/// assert_eq!(
+///     report.to_string(),
+///     "environment variable \"MYAPP_PORT\" is invalid: expected an integer, found \"eighty\""
+/// );
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigLoader {
+    prefix: String,
+    schema: EnvSchema,
+}
+
+impl ConfigLoader {
+    /// A loader for the variables whose names start with `prefix`, such as
+    /// `MYAPP_`, declaring none of them.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        ConfigLoader {
+            prefix: prefix.into(),
+            schema: EnvSchema::new(),
+        }
+    }
+
+    /// Declare a variable that must be set to a value of `kind`.
+    pub fn required(mut self, name: &str, kind: VarKind) -> Self {
+        let name = self.prefixed(name);
+        self.schema = self.schema.required(name, kind);
+        self
+    }
+
+    /// Declare a variable that may be set to a value of `kind`.
+    pub fn optional(mut self, name: &str, kind: VarKind) -> Self {
+        let name = self.prefixed(name);
+        self.schema = self.schema.optional(name, kind);
+        self
+    }
+
+    /// Declare a variable that may be set to a value of `kind`, and is
+    /// `default` otherwise.
+    pub fn with_default(mut self, name: &str, kind: VarKind, default: impl Into<String>) -> Self {
+        let name = self.prefixed(name);
+        self.schema = self.schema.with_default(name, kind, default);
+        self
+    }
+
+    /// Report variables under the prefix that are not declared, instead of
+    /// loading them.
+    pub fn deny_unknown(mut self) -> Self {
+        self.schema = self.schema.prefix(self.prefix.clone());
+        self
+    }
+
+    /// Mask the values `policy` covers in the problems reported. Patterns
+    /// match the names with the prefix.
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.schema = self.schema.redaction_policy(policy);
+        self
+    }
+
+    /// The schema the variables are checked against, with their names
+    /// prefixed, such as to render an example dotenv file of them.
+    pub fn schema(&self) -> &EnvSchema {
+        &self.schema
+    }
+
+    /// Load every variable under the prefix, with the prefix removed from
+    /// their names, and the defaults of declared variables that are not set.
+    ///
+    /// # Errors
+    /// If any variable is missing, of the wrong kind, or not valid Unicode,
+    /// or, with [`deny_unknown`](ConfigLoader::deny_unknown), not declared,
+    /// it returns a report of every such problem.
+    pub fn load_map(
+        &self,
+        env: &impl EnumerableEnvironment,
+    ) -> Result<BTreeMap<String, String>, ValidationReport> {
+        let mut report = self.schema.validate(env);
+        let mut vars: BTreeMap<String, String> = self
+            .schema
+            .defaults()
+            .map(|(name, default)| (self.unprefixed(name).into(), default.into()))
+            .collect();
+        let mut not_unicode = Vec::new();
+        for (key, value) in StripPrefixView::new(env, &self.prefix).vars_os() {
+            match (key.into_string(), value.into_string()) {
+                (Ok(key), Ok(value)) => {
+                    vars.insert(key, value);
+                }
+                (key, _) => {
+                    let key = key.unwrap_or_else(|key| key.to_string_lossy().into_owned());
+                    not_unicode.push(self.prefixed(&key));
+                }
+            }
+        }
+        not_unicode.sort();
+        for name in not_unicode {
+            // Declared and unknown variables are already reported.
+            if !report
+                .problems()
+                .iter()
+                .any(|problem| names(problem, &name))
+            {
+                report.push(Problem::NotUnicode { name });
+            }
+        }
+
+        if report.is_valid() {
+            Ok(vars)
+        } else {
+            Err(report)
+        }
+    }
+
+    /// Load the variables as by [`load_map`](ConfigLoader::load_map), then
+    /// deserialize them into `T` as by [`from_env`](crate::from_env), each
+    /// field from the variable named after it without the prefix. Requires
+    /// the `serde` feature.
+    ///
+    /// # Errors
+    /// If the variables do not match the declarations, it returns
+    /// [`LoadError::Invalid`] with every problem found. Otherwise, if a
+    /// field cannot be deserialized, such as one whose variable is not
+    /// declared and not set, it returns [`LoadError::Deserialize`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{ConfigLoader, Environment, FakeEnvironment, LoadError, VarKind};
+    /// #[derive(Debug, serde::Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    ///     database_url: String,
+    ///     features: Vec<String>,
+    ///     request_timeout_secs: u64,
+    /// }
+    ///
+    /// fn loader() -> ConfigLoader {
+    ///     ConfigLoader::new("MYAPP_")
+    ///         .required("PORT", VarKind::Int)
+    ///         .required("DATABASE_URL", VarKind::String)
+    ///         .with_default("FEATURES", VarKind::String, "")
+    ///         .with_default("REQUEST_TIMEOUT_SECS", VarKind::Int, "30")
+    ///         .deny_unknown()
+    /// }
+    ///
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("MYAPP_PORT", "8080");
+    /// fake_env.set_var("MYAPP_DATABASE_URL", "postgres://db/myapp");
+    /// fake_env.set_var("MYAPP_FEATURES", "search,billing");
+    ///
+    /// let config: Config = loader().load(&fake_env).unwrap();
+    ///
+    /// assert_eq!(config.port, 8080);
+    /// assert_eq!(config.features, ["search", "billing"]);
+    /// assert_eq!(config.request_timeout_secs, 30);
+    ///
+    /// fake_env.remove_var("MYAPP_DATABASE_URL");
+    /// fake_env.set_var("MYAPP_FEATUERS", "search");
+    ///
+    /// let Err(LoadError::Invalid(report)) = loader().load::<Config>(&fake_env) else {
+    ///     panic!("expected the problems to be reported");
+    /// };
+    /// assert_eq!(
+    ///     report.to_string(),
+    ///     "environment variable \"MYAPP_DATABASE_URL\" is required but not set\n\
+    ///      environment variable \"MYAPP_FEATUERS\" is not recognized; did you mean \
+    ///      \"MYAPP_FEATURES\"?"
+    /// );
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        env: &impl EnumerableEnvironment,
+    ) -> Result<T, LoadError> {
+        let vars = self.load_map(env).map_err(LoadError::Invalid)?;
+        let mut loaded = FakeEnvironment::new();
+        for (key, value) in vars {
+            loaded.set_var(key, value);
+        }
+        from_env(&loaded).map_err(|error| LoadError::Deserialize(error.prefixed(&self.prefix)))
+    }
+
+    fn prefixed(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    fn unprefixed<'a>(&self, name: &'a str) -> &'a str {
+        name.strip_prefix(self.prefix.as_str()).unwrap_or(name)
+    }
+}
+
+/// Whether `problem` is with the variable named `name`.
+fn names(problem: &Problem, name: &str) -> bool {
+    match problem {
+        Problem::Missing { name: named }
+        | Problem::NotUnicode { name: named }
+        | Problem::Unavailable { name: named, .. }
+        | Problem::WrongType { name: named, .. }
+        | Problem::Unknown { name: named, .. } => named == name,
+    }
+}
+
+/// An error from [`ConfigLoader::load`](ConfigLoader::load). Requires the
+/// `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// The variables do not match the declarations.
+    Invalid(ValidationReport),
+    /// The variables match the declarations, but could not be deserialized.
+    Deserialize(DeError),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Invalid(report) => write!(f, "{report}"),
+            LoadError::Deserialize(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for LoadError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::ConfigLoader;
+    use crate::{test_helpers::fake_env, Environment, Problem, VarKind};
+    #[cfg(feature = "serde")]
+    use crate::{DeError, LoadError};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn service_loader() -> ConfigLoader {
+        ConfigLoader::new("MYAPP_")
+            .required("PORT", VarKind::Int)
+            .required("DATABASE_URL", VarKind::String)
+            .with_default("ENABLE_SEARCH", VarKind::Bool, "false")
+            .with_default("REQUEST_TIMEOUT", VarKind::Duration, "30s")
+            .optional("SHUTDOWN_TIMEOUT", VarKind::Duration)
+    }
+
+    #[test]
+    fn given_a_valid_service_config_when_loading_a_map_then_it_holds_values_and_defaults() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_PORT", "8080"),
+            ("MYAPP_DATABASE_URL", "postgres://db/myapp"),
+            ("MYAPP_ENABLE_SEARCH", "true"),
+            ("MYAPP_REGION", "eu-west-1"),
+            ("HOME", "/home/user"),
+        ]);
+
+        // Act
+        let config = service_loader().load_map(&env).unwrap();
+
+        // Assert
+        assert_eq!(
+            config.into_iter().collect::<Vec<_>>(),
+            [
+                ("DATABASE_URL".into(), "postgres://db/myapp".into()),
+                ("ENABLE_SEARCH".into(), "true".into()),
+                ("PORT".into(), "8080".into()),
+                ("REGION".into(), "eu-west-1".into()),
+                ("REQUEST_TIMEOUT".into(), "30s".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_several_problems_when_loading_then_every_one_is_reported() {
+        // Arrange
+        let mut env = fake_env(&[
+            ("MYAPP_PORT", "eighty"),
+            ("MYAPP_REQUEST_TIMOUT", "5s"),
+            ("MYAPP_SHUTDOWN_TIMEOUT", "soon"),
+        ]);
+        env.set_var("MYAPP_ENABLE_SEARCH", OsStr::from_bytes(&INVALID_UTF8));
+        env.set_var("MYAPP_NOTES", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let report = service_loader().deny_unknown().load_map(&env).unwrap_err();
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [
+                Problem::WrongType {
+                    name: "MYAPP_PORT".into(),
+                    value: "eighty".into(),
+                    expected: VarKind::Int,
+                },
+                Problem::Missing {
+                    name: "MYAPP_DATABASE_URL".into(),
+                },
+                Problem::NotUnicode {
+                    name: "MYAPP_ENABLE_SEARCH".into(),
+                },
+                Problem::WrongType {
+                    name: "MYAPP_SHUTDOWN_TIMEOUT".into(),
+                    value: "soon".into(),
+                    expected: VarKind::Duration,
+                },
+                Problem::Unknown {
+                    name: "MYAPP_NOTES".into(),
+                    suggestion: None,
+                },
+                Problem::Unknown {
+                    name: "MYAPP_REQUEST_TIMOUT".into(),
+                    suggestion: Some("MYAPP_REQUEST_TIMEOUT".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn given_an_undeclared_non_unicode_value_when_loading_then_it_is_reported() {
+        // Arrange
+        let mut env = fake_env(&[("MYAPP_PORT", "8080"), ("MYAPP_DATABASE_URL", "db")]);
+        env.set_var("MYAPP_NOTES", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let report = service_loader().load_map(&env).unwrap_err();
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [Problem::NotUnicode {
+                name: "MYAPP_NOTES".into(),
+            }]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct ServiceConfig {
+        port: u16,
+        database_url: String,
+        enable_search: bool,
+        features: Vec<String>,
+        request_timeout_secs: u64,
+    }
+
+    #[cfg(feature = "serde")]
+    fn typed_loader() -> ConfigLoader {
+        ConfigLoader::new("MYAPP_")
+            .required("PORT", VarKind::Int)
+            .required("DATABASE_URL", VarKind::String)
+            .with_default("ENABLE_SEARCH", VarKind::Bool, "false")
+            .with_default("FEATURES", VarKind::String, "")
+            .with_default("REQUEST_TIMEOUT_SECS", VarKind::Int, "30")
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn given_a_valid_service_config_when_loading_a_struct_then_fields_are_typed() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_PORT", "8080"),
+            ("MYAPP_DATABASE_URL", "postgres://db/myapp"),
+            ("MYAPP_FEATURES", "search, billing"),
+        ]);
+
+        // Act
+        let config: ServiceConfig = typed_loader().load(&env).unwrap();
+
+        // Assert
+        assert_eq!(
+            config,
+            ServiceConfig {
+                port: 8080,
+                database_url: "postgres://db/myapp".into(),
+                enable_search: false,
+                features: vec!["search".into(), "billing".into()],
+                request_timeout_secs: 30,
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn given_an_undeclared_field_that_is_unset_when_loading_a_struct_then_it_names_the_variable() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_PORT", "8080"), ("MYAPP_ENABLE_SEARCH", "true")]);
+        let loader = ConfigLoader::new("MYAPP_").required("PORT", VarKind::Int);
+
+        // Act
+        let error = loader.load::<ServiceConfig>(&env).unwrap_err();
+
+        // Assert
+        assert_eq!(
+            error,
+            LoadError::Deserialize(DeError::Missing("MYAPP_DATABASE_URL".into()))
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::ReadEnvironment;
+
+/// A category of the locale, each with its own `LC_*` variable.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum LocaleCategory {
+    /// Character classification and case conversion, from `LC_CTYPE`.
+    Ctype,
+    /// Number formatting, from `LC_NUMERIC`.
+    Numeric,
+    /// Date and time formatting, from `LC_TIME`.
+    Time,
+    /// String collation, from `LC_COLLATE`.
+    Collate,
+    /// Currency formatting, from `LC_MONETARY`.
+    Monetary,
+    /// The language of messages, from `LC_MESSAGES`.
+    Messages,
+}
+
+impl LocaleCategory {
+    /// The name of the category's variable, such as `LC_NUMERIC`.
+    pub fn variable(self) -> &'static str {
+        match self {
+            LocaleCategory::Ctype => "LC_CTYPE",
+            LocaleCategory::Numeric => "LC_NUMERIC",
+            LocaleCategory::Time => "LC_TIME",
+            LocaleCategory::Collate => "LC_COLLATE",
+            LocaleCategory::Monetary => "LC_MONETARY",
+            LocaleCategory::Messages => "LC_MESSAGES",
+        }
+    }
+}
+
+/// The locale `env` selects for `category`, following POSIX: `LC_ALL`
+/// overrides the category's own variable, such as `LC_NUMERIC`, which
+/// overrides `LANG`.
+///
+/// Variables that are empty or not valid Unicode count as unset. `POSIX` is
+/// returned as `C`, its equivalent. Returns `None` if none of the variables
+/// is set, in which case programs conventionally use `C`.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{resolve_locale, Environment, FakeEnvironment, LocaleCategory};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("LANG", "de_DE.UTF-8");
+/// fake_env.set_var("LC_NUMERIC", "POSIX");
+///
+/// assert_eq!(
+///     resolve_locale(&fake_env, LocaleCategory::Numeric).as_deref(),
+///     Some("C")
+/// );
+/// assert_eq!(
+///     resolve_locale(&fake_env, LocaleCategory::Time).as_deref(),
+///     Some("de_DE.UTF-8")
+/// );
+/// ```
+pub fn resolve_locale(env: &impl ReadEnvironment, category: LocaleCategory) -> Option<String> {
+    ["LC_ALL", category.variable(), "LANG"]
+        .into_iter()
+        .filter_map(|name| env.var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| if value == "POSIX" { "C".into() } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{resolve_locale, LocaleCategory};
+    use crate::{test_helpers::fake_env, Environment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_lc_all_when_resolving_then_it_overrides_every_other_variable() {
+        // Arrange
+        let env = fake_env(&[
+            ("LC_ALL", "fr_FR.UTF-8"),
+            ("LC_NUMERIC", "de_DE.UTF-8"),
+            ("LANG", "en_US.UTF-8"),
+        ]);
+
+        // Act/Assert
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Numeric).as_deref(),
+            Some("fr_FR.UTF-8")
+        );
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Messages).as_deref(),
+            Some("fr_FR.UTF-8")
+        );
+    }
+
+    #[test]
+    fn given_a_category_variable_when_resolving_then_it_overrides_lang_for_that_category_only() {
+        // Arrange
+        let env = fake_env(&[("LC_NUMERIC", "de_DE.UTF-8"), ("LANG", "en_US.UTF-8")]);
+
+        // Act/Assert
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Numeric).as_deref(),
+            Some("de_DE.UTF-8")
+        );
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Time).as_deref(),
+            Some("en_US.UTF-8")
+        );
+    }
+
+    #[test]
+    fn given_only_lang_when_resolving_then_every_category_uses_it() {
+        // Arrange
+        let env = fake_env(&[("LANG", "POSIX")]);
+
+        // Act/Assert
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Collate).as_deref(),
+            Some("C")
+        );
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Ctype).as_deref(),
+            Some("C")
+        );
+    }
+
+    #[test]
+    fn given_empty_or_non_unicode_values_when_resolving_then_they_count_as_unset() {
+        // Arrange
+        let mut env = fake_env(&[("LC_ALL", ""), ("LC_MONETARY", ""), ("LANG", "C")]);
+        env.set_var("LC_TIME", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act/Assert
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Monetary).as_deref(),
+            Some("C")
+        );
+        assert_eq!(
+            resolve_locale(&env, LocaleCategory::Time).as_deref(),
+            Some("C")
+        );
+    }
+
+    #[test]
+    fn given_an_unset_chain_when_resolving_then_there_is_no_locale() {
+        // Arrange
+        let env = fake_env(&[("LC_ALL", ""), ("LANGUAGE", "fr")]);
+
+        // Act/Assert
+        assert_eq!(resolve_locale(&env, LocaleCategory::Messages), None);
+    }
+}
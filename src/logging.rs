@@ -0,0 +1,293 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use log::{debug, info, warn};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, RedactionPolicy,
+    SourcedEnvironment,
+};
+
+const DEFAULT_TARGET: &str = "env_wrapper";
+
+/// A wrapper that logs every read, write, and removal through the
+/// [`log`](https://docs.rs/log) crate. Requires the `log` feature.
+///
+/// * Reads of variables that are not set are logged at `warn`.
+/// * Other reads are logged at `debug`.
+/// * Writes and removals are logged at `info`.
+///
+/// Only keys are logged unless values are enabled with
+/// [`record_values`](LoggingEnvironment::record_values). Records use the
+/// `env_wrapper` target unless configured otherwise. Listing variables is
+/// not logged.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{FakeEnvironment, LoggingEnvironment, ReadEnvironment};
+/// let env = LoggingEnvironment::new(FakeEnvironment::new()).target("myapp::config");
+///
+/// // Logs `environment variable "HOME" is not set` at warn.
+/// let _ = env.var("HOME");
+/// ```
+#[derive(Clone, Debug)]
+pub struct LoggingEnvironment<E> {
+    inner: E,
+    target: String,
+    record_values: bool,
+    redaction_policy: RedactionPolicy,
+}
+
+impl<E: ReadEnvironment> LoggingEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        LoggingEnvironment {
+            inner,
+            target: DEFAULT_TARGET.into(),
+            record_values: false,
+            redaction_policy: RedactionPolicy::new(),
+        }
+    }
+
+    /// Log with `target` instead of `env_wrapper`, for filtering.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = target.into();
+        self
+    }
+
+    /// Whether to include values in records. Off by default, since values
+    /// often hold secrets.
+    pub fn record_values(mut self, record_values: bool) -> Self {
+        self.record_values = record_values;
+        self
+    }
+
+    /// When values are included, mask those `policy` covers.
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = policy;
+        self
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn log_get(&self, key: &OsStr, value: Option<&OsStr>) {
+        match value {
+            None => warn!(target: &self.target, "environment variable {key:?} is not set"),
+            Some(value) if self.record_values => {
+                let value = self.redaction_policy.redact(key, value);
+                debug!(target: &self.target, "read environment variable {key:?} = {value:?}")
+            }
+            Some(_) => debug!(target: &self.target, "read environment variable {key:?}"),
+        }
+    }
+
+    fn log_set(&self, key: &OsStr, value: &OsStr) {
+        if self.record_values {
+            let value = self.redaction_policy.redact(key, value);
+            info!(target: &self.target, "set environment variable {key:?} to {value:?}")
+        } else {
+            info!(target: &self.target, "set environment variable {key:?}")
+        }
+    }
+
+    fn log_remove(&self, key: &OsStr) {
+        info!(target: &self.target, "removed environment variable {key:?}")
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for LoggingEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        let result = self.inner.var(&key);
+        match &result {
+            Ok(value) => self.log_get(key.as_ref(), Some(value.as_ref())),
+            Err(VarError::NotUnicode(value)) => self.log_get(key.as_ref(), Some(value)),
+            Err(VarError::NotPresent) => self.log_get(key.as_ref(), None),
+        }
+        result
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key);
+        self.log_get(key.as_ref(), value.as_deref());
+        value
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for LoggingEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let result = self.inner.var_with_source(&key);
+        match &result {
+            Ok((value, _)) => self.log_get(key.as_ref(), Some(value.as_ref())),
+            Err(VarError::NotUnicode(value)) => self.log_get(key.as_ref(), Some(value)),
+            Err(VarError::NotPresent) => self.log_get(key.as_ref(), None),
+        }
+        result
+    }
+}
+
+impl<E: Environment> Environment for LoggingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.log_set(key.as_ref(), value.as_ref());
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.log_remove(key.as_ref());
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.log_set(key.as_ref(), value.as_ref());
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.log_remove(key.as_ref());
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for LoggingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, Once};
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+
+    use super::LoggingEnvironment;
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    /// Captures every record. The logger is global, so each test logs with
+    /// its own target and only looks at its own records.
+    struct CapturingLogger {
+        records: Mutex<Vec<(String, Level, String)>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            self.records.lock().unwrap().push((
+                record.target().into(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger {
+        records: Mutex::new(Vec::new()),
+    };
+
+    fn init_logger() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(LevelFilter::Trace);
+        });
+    }
+
+    fn records_for(target: &str) -> Vec<(Level, String)> {
+        LOGGER
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(record_target, _, _)| record_target == target)
+            .map(|(_, level, message)| (*level, message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn given_set_and_unset_keys_when_reading_then_hits_are_debug_and_misses_are_warnings() {
+        // Arrange
+        init_logger();
+        let target = "logging_tests::reads";
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("HOME", "/home/me");
+        let env = LoggingEnvironment::new(inner).target(target);
+
+        // Act
+        let _ = env.var("HOME");
+        let _ = env.var_os("MISSING");
+
+        // Assert
+        assert_eq!(
+            records_for(target),
+            vec![
+                (Level::Debug, "read environment variable \"HOME\"".into()),
+                (
+                    Level::Warn,
+                    "environment variable \"MISSING\" is not set".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn when_writing_and_removing_then_they_are_logged_at_info_without_values() {
+        // Arrange
+        init_logger();
+        let target = "logging_tests::writes";
+        let mut env = LoggingEnvironment::new(FakeEnvironment::new()).target(target);
+
+        // Act
+        env.set_var("TOKEN", "secret");
+        env.remove_var("TOKEN");
+
+        // Assert
+        assert_eq!(
+            records_for(target),
+            vec![
+                (Level::Info, "set environment variable \"TOKEN\"".into()),
+                (Level::Info, "removed environment variable \"TOKEN\"".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_values_recorded_when_writing_and_reading_then_values_are_logged() {
+        // Arrange
+        init_logger();
+        let target = "logging_tests::values";
+        let mut env = LoggingEnvironment::new(FakeEnvironment::new())
+            .target(target)
+            .record_values(true);
+
+        // Act
+        env.set_var("MODE", "fast");
+        let _ = env.var("MODE");
+
+        // Assert
+        assert_eq!(
+            records_for(target),
+            vec![
+                (
+                    Level::Info,
+                    "set environment variable \"MODE\" to \"fast\"".into()
+                ),
+                (
+                    Level::Debug,
+                    "read environment variable \"MODE\" = \"fast\"".into()
+                ),
+            ]
+        );
+    }
+}
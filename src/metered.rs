@@ -0,0 +1,293 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use metrics::{counter, histogram};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+const DEFAULT_NAME_PREFIX: &str = "env_wrapper";
+const PREFIX_LABEL: &str = "prefix";
+
+/// A wrapper that reports every read, write, and removal to the
+/// [`metrics`](https://docs.rs/metrics) facade, for dashboards and alerts on
+/// how a service reads its configuration, such as variables missing after a
+/// deployment. Requires the `metrics` feature.
+///
+/// With the default names, it emits:
+/// * `env_wrapper_gets_total`, a counter of reads, whether or not the
+///   variable was set,
+/// * `env_wrapper_misses_total`, a counter of reads of variables that were
+///   not set,
+/// * `env_wrapper_value_bytes`, a histogram of the length of the values
+///   read,
+/// * `env_wrapper_sets_total` and `env_wrapper_removals_total`, counters of
+///   writes and removals.
+///
+/// Each has a `prefix` label holding the part of the key before its first
+/// `_`, or the whole key if it has none, so `MYAPP_PORT` counts towards
+/// `MYAPP`. Values are never recorded, only their length. Listing variables
+/// is not reported.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{FakeEnvironment, MeteredEnvironment, ReadEnvironment};
+/// let env = MeteredEnvironment::new(FakeEnvironment::new()).name_prefix("myapp_env");
+///
+/// // Increments `myapp_env_gets_total` and `myapp_env_misses_total`, with
+/// // `prefix` = "DATABASE".
+/// let _ = env.var("DATABASE_URL");
+/// ```
+#[derive(Clone, Debug)]
+pub struct MeteredEnvironment<E> {
+    inner: E,
+    names: MetricNames,
+    prefix_separator: char,
+}
+
+#[derive(Clone, Debug)]
+struct MetricNames {
+    gets: String,
+    misses: String,
+    value_bytes: String,
+    sets: String,
+    removals: String,
+}
+
+impl MetricNames {
+    fn new(prefix: &str) -> Self {
+        MetricNames {
+            gets: format!("{prefix}_gets_total"),
+            misses: format!("{prefix}_misses_total"),
+            value_bytes: format!("{prefix}_value_bytes"),
+            sets: format!("{prefix}_sets_total"),
+            removals: format!("{prefix}_removals_total"),
+        }
+    }
+}
+
+impl<E: ReadEnvironment> MeteredEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        MeteredEnvironment {
+            inner,
+            names: MetricNames::new(DEFAULT_NAME_PREFIX),
+            prefix_separator: '_',
+        }
+    }
+
+    /// Start metric names with `prefix` instead of `env_wrapper`, such as
+    /// `myapp_env` for `myapp_env_gets_total`.
+    pub fn name_prefix(mut self, prefix: &str) -> Self {
+        self.names = MetricNames::new(prefix);
+        self
+    }
+
+    /// End the `prefix` label at the first `separator` in the key instead
+    /// of the first `_`.
+    pub fn prefix_separator(mut self, separator: char) -> Self {
+        self.prefix_separator = separator;
+        self
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn key_prefix(&self, key: &OsStr) -> String {
+        let key = key.to_string_lossy();
+        match key.split_once(self.prefix_separator) {
+            Some((prefix, _)) => prefix.into(),
+            None => key.into_owned(),
+        }
+    }
+
+    fn record_get(&self, key: &OsStr, value_len: Option<usize>) {
+        let prefix = self.key_prefix(key);
+        match value_len {
+            Some(len) => {
+                histogram!(self.names.value_bytes.clone(), PREFIX_LABEL => prefix.clone())
+                    .record(len as f64);
+            }
+            None => {
+                counter!(self.names.misses.clone(), PREFIX_LABEL => prefix.clone()).increment(1)
+            }
+        }
+        counter!(self.names.gets.clone(), PREFIX_LABEL => prefix).increment(1);
+    }
+
+    fn record_set(&self, key: &OsStr) {
+        counter!(self.names.sets.clone(), PREFIX_LABEL => self.key_prefix(key)).increment(1);
+    }
+
+    fn record_remove(&self, key: &OsStr) {
+        counter!(self.names.removals.clone(), PREFIX_LABEL => self.key_prefix(key)).increment(1);
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for MeteredEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        let result = self.inner.var(&key);
+        let value_len = match &result {
+            Ok(value) => Some(value.len()),
+            Err(VarError::NotUnicode(value)) => Some(value.len()),
+            Err(VarError::NotPresent) => None,
+        };
+        self.record_get(key.as_ref(), value_len);
+        result
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key);
+        self.record_get(key.as_ref(), value.as_ref().map(|value| value.len()));
+        value
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for MeteredEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let result = self.inner.var_with_source(&key);
+        let value_len = match &result {
+            Ok((value, _)) => Some(value.len()),
+            Err(VarError::NotUnicode(value)) => Some(value.len()),
+            Err(VarError::NotPresent) => None,
+        };
+        self.record_get(key.as_ref(), value_len);
+        result
+    }
+}
+
+impl<E: Environment> Environment for MeteredEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.record_set(key.as_ref());
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.record_remove(key.as_ref());
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.record_set(key.as_ref());
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.record_remove(key.as_ref());
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for MeteredEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::MeteredEnvironment;
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    /// Every metric recorded while running `f`, by name and `prefix` label,
+    /// with counters as their count and histograms as their samples.
+    fn record(f: impl FnOnce()) -> BTreeMap<(String, String), String> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, f);
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                let value = match value {
+                    DebugValue::Counter(count) => count.to_string(),
+                    DebugValue::Histogram(samples) => format!("{samples:?}"),
+                    DebugValue::Gauge(gauge) => format!("gauge {gauge}"),
+                };
+                ((key.name().to_string(), labels.join(",")), value)
+            })
+            .collect()
+    }
+
+    fn metric(name: &str, prefix: &str, value: &str) -> ((String, String), String) {
+        ((name.into(), format!("prefix={prefix}")), value.into())
+    }
+
+    #[test]
+    fn given_a_scripted_startup_when_metering_it_then_counters_are_labeled_by_key_prefix() {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("MYAPP_PORT", "8080");
+        fake_env.set_var("MYAPP_DATABASE_URL", "postgres://user:hunter2@db");
+        let mut env = MeteredEnvironment::new(fake_env);
+
+        // Act
+        let metrics = record(|| {
+            let _ = env.var("MYAPP_PORT");
+            let _ = env.var_os("MYAPP_DATABASE_URL");
+            let _ = env.var("MYAPP_LOG_LEVEL");
+            assert!(!env.contains("HOME"));
+            env.set_var("MYAPP_MODE", "fast");
+            env.try_set_var("OTHER_FLAG", "1").unwrap();
+            env.remove_var("MYAPP_MODE");
+        });
+
+        // Assert
+        assert_eq!(
+            metrics,
+            BTreeMap::from([
+                metric("env_wrapper_gets_total", "HOME", "1"),
+                metric("env_wrapper_gets_total", "MYAPP", "3"),
+                metric("env_wrapper_misses_total", "HOME", "1"),
+                metric("env_wrapper_misses_total", "MYAPP", "1"),
+                metric("env_wrapper_removals_total", "MYAPP", "1"),
+                metric("env_wrapper_sets_total", "MYAPP", "1"),
+                metric("env_wrapper_sets_total", "OTHER", "1"),
+                metric("env_wrapper_value_bytes", "MYAPP", "[4.0, 26.0]"),
+            ])
+        );
+    }
+
+    #[test]
+    fn given_custom_names_and_separator_when_metering_then_they_are_used() {
+        // Arrange
+        let mut env = MeteredEnvironment::new(FakeEnvironment::new())
+            .name_prefix("myapp_env")
+            .prefix_separator('.');
+
+        // Act
+        let metrics = record(|| {
+            env.set_var("db.url", "postgres://user:hunter2@db");
+            let _ = env.var("db.url");
+        });
+
+        // Assert
+        assert_eq!(
+            metrics.keys().collect::<Vec<_>>(),
+            [
+                &("myapp_env_gets_total".into(), "prefix=db".into()),
+                &("myapp_env_sets_total".into(), "prefix=db".into()),
+                &("myapp_env_value_bytes".into(), "prefix=db".into()),
+            ]
+        );
+        assert!(metrics.values().all(|value| !value.contains("hunter2")));
+    }
+}
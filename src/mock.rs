@@ -0,0 +1,356 @@
+use std::{
+    cell::RefCell,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{Environment, ReadEnvironment};
+
+/// What a [`MockEnvironment`](MockEnvironment) does when it gets a call no
+/// expectation allows.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnexpectedCalls {
+    /// Panic at the call.
+    #[default]
+    Panic,
+    /// Record the call, treat it as a read of an unset variable or a write
+    /// that does nothing, and panic in [`verify`](MockEnvironment::verify).
+    Record,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CallKind {
+    Var,
+    Set,
+    Remove,
+}
+
+/// An expected call on a [`MockEnvironment`](MockEnvironment), returned by
+/// its `expect_` methods for configuring it further.
+///
+/// By default, an expectation must be met at least once.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    kind: CallKind,
+    key: OsString,
+    value: Option<OsString>,
+    returning: Option<OsString>,
+    min_calls: usize,
+    max_calls: Option<usize>,
+    calls: usize,
+}
+
+impl Expectation {
+    fn new(kind: CallKind, key: &OsStr) -> Self {
+        Expectation {
+            kind,
+            key: key.into(),
+            value: None,
+            returning: None,
+            min_calls: 1,
+            max_calls: None,
+            calls: 0,
+        }
+    }
+
+    /// Expect exactly `times` calls.
+    pub fn times(&mut self, times: usize) -> &mut Self {
+        self.min_calls = times;
+        self.max_calls = Some(times);
+        self
+    }
+
+    /// For reads, the value to return. Without one, the variable reads as
+    /// not set.
+    pub fn returning(&mut self, value: impl AsRef<OsStr>) -> &mut Self {
+        self.returning = Some(value.as_ref().into());
+        self
+    }
+
+    /// For writes, only expect writes of `value`. Without one, any value is
+    /// expected.
+    pub fn with_value(&mut self, value: impl AsRef<OsStr>) -> &mut Self {
+        self.value = Some(value.as_ref().into());
+        self
+    }
+
+    fn matches(&self, kind: CallKind, key: &OsStr, value: Option<&OsStr>) -> bool {
+        self.kind == kind
+            && self.key == key
+            && (self.value.is_none() || self.value.as_deref() == value)
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.max_calls
+            .is_some_and(|max_calls| self.calls >= max_calls)
+    }
+
+    fn describe_times(&self) -> String {
+        match self.max_calls {
+            Some(max_calls) if max_calls == self.min_calls => format!("exactly {max_calls}"),
+            _ => format!("at least {}", self.min_calls),
+        }
+    }
+}
+
+fn describe_call(kind: CallKind, key: &OsStr, value: Option<&OsStr>) -> String {
+    match (kind, value) {
+        (CallKind::Var, _) => format!("var({key:?})"),
+        (CallKind::Set, Some(value)) => format!("set_var({key:?}, {value:?})"),
+        (CallKind::Set, None) => format!("set_var({key:?}, _)"),
+        (CallKind::Remove, _) => format!("remove_var({key:?})"),
+    }
+}
+
+/// A mock environment for tests that check exactly which calls are made:
+/// each read, write, and removal must be declared up front, and
+/// [`verify`](MockEnvironment::verify) fails the test if a declared call did
+/// not happen the expected number of times.
+///
+/// Reads with `var`, `var_os`, and `contains` all meet `expect_var`
+/// expectations. When several expectations match a call, the first one
+/// declared that can still take calls is used.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, MockEnvironment, ReadEnvironment};
+/// let mut mock_env = MockEnvironment::new();
+/// mock_env.expect_var("TOKEN").times(1).returning("abc");
+/// mock_env.expect_set("STATE");
+///
+/// assert_eq!(mock_env.var("TOKEN").unwrap(), "abc");
+/// mock_env.set_var("STATE", "ready");
+///
+/// mock_env.verify();
+/// ```
+#[derive(Debug, Default)]
+pub struct MockEnvironment {
+    expectations: RefCell<Vec<Expectation>>,
+    unexpected: RefCell<Vec<String>>,
+    on_unexpected: UnexpectedCalls,
+}
+
+impl MockEnvironment {
+    /// A mock that expects no calls and panics on any.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose what happens on calls no expectation allows.
+    pub fn unexpected_calls(&mut self, policy: UnexpectedCalls) -> &mut Self {
+        self.on_unexpected = policy;
+        self
+    }
+
+    /// Expect `key` to be read.
+    pub fn expect_var(&mut self, key: impl AsRef<OsStr>) -> &mut Expectation {
+        self.expect(Expectation::new(CallKind::Var, key.as_ref()))
+    }
+
+    /// Expect `key` to be set.
+    pub fn expect_set(&mut self, key: impl AsRef<OsStr>) -> &mut Expectation {
+        self.expect(Expectation::new(CallKind::Set, key.as_ref()))
+    }
+
+    /// Expect `key` to be removed.
+    pub fn expect_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Expectation {
+        self.expect(Expectation::new(CallKind::Remove, key.as_ref()))
+    }
+
+    /// Check that every expectation was met and, with
+    /// [`UnexpectedCalls::Record`](UnexpectedCalls::Record), that no
+    /// unexpected calls were made.
+    ///
+    /// # Panics
+    /// If not, listing each problem.
+    pub fn verify(&self) {
+        let mut problems: Vec<String> = self
+            .expectations
+            .borrow()
+            .iter()
+            .filter(|expectation| expectation.calls < expectation.min_calls)
+            .map(|expectation| {
+                format!(
+                    "expected {} to be called {} time(s), but it was called {} time(s)",
+                    describe_call(
+                        expectation.kind,
+                        &expectation.key,
+                        expectation.value.as_deref()
+                    ),
+                    expectation.describe_times(),
+                    expectation.calls
+                )
+            })
+            .collect();
+        problems.extend(self.unexpected.borrow().iter().cloned());
+        if !problems.is_empty() {
+            panic!(
+                "MockEnvironment was not used as expected:\n{}",
+                problems.join("\n")
+            );
+        }
+    }
+
+    /// [`verify`](MockEnvironment::verify), then forget all expectations and
+    /// recorded calls so new ones can be declared for the next phase of a
+    /// test.
+    ///
+    /// # Panics
+    /// As for [`verify`](MockEnvironment::verify).
+    pub fn checkpoint(&mut self) {
+        self.verify();
+        self.expectations.get_mut().clear();
+        self.unexpected.get_mut().clear();
+    }
+
+    fn expect(&mut self, expectation: Expectation) -> &mut Expectation {
+        let expectations = self.expectations.get_mut();
+        expectations.push(expectation);
+        expectations.last_mut().unwrap()
+    }
+
+    fn call(&self, kind: CallKind, key: &OsStr, value: Option<&OsStr>) -> Option<OsString> {
+        let mut expectations = self.expectations.borrow_mut();
+        let mut matched = false;
+        for expectation in expectations
+            .iter_mut()
+            .filter(|expectation| expectation.matches(kind, key, value))
+        {
+            if !expectation.is_saturated() {
+                expectation.calls += 1;
+                return expectation.returning.clone();
+            }
+            matched = true;
+        }
+        drop(expectations);
+
+        let call = describe_call(kind, key, value);
+        let problem = if matched {
+            format!("{call} was called more times than expected")
+        } else {
+            format!("unexpected call to {call}")
+        };
+        match self.on_unexpected {
+            UnexpectedCalls::Panic => panic!("{problem}"),
+            UnexpectedCalls::Record => self.unexpected.borrow_mut().push(problem),
+        }
+        None
+    }
+}
+
+impl ReadEnvironment for MockEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.call(CallKind::Var, key.as_ref(), None)
+    }
+}
+
+impl Environment for MockEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.call(CallKind::Set, key.as_ref(), Some(value.as_ref()));
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.call(CallKind::Remove, key.as_ref(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::VarError,
+        panic::{catch_unwind, AssertUnwindSafe},
+    };
+
+    use super::{MockEnvironment, UnexpectedCalls};
+    use crate::{Environment, ReadEnvironment};
+
+    #[test]
+    fn given_satisfied_expectations_when_verifying_then_it_passes() {
+        // Arrange
+        let mut mock_env = MockEnvironment::new();
+        mock_env.expect_var("TOKEN").times(2).returning("abc");
+        mock_env.expect_var("MISSING");
+        mock_env.expect_set("STATE");
+        mock_env.expect_remove("TMP").times(1);
+
+        // Act
+        let first = mock_env.var("TOKEN");
+        let second = mock_env.var_os("TOKEN");
+        let missing = mock_env.var("MISSING");
+        mock_env.set_var("STATE", "ready");
+        mock_env.set_var("STATE", "done");
+        mock_env.remove_var("TMP");
+
+        // Assert
+        assert_eq!(first.unwrap(), "abc");
+        assert_eq!(second.unwrap(), "abc");
+        assert_eq!(missing.unwrap_err(), VarError::NotPresent);
+        mock_env.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected var(\"TOKEN\") to be called exactly 1 time(s)")]
+    fn given_an_unmet_expectation_when_verifying_then_it_panics() {
+        // Arrange
+        let mut mock_env = MockEnvironment::new();
+        mock_env.expect_var("TOKEN").times(1).returning("abc");
+
+        // Act
+        mock_env.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "var(\"TOKEN\") was called more times than expected")]
+    fn given_an_over_satisfied_expectation_when_calling_again_then_it_panics() {
+        // Arrange
+        let mut mock_env = MockEnvironment::new();
+        mock_env.expect_var("TOKEN").times(1).returning("abc");
+        let _ = mock_env.var("TOKEN");
+
+        // Act
+        let _ = mock_env.var("TOKEN");
+    }
+
+    #[test]
+    fn given_recorded_unexpected_calls_when_verifying_then_it_panics() {
+        // Arrange
+        let mut mock_env = MockEnvironment::new();
+        mock_env.unexpected_calls(UnexpectedCalls::Record);
+        mock_env.expect_var("TOKEN").times(1).returning("abc");
+        mock_env.expect_set("STATE").with_value("ready");
+
+        // Act
+        let _ = mock_env.var("TOKEN");
+        let over_satisfied = mock_env.var("TOKEN");
+        mock_env.set_var("STATE", "ready");
+        mock_env.set_var("STATE", "broken");
+        let result = catch_unwind(AssertUnwindSafe(|| mock_env.verify()));
+
+        // Assert
+        assert_eq!(over_satisfied.unwrap_err(), VarError::NotPresent);
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("var(\"TOKEN\") was called more times than expected"));
+        assert!(message.contains("unexpected call to set_var(\"STATE\", \"broken\")"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected call to remove_var(\"TMP\")")]
+    fn given_no_expectations_when_calling_then_it_panics() {
+        MockEnvironment::new().remove_var("TMP");
+    }
+
+    #[test]
+    fn given_met_expectations_when_checkpointing_then_new_expectations_can_be_declared() {
+        // Arrange
+        let mut mock_env = MockEnvironment::new();
+        mock_env.expect_var("PHASE").returning("one");
+        let _ = mock_env.var("PHASE");
+
+        // Act
+        mock_env.checkpoint();
+        mock_env.expect_var("PHASE").returning("two");
+
+        // Assert
+        assert_eq!(mock_env.var("PHASE").unwrap(), "two");
+        mock_env.verify();
+    }
+}
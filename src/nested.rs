@@ -0,0 +1,412 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    ffi::OsString,
+    fmt,
+};
+
+use crate::EnumerableEnvironment;
+
+/// A tree of environment variables, built by
+/// [`to_nested_map`](to_nested_map) from names that encode nesting.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NestedValue {
+    /// The value of a variable.
+    Leaf(String),
+    /// The values nested under a common name, keyed by the next part of
+    /// their names.
+    Map(BTreeMap<String, NestedValue>),
+}
+
+impl NestedValue {
+    /// The value nested under `key`, if this is a map containing it.
+    pub fn get(&self, key: &str) -> Option<&NestedValue> {
+        self.as_map()?.get(key)
+    }
+
+    /// The value of the variable, if this is a leaf.
+    pub fn as_leaf(&self) -> Option<&str> {
+        match self {
+            NestedValue::Leaf(value) => Some(value),
+            NestedValue::Map(_) => None,
+        }
+    }
+
+    /// The nested values, if this is a map.
+    pub fn as_map(&self) -> Option<&BTreeMap<String, NestedValue>> {
+        match self {
+            NestedValue::Leaf(_) => None,
+            NestedValue::Map(map) => Some(map),
+        }
+    }
+}
+
+/// An error from building a [`NestedValue`](NestedValue) with
+/// [`to_nested_map`](to_nested_map).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum NestedError {
+    /// One variable is set, so another cannot nest under it, as with
+    /// `MYAPP_DB` and `MYAPP_DB__HOST`.
+    Conflict { variable: String, nested: String },
+    /// The value of the named variable is not valid Unicode.
+    NotUnicode(String),
+    /// The name of the named variable has an empty part, such as two
+    /// separators in a row.
+    EmptyPart(String),
+    /// Two variables name the same leaf once their names are lower-cased,
+    /// as with `MYAPP_DB_HOST` and `MYAPP_db_host`.
+    Duplicate { first: String, second: String },
+}
+
+impl fmt::Display for NestedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NestedError::Conflict { variable, nested } => write!(
+                f,
+                "environment variable {variable:?} is set, so {nested:?} cannot nest under it"
+            ),
+            NestedError::NotUnicode(name) => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            NestedError::EmptyPart(name) => {
+                write!(
+                    f,
+                    "environment variable {name:?} has an empty part in its name"
+                )
+            }
+            NestedError::Duplicate { first, second } => write!(
+                f,
+                "environment variables {first:?} and {second:?} differ only in case"
+            ),
+        }
+    }
+}
+
+impl Error for NestedError {}
+
+/// Build a tree from the variables in `env` whose names start with `prefix`,
+/// nesting at each `separator` in the rest of their names. So with the
+/// prefix `MYAPP_` and the separator `__`, `MYAPP_DB__HOST` is the leaf at
+/// `DB` then `HOST`. Parts of names keep their case. An empty `separator`
+/// disables nesting.
+///
+/// Variables whose names do not start with `prefix`, or are not valid
+/// Unicode, are ignored. With the `serde` feature, the tree can be
+/// deserialized with [`from_nested`](crate::from_nested).
+///
+/// # Errors
+/// * [`NestedError::Conflict`](NestedError::Conflict) if a variable is set
+///   and others nest under its name.
+/// * [`NestedError::NotUnicode`](NestedError::NotUnicode) for a value that
+///   is not valid Unicode.
+/// * [`NestedError::EmptyPart`](NestedError::EmptyPart) for a name that has
+///   nothing after the prefix or between separators.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{to_nested_map, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_DB__HOST", "localhost");
+/// fake_env.set_var("MYAPP_DB__PORT", "5432");
+/// fake_env.set_var("MYAPP_CACHE__TTL", "60");
+///
+/// let tree = to_nested_map(&fake_env, "MYAPP_", "__").unwrap();
+///
+/// let db = tree.get("DB").unwrap();
+/// assert_eq!(db.get("HOST").unwrap().as_leaf(), Some("localhost"));
+/// assert_eq!(tree.get("CACHE").unwrap().get("TTL").unwrap().as_leaf(), Some("60"));
+/// ```
+pub fn to_nested_map(
+    env: &impl EnumerableEnvironment,
+    prefix: &str,
+    separator: &str,
+) -> Result<NestedValue, NestedError> {
+    build(env.vars_os(), prefix, separator, false)
+}
+
+/// Build the tree of [`to_nested_map`](to_nested_map) from `vars`, with the
+/// parts of names in lower case if `lowercase` is `true`.
+pub(crate) fn build(
+    vars: Vec<(OsString, OsString)>,
+    prefix: &str,
+    separator: &str,
+    lowercase: bool,
+) -> Result<NestedValue, NestedError> {
+    let key = |part: &str| {
+        if lowercase {
+            part.to_lowercase()
+        } else {
+            part.to_owned()
+        }
+    };
+    // Sorting puts each variable before any that nest under it, so conflicts
+    // are always found at a leaf on the way down, and puts names that only
+    // differ in case next to each other.
+    let mut vars: Vec<_> = vars
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, value)| (key(&name), name, value))
+        .collect();
+    vars.sort();
+
+    let mut root = BTreeMap::new();
+    let mut previous: Option<(String, String)> = None;
+    // The names of the leaves by sort key, to name the variable a conflict
+    // is with in its own case.
+    let mut leaves = HashMap::new();
+    for (sort_key, name, value) in vars {
+        if let Some((previous_key, first)) = previous.take() {
+            if previous_key == sort_key {
+                return Err(NestedError::Duplicate {
+                    first,
+                    second: name,
+                });
+            }
+        }
+        previous = Some((sort_key.clone(), name.clone()));
+
+        let rest = &name[prefix.len()..];
+        let parts: Vec<_> = if separator.is_empty() {
+            vec![rest]
+        } else {
+            rest.split(separator).collect()
+        };
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(NestedError::EmptyPart(name));
+        }
+        let Ok(value) = value.into_string() else {
+            return Err(NestedError::NotUnicode(name));
+        };
+
+        let (last, parents) = parts.split_last().expect("split yields a part");
+        let mut map = &mut root;
+        for (depth, part) in parents.iter().enumerate() {
+            let node = map
+                .entry(key(part))
+                .or_insert_with(|| NestedValue::Map(BTreeMap::new()));
+            map = match node {
+                NestedValue::Map(map) => map,
+                NestedValue::Leaf(_) => {
+                    let variable = format!("{prefix}{}", parts[..=depth].join(separator));
+                    let variable = leaves.remove(&key(&variable)).unwrap_or(variable);
+                    return Err(NestedError::Conflict {
+                        variable,
+                        nested: name,
+                    });
+                }
+            };
+        }
+        map.insert(key(last), NestedValue::Leaf(value));
+        leaves.insert(sort_key, name);
+    }
+    Ok(NestedValue::Map(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{to_nested_map, NestedError, NestedValue};
+    use crate::{test_helpers::fake_env, EnumerableEnvironment, Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn leaf(value: &str) -> NestedValue {
+        NestedValue::Leaf(value.into())
+    }
+
+    fn map<const N: usize>(entries: [(&str, NestedValue); N]) -> NestedValue {
+        NestedValue::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn given_two_levels_of_nesting_when_building_then_the_tree_mirrors_the_names() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_NAME", "api"),
+            ("MYAPP_DB__HOST", "localhost"),
+            ("MYAPP_DB__POOL__MAX", "10"),
+            ("MYAPP_DB__POOL__MIN", "1"),
+            ("MYAPP_CACHE__TTL", "60"),
+        ]);
+
+        // Act
+        let tree = to_nested_map(&env, "MYAPP_", "__").unwrap();
+
+        // Assert
+        assert_eq!(
+            tree,
+            map([
+                ("NAME", leaf("api")),
+                (
+                    "DB",
+                    map([
+                        ("HOST", leaf("localhost")),
+                        ("POOL", map([("MAX", leaf("10")), ("MIN", leaf("1"))])),
+                    ])
+                ),
+                ("CACHE", map([("TTL", leaf("60"))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn given_variables_outside_the_prefix_when_building_then_they_are_ignored() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_PORT", "8080"), ("OTHER_PORT", "1"), ("PORT", "2")]);
+
+        // Act
+        let tree = to_nested_map(&env, "MYAPP_", "__").unwrap();
+
+        // Assert
+        assert_eq!(tree, map([("PORT", leaf("8080"))]));
+    }
+
+    #[test]
+    fn given_a_leaf_that_is_also_a_branch_when_building_then_it_is_a_conflict() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_DB", "postgres://db"), ("MYAPP_DB__HOST", "db")]);
+
+        // Act
+        let result = to_nested_map(&env, "MYAPP_", "__");
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(
+            error,
+            NestedError::Conflict {
+                variable: "MYAPP_DB".into(),
+                nested: "MYAPP_DB__HOST".into(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "environment variable \"MYAPP_DB\" is set, so \"MYAPP_DB__HOST\" cannot nest under it"
+        );
+    }
+
+    #[test]
+    fn given_an_empty_part_when_building_then_it_is_an_error() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_DB____HOST", "db")]);
+
+        // Act
+        let result = to_nested_map(&env, "MYAPP_", "__");
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            NestedError::EmptyPart("MYAPP_DB____HOST".into())
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_building_then_it_is_an_error() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("MYAPP_DB__HOST", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = to_nested_map(&env, "MYAPP_", "__");
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            NestedError::NotUnicode("MYAPP_DB__HOST".into())
+        );
+    }
+
+    #[test]
+    fn given_an_empty_separator_when_building_then_nothing_is_nested() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_DB__HOST", "db")]);
+
+        // Act
+        let tree = to_nested_map(&env, "MYAPP_", "").unwrap();
+
+        // Assert
+        assert_eq!(
+            tree.get("DB__HOST").and_then(NestedValue::as_leaf),
+            Some("db")
+        );
+    }
+
+    #[test]
+    fn given_two_levels_when_grouping_by_prefix_then_parts_are_lower_cased() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_DB_HOST", "localhost"),
+            ("MYAPP_DB_PORT", "5432"),
+            ("MYAPP_DB_POOL_MAX", "10"),
+            ("MYAPP_CACHE_TTL", "60"),
+            ("OTHER_DB_HOST", "elsewhere"),
+            ("DB_HOST", "nowhere"),
+        ]);
+
+        // Act
+        let tree = env.group_by_prefix("MYAPP_", '_').unwrap();
+
+        // Assert
+        assert_eq!(
+            tree,
+            map([
+                (
+                    "db",
+                    map([
+                        ("host", leaf("localhost")),
+                        ("port", leaf("5432")),
+                        ("pool", map([("max", leaf("10"))])),
+                    ])
+                ),
+                ("cache", map([("ttl", leaf("60"))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn given_a_leaf_that_is_also_a_group_when_grouping_by_prefix_then_it_is_a_conflict() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_DB", "postgres://db"), ("MYAPP_db_HOST", "db")]);
+
+        // Act
+        let result = env.group_by_prefix("MYAPP_", '_');
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            NestedError::Conflict {
+                variable: "MYAPP_DB".into(),
+                nested: "MYAPP_db_HOST".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn given_names_that_differ_only_in_case_when_grouping_by_prefix_then_they_are_duplicates() {
+        // Arrange
+        let env = fake_env(&[("MYAPP_DB_HOST", "a"), ("MYAPP_db_host", "b")]);
+
+        // Act
+        let error = env.group_by_prefix("MYAPP_", '_').unwrap_err();
+
+        // Assert
+        assert_eq!(
+            error,
+            NestedError::Duplicate {
+                first: "MYAPP_DB_HOST".into(),
+                second: "MYAPP_db_host".into(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "environment variables \"MYAPP_DB_HOST\" and \"MYAPP_db_host\" differ only in case"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    sync::Arc,
+};
+
+use crate::{EnumerableEnvironment, EnvError, Environment, ReadEnvironment};
+
+/// A wrapper that cleans up values as they are read, e.g. trimming the
+/// trailing newline or surrounding quotes a CI pipeline left in a value.
+///
+/// Transformations run in the order they were added. Values that are not
+/// valid UTF-8 are returned untransformed. Writes and removals pass through
+/// unchanged, and the wrapped environment keeps the raw values: read them
+/// through [`inner`](NormalizingEnvironment::inner).
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, NormalizingEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", " \"8080\"\n");
+///
+/// let env = NormalizingEnvironment::new(fake_env)
+///     .trim_whitespace()
+///     .strip_quotes();
+///
+/// assert_eq!(env.var("PORT").unwrap(), "8080");
+/// assert_eq!(env.inner().var("PORT").unwrap(), " \"8080\"\n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct NormalizingEnvironment<E> {
+    inner: E,
+    transforms: Vec<Transform>,
+}
+
+#[derive(Clone)]
+enum Transform {
+    TrimWhitespace,
+    StripQuotes,
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl Transform {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Transform::TrimWhitespace => value.trim().into(),
+            Transform::StripQuotes => strip_quotes(&value).into(),
+            Transform::Custom(transform) => transform(&value),
+        }
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::TrimWhitespace => f.write_str("TrimWhitespace"),
+            Transform::StripQuotes => f.write_str("StripQuotes"),
+            Transform::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Remove one layer of matching single or double quotes around `value`.
+fn strip_quotes(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(unquoted) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return unquoted;
+        }
+    }
+    value
+}
+
+impl<E: ReadEnvironment> NormalizingEnvironment<E> {
+    /// Wrap `inner` without any transformations yet.
+    pub fn new(inner: E) -> Self {
+        NormalizingEnvironment {
+            inner,
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Trim leading and trailing whitespace, including newlines.
+    pub fn trim_whitespace(mut self) -> Self {
+        self.transforms.push(Transform::TrimWhitespace);
+        self
+    }
+
+    /// Remove one layer of matching single or double quotes surrounding the
+    /// value, so `"8080"` and `'8080'` read as `8080`. Unmatched quotes are
+    /// kept.
+    pub fn strip_quotes(mut self) -> Self {
+        self.transforms.push(Transform::StripQuotes);
+        self
+    }
+
+    /// Transform values with `transform`.
+    pub fn transform(mut self, transform: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.transforms.push(Transform::Custom(Arc::new(transform)));
+        self
+    }
+
+    /// The wrapped environment, for reading raw values.
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn normalize(&self, value: OsString) -> OsString {
+        match value.into_string() {
+            Ok(value) => self
+                .transforms
+                .iter()
+                .fold(value, |value, transform| transform.apply(value))
+                .into(),
+            Err(value) => value,
+        }
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for NormalizingEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(key).map(|value| self.normalize(value))
+    }
+}
+
+impl<E: Environment> Environment for NormalizingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for NormalizingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner
+            .vars_os()
+            .into_iter()
+            .map(|(key, value)| (key, self.normalize(value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::NormalizingEnvironment;
+    use crate::{
+        test_helpers::fake_env, EnumerableEnvironment, Environment, FakeEnvironment,
+        ReadEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_whitespace_around_a_value_when_trimming_then_it_is_removed() {
+        // Arrange
+        let env = NormalizingEnvironment::new(fake_env(&[("PORT", "\t8080 \n")])).trim_whitespace();
+
+        // Act
+        let result = env.var("PORT");
+
+        // Assert
+        assert_eq!(result.unwrap(), "8080");
+    }
+
+    #[test]
+    fn given_quoted_values_when_stripping_quotes_then_one_matching_layer_is_removed() {
+        // Arrange
+        let env = NormalizingEnvironment::new(fake_env(&[
+            ("DOUBLE", "\"8080\""),
+            ("SINGLE", "'8080'"),
+            ("NESTED", "\"'8080'\""),
+            ("MISMATCHED", "\"8080'"),
+            ("LONE", "\""),
+        ]))
+        .strip_quotes();
+
+        // Act/Assert
+        assert_eq!(env.var("DOUBLE").unwrap(), "8080");
+        assert_eq!(env.var("SINGLE").unwrap(), "8080");
+        assert_eq!(env.var("NESTED").unwrap(), "'8080'");
+        assert_eq!(env.var("MISMATCHED").unwrap(), "\"8080'");
+        assert_eq!(env.var("LONE").unwrap(), "\"");
+    }
+
+    #[test]
+    fn given_a_custom_transform_when_reading_then_it_is_applied() {
+        // Arrange
+        let env = NormalizingEnvironment::new(fake_env(&[("MODE", "Fast")]))
+            .transform(|value| value.to_lowercase());
+
+        // Act
+        let result = env.var("MODE");
+
+        // Assert
+        assert_eq!(result.unwrap(), "fast");
+    }
+
+    #[test]
+    fn given_combined_transforms_when_reading_and_listing_then_they_apply_in_order() {
+        // Arrange
+        let env = NormalizingEnvironment::new(fake_env(&[("PORT", " \"8080\"\n")]))
+            .trim_whitespace()
+            .strip_quotes()
+            .transform(|value| format!("{value}/tcp"));
+
+        // Act
+        let read = env.var("PORT");
+        let listed = env.vars_os();
+
+        // Assert
+        assert_eq!(read.unwrap(), "8080/tcp");
+        assert_eq!(listed, vec![("PORT".into(), "8080/tcp".into())]);
+    }
+
+    #[test]
+    fn given_transforms_when_reading_then_the_underlying_value_is_unchanged() {
+        // Arrange
+        let env = NormalizingEnvironment::new(fake_env(&[("PORT", "'8080'\n")]))
+            .trim_whitespace()
+            .strip_quotes();
+
+        // Act
+        let normalized = env.var("PORT");
+
+        // Assert
+        assert_eq!(normalized.unwrap(), "8080");
+        assert_eq!(env.into_inner().var("PORT").unwrap(), "'8080'\n");
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_reading_then_it_passes_through_untransformed() {
+        // Arrange
+        let invalid = OsStr::from_bytes(&INVALID_UTF8);
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("BINARY", invalid);
+        let env = NormalizingEnvironment::new(inner)
+            .trim_whitespace()
+            .transform(|_| "replaced".into());
+
+        // Act
+        let var_os_result = env.var_os("BINARY");
+        let var_result = env.var("BINARY");
+
+        // Assert
+        assert_eq!(var_os_result.unwrap(), invalid);
+        assert_eq!(var_result, Err(VarError::NotUnicode(invalid.into())));
+    }
+}
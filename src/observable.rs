@@ -0,0 +1,314 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+type SetObserver = Box<dyn FnMut(&OsStr, Option<&OsStr>, &OsStr) + Send>;
+type RemoveObserver = Box<dyn FnMut(&OsStr, Option<&OsStr>) + Send>;
+
+/// Identifies an observer registered with an
+/// [`ObservableEnvironment`](ObservableEnvironment), for removing it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ObserverId(u64);
+
+enum Observer {
+    Set(SetObserver),
+    Remove(RemoveObserver),
+}
+
+/// A wrapper that calls observers whenever a variable is set or removed,
+/// e.g. to fail fast when code under test touches `LD_PRELOAD`.
+///
+/// Observers run after the change has been made to the wrapped environment,
+/// in the order they were registered. Set observers receive the key, the
+/// previous value, and the new value; remove observers receive the key and
+/// the previous value. Failed `try_set_var` and `try_remove_var` calls are
+/// not observed.
+///
+/// If an observer panics, the change has already been made, the remaining
+/// observers are not called, and the panic propagates to the caller of
+/// `set_var` or `remove_var`.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ObservableEnvironment};
+/// let mut env = ObservableEnvironment::new(FakeEnvironment::new());
+/// env.on_set(|key, _old, _new| {
+///     assert_ne!(key, "LD_PRELOAD", "code under test must not set LD_PRELOAD");
+/// });
+///
+/// env.set_var("LOG_LEVEL", "debug");
+/// ```
+pub struct ObservableEnvironment<E> {
+    inner: E,
+    observers: Vec<(ObserverId, Observer)>,
+    next_id: u64,
+}
+
+impl<E: ReadEnvironment> ObservableEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        ObservableEnvironment {
+            inner,
+            observers: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Call `observer` with the key, previous value, and new value after each
+    /// variable is set.
+    pub fn on_set(
+        &mut self,
+        observer: impl FnMut(&OsStr, Option<&OsStr>, &OsStr) + Send + 'static,
+    ) -> ObserverId {
+        self.register(Observer::Set(Box::new(observer)))
+    }
+
+    /// Call `observer` with the key and previous value after each variable is
+    /// removed.
+    pub fn on_remove(
+        &mut self,
+        observer: impl FnMut(&OsStr, Option<&OsStr>) + Send + 'static,
+    ) -> ObserverId {
+        self.register(Observer::Remove(Box::new(observer)))
+    }
+
+    /// Stop calling the observer registered as `id`. Returns whether it was
+    /// registered.
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        let observer_count = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+        self.observers.len() != observer_count
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn register(&mut self, observer: Observer) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    fn notify_set(&mut self, key: &OsStr, old: Option<&OsStr>, new: &OsStr) {
+        for (_, observer) in &mut self.observers {
+            if let Observer::Set(observer) = observer {
+                observer(key, old, new);
+            }
+        }
+    }
+
+    fn notify_remove(&mut self, key: &OsStr, old: Option<&OsStr>) {
+        for (_, observer) in &mut self.observers {
+            if let Observer::Remove(observer) = observer {
+                observer(key, old);
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for ObservableEnvironment<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservableEnvironment")
+            .field("inner", &self.inner)
+            .field("observer_count", &self.observers.len())
+            .finish()
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for ObservableEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.inner.var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(key)
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for ObservableEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.inner.var_with_source(key)
+    }
+}
+
+impl<E: Environment> Environment for ObservableEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let old = self.inner.var_os(&key);
+        self.inner.set_var(&key, &value);
+        self.notify_set(key.as_ref(), old.as_deref(), value.as_ref());
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let old = self.inner.var_os(&key);
+        self.inner.remove_var(&key);
+        self.notify_remove(key.as_ref(), old.as_deref());
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let old = self.inner.var_os(&key);
+        self.inner.try_set_var(&key, &value)?;
+        self.notify_set(key.as_ref(), old.as_deref(), value.as_ref());
+        Ok(())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        let old = self.inner.var_os(&key);
+        self.inner.try_remove_var(&key)?;
+        self.notify_remove(key.as_ref(), old.as_deref());
+        Ok(())
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for ObservableEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        panic::{catch_unwind, AssertUnwindSafe},
+        sync::{Arc, Mutex},
+    };
+
+    use super::ObservableEnvironment;
+    use crate::{
+        Environment, FakeEnvironment, ReadEnvironment, ReadOnlyEnvironment, SharedFakeEnvironment,
+    };
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn describe(value: Option<&OsStr>) -> String {
+        value.map_or("<unset>".into(), |value| value.to_string_lossy().into())
+    }
+
+    fn observe(env: &mut ObservableEnvironment<FakeEnvironment>, name: &'static str, log: &Log) {
+        let set_log = Arc::clone(log);
+        env.on_set(move |key, old, new| {
+            set_log.lock().unwrap().push(format!(
+                "{name}: set {key:?} from {} to {new:?}",
+                describe(old)
+            ))
+        });
+        let remove_log = Arc::clone(log);
+        env.on_remove(move |key, old| {
+            remove_log
+                .lock()
+                .unwrap()
+                .push(format!("{name}: removed {key:?}, was {}", describe(old)))
+        });
+    }
+
+    #[test]
+    fn given_two_observers_when_setting_and_removing_then_both_are_called_in_registration_order() {
+        // Arrange
+        let log = Log::default();
+        let mut env = ObservableEnvironment::new(FakeEnvironment::new());
+        observe(&mut env, "first", &log);
+        observe(&mut env, "second", &log);
+
+        // Act
+        env.set_var("MODE", "fast");
+        env.set_var("MODE", "slow");
+        env.remove_var("MODE");
+
+        // Assert
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first: set \"MODE\" from <unset> to \"fast\"",
+                "second: set \"MODE\" from <unset> to \"fast\"",
+                "first: set \"MODE\" from fast to \"slow\"",
+                "second: set \"MODE\" from fast to \"slow\"",
+                "first: removed \"MODE\", was slow",
+                "second: removed \"MODE\", was slow",
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_removed_observer_when_setting_then_only_the_remaining_one_is_called() {
+        // Arrange
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut env = ObservableEnvironment::new(FakeEnvironment::new());
+        let first_calls = Arc::clone(&calls);
+        let first = env.on_set(move |_, _, _| first_calls.lock().unwrap().push("first"));
+        let second_calls = Arc::clone(&calls);
+        env.on_set(move |_, _, _| second_calls.lock().unwrap().push("second"));
+
+        // Act
+        let removed = env.remove_observer(first);
+        let removed_again = env.remove_observer(first);
+        env.set_var("MODE", "fast");
+
+        // Assert
+        assert!(removed);
+        assert!(!removed_again);
+        assert_eq!(*calls.lock().unwrap(), vec!["second"]);
+    }
+
+    #[test]
+    fn given_an_observer_when_it_is_called_then_the_change_has_already_been_made() {
+        // Arrange
+        let mut env = ObservableEnvironment::new(SharedFakeEnvironment::new());
+        let inner = env.inner.clone();
+        let seen = Arc::new(Mutex::new(None::<OsString>));
+        let seen_by_observer = Arc::clone(&seen);
+        env.on_set(move |key, _, _| *seen_by_observer.lock().unwrap() = inner.var_os(key));
+
+        // Act
+        env.set_var("MODE", "fast");
+
+        // Assert
+        assert_eq!(seen.lock().unwrap().as_deref().unwrap(), "fast");
+    }
+
+    #[test]
+    fn given_a_panicking_observer_when_setting_then_the_change_is_kept_and_later_observers_are_skipped(
+    ) {
+        // Arrange
+        let later_called = Arc::new(Mutex::new(false));
+        let mut env = ObservableEnvironment::new(FakeEnvironment::new());
+        env.on_set(|_, _, _| panic!("LD_PRELOAD must not be set"));
+        let later = Arc::clone(&later_called);
+        env.on_set(move |_, _, _| *later.lock().unwrap() = true);
+
+        // Act
+        let result = catch_unwind(AssertUnwindSafe(|| env.set_var("LD_PRELOAD", "evil.so")));
+
+        // Assert
+        assert!(result.is_err());
+        assert!(!*later_called.lock().unwrap());
+        assert_eq!(env.var("LD_PRELOAD").unwrap(), "evil.so");
+    }
+
+    #[test]
+    fn given_a_failed_try_set_when_observing_then_no_observer_is_called() {
+        // Arrange
+        let called = Arc::new(Mutex::new(false));
+        let mut env = ObservableEnvironment::new(ReadOnlyEnvironment::new(FakeEnvironment::new()));
+        let observer_called = Arc::clone(&called);
+        env.on_set(move |_, _, _| *observer_called.lock().unwrap() = true);
+
+        // Act
+        let result = env.try_set_var("MODE", "fast");
+
+        // Assert
+        assert!(result.is_err());
+        assert!(!*called.lock().unwrap());
+    }
+}
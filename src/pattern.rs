@@ -0,0 +1,304 @@
+use std::ffi::{OsStr, OsString};
+
+/// A pattern matching environment variable names.
+///
+/// A pattern without wildcards matches exactly one name. A `*` matches any run
+/// of characters, including none, so `MYAPP_*` matches every name starting
+/// with `MYAPP_` and `*_TOKEN` every name ending with `_TOKEN`. A `?` matches
+/// exactly one character, so `DB?_URL` matches `DB1_URL` but not `DB_URL`.
+/// Patterns match whole names.
+///
+/// Names that are not valid UTF-8 only match patterns without wildcards.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::KeyPattern;
+/// let pattern = KeyPattern::new("AWS_*");
+///
+/// assert!(pattern.matches("AWS_SECRET_ACCESS_KEY"));
+/// assert!(!pattern.matches("HOME"));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct KeyPattern {
+    pattern: String,
+}
+
+impl KeyPattern {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        KeyPattern {
+            pattern: pattern.into(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, key: impl AsRef<OsStr>) -> bool {
+        let key = key.as_ref();
+        if !self.pattern.contains(['*', '?']) {
+            return key == OsStr::new(&self.pattern);
+        }
+        match key.to_str() {
+            Some(key) => wildcard_match(&self.pattern, key),
+            None => false,
+        }
+    }
+
+    /// Like [`matches`](KeyPattern::matches), but ignoring ASCII case, as
+    /// Windows does when looking up variables.
+    pub(crate) fn matches_ignore_ascii_case(&self, key: impl AsRef<OsStr>) -> bool {
+        let key = key.as_ref();
+        if !self.pattern.contains(['*', '?']) {
+            return key.eq_ignore_ascii_case(&self.pattern);
+        }
+        match key.to_str() {
+            Some(key) => wildcard_match(
+                &self.pattern.to_ascii_uppercase(),
+                &key.to_ascii_uppercase(),
+            ),
+            None => false,
+        }
+    }
+}
+
+impl From<&str> for KeyPattern {
+    fn from(pattern: &str) -> Self {
+        KeyPattern::new(pattern)
+    }
+}
+
+impl From<String> for KeyPattern {
+    fn from(pattern: String) -> Self {
+        KeyPattern::new(pattern)
+    }
+}
+
+/// The variables among `vars` whose names are valid UTF-8 and satisfy
+/// `matches`, sorted by name.
+pub(crate) fn vars_where(
+    vars: Vec<(OsString, OsString)>,
+    matches: impl Fn(&str) -> bool,
+) -> Vec<(String, OsString)> {
+    let mut vars: Vec<_> = vars
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value)))
+        .filter(|(key, _)| matches(key))
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+/// and `?` any one character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` seen, and how far into the text it
+    // reaches so far. On a mismatch, the `*` is made to reach one further.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::KeyPattern;
+    use crate::{
+        test_helpers::fake_env,
+        test_util::{unique_key, ScopedTestVar},
+        EnumerableEnvironment, Environment, RealEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn keys(vars: Vec<(String, std::ffi::OsString)>) -> Vec<String> {
+        vars.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn given_patterns_when_matching_ignoring_ascii_case_then_case_does_not_matter() {
+        // Arrange
+        let wildcard = KeyPattern::new("AWS_*");
+        let exact = KeyPattern::new("Home");
+
+        // Act/Assert
+        assert!(wildcard.matches_ignore_ascii_case("aws_secret_access_key"));
+        assert!(exact.matches_ignore_ascii_case("HOME"));
+        assert!(!wildcard.matches_ignore_ascii_case("AWSX"));
+        assert!(!exact.matches_ignore_ascii_case(OsStr::from_bytes(&INVALID_UTF8)));
+    }
+
+    #[test]
+    fn given_a_pattern_without_wildcards_when_matching_then_only_the_exact_name_matches() {
+        // Arrange
+        let pattern = KeyPattern::new("HOME");
+
+        // Act/Assert
+        assert!(pattern.matches("HOME"));
+        assert!(!pattern.matches("HOMER"));
+        assert!(!pattern.matches("home"));
+    }
+
+    #[test]
+    fn given_a_prefix_pattern_when_matching_then_names_with_the_prefix_match() {
+        // Arrange
+        let pattern = KeyPattern::new("AWS_*");
+
+        // Act/Assert
+        assert!(pattern.matches("AWS_SECRET_ACCESS_KEY"));
+        assert!(pattern.matches("AWS_"));
+        assert!(!pattern.matches("MY_AWS_KEY"));
+    }
+
+    #[test]
+    fn given_a_suffix_pattern_when_matching_then_names_with_the_suffix_match() {
+        // Arrange
+        let pattern = KeyPattern::new("*_TOKEN");
+
+        // Act/Assert
+        assert!(pattern.matches("GITHUB_TOKEN"));
+        assert!(!pattern.matches("TOKEN_COUNT"));
+    }
+
+    #[test]
+    fn given_several_wildcards_when_matching_then_the_pieces_must_appear_in_order() {
+        // Arrange
+        let pattern = KeyPattern::new("MYAPP_*_URL");
+
+        // Act/Assert
+        assert!(pattern.matches("MYAPP_DB_URL"));
+        assert!(pattern.matches("MYAPP_CACHE_PRIMARY_URL"));
+        assert!(!pattern.matches("MYAPP_URL"));
+        assert!(!pattern.matches("MYAPP_DB_URL_OLD"));
+    }
+
+    #[test]
+    fn given_a_non_unicode_name_when_matching_a_wildcard_pattern_then_it_does_not_match() {
+        // Arrange
+        let pattern = KeyPattern::new("*");
+        let name = OsStr::from_bytes(&INVALID_UTF8);
+
+        // Act/Assert
+        assert!(!pattern.matches(name));
+        assert!(pattern.matches("ANYTHING"));
+    }
+
+    #[test]
+    fn given_question_marks_when_matching_then_each_matches_exactly_one_character() {
+        // Arrange
+        let pattern = KeyPattern::new("DB?_URL");
+
+        // Act/Assert
+        assert!(pattern.matches("DB1_URL"));
+        assert!(pattern.matches("DBé_URL"));
+        assert!(!pattern.matches("DB_URL"));
+        assert!(!pattern.matches("DB12_URL"));
+        assert!(KeyPattern::new("*_??").matches("LOG_ID"));
+        assert!(!KeyPattern::new("*_??").matches("LOG_I"));
+    }
+
+    #[test]
+    fn given_a_star_that_must_backtrack_when_matching_then_the_whole_name_is_tried() {
+        // Arrange
+        let pattern = KeyPattern::new("*_URL");
+
+        // Act/Assert
+        assert!(pattern.matches("A_URL_B_URL"));
+        assert!(!pattern.matches("A_URL_B"));
+        assert!(KeyPattern::new("A*B*C").matches("AXBYBZC"));
+    }
+
+    #[test]
+    fn given_a_glob_when_listing_matching_fake_variables_then_it_is_anchored_at_both_ends() {
+        // Arrange
+        let mut env = fake_env(&[
+            ("MYAPP_DB_URL", "postgres://db"),
+            ("MYAPP_CACHE_URL", "redis://cache"),
+            ("MYAPP_DB_URL_OLD", "postgres://old"),
+            ("OTHER_MYAPP_DB_URL", "postgres://other"),
+        ]);
+        env.set_var(OsStr::from_bytes(&INVALID_UTF8), "binary");
+
+        // Act
+        let urls = env.vars_matching("MYAPP_*_URL");
+        let none = env.vars_matching("MYAPP_?_URL");
+
+        // Assert
+        assert_eq!(
+            urls,
+            [
+                ("MYAPP_CACHE_URL".into(), "redis://cache".into()),
+                ("MYAPP_DB_URL".into(), "postgres://db".into()),
+            ]
+        );
+        assert!(none.is_empty());
+        assert_eq!(env.vars_matching("*").len(), 4);
+    }
+
+    #[test]
+    fn given_a_glob_when_listing_matching_real_variables_then_only_matching_names_are_listed() {
+        // Arrange
+        let prefix = unique_key("ENV_WRAPPER_GLOB");
+        let mut env = RealEnvironment;
+        let mut url = ScopedTestVar::new(&mut env, format!("{prefix}_DB_URL"), "postgres://db");
+        let old = ScopedTestVar::new(&mut *url, format!("{prefix}_DB_URL_OLD"), "old");
+
+        // Act
+        let urls = old.vars_matching(format!("{prefix}_*_URL"));
+        let none = old.vars_matching(format!("{prefix}_?_URL"));
+
+        // Assert
+        assert_eq!(keys(urls), [format!("{prefix}_DB_URL")]);
+        assert!(none.is_empty());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn given_a_regex_with_alternation_when_listing_matching_variables_then_both_arms_match() {
+        // Arrange
+        let regex = regex::Regex::new(r"^MYAPP_(DB|CACHE)_").unwrap();
+        let env = fake_env(&[
+            ("MYAPP_DB_HOST", "db"),
+            ("MYAPP_CACHE_HOST", "cache"),
+            ("MYAPP_QUEUE_HOST", "queue"),
+            ("OLD_MYAPP_DB_HOST", "old"),
+        ]);
+        let prefix = unique_key("ENV_WRAPPER_REGEX");
+        let mut real_env = RealEnvironment;
+        let guard = ScopedTestVar::new(&mut real_env, format!("{prefix}_DB"), "db");
+
+        // Act
+        let hosts = env.vars_matching_regex(&regex);
+        let real = guard
+            .vars_matching_regex(&regex::Regex::new(&format!("^{prefix}_(DB|CACHE)$")).unwrap());
+        let none = env.vars_matching_regex(&regex::Regex::new("^NOTHING_").unwrap());
+
+        // Assert
+        assert_eq!(keys(hosts), ["MYAPP_CACHE_HOST", "MYAPP_DB_HOST"]);
+        assert_eq!(keys(real), [format!("{prefix}_DB")]);
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+/// A view of an environment where every key is namespaced by a prefix, so
+/// `var("TIMEOUT")` reads `MYAPP_TIMEOUT` from the wrapped environment.
+///
+/// The prefix applies to every operation, including writes and removals.
+/// Listing variables only includes keys bearing the prefix, with the prefix
+/// stripped.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, PrefixedEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_TIMEOUT", "30");
+///
+/// let env = PrefixedEnvironment::new(fake_env, "MYAPP_");
+///
+/// assert_eq!(env.var("TIMEOUT").unwrap(), "30");
+/// ```
+#[derive(Clone, Debug)]
+pub struct PrefixedEnvironment<E> {
+    inner: E,
+    prefix: OsString,
+}
+
+impl<E: ReadEnvironment> PrefixedEnvironment<E> {
+    pub fn new(inner: E, prefix: impl AsRef<OsStr>) -> Self {
+        PrefixedEnvironment {
+            inner,
+            prefix: prefix.as_ref().into(),
+        }
+    }
+
+    pub fn prefix(&self) -> &OsStr {
+        &self.prefix
+    }
+
+    /// Unwrap the view, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn prefixed(&self, key: impl AsRef<OsStr>) -> OsString {
+        let mut prefixed = self.prefix.clone();
+        prefixed.push(key);
+        prefixed
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for PrefixedEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.inner.var(self.prefixed(key))
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(self.prefixed(key))
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for PrefixedEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.inner.var_with_source(self.prefixed(key))
+    }
+}
+
+impl<E: Environment> Environment for PrefixedEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = self.prefixed(key);
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = self.prefixed(key);
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let key = self.prefixed(key);
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        let key = self.prefixed(key);
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for PrefixedEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner
+            .vars_os()
+            .into_iter()
+            .filter_map(|(key, value)| Some((strip_prefix(&key, &self.prefix)?, value)))
+            .collect()
+    }
+}
+
+/// A read-only view of the variables in an environment that bear a prefix,
+/// with the prefix removed, so `MYAPP_DB_HOST` appears as `DB_HOST`.
+///
+/// Keys without the prefix are hidden. When both `MYAPP_X` and a literal `X`
+/// exist underneath, the view's `X` is always `MYAPP_X`; the literal `X` never
+/// shows through, even when `MYAPP_X` is absent.
+///
+/// This is the read-only counterpart of
+/// [`PrefixedEnvironment`](PrefixedEnvironment), which it reads through, and
+/// can borrow the environment it views.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment, StripPrefixView};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_DB_HOST", "localhost");
+///
+/// let view = StripPrefixView::new(&fake_env, "MYAPP_");
+///
+/// assert_eq!(view.var("DB_HOST").unwrap(), "localhost");
+/// ```
+#[derive(Clone, Debug)]
+pub struct StripPrefixView<E>(PrefixedEnvironment<E>);
+
+impl<E: ReadEnvironment> StripPrefixView<E> {
+    pub fn new(inner: E, prefix: impl AsRef<OsStr>) -> Self {
+        StripPrefixView(PrefixedEnvironment::new(inner, prefix))
+    }
+
+    pub fn prefix(&self) -> &OsStr {
+        self.0.prefix()
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for StripPrefixView<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.0.var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.0.var_os(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for StripPrefixView<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.0.vars_os()
+    }
+}
+
+/// Strip `prefix` from the start of `key`, if `key` starts with it. Prefixes
+/// that are not valid UTF-8 never match.
+pub(crate) fn strip_prefix(key: &OsStr, prefix: &OsStr) -> Option<OsString> {
+    let prefix = prefix.to_str()?;
+    let stripped = key.as_encoded_bytes().strip_prefix(prefix.as_bytes())?;
+    // SAFETY: the bytes were split immediately after a valid UTF-8 substring.
+    Some(unsafe { OsStr::from_encoded_bytes_unchecked(stripped) }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{PrefixedEnvironment, StripPrefixView};
+    use crate::{EnumerableEnvironment, Environment, FakeEnvironment, ReadEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_a_prefixed_key_in_the_base_when_reading_through_the_view_then_it_is_found_by_its_unprefixed_name(
+    ) {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_TIMEOUT", "30");
+        let env = PrefixedEnvironment::new(base, "MYAPP_");
+
+        // Act
+        let result = env.var("TIMEOUT");
+
+        // Assert
+        assert_eq!(result.unwrap(), "30");
+    }
+
+    #[test]
+    fn given_an_unprefixed_key_in_the_base_when_reading_through_the_view_then_it_is_invisible() {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("TIMEOUT", "30");
+        let env = PrefixedEnvironment::new(base, "MYAPP_");
+
+        // Act
+        let result = env.var("TIMEOUT");
+
+        // Assert
+        assert_eq!(result.unwrap_err(), VarError::NotPresent);
+        assert!(env.vars_os().is_empty());
+    }
+
+    #[test]
+    fn when_writing_through_the_view_then_the_write_lands_on_the_prefixed_key() {
+        // Arrange
+        let mut env = PrefixedEnvironment::new(FakeEnvironment::new(), "MYAPP_");
+
+        // Act
+        env.set_var("MODE", "fast");
+
+        // Assert
+        let base = env.into_inner();
+        assert_eq!(base.var("MYAPP_MODE").unwrap(), "fast");
+        assert!(base.var_os("MODE").is_none());
+    }
+
+    #[test]
+    fn when_removing_through_the_view_then_only_the_prefixed_key_is_removed() {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_MODE", "fast");
+        base.set_var("MODE", "slow");
+        let mut env = PrefixedEnvironment::new(base, "MYAPP_");
+
+        // Act
+        env.remove_var("MODE");
+
+        // Assert
+        let base = env.into_inner();
+        assert!(base.var_os("MYAPP_MODE").is_none());
+        assert_eq!(base.var("MODE").unwrap(), "slow");
+    }
+
+    #[test]
+    fn when_listing_through_the_view_then_only_prefixed_keys_are_listed_with_the_prefix_stripped() {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_A", "1");
+        base.set_var("MYAPP_B", OsStr::from_bytes(&INVALID_UTF8));
+        base.set_var("OTHER_C", "3");
+        let env = PrefixedEnvironment::new(base, "MYAPP_");
+
+        // Act
+        let mut vars = env.vars_os();
+
+        // Assert
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                ("A".into(), "1".into()),
+                ("B".into(), OsStr::from_bytes(&INVALID_UTF8).into())
+            ]
+        );
+    }
+
+    #[test]
+    fn given_prefixed_and_unprefixed_keys_when_reading_through_a_strip_prefix_view_then_only_prefixed_keys_are_visible(
+    ) {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_DB_HOST", "localhost");
+        base.set_var("DB_PORT", "5432");
+        let view = StripPrefixView::new(&base, "MYAPP_");
+
+        // Act
+        let host = view.var("DB_HOST");
+        let port = view.var("DB_PORT");
+
+        // Assert
+        assert_eq!(host.unwrap(), "localhost");
+        assert_eq!(port.unwrap_err(), VarError::NotPresent);
+        assert_eq!(view.vars_os(), vec![("DB_HOST".into(), "localhost".into())]);
+    }
+
+    #[test]
+    fn given_both_a_prefixed_and_a_literal_key_when_reading_through_a_strip_prefix_view_then_the_prefixed_value_wins(
+    ) {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_X", "prefixed");
+        base.set_var("X", "literal");
+        base.set_var("Y", "literal");
+        let view = StripPrefixView::new(&base, "MYAPP_");
+
+        // Act
+        let x = view.var("X");
+        let y = view.var("Y");
+
+        // Assert
+        assert_eq!(x.unwrap(), "prefixed");
+        assert_eq!(y.unwrap_err(), VarError::NotPresent);
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_reading_through_a_strip_prefix_view_then_it_passes_through() {
+        // Arrange
+        let mut base = FakeEnvironment::new();
+        base.set_var("MYAPP_BIN", OsStr::from_bytes(&INVALID_UTF8));
+        let view = StripPrefixView::new(&base, "MYAPP_");
+
+        // Act
+        let result = view.var("BIN");
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            VarError::NotUnicode(OsStr::from_bytes(&INVALID_UTF8).into())
+        );
+        assert_eq!(
+            view.var_os("BIN").unwrap(),
+            OsStr::from_bytes(&INVALID_UTF8)
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::{env::VarError, ffi::OsStr, path::PathBuf};
+
+use crate::ReadEnvironment;
+
+/// Where a value read through
+/// [`var_with_source`](SourcedEnvironment::var_with_source) came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Provenance {
+    /// The process's environment.
+    Process,
+    /// A fake environment, or the in-memory changes made to an environment
+    /// backed by something else, such as a dotenv file.
+    Fake,
+    /// A default registered with [`WithDefaults`](crate::WithDefaults),
+    /// holding the default value.
+    Default(String),
+    /// The dotenv file at the path held.
+    DotenvFile(PathBuf),
+}
+
+/// An environment that can tell where each value it reads comes from, to
+/// explain a surprising configuration value.
+///
+/// Environments that hold their own values report a fixed provenance, such
+/// as [`Provenance::Process`] for the
+/// [`RealEnvironment`](crate::RealEnvironment), and wrappers report the
+/// provenance of the environment they read the value from.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, Provenance, SourcedEnvironment, WithDefaults};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("WORKERS", "8");
+///
+/// let env = WithDefaults::new(fake_env).default("LOG_LEVEL", "info");
+///
+/// assert_eq!(
+///     env.var_with_source("LOG_LEVEL").unwrap(),
+///     ("info".into(), Provenance::Default("info".into()))
+/// );
+/// assert_eq!(
+///     env.var_with_source("WORKERS").unwrap(),
+///     ("8".into(), Provenance::Fake)
+/// );
+/// ```
+pub trait SourcedEnvironment: ReadEnvironment {
+    /// Get an environment variable as [`var`](ReadEnvironment::var) does,
+    /// along with where its value came from.
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError>;
+}
+
+impl<E: SourcedEnvironment + ?Sized> SourcedEnvironment for &E {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        (**self).var_with_source(key)
+    }
+}
+
+impl<E: SourcedEnvironment + ?Sized> SourcedEnvironment for &mut E {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        (**self).var_with_source(key)
+    }
+}
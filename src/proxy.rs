@@ -0,0 +1,401 @@
+use std::{fmt, net::IpAddr};
+
+use crate::{EnvError, ReadEnvironment};
+
+/// The HTTP proxy settings of an environment, from the conventional
+/// `http_proxy`, `https_proxy`, `all_proxy`, and `no_proxy` variables, to
+/// choose a proxy for each URL the way curl and most HTTP clients do.
+///
+/// Each variable may also be set in upper case. When both are set, the
+/// lower-case one wins, as in curl, since it is the older convention and the
+/// one every tool reads. Unlike curl, `HTTP_PROXY` is still honored when
+/// `http_proxy` is not set; programs run as CGI scripts, where a request's
+/// `Proxy` header arrives as `HTTP_PROXY`, should read a fake environment
+/// without it. Empty variables count as unset.
+///
+/// URLs are sent through the proxy for their scheme, `http_proxy` or
+/// `https_proxy`, falling back to `all_proxy`, which is also the proxy for
+/// every other scheme. Proxies given without a scheme are assumed to be
+/// `http://`.
+///
+/// `no_proxy` is a comma-separated list of hosts to reach directly. Each
+/// entry is either:
+/// * `*`, which matches every host,
+/// * an IP address, which matches that address,
+/// * a network in CIDR notation, such as `10.0.0.0/8`, which matches the
+///   addresses in it, or
+/// * a domain name, which matches that name and any subdomain of it, with
+///   any leading `.` or `*.` ignored, so `example.com`, `.example.com`, and
+///   `*.example.com` all match both `example.com` and `api.example.com`.
+///
+/// Names are compared case-insensitively, and ports are not compared.
+/// Malformed entries, such as `10.0.0.0/abc`, are skipped.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ProxyConfig};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("HTTPS_PROXY", "http://proxy.internal:3128");
+/// fake_env.set_var("NO_PROXY", "localhost,.svc.cluster.local,10.0.0.0/8");
+///
+/// let proxies = ProxyConfig::from_env(&fake_env).unwrap();
+///
+/// assert_eq!(
+///     proxies.proxy_for_url("https://example.com/api").unwrap().as_str(),
+///     "http://proxy.internal:3128"
+/// );
+/// assert_eq!(proxies.proxy_for_url("https://db.svc.cluster.local"), None);
+/// assert_eq!(proxies.proxy_for_url("https://10.1.2.3:8443"), None);
+/// assert_eq!(proxies.proxy_for_url("http://example.com"), None);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProxyConfig {
+    http: Option<ProxyUrl>,
+    https: Option<ProxyUrl>,
+    all: Option<ProxyUrl>,
+    no_proxy: Vec<NoProxyEntry>,
+}
+
+/// The URL of a proxy, from a [`ProxyConfig`](ProxyConfig).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ProxyUrl(String);
+
+/// An entry of `no_proxy`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum NoProxyEntry {
+    Wildcard,
+    Network { address: IpAddr, prefix_len: u8 },
+    Domain(String),
+}
+
+impl ProxyConfig {
+    /// Read the proxy settings of `env`.
+    ///
+    /// # Errors
+    /// If one of the variables read is not valid Unicode. The error does not
+    /// hold its value, which may include a password.
+    pub fn from_env(env: &impl ReadEnvironment) -> Result<Self, EnvError> {
+        let no_proxy = read(env, "no_proxy")?.unwrap_or_default();
+        Ok(ProxyConfig {
+            http: read(env, "http_proxy")?.map(ProxyUrl::new),
+            https: read(env, "https_proxy")?.map(ProxyUrl::new),
+            all: read(env, "all_proxy")?.map(ProxyUrl::new),
+            no_proxy: no_proxy
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .filter_map(NoProxyEntry::parse)
+                .collect(),
+        })
+    }
+
+    /// The proxy to reach `url_or_host` through, or `None` to reach it
+    /// directly. It may be a URL, such as `https://example.com/api`, or a
+    /// host, with or without a port, which is treated as an `http` URL.
+    pub fn proxy_for_url(&self, url_or_host: &str) -> Option<&ProxyUrl> {
+        let (scheme, host) = match url_or_host.split_once("://") {
+            Some((scheme, rest)) => (scheme, host_of(rest)),
+            None => ("http", host_of(url_or_host)),
+        };
+        if self.bypasses(host) {
+            return None;
+        }
+        let proxy = if scheme.eq_ignore_ascii_case("http") {
+            self.http.as_ref()
+        } else if scheme.eq_ignore_ascii_case("https") {
+            self.https.as_ref()
+        } else {
+            None
+        };
+        proxy.or(self.all.as_ref())
+    }
+
+    /// Whether `no_proxy` lists `host`, so it is reached directly.
+    pub fn bypasses(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let address = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .ok();
+        self.no_proxy
+            .iter()
+            .any(|entry| entry.matches(&host, address))
+    }
+}
+
+/// The value of `name` in lower case, or else in upper case, if either is
+/// set and not empty.
+fn read(env: &impl ReadEnvironment, name: &str) -> Result<Option<String>, EnvError> {
+    for name in [name.to_owned(), name.to_ascii_uppercase()] {
+        match env.var_redacted(&name) {
+            Ok(value) if !value.is_empty() => return Ok(Some(value)),
+            Ok(_) | Err(EnvError::NotPresent(_)) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(None)
+}
+
+/// The host of a URL without its scheme, without any credentials, port, or
+/// path. IPv6 addresses keep their brackets.
+fn host_of(rest: &str) -> &str {
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = match authority.rsplit_once('@') {
+        Some((_, host_port)) => host_port,
+        None => authority,
+    };
+    if host_port.starts_with('[') {
+        return match host_port.find(']') {
+            Some(end) => &host_port[..=end],
+            None => host_port,
+        };
+    }
+    match host_port.split_once(':') {
+        Some((host, _)) => host,
+        None => host_port,
+    }
+}
+
+impl NoProxyEntry {
+    /// Parse `entry`, or return `None` if it is malformed, such as a network
+    /// whose prefix length is missing, not a number, or too long.
+    fn parse(entry: &str) -> Option<Self> {
+        if entry == "*" {
+            return Some(NoProxyEntry::Wildcard);
+        }
+        let (address, prefix_len) = match entry.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (entry, None),
+        };
+        let address = address.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(address) = address.parse::<IpAddr>() {
+            let max_len = if address.is_ipv4() { 32 } else { 128 };
+            let prefix_len = match prefix_len {
+                Some(prefix_len) => prefix_len
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|len| *len <= max_len)?,
+                None => max_len,
+            };
+            return Some(NoProxyEntry::Network {
+                address,
+                prefix_len,
+            });
+        }
+        if prefix_len.is_some() {
+            return None;
+        }
+        let domain = entry.trim_start_matches('*').trim_start_matches('.');
+        Some(NoProxyEntry::Domain(
+            domain.trim_end_matches('.').to_ascii_lowercase(),
+        ))
+    }
+
+    fn matches(&self, host: &str, address: Option<IpAddr>) -> bool {
+        match self {
+            NoProxyEntry::Wildcard => true,
+            NoProxyEntry::Network {
+                address: network,
+                prefix_len,
+            } => address.is_some_and(|address| in_network(address, *network, *prefix_len)),
+            NoProxyEntry::Domain(domain) => {
+                host == domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|subdomain| subdomain.ends_with('.'))
+            }
+        }
+    }
+}
+
+fn in_network(address: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+impl ProxyUrl {
+    fn new(url: String) -> Self {
+        if url.contains("://") {
+            ProxyUrl(url)
+        } else {
+            ProxyUrl(format!("http://{url}"))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ProxyUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProxyConfig;
+    use crate::{test_helpers::fake_env, FakeEnvironment};
+
+    fn proxy_for(config: &ProxyConfig, url: &str) -> Option<String> {
+        config.proxy_for_url(url).map(|proxy| proxy.to_string())
+    }
+
+    #[test]
+    fn given_mixed_case_variables_when_choosing_a_proxy_then_lower_case_ones_win() {
+        // Arrange
+        let env = fake_env(&[
+            ("http_proxy", "http://lower:3128"),
+            ("HTTP_PROXY", "http://upper:3128"),
+            ("HTTPS_PROXY", "secure:3128"),
+            ("all_proxy", ""),
+            ("ALL_PROXY", "socks5://all:1080"),
+        ]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(
+            proxy_for(&config, "http://example.com"),
+            Some("http://lower:3128".into())
+        );
+        assert_eq!(
+            proxy_for(&config, "HTTPS://example.com/path"),
+            Some("http://secure:3128".into())
+        );
+        assert_eq!(
+            proxy_for(&config, "ftp://example.com"),
+            Some("socks5://all:1080".into())
+        );
+        assert_eq!(
+            proxy_for(&config, "example.com:8080"),
+            Some("http://lower:3128".into())
+        );
+    }
+
+    #[test]
+    fn given_only_all_proxy_when_choosing_a_proxy_then_every_scheme_uses_it() {
+        // Arrange
+        let env = fake_env(&[("all_proxy", "http://all:3128")]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(
+            proxy_for(&config, "https://example.com"),
+            Some("http://all:3128".into())
+        );
+        assert_eq!(
+            proxy_for(&config, "http://example.com"),
+            Some("http://all:3128".into())
+        );
+    }
+
+    #[test]
+    fn given_no_proxy_domains_when_choosing_a_proxy_then_exact_and_suffix_matches_are_direct() {
+        // Arrange
+        let env = fake_env(&[
+            ("https_proxy", "http://proxy:3128"),
+            (
+                "NO_PROXY",
+                " localhost, .Internal.Example, *.svc.local,,corp.com.",
+            ),
+        ]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(proxy_for(&config, "https://localhost:8443"), None);
+        assert_eq!(proxy_for(&config, "https://internal.example"), None);
+        assert_eq!(proxy_for(&config, "https://API.internal.example/v1"), None);
+        assert_eq!(proxy_for(&config, "https://user:pw@db.svc.local"), None);
+        assert_eq!(proxy_for(&config, "https://www.corp.com."), None);
+        assert!(config.proxy_for_url("https://notlocalhost").is_some());
+        assert!(config.proxy_for_url("https://badcorp.com").is_some());
+        assert!(config
+            .proxy_for_url("https://example.com/?q=localhost")
+            .is_some());
+    }
+
+    #[test]
+    fn given_no_proxy_addresses_and_networks_when_choosing_a_proxy_then_matching_ips_are_direct() {
+        // Arrange
+        let env = fake_env(&[
+            ("http_proxy", "http://proxy:3128"),
+            ("no_proxy", "10.0.0.0/8,192.168.1.10,fd00::/8,[::1]"),
+        ]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(proxy_for(&config, "http://10.20.30.40:8080"), None);
+        assert_eq!(proxy_for(&config, "192.168.1.10"), None);
+        assert_eq!(proxy_for(&config, "http://[fd12::1]:8080/"), None);
+        assert_eq!(proxy_for(&config, "http://[::1]"), None);
+        assert!(config.proxy_for_url("http://11.0.0.1").is_some());
+        assert!(config.proxy_for_url("http://192.168.1.11").is_some());
+        assert!(config.proxy_for_url("http://[fe80::1]").is_some());
+    }
+
+    #[test]
+    fn given_malformed_no_proxy_networks_when_choosing_a_proxy_then_they_are_skipped() {
+        // Arrange
+        let env = fake_env(&[
+            ("http_proxy", "http://proxy:3128"),
+            ("no_proxy", "10.0.0.0/abc,10.0.0.0/,10.0.0.0/33,example.com"),
+        ]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(config.no_proxy.len(), 1);
+        assert!(config.proxy_for_url("http://10.0.0.0").is_some());
+        assert_eq!(proxy_for(&config, "http://example.com"), None);
+    }
+
+    #[test]
+    fn given_a_wildcard_no_proxy_when_choosing_a_proxy_then_every_host_is_direct() {
+        // Arrange
+        let env = fake_env(&[("HTTP_PROXY", "http://proxy:3128"), ("no_proxy", "*")]);
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(proxy_for(&config, "http://example.com"), None);
+        assert_eq!(proxy_for(&config, "http://10.0.0.1"), None);
+    }
+
+    #[test]
+    fn given_no_proxy_variables_when_choosing_a_proxy_then_every_host_is_direct() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let config = ProxyConfig::from_env(&env).unwrap();
+
+        // Assert
+        assert_eq!(config, ProxyConfig::default());
+        assert_eq!(proxy_for(&config, "https://example.com"), None);
+    }
+}
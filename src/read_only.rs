@@ -0,0 +1,158 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+/// A wrapper that allows reading an environment but never changing it.
+///
+/// `set_var` and `remove_var` panic, naming the key. Use `try_set_var` and
+/// `try_remove_var` to get an [`EnvError::ReadOnly`](EnvError::ReadOnly)
+/// instead. Either way, the wrapped environment is left unchanged.
+///
+/// Code that only needs to read should take an
+/// `impl `[`ReadEnvironment`](ReadEnvironment) instead, so that attempting to
+/// write does not compile. This wrapper is for code that insists on an
+/// `impl `[`Environment`](Environment).
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MODE", "fast");
+///
+/// let mut env = fake_env.read_only();
+///
+/// assert_eq!(env.var("MODE").unwrap(), "fast");
+/// assert!(env.try_set_var("MODE", "slow").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReadOnlyEnvironment<E> {
+    inner: E,
+}
+
+impl<E: ReadEnvironment> ReadOnlyEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        ReadOnlyEnvironment { inner }
+    }
+
+    /// Unwrap the environment, allowing it to be changed again.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for ReadOnlyEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.inner.var(key)
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner.var_os(key)
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for ReadOnlyEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.inner.var_with_source(key)
+    }
+}
+
+impl<E: ReadEnvironment> Environment for ReadOnlyEnvironment<E> {
+    /// # Panics
+    /// Always panics, since the environment is read-only.
+    fn set_var(&mut self, key: impl AsRef<OsStr>, _value: impl AsRef<OsStr>) {
+        panic!("{}", EnvError::ReadOnly(key.as_ref().into()))
+    }
+
+    /// # Panics
+    /// Always panics, since the environment is read-only.
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        panic!("{}", EnvError::ReadOnly(key.as_ref().into()))
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        _value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        Err(EnvError::ReadOnly(key.as_ref().into()))
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        Err(EnvError::ReadOnly(key.as_ref().into()))
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for ReadOnlyEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::ReadOnlyEnvironment;
+    use crate::{EnumerableEnvironment, EnvError, Environment, FakeEnvironment, ReadEnvironment};
+
+    fn frozen_env() -> ReadOnlyEnvironment<FakeEnvironment> {
+        let mut env = FakeEnvironment::new();
+        env.set_var("MODE", "fast");
+        env.read_only()
+    }
+
+    #[test]
+    fn given_a_read_only_environment_when_reading_then_values_pass_through() {
+        // Arrange
+        let env = frozen_env();
+
+        // Act/Assert
+        assert_eq!(env.var("MODE").unwrap(), "fast");
+        assert_eq!(env.var_os("MODE").unwrap(), "fast");
+        assert_eq!(env.vars_os(), vec![("MODE".into(), "fast".into())]);
+    }
+
+    #[test]
+    fn given_a_read_only_environment_when_trying_to_write_then_it_is_a_read_only_error() {
+        // Arrange
+        let mut env = frozen_env();
+
+        // Act
+        let set_result = env.try_set_var("MODE", "slow");
+        let remove_result = env.try_remove_var("MODE");
+
+        // Assert
+        assert_eq!(set_result.unwrap_err(), EnvError::ReadOnly("MODE".into()));
+        assert_eq!(
+            remove_result.unwrap_err(),
+            EnvError::ReadOnly("MODE".into())
+        );
+        assert_eq!(env.into_inner().var("MODE").unwrap(), "fast");
+    }
+
+    #[test]
+    fn given_a_read_only_environment_when_writing_then_panic_and_leave_the_environment_unchanged() {
+        // Arrange
+        let mut env = frozen_env();
+
+        // Act
+        let set_result = catch_unwind(AssertUnwindSafe(|| env.set_var("MODE", "slow")));
+        let remove_result = catch_unwind(AssertUnwindSafe(|| env.remove_var("MODE")));
+
+        // Assert
+        assert!(set_result.is_err());
+        assert!(remove_result.is_err());
+        assert_eq!(env.into_inner().var("MODE").unwrap(), "fast");
+    }
+
+    #[test]
+    #[should_panic(expected = "\"MODE\"")]
+    fn given_a_read_only_environment_when_writing_then_the_panic_message_names_the_key() {
+        frozen_env().set_var("MODE", "slow");
+    }
+}
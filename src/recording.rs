@@ -0,0 +1,255 @@
+use std::{
+    cell::{Ref, RefCell},
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, SourcedEnvironment,
+};
+
+/// An operation performed on a [`RecordingEnvironment`](RecordingEnvironment).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EnvCall {
+    /// A read with `var`, `var_os`, or `contains`. `hit` is whether the
+    /// variable was set, even if its value was not valid Unicode.
+    Get { key: OsString, hit: bool },
+    /// A write with `set_var` or `try_set_var`.
+    Set { key: OsString, value: OsString },
+    /// A removal with `remove_var` or `try_remove_var`.
+    Remove { key: OsString },
+}
+
+/// A spy that records every read, write, and removal made through it, in
+/// order, before passing it on to the wrapped environment.
+///
+/// Useful for checking that code reads exactly the variables it documents.
+/// Listing variables passes through and is not recorded.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{EnvCall, Environment, FakeEnvironment, ReadEnvironment, RecordingEnvironment};
+/// let mut env = RecordingEnvironment::new(FakeEnvironment::new());
+///
+/// env.set_var("MODE", "fast");
+/// let _ = env.var("HOME");
+///
+/// assert_eq!(
+///     *env.calls(),
+///     [
+///         EnvCall::Set { key: "MODE".into(), value: "fast".into() },
+///         EnvCall::Get { key: "HOME".into(), hit: false },
+///     ]
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecordingEnvironment<E> {
+    inner: E,
+    calls: RefCell<Vec<EnvCall>>,
+}
+
+impl<E: ReadEnvironment> RecordingEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        RecordingEnvironment {
+            inner,
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The calls recorded so far, oldest first.
+    ///
+    /// # Panics
+    /// Reading through the environment while holding the returned borrow
+    /// panics, since that would record a new call.
+    pub fn calls(&self) -> Ref<'_, [EnvCall]> {
+        Ref::map(self.calls.borrow(), Vec::as_slice)
+    }
+
+    /// Forget the calls recorded so far.
+    pub fn reset(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn record(&self, call: EnvCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for RecordingEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        let result = self.inner.var(&key);
+        self.record(EnvCall::Get {
+            key: key.as_ref().into(),
+            hit: !matches!(result, Err(VarError::NotPresent)),
+        });
+        result
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key);
+        self.record(EnvCall::Get {
+            key: key.as_ref().into(),
+            hit: value.is_some(),
+        });
+        value
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for RecordingEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let result = self.inner.var_with_source(&key);
+        self.record(EnvCall::Get {
+            key: key.as_ref().into(),
+            hit: !matches!(result, Err(VarError::NotPresent)),
+        });
+        result
+    }
+}
+
+impl<E: Environment> Environment for RecordingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.record(EnvCall::Set {
+            key: key.as_ref().into(),
+            value: value.as_ref().into(),
+        });
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.record(EnvCall::Remove {
+            key: key.as_ref().into(),
+        });
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.record(EnvCall::Set {
+            key: key.as_ref().into(),
+            value: value.as_ref().into(),
+        });
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.record(EnvCall::Remove {
+            key: key.as_ref().into(),
+        });
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for RecordingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{EnvCall, RecordingEnvironment};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn get(key: &str, hit: bool) -> EnvCall {
+        EnvCall::Get {
+            key: key.into(),
+            hit,
+        }
+    }
+
+    #[test]
+    fn given_mixed_operations_when_inspecting_calls_then_they_are_recorded_in_order() {
+        // Arrange
+        let mut env = RecordingEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        let _ = env.var("HOME");
+        env.set_var("MODE", "x");
+        let _ = env.var_os("MODE");
+        env.remove_var("TMP");
+
+        // Assert
+        assert_eq!(
+            *env.calls(),
+            [
+                get("HOME", false),
+                EnvCall::Set {
+                    key: "MODE".into(),
+                    value: "x".into()
+                },
+                get("MODE", true),
+                EnvCall::Remove { key: "TMP".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn given_set_and_unset_keys_when_reading_then_hits_and_misses_are_flagged() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("SET", "1");
+        inner.set_var("BIN", OsStr::from_bytes(&INVALID_UTF8));
+        let env = RecordingEnvironment::new(inner);
+
+        // Act
+        let _ = env.var("SET");
+        let _ = env.var("BIN");
+        let _ = env.var("UNSET");
+        env.contains("UNSET");
+
+        // Assert
+        assert_eq!(
+            *env.calls(),
+            [
+                get("SET", true),
+                get("BIN", true),
+                get("UNSET", false),
+                get("UNSET", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_recorded_calls_when_resetting_then_only_later_calls_are_kept() {
+        // Arrange
+        let mut env = RecordingEnvironment::new(FakeEnvironment::new());
+        env.set_var("MODE", "x");
+
+        // Act
+        env.reset();
+        let _ = env.var("MODE");
+
+        // Assert
+        assert_eq!(*env.calls(), [get("MODE", true)]);
+    }
+
+    #[test]
+    fn when_writing_through_the_recorder_then_the_inner_environment_changes() {
+        // Arrange
+        let mut env = RecordingEnvironment::new(FakeEnvironment::new());
+
+        // Act
+        env.try_set_var("MODE", "x").unwrap();
+        env.set_var("TMP", "/tmp");
+        env.try_remove_var("TMP").unwrap();
+
+        // Assert
+        assert_eq!(env.calls().len(), 3);
+        let inner = env.into_inner();
+        assert_eq!(inner.var("MODE").unwrap(), "x");
+        assert!(inner.var_os("TMP").is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use std::ffi::{OsStr, OsString};
+
+use crate::{
+    redaction::MASK, EnumerableEnvironment, EnvError, Environment, KeyPattern, ReadEnvironment,
+    RedactionPolicy,
+};
+
+/// A wrapper that masks the values of secret variables, for handing to code
+/// that logs or echoes what it reads, such as debug dumps or crash reports.
+///
+/// Reads and listings of variables marked secret return the mask (`***` by
+/// default) instead of their value. Variables that are not set still read as
+/// not present. Other variables, and all writes, pass through unchanged. Code
+/// that needs a secret's real value should read the wrapped environment
+/// directly.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment, RedactingEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("GITHUB_TOKEN", "ghp_abc123");
+/// fake_env.set_var("LOG_LEVEL", "debug");
+///
+/// let mut env = RedactingEnvironment::new(fake_env);
+/// env.mark_secret("*_TOKEN");
+///
+/// assert_eq!(env.var("GITHUB_TOKEN").unwrap(), "***");
+/// assert_eq!(env.var("LOG_LEVEL").unwrap(), "debug");
+/// ```
+#[derive(Clone, Debug)]
+pub struct RedactingEnvironment<E> {
+    inner: E,
+    policy: RedactionPolicy,
+    mask: OsString,
+}
+
+impl<E: ReadEnvironment> RedactingEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        Self::with_policy(inner, RedactionPolicy::new())
+    }
+
+    /// Mask the values `policy` covers, including long values if it limits
+    /// their length.
+    pub fn with_policy(inner: E, policy: RedactionPolicy) -> Self {
+        RedactingEnvironment {
+            inner,
+            policy,
+            mask: MASK.into(),
+        }
+    }
+
+    /// Mark the variables matching `pattern` as secret. The pattern may be an
+    /// exact name or use `*` wildcards, e.g. `*_SECRET`.
+    pub fn mark_secret(&mut self, pattern: impl Into<KeyPattern>) -> &mut Self {
+        self.policy.add_secret(pattern.into());
+        self
+    }
+
+    /// Replace secret values with `mask` instead of `***`.
+    pub fn set_mask(&mut self, mask: impl AsRef<OsStr>) -> &mut Self {
+        self.mask = mask.as_ref().into();
+        self
+    }
+
+    pub fn is_secret(&self, key: impl AsRef<OsStr>) -> bool {
+        self.policy.is_secret(key)
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn redact(&self, key: &OsStr, value: OsString) -> OsString {
+        if self.policy.redacts(key, &value) {
+            self.mask.clone()
+        } else {
+            value
+        }
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for RedactingEnvironment<E> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key)?;
+        Some(self.redact(key.as_ref(), value))
+    }
+}
+
+impl<E: Environment> Environment for RedactingEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for RedactingEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner
+            .vars_os()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.redact(&key, value);
+                (key, value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::RedactingEnvironment;
+    use crate::{
+        EnumerableEnvironment, Environment, FakeEnvironment, ReadEnvironment, RedactionPolicy,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn fake_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("DB_PASSWORD", "hunter2");
+        env.set_var("GITHUB_TOKEN", "ghp_abc123");
+        env.set_var("BINARY_SECRET", OsStr::from_bytes(&INVALID_UTF8));
+        env.set_var("LOG_LEVEL", "debug");
+        env
+    }
+
+    #[test]
+    fn given_a_key_marked_secret_when_reading_then_the_value_is_masked() {
+        // Arrange
+        let mut env = RedactingEnvironment::new(fake_env());
+        env.mark_secret("DB_PASSWORD");
+
+        // Act/Assert
+        assert_eq!(env.var("DB_PASSWORD").unwrap(), "***");
+        assert_eq!(env.var_os("DB_PASSWORD").unwrap(), "***");
+    }
+
+    #[test]
+    fn given_secret_patterns_when_reading_then_every_matching_key_is_masked() {
+        // Arrange
+        let mut env = RedactingEnvironment::new(fake_env());
+        env.mark_secret("*_TOKEN").mark_secret("*_SECRET");
+
+        // Act/Assert
+        assert_eq!(env.var("GITHUB_TOKEN").unwrap(), "***");
+        assert_eq!(env.var("BINARY_SECRET").unwrap(), "***");
+        assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn given_a_policy_with_a_length_limit_when_reading_then_long_values_are_masked_too() {
+        // Arrange
+        let policy = RedactionPolicy::new()
+            .secret("DB_PASSWORD")
+            .redact_values_longer_than(8);
+        let env = RedactingEnvironment::with_policy(fake_env(), policy);
+
+        // Act/Assert
+        assert_eq!(env.var("DB_PASSWORD").unwrap(), "***");
+        assert_eq!(env.var("GITHUB_TOKEN").unwrap(), "***");
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "debug");
+        assert!(!env.is_secret("GITHUB_TOKEN"));
+    }
+
+    #[test]
+    fn given_a_secret_that_is_not_set_when_reading_then_it_is_not_present() {
+        // Arrange
+        let mut env = RedactingEnvironment::new(fake_env());
+        env.mark_secret("*_TOKEN");
+
+        // Act/Assert
+        assert_eq!(env.var("NPM_TOKEN").unwrap_err(), VarError::NotPresent);
+        assert!(env.var_os("NPM_TOKEN").is_none());
+    }
+
+    #[test]
+    fn given_a_custom_mask_when_listing_variables_then_secrets_are_masked_and_others_pass_through()
+    {
+        // Arrange
+        let mut env = RedactingEnvironment::new(fake_env());
+        env.mark_secret("*_TOKEN").set_mask("[redacted]");
+
+        // Act
+        let mut vars = env.vars_os();
+
+        // Assert
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                (
+                    "BINARY_SECRET".into(),
+                    OsStr::from_bytes(&INVALID_UTF8).into()
+                ),
+                ("DB_PASSWORD".into(), "hunter2".into()),
+                ("GITHUB_TOKEN".into(), "[redacted]".into()),
+                ("LOG_LEVEL".into(), "debug".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_secret_when_writing_through_the_wrapper_then_the_real_value_is_stored() {
+        // Arrange
+        let mut env = RedactingEnvironment::new(FakeEnvironment::new());
+        env.mark_secret("API_KEY");
+
+        // Act
+        env.set_var("API_KEY", "real");
+
+        // Assert
+        assert_eq!(env.var("API_KEY").unwrap(), "***");
+        assert_eq!(env.into_inner().var("API_KEY").unwrap(), "real");
+    }
+}
@@ -0,0 +1,150 @@
+use std::ffi::OsStr;
+
+use crate::KeyPattern;
+
+/// What values show as when redacted.
+pub(crate) const MASK: &str = "***";
+
+/// Which values are secrets, to declare once and hand to everything in the
+/// crate that renders values, so none of them show a secret by mistake.
+///
+/// A value is redacted if its key matches one of the secret patterns, which
+/// may be exact names or use `*` wildcards, or if it is longer than the
+/// length limit, since long opaque values are often keys or tokens.
+/// Redacted values show as `***`.
+///
+/// It is honored by:
+/// * the `Debug` output of a [`FakeEnvironment`](crate::FakeEnvironment) it
+///   is attached to with
+///   [`set_redaction_policy`](crate::FakeEnvironment::set_redaction_policy),
+/// * [`EnvDiff::redacted`](crate::EnvDiff::redacted),
+/// * [`Resolution::redacted`](crate::Resolution::redacted),
+/// * [`CanonicalOptions::redaction_policy`](crate::CanonicalOptions::redaction_policy),
+/// * [`EnvSchema::redaction_policy`](crate::EnvSchema::redaction_policy),
+///   for the values in validation problems,
+/// * [`RedactingEnvironment::with_policy`](crate::RedactingEnvironment::with_policy),
+/// * and the `redaction_policy` of the `log` and `tracing` wrappers, for the
+///   values they record.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::RedactionPolicy;
+/// let policy = RedactionPolicy::new()
+///     .secret("DATABASE_URL")
+///     .secret("*_SECRET")
+///     .secret("*_TOKEN")
+///     .redact_values_longer_than(64);
+///
+/// assert_eq!(policy.redact("GITHUB_TOKEN", "ghp_abc123"), "***");
+/// assert_eq!(policy.redact("LOG_LEVEL", "debug"), "debug");
+/// assert_eq!(policy.redact("SIGNING_KEY", &"k".repeat(65)), "***");
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RedactionPolicy {
+    secrets: Vec<KeyPattern>,
+    max_value_len: Option<usize>,
+}
+
+impl RedactionPolicy {
+    /// A policy that redacts nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redact the values of variables matching `pattern`, which may be an
+    /// exact name or use `*` wildcards, e.g. `*_SECRET`.
+    pub fn secret(mut self, pattern: impl Into<KeyPattern>) -> Self {
+        self.secrets.push(pattern.into());
+        self
+    }
+
+    /// Redact every value longer than `len` bytes, whatever its key.
+    pub fn redact_values_longer_than(mut self, len: usize) -> Self {
+        self.max_value_len = Some(len);
+        self
+    }
+
+    /// Whether `key` matches one of the secret patterns.
+    pub fn is_secret(&self, key: impl AsRef<OsStr>) -> bool {
+        self.secrets
+            .iter()
+            .any(|pattern| pattern.matches(key.as_ref()))
+    }
+
+    /// Whether `value`, held by `key`, is redacted.
+    pub fn redacts(&self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> bool {
+        self.max_value_len
+            .is_some_and(|max_value_len| value.as_ref().len() > max_value_len)
+            || self.is_secret(key)
+    }
+
+    /// `value` as it should be shown: `***` if it is redacted, and `value`
+    /// otherwise.
+    pub fn redact<'a>(
+        &self,
+        key: impl AsRef<OsStr>,
+        value: &'a (impl AsRef<OsStr> + ?Sized),
+    ) -> &'a OsStr {
+        let value = value.as_ref();
+        if self.redacts(key, value) {
+            OsStr::new(MASK)
+        } else {
+            value
+        }
+    }
+
+    pub(crate) fn add_secret(&mut self, pattern: KeyPattern) {
+        self.secrets.push(pattern);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::RedactionPolicy;
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_exact_and_wildcard_secrets_when_redacting_then_only_matching_keys_are_masked() {
+        // Arrange
+        let policy = RedactionPolicy::new()
+            .secret("DATABASE_URL")
+            .secret("*_SECRET")
+            .secret("*_TOKEN");
+
+        // Act/Assert
+        assert_eq!(policy.redact("DATABASE_URL", "postgres://u:p@db"), "***");
+        assert_eq!(policy.redact("CLIENT_SECRET", "s3cr3t"), "***");
+        assert_eq!(
+            policy.redact("GITHUB_TOKEN", OsStr::from_bytes(&INVALID_UTF8)),
+            "***"
+        );
+        assert_eq!(policy.redact("DATABASE_URL_FILE", "/run/db"), "/run/db");
+        assert_eq!(policy.redact("TOKEN_TTL", "60"), "60");
+    }
+
+    #[test]
+    fn given_a_length_limit_when_redacting_then_only_longer_values_are_masked() {
+        // Arrange
+        let policy = RedactionPolicy::new().redact_values_longer_than(8);
+
+        // Act/Assert
+        assert_eq!(policy.redact("MODE", "12345678"), "12345678");
+        assert_eq!(policy.redact("MODE", "123456789"), "***");
+        assert!(!policy.is_secret("MODE"));
+    }
+
+    #[test]
+    fn given_the_default_policy_when_redacting_then_nothing_is_masked() {
+        // Arrange
+        let policy = RedactionPolicy::default();
+
+        // Act/Assert
+        assert_eq!(
+            policy.redact("API_TOKEN", &"x".repeat(10_000)).len(),
+            10_000
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::{self, Display},
+};
+
+use crate::{redaction::MASK, RedactionPolicy};
+
+/// How a variable was resolved through the layers of a
+/// [`LayeredEnvironment`](crate::LayeredEnvironment) or the sources of a
+/// [`ChainEnvironment`](crate::ChainEnvironment), from their `explain`
+/// method.
+///
+/// Its `Display` starts with the outcome, then lists what each layer held,
+/// in the order they are consulted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Resolution {
+    pub key: OsString,
+    /// Every layer, in the order they are consulted, including those below
+    /// the one that decided the outcome.
+    pub steps: Vec<ResolutionStep>,
+}
+
+/// What one layer held for the key being resolved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolutionStep {
+    pub layer: LayerId,
+    pub outcome: LayerOutcome,
+}
+
+/// A layer of a [`LayeredEnvironment`](crate::LayeredEnvironment) or source
+/// of a [`ChainEnvironment`](crate::ChainEnvironment).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LayerId {
+    /// The base of a layered environment.
+    Base,
+    /// A pushed layer, numbered from 0 for the first pushed.
+    Layer(usize),
+    /// A source of a chain, numbered from 0 for the first.
+    Source(usize),
+}
+
+/// What a layer held for a key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LayerOutcome {
+    /// The layer supplied the value read.
+    Supplied(OsString),
+    /// The layer held a value, but a layer consulted earlier decided the
+    /// outcome first.
+    Shadowed(OsString),
+    /// The variable was removed in the layer, hiding the layers below.
+    Tombstoned,
+    /// The layer did not hold the variable.
+    NotSet,
+}
+
+impl Resolution {
+    /// The resolution of `key` from what each layer holds, in the order they
+    /// are consulted, with every value after the first layer that held the
+    /// key or removed it marked as shadowed.
+    pub(crate) fn from_layers(
+        key: &OsStr,
+        layers: impl IntoIterator<Item = (LayerId, LayerOutcome)>,
+    ) -> Self {
+        let mut decided = false;
+        let steps = layers
+            .into_iter()
+            .map(|(layer, outcome)| {
+                let outcome = match outcome {
+                    LayerOutcome::Supplied(value) if decided => LayerOutcome::Shadowed(value),
+                    LayerOutcome::NotSet => LayerOutcome::NotSet,
+                    outcome => {
+                        decided = true;
+                        outcome
+                    }
+                };
+                ResolutionStep { layer, outcome }
+            })
+            .collect();
+        Resolution {
+            key: key.into(),
+            steps,
+        }
+    }
+
+    /// The value read, if any.
+    pub fn value(&self) -> Option<&OsStr> {
+        self.steps.iter().find_map(|step| match &step.outcome {
+            LayerOutcome::Supplied(value) => Some(value.as_os_str()),
+            _ => None,
+        })
+    }
+
+    /// The layer that supplied the value read, if any.
+    pub fn winner(&self) -> Option<LayerId> {
+        self.steps
+            .iter()
+            .find(|step| matches!(step.outcome, LayerOutcome::Supplied(_)))
+            .map(|step| step.layer)
+    }
+
+    /// The same resolution with the values `policy` covers masked, to show
+    /// or log it.
+    pub fn redacted(mut self, policy: &RedactionPolicy) -> Self {
+        for step in &mut self.steps {
+            if let LayerOutcome::Supplied(value) | LayerOutcome::Shadowed(value) = &mut step.outcome
+            {
+                if policy.redacts(&self.key, &*value) {
+                    *value = MASK.into();
+                }
+            }
+        }
+        self
+    }
+}
+
+impl Display for LayerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerId::Base => write!(f, "base"),
+            LayerId::Layer(index) => write!(f, "layer {index}"),
+            LayerId::Source(index) => write!(f, "source {index}"),
+        }
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = &self.key;
+        let decided_by = self
+            .steps
+            .iter()
+            .find(|step| !matches!(step.outcome, LayerOutcome::NotSet));
+        match decided_by {
+            Some(ResolutionStep {
+                layer,
+                outcome: LayerOutcome::Supplied(value),
+            }) => write!(f, "{key:?} = {value:?}, from {layer}")?,
+            Some(ResolutionStep { layer, .. }) => {
+                write!(f, "{key:?} is not set: it was removed in {layer}")?
+            }
+            None => write!(f, "{key:?} is not set in any layer")?,
+        }
+        for ResolutionStep { layer, outcome } in &self.steps {
+            match outcome {
+                LayerOutcome::Supplied(value) => write!(f, "\n  {layer}: {value:?}")?,
+                LayerOutcome::Shadowed(value) => write!(f, "\n  {layer}: {value:?} (shadowed)")?,
+                LayerOutcome::Tombstoned => write!(f, "\n  {layer}: removed")?,
+                LayerOutcome::NotSet => write!(f, "\n  {layer}: not set")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+//! Ready-made [`rstest`](https://docs.rs/rstest) fixtures that inject a
+//! [`FakeEnvironment`](crate::FakeEnvironment) into tests. Requires the
+//! `rstest` feature.
+//!
+//! Name a fixture as a test argument to get a fresh environment in each
+//! test, and pass a different file to a file-backed fixture with `#[with]`:
+//! ```rust
+//! # use env_wrapper::{rstest_support::{dotenv_env, unix_env}, FakeEnvironment, ReadEnvironment};
+//! # use rstest::rstest;
+//! #[rstest]
+//! fn reads_the_home_directory(unix_env: FakeEnvironment) {
+//!     assert_eq!(unix_env.var("HOME").unwrap(), "/home/test");
+//! }
+//!
+//! #[rstest]
+//! fn reads_the_staging_settings(#[with("tests/staging.env")] dotenv_env: FakeEnvironment) {
+//!     // ...
+//! }
+//! ```
+//!
+//! Build fixtures of your own from these, or from a closure with
+//! [`fake_env_with`](fake_env_with):
+//! ```rust
+//! # use env_wrapper::{rstest_support::{fake_env_with, unix_env}, Environment, FakeEnvironment, ReadEnvironment};
+//! # use rstest::{fixture, rstest};
+//! #[fixture]
+//! fn debug_env(mut unix_env: FakeEnvironment) -> FakeEnvironment {
+//!     unix_env.set_var("LOG_LEVEL", "debug");
+//!     unix_env
+//! }
+//!
+//! #[fixture]
+//! fn strict_env() -> FakeEnvironment {
+//!     fake_env_with(|env| {
+//!         env.set_var("MODE", "strict");
+//!         env.protect("MODE");
+//!     })
+//! }
+//!
+//! #[rstest]
+//! fn rejects_a_mode_change(debug_env: FakeEnvironment, strict_env: FakeEnvironment) {
+//!     // ...
+//! }
+//! # assert_eq!(debug_env(unix_env()).var("LOG_LEVEL").unwrap(), "debug");
+//! # assert!(strict_env().is_protected("MODE"));
+//! ```
+//!
+//! To run one test against several environments, give each `#[case]` an
+//! environment built with [`env_case!`](crate::env_case), named so a
+//! failure says which setup it was:
+//! ```rust
+//! # use env_wrapper::{env_case, rstest_support::unix_env, FakeEnvironment, ReadEnvironment};
+//! # use rstest::rstest;
+//! fn log_level(env: &impl ReadEnvironment) -> String {
+//!     env.var("LOG_LEVEL").unwrap_or_else(|_| "info".into())
+//! }
+//!
+//! #[rstest]
+//! #[case::unset(env_case!(), "info")]
+//! #[case::debug(env_case!("LOG_LEVEL" => "debug"), "debug")]
+//! #[case::on_a_workstation(env_case!(base = unix_env(); "LOG_LEVEL" => "warn"), "warn")]
+//! fn reads_the_log_level(#[case] env: FakeEnvironment, #[case] expected: &str) {
+//!     assert_eq!(log_level(&env), expected);
+//! }
+//! ```
+
+use std::fs::File;
+
+use rstest::fixture;
+
+use crate::{Environment, FakeEnvironment};
+
+/// An empty fake environment.
+#[fixture]
+pub fn fake_env() -> FakeEnvironment {
+    FakeEnvironment::new()
+}
+
+/// A fake environment with the variables a login shell on a Unix
+/// workstation usually has, for user `test`:
+///
+/// | Variable  | Value                            |
+/// |-----------|----------------------------------|
+/// | `HOME`    | `/home/test`                     |
+/// | `LANG`    | `C.UTF-8`                        |
+/// | `LOGNAME` | `test`                           |
+/// | `PATH`    | `/usr/local/bin:/usr/bin:/bin`   |
+/// | `SHELL`   | `/bin/sh`                        |
+/// | `TMPDIR`  | `/tmp`                           |
+/// | `USER`    | `test`                           |
+#[fixture]
+pub fn unix_env() -> FakeEnvironment {
+    seeded(&[
+        ("HOME", "/home/test"),
+        ("LANG", "C.UTF-8"),
+        ("LOGNAME", "test"),
+        ("PATH", "/usr/local/bin:/usr/bin:/bin"),
+        ("SHELL", "/bin/sh"),
+        ("TMPDIR", "/tmp"),
+        ("USER", "test"),
+    ])
+}
+
+/// A fake environment with the variables GitHub Actions sets for a push to
+/// `main`, which [`CiInfo::detect`](crate::CiInfo::detect) recognizes:
+/// `CI`, `GITHUB_ACTIONS`, `GITHUB_REF`, `GITHUB_REF_NAME`, and `RUNNER_OS`.
+#[fixture]
+pub fn ci_env() -> FakeEnvironment {
+    seeded(&[
+        ("CI", "true"),
+        ("GITHUB_ACTIONS", "true"),
+        ("GITHUB_REF", "refs/heads/main"),
+        ("GITHUB_REF_NAME", "main"),
+        ("RUNNER_OS", "Linux"),
+    ])
+}
+
+/// A fake environment with the variables of the dotenv file at `path`,
+/// `.env` by default, relative to the directory tests run in, which for
+/// `cargo test` is the package's.
+///
+/// # Panics
+/// If the file cannot be read or parsed, naming the file.
+#[fixture]
+pub fn dotenv_env(#[default(".env")] path: &str) -> FakeEnvironment {
+    File::open(path)
+        .and_then(FakeEnvironment::from_dotenv_reader)
+        .unwrap_or_else(|error| panic!("cannot load dotenv file {path:?}: {error}"))
+}
+
+/// A fake environment with the variables of the fixture file at `path`,
+/// as written by [`save_fixture`](FakeEnvironment::save_fixture).
+///
+/// # Panics
+/// If the file cannot be loaded, naming the file.
+#[fixture]
+pub fn saved_env(#[default("env.fixture")] path: &str) -> FakeEnvironment {
+    FakeEnvironment::load_fixture(path)
+        .unwrap_or_else(|error| panic!("cannot load fixture file {path:?}: {error}"))
+}
+
+/// An empty fake environment, after `build` has set it up, for writing
+/// fixtures with a closure.
+pub fn fake_env_with(build: impl FnOnce(&mut FakeEnvironment)) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    build(&mut env);
+    env
+}
+
+fn seeded(vars: &[(&str, &str)]) -> FakeEnvironment {
+    fake_env_with(|env| {
+        for (key, value) in vars {
+            env.set_var(key, value);
+        }
+    })
+}
+
+/// A [`FakeEnvironment`](crate::FakeEnvironment) with the variables listed
+/// as `key => value`, set in order, for parameterizing a test over several
+/// environments with `rstest`'s `#[case]`. Requires the `rstest` feature.
+///
+/// Start from another environment with `base = expression;`, e.g. one of the
+/// fixtures in [`rstest_support`](crate::rstest_support), to change only a
+/// few of its variables. See the module's documentation for an example.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{env_case, rstest_support::unix_env, ReadEnvironment};
+/// let env = env_case!(base = unix_env(); "HOME" => "/root", "USER" => "root");
+///
+/// assert_eq!(env.var("HOME").unwrap(), "/root");
+/// assert_eq!(env.var("SHELL").unwrap(), "/bin/sh");
+/// ```
+#[macro_export]
+macro_rules! env_case {
+    (base = $base:expr; $($key:expr => $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut env: $crate::FakeEnvironment = $base;
+        $($crate::Environment::set_var(&mut env, $key, $value);)*
+        env
+    }};
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::env_case!(base = $crate::FakeEnvironment::new(); $($key => $value),*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::{fixture, rstest};
+
+    use super::{ci_env, dotenv_env, fake_env, fake_env_with, saved_env, unix_env};
+    use crate::{
+        test_helpers::TempFile, CiInfo, CiProvider, EnumerableEnvironment, Environment,
+        FakeEnvironment, ReadEnvironment,
+    };
+
+    #[fixture]
+    fn debug_env(mut unix_env: FakeEnvironment) -> FakeEnvironment {
+        unix_env.set_var("LOG_LEVEL", "debug");
+        unix_env
+    }
+
+    #[rstest]
+    fn given_the_empty_fixture_when_injected_then_it_has_no_variables(fake_env: FakeEnvironment) {
+        // Act/Assert
+        assert!(fake_env.vars_os().is_empty());
+    }
+
+    #[rstest]
+    fn given_a_fixture_built_on_a_preset_when_injected_then_both_apply(debug_env: FakeEnvironment) {
+        // Act/Assert
+        assert_eq!(debug_env.var("LOG_LEVEL").unwrap(), "debug");
+        assert_eq!(debug_env.var("HOME").unwrap(), "/home/test");
+    }
+
+    #[rstest]
+    fn given_the_ci_preset_when_detecting_ci_then_it_is_github_actions(ci_env: FakeEnvironment) {
+        // Act
+        let ci = CiInfo::detect(&ci_env).unwrap();
+
+        // Assert
+        assert_eq!(ci.provider, CiProvider::GitHubActions);
+        assert_eq!(ci.branch.as_deref(), Some("main"));
+    }
+
+    #[rstest]
+    #[case::empty(env_case!(), None)]
+    #[case::debug(env_case!("LOG_LEVEL" => "debug"), Some("debug"))]
+    #[case::later_wins(env_case!("LOG_LEVEL" => "debug", "LOG_LEVEL" => "warn",), Some("warn"))]
+    #[case::over_a_preset(env_case!(base = unix_env(); "LOG_LEVEL" => "trace"), Some("trace"))]
+    fn given_named_cases_when_reading_then_each_has_its_own_variables(
+        #[case] env: FakeEnvironment,
+        #[case] expected: Option<&str>,
+    ) {
+        // Act
+        let log_level = env.var("LOG_LEVEL").ok();
+
+        // Assert
+        assert_eq!(log_level.as_deref(), expected);
+    }
+
+    #[rstest]
+    fn given_cases_and_fixtures_when_combined_then_both_are_injected(
+        #[values(env_case!("MODE" => "fast"), fake_env_with(|env| env.set_var("MODE", "slow")))]
+        env: FakeEnvironment,
+        unix_env: FakeEnvironment,
+    ) {
+        // Act/Assert
+        assert!(env.contains("MODE"));
+        assert!(!env.contains("HOME"));
+        assert!(unix_env.contains("HOME"));
+    }
+
+    #[test]
+    fn given_files_when_loading_them_as_fixtures_then_their_variables_are_set() {
+        // Arrange
+        let dotenv = TempFile::with_contents("# settings\nPORT=8080\n");
+        let saved = TempFile::new();
+        env_case!("MODE" => "fast")
+            .save_fixture(saved.path())
+            .unwrap();
+
+        // Act
+        let from_dotenv = dotenv_env(dotenv.path().to_str().unwrap());
+        let from_saved = saved_env(saved.path().to_str().unwrap());
+
+        // Assert
+        assert_eq!(from_dotenv.var("PORT").unwrap(), "8080");
+        assert_eq!(from_saved.var("MODE").unwrap(), "fast");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot load dotenv file \"missing.env\"")]
+    fn given_a_missing_file_when_loading_it_as_a_fixture_then_it_panics() {
+        // Act
+        dotenv_env("missing.env");
+    }
+}
@@ -0,0 +1,772 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use crate::{dotenv::quote, redaction::MASK, EnumerableEnvironment, EnvError, RedactionPolicy};
+
+/// The most edits a variable name may be from a declared one to be suggested
+/// as a typo of it.
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// A declaration of the variables an application reads, for checking an
+/// environment against all at once with [`validate`](EnvSchema::validate).
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{EnvSchema, Environment, FakeEnvironment, VarKind};
+/// let schema = EnvSchema::new()
+///     .prefix("MYAPP_")
+///     .required("MYAPP_PORT", VarKind::Int)
+///     .optional("MYAPP_DEBUG", VarKind::Bool)
+///     .with_default("MYAPP_TIMEOUT", VarKind::Duration, "30s")
+///     .optional("MYAPP_LOG", VarKind::one_of(["debug", "info", "warn"]));
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("MYAPP_PORT", "eighty");
+/// fake_env.set_var("MYAPP_DEBGU", "true");
+///
+/// let report = schema.validate(&fake_env);
+///
+/// assert_eq!(report.problems().len(), 2);
+/// assert_eq!(
+///     report.to_string(),
+///     "environment variable \"MYAPP_PORT\" is invalid: expected an integer, found \"eighty\"\n\
+///      environment variable \"MYAPP_DEBGU\" is not recognized; did you mean \"MYAPP_DEBUG\"?"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnvSchema {
+    prefix: Option<String>,
+    vars: Vec<VarSpec>,
+    redaction_policy: RedactionPolicy,
+}
+
+/// A declared variable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct VarSpec {
+    name: String,
+    kind: VarKind,
+    required: bool,
+    default: Option<String>,
+}
+
+impl EnvSchema {
+    /// A schema declaring no variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report variables whose names start with `prefix` but are not declared,
+    /// as they are likely typos.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Mask the values `policy` covers in the problems reported, so a
+    /// report can be logged even when a secret is of the wrong kind.
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = policy;
+        self
+    }
+
+    /// Declare a variable that must be set to a value of `kind`.
+    pub fn required(self, name: impl Into<String>, kind: VarKind) -> Self {
+        self.declare(name.into(), kind, true, None)
+    }
+
+    /// Declare a variable that may be set to a value of `kind`.
+    pub fn optional(self, name: impl Into<String>, kind: VarKind) -> Self {
+        self.declare(name.into(), kind, false, None)
+    }
+
+    /// Declare a variable that may be set to a value of `kind`, and is
+    /// `default` otherwise. The default is validated like a set value.
+    pub fn with_default(
+        self,
+        name: impl Into<String>,
+        kind: VarKind,
+        default: impl Into<String>,
+    ) -> Self {
+        self.declare(name.into(), kind, false, Some(default.into()))
+    }
+
+    fn declare(
+        mut self,
+        name: String,
+        kind: VarKind,
+        required: bool,
+        default: Option<String>,
+    ) -> Self {
+        self.vars.retain(|var| var.name != name);
+        self.vars.push(VarSpec {
+            name,
+            kind,
+            required,
+            default,
+        });
+        self
+    }
+
+    /// Check `env` against the schema, reporting every problem found:
+    /// declared variables in the order they were declared, then unknown
+    /// variables under the prefix in name order.
+    ///
+    /// Declared variables are read with
+    /// [`var_redacted`](crate::ReadEnvironment::var_redacted), so one the
+    /// environment cannot read is reported as unavailable, and does not take
+    /// its default.
+    pub fn validate(&self, env: &impl EnumerableEnvironment) -> ValidationReport {
+        let mut problems = Vec::new();
+        for var in &self.vars {
+            let value = match env.var_redacted(&var.name) {
+                Ok(value) => value,
+                Err(EnvError::NotPresent(_)) => match &var.default {
+                    Some(default) => default.clone(),
+                    None => {
+                        if var.required {
+                            problems.push(Problem::Missing {
+                                name: var.name.clone(),
+                            });
+                        }
+                        continue;
+                    }
+                },
+                Err(EnvError::NotUnicode { .. }) => {
+                    problems.push(Problem::NotUnicode {
+                        name: var.name.clone(),
+                    });
+                    continue;
+                }
+                Err(error) => {
+                    problems.push(Problem::Unavailable {
+                        name: var.name.clone(),
+                        message: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if !var.kind.accepts(&value) {
+                let value = if self.redaction_policy.redacts(&var.name, &value) {
+                    MASK.into()
+                } else {
+                    value
+                };
+                problems.push(Problem::WrongType {
+                    name: var.name.clone(),
+                    value,
+                    expected: var.kind.clone(),
+                });
+            }
+        }
+
+        if let Some(prefix) = &self.prefix {
+            let mut unknown: Vec<_> = env
+                .vars_os()
+                .into_iter()
+                .map(|(name, _)| name.to_string_lossy().into_owned())
+                .filter(|name| name.starts_with(prefix.as_str()))
+                .filter(|name| !self.vars.iter().any(|var| &var.name == name))
+                .collect();
+            unknown.sort();
+            problems.extend(unknown.into_iter().map(|name| Problem::Unknown {
+                suggestion: self.closest(&name),
+                name,
+            }));
+        }
+        ValidationReport { problems }
+    }
+
+    /// Each declared variable that has a default, with the default.
+    pub(crate) fn defaults(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .iter()
+            .filter_map(|var| Some((var.name.as_str(), var.default.as_deref()?)))
+    }
+
+    /// Render an example dotenv file documenting each declared variable, in
+    /// the order they were declared. Each has a comment giving its kind,
+    /// whether it is required, and its default, followed by an assignment of
+    /// an example value. Assignments of optional variables are commented
+    /// out, and use the default if there is one.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{EnvSchema, VarKind};
+    /// let schema = EnvSchema::new()
+    ///     .required("MYAPP_PORT", VarKind::Int)
+    ///     .with_default("MYAPP_TIMEOUT", VarKind::Duration, "30s");
+    ///
+    /// assert_eq!(
+    ///     schema.render_example(),
+    ///     "# int, required\n\
+    ///      MYAPP_PORT=0\n\
+    ///      \n\
+    ///      ## duration, optional, default: 30s\n\
+    ///      #MYAPP_TIMEOUT=30s\n"
+    /// );
+    /// ```
+    pub fn render_example(&self) -> String {
+        let entries: Vec<_> = self
+            .vars
+            .iter()
+            .map(|var| {
+                let requirement = if var.required { "required" } else { "optional" };
+                let mut comment = format!("# {}, {requirement}", var.kind.label());
+                if let Some(default) = &var.default {
+                    comment.push_str(&format!(", default: {default}"));
+                }
+                let value = var
+                    .default
+                    .clone()
+                    .unwrap_or_else(|| var.kind.placeholder());
+                let disabled = if var.required { "" } else { "#" };
+                format!("{comment}\n{disabled}{}={}\n", var.name, quote(&value))
+            })
+            .collect();
+        entries.join("\n")
+    }
+
+    /// Write the example from [`render_example`](EnvSchema::render_example)
+    /// to the file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    /// If the file cannot be written, it returns the I/O error.
+    pub fn write_example(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.render_example())
+    }
+
+    /// The declared name closest to `name`, if it is close enough to be a
+    /// likely typo.
+    fn closest(&self, name: &str) -> Option<String> {
+        self.vars
+            .iter()
+            .map(|var| (edit_distance(name, &var.name), &var.name))
+            .filter(|(distance, _)| *distance <= MAX_TYPO_DISTANCE)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name.clone())
+    }
+}
+
+/// The kind of value a declared variable holds.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum VarKind {
+    /// Any valid Unicode.
+    String,
+    /// A signed 64-bit integer.
+    Int,
+    /// `true`, `false`, `1`, or `0`, ignoring case.
+    Bool,
+    /// One or more numbers each followed by a unit, `ms`, `s`, `m`, `h`, or
+    /// `d`, such as `30s` or `1h30m`.
+    Duration,
+    /// Exactly one of the given values.
+    OneOf(Vec<String>),
+}
+
+impl VarKind {
+    /// A [`OneOf`](VarKind::OneOf) kind from any list of values.
+    pub fn one_of<I>(values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        VarKind::OneOf(values.into_iter().map(Into::into).collect())
+    }
+
+    /// A short name for the kind, for example files.
+    fn label(&self) -> String {
+        match self {
+            VarKind::String => "string".into(),
+            VarKind::Int => "int".into(),
+            VarKind::Bool => "bool".into(),
+            VarKind::Duration => "duration".into(),
+            VarKind::OneOf(values) => format!("one of: {}", values.join(", ")),
+        }
+    }
+
+    /// An example value of the kind, for example files.
+    fn placeholder(&self) -> String {
+        match self {
+            VarKind::String => String::new(),
+            VarKind::Int => "0".into(),
+            VarKind::Bool => "false".into(),
+            VarKind::Duration => "30s".into(),
+            VarKind::OneOf(values) => values.first().cloned().unwrap_or_default(),
+        }
+    }
+
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            VarKind::String => true,
+            VarKind::Int => value.parse::<i64>().is_ok(),
+            VarKind::Bool => ["true", "false", "1", "0"]
+                .iter()
+                .any(|accepted| value.eq_ignore_ascii_case(accepted)),
+            VarKind::Duration => is_duration(value),
+            VarKind::OneOf(values) => values.iter().any(|accepted| accepted == value),
+        }
+    }
+}
+
+impl fmt::Display for VarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarKind::String => f.write_str("a string"),
+            VarKind::Int => f.write_str("an integer"),
+            VarKind::Bool => f.write_str("a boolean"),
+            VarKind::Duration => f.write_str("a duration such as 30s"),
+            VarKind::OneOf(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+
+/// Whether `value` is a sequence of numbers each followed by a unit.
+fn is_duration(value: &str) -> bool {
+    let mut rest = value;
+    if rest.is_empty() {
+        return false;
+    }
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let Some(unit) = ["ms", "s", "m", "h", "d"]
+            .iter()
+            .find(|unit| rest.starts_with(*unit))
+        else {
+            return false;
+        };
+        rest = &rest[unit.len()..];
+    }
+    true
+}
+
+/// The number of single-character insertions, deletions, and substitutions
+/// to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Every problem [`EnvSchema::validate`](EnvSchema::validate) found with an
+/// environment. It displays as one line per problem.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationReport {
+    problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    pub(crate) fn push(&mut self, problem: Problem) {
+        self.problems.push(problem);
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.problems.is_empty() {
+            return f.write_str("the environment is valid");
+        }
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationReport {}
+
+/// A problem with one variable, found by
+/// [`EnvSchema::validate`](EnvSchema::validate).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Problem {
+    /// A required variable is not set.
+    Missing { name: String },
+    /// A declared variable's value is not valid Unicode.
+    NotUnicode { name: String },
+    /// A declared variable could not be read, such as a secret whose backend
+    /// is unreachable. Holds the environment's error, never a value.
+    Unavailable { name: String, message: String },
+    /// A declared variable's value, or its default, is not of its kind.
+    WrongType {
+        name: String,
+        value: String,
+        expected: VarKind,
+    },
+    /// A variable under the prefix is not declared. Holds the closest
+    /// declared name, if it looks like a typo of one.
+    Unknown {
+        name: String,
+        suggestion: Option<String>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Missing { name } => {
+                write!(f, "environment variable {name:?} is required but not set")
+            }
+            Problem::NotUnicode { name } => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            Problem::Unavailable { message, .. } => f.write_str(message),
+            Problem::WrongType {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "environment variable {name:?} is invalid: expected {expected}, found {value:?}"
+            ),
+            Problem::Unknown { name, suggestion } => {
+                write!(f, "environment variable {name:?} is not recognized")?;
+                match suggestion {
+                    Some(suggestion) => write!(f, "; did you mean {suggestion:?}?"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        os::unix::ffi::OsStrExt,
+    };
+
+    use super::{EnvSchema, Problem, VarKind};
+    use crate::{
+        test_helpers::fake_env, test_helpers::TempFile, DotenvEnvironment, EnumerableEnvironment,
+        EnvError, Environment, FakeEnvironment, ReadEnvironment, RedactionPolicy,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn schema() -> EnvSchema {
+        EnvSchema::new()
+            .prefix("MYAPP_")
+            .required("MYAPP_DATABASE_URL", VarKind::String)
+            .required("MYAPP_PORT", VarKind::Int)
+            .optional("MYAPP_DEBUG", VarKind::Bool)
+            .with_default("MYAPP_TIMEOUT", VarKind::Duration, "30s")
+            .optional("MYAPP_LOG_LEVEL", VarKind::one_of(["debug", "info"]))
+    }
+
+    #[test]
+    fn given_a_redaction_policy_when_a_secret_has_the_wrong_kind_then_the_problem_masks_it() {
+        // Arrange
+        let schema = EnvSchema::new()
+            .required("MYAPP_API_TOKEN", VarKind::Int)
+            .required("MYAPP_PORT", VarKind::Int)
+            .redaction_policy(RedactionPolicy::new().secret("*_TOKEN"));
+        let env = fake_env(&[("MYAPP_API_TOKEN", "tok_abc123"), ("MYAPP_PORT", "eighty")]);
+
+        // Act
+        let report = schema.validate(&env);
+
+        // Assert
+        assert_eq!(
+            report.to_string(),
+            "environment variable \"MYAPP_API_TOKEN\" is invalid: expected an integer, found \
+             \"***\"\n\
+             environment variable \"MYAPP_PORT\" is invalid: expected an integer, found \"eighty\""
+        );
+        assert!(!format!("{report:?}").contains("tok_abc123"));
+    }
+
+    #[test]
+    fn given_a_fully_valid_environment_when_validating_then_there_are_no_problems() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_DATABASE_URL", "postgres://localhost/app"),
+            ("MYAPP_PORT", "8080"),
+            ("MYAPP_DEBUG", "TRUE"),
+            ("MYAPP_TIMEOUT", "1h30m"),
+            ("MYAPP_LOG_LEVEL", "info"),
+            ("HOME", "/home/me"),
+        ]);
+
+        // Act
+        let report = schema().validate(&env);
+
+        // Assert
+        assert!(report.is_valid());
+        assert_eq!(report.to_string(), "the environment is valid");
+    }
+
+    #[test]
+    fn given_missing_required_variables_when_validating_then_each_is_reported() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let report = schema().validate(&env);
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [
+                Problem::Missing {
+                    name: "MYAPP_DATABASE_URL".into()
+                },
+                Problem::Missing {
+                    name: "MYAPP_PORT".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn given_values_of_the_wrong_type_when_validating_then_each_is_reported() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_DATABASE_URL", "postgres://localhost/app"),
+            ("MYAPP_PORT", "eighty"),
+            ("MYAPP_DEBUG", "yes please"),
+            ("MYAPP_TIMEOUT", "30"),
+            ("MYAPP_LOG_LEVEL", "verbose"),
+        ]);
+
+        // Act
+        let report = schema().validate(&env);
+
+        // Assert
+        let names: Vec<_> = report
+            .problems()
+            .iter()
+            .map(|problem| match problem {
+                Problem::WrongType { name, .. } => name.as_str(),
+                other => panic!("unexpected problem {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "MYAPP_PORT",
+                "MYAPP_DEBUG",
+                "MYAPP_TIMEOUT",
+                "MYAPP_LOG_LEVEL"
+            ]
+        );
+        assert_eq!(
+            report.problems()[3].to_string(),
+            "environment variable \"MYAPP_LOG_LEVEL\" is invalid: expected one of debug, info, \
+             found \"verbose\""
+        );
+    }
+
+    #[test]
+    fn given_unknown_variables_under_the_prefix_when_validating_then_they_are_reported() {
+        // Arrange
+        let env = fake_env(&[
+            ("MYAPP_DATABASE_URL", "postgres://localhost/app"),
+            ("MYAPP_PORT", "8080"),
+            ("MYAPP_DEBGU", "true"),
+            ("MYAPP_UNRELATED", "1"),
+            ("OTHER_DEBGU", "true"),
+        ]);
+
+        // Act
+        let report = schema().validate(&env);
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [
+                Problem::Unknown {
+                    name: "MYAPP_DEBGU".into(),
+                    suggestion: Some("MYAPP_DEBUG".into()),
+                },
+                Problem::Unknown {
+                    name: "MYAPP_UNRELATED".into(),
+                    suggestion: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn given_an_invalid_default_when_validating_then_it_is_reported() {
+        // Arrange
+        let schema = EnvSchema::new().with_default("TIMEOUT", VarKind::Duration, "soon");
+        let env = FakeEnvironment::new();
+
+        // Act
+        let report = schema.validate(&env);
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [Problem::WrongType {
+                name: "TIMEOUT".into(),
+                value: "soon".into(),
+                expected: VarKind::Duration,
+            }]
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_validating_then_it_is_reported() {
+        // Arrange
+        let schema = EnvSchema::new().optional("NAME", VarKind::String);
+        let mut env = FakeEnvironment::new();
+        env.set_var("NAME", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let report = schema.validate(&env);
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [Problem::NotUnicode {
+                name: "NAME".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn given_a_variable_the_environment_cannot_read_when_validating_then_it_is_unavailable() {
+        // Arrange
+        /// An environment whose backend never answers.
+        struct Unreachable;
+
+        impl ReadEnvironment for Unreachable {
+            fn var_os(&self, _key: impl AsRef<OsStr>) -> Option<OsString> {
+                None
+            }
+
+            fn var_redacted(&self, key: impl AsRef<OsStr>) -> Result<String, EnvError> {
+                Err(EnvError::Unavailable {
+                    key: key.as_ref().into(),
+                    message: "timed out".into(),
+                })
+            }
+        }
+
+        impl EnumerableEnvironment for Unreachable {
+            fn vars_os(&self) -> Vec<(OsString, OsString)> {
+                Vec::new()
+            }
+        }
+        let schema = EnvSchema::new().with_default("MYAPP_TIMEOUT", VarKind::Duration, "30s");
+
+        // Act
+        let report = schema.validate(&Unreachable);
+
+        // Assert
+        assert_eq!(
+            report.problems(),
+            [Problem::Unavailable {
+                name: "MYAPP_TIMEOUT".into(),
+                message: "environment variable \"MYAPP_TIMEOUT\" is unavailable: timed out".into(),
+            }]
+        );
+    }
+
+    fn example_schema() -> EnvSchema {
+        EnvSchema::new()
+            .required("MYAPP_DATABASE_URL", VarKind::String)
+            .required("MYAPP_PORT", VarKind::Int)
+            .optional("MYAPP_DEBUG", VarKind::Bool)
+            .with_default("MYAPP_TIMEOUT", VarKind::Duration, "30s")
+            .required("MYAPP_LOG_LEVEL", VarKind::one_of(["debug", "info"]))
+            .with_default("MYAPP_GREETING", VarKind::String, "hello \"world\" # hi")
+    }
+
+    #[test]
+    fn given_every_kind_when_rendering_an_example_then_each_variable_is_documented() {
+        // Arrange
+        let schema = example_schema();
+
+        // Act
+        let example = schema.render_example();
+
+        // Assert
+        assert_eq!(
+            example,
+            "# string, required\n\
+             MYAPP_DATABASE_URL=\n\
+             \n\
+             # int, required\n\
+             MYAPP_PORT=0\n\
+             \n\
+             # bool, optional\n\
+             #MYAPP_DEBUG=false\n\
+             \n\
+             # duration, optional, default: 30s\n\
+             #MYAPP_TIMEOUT=30s\n\
+             \n\
+             # one of: debug, info, required\n\
+             MYAPP_LOG_LEVEL=debug\n\
+             \n\
+             # string, optional, default: hello \"world\" # hi\n\
+             #MYAPP_GREETING=\"hello \\\"world\\\" # hi\"\n"
+        );
+    }
+
+    #[test]
+    fn given_a_written_example_when_loading_it_then_required_variables_are_set() {
+        // Arrange
+        let file = TempFile::new();
+
+        // Act
+        example_schema().write_example(file.path()).unwrap();
+
+        // Assert
+        let env = DotenvEnvironment::open(file.path()).unwrap();
+        assert_eq!(env.var("MYAPP_DATABASE_URL").unwrap(), "");
+        assert_eq!(env.var("MYAPP_PORT").unwrap(), "0");
+        assert_eq!(env.var("MYAPP_LOG_LEVEL").unwrap(), "debug");
+        assert!(!env.contains("MYAPP_TIMEOUT"));
+        assert!(example_schema().validate(&env).is_valid());
+    }
+
+    #[test]
+    fn given_an_uncommented_example_when_loading_it_then_defaults_parse_back_unchanged() {
+        // Arrange
+        let example = example_schema().render_example();
+        let uncommented: String = example
+            .lines()
+            .map(|line| {
+                line.strip_prefix("#MYAPP_")
+                    .map_or(line.to_string(), |rest| format!("MYAPP_{rest}"))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let file = TempFile::with_contents(&uncommented);
+
+        // Act
+        let env = DotenvEnvironment::open(file.path()).unwrap();
+
+        // Assert
+        assert_eq!(env.var("MYAPP_TIMEOUT").unwrap(), "30s");
+        assert_eq!(env.var("MYAPP_DEBUG").unwrap(), "false");
+        assert_eq!(env.var("MYAPP_GREETING").unwrap(), "hello \"world\" # hi");
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    env::VarError,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{var_from_os, EnvError, Environment, ReadEnvironment};
+
+/// A backend that secrets are fetched from, such as a vault or a cloud
+/// secrets manager, for [`SecretsOverlayEnvironment`](SecretsOverlayEnvironment).
+pub trait SecretSource {
+    /// Fetch the secret named `key`, or `None` if the source has no such
+    /// secret.
+    ///
+    /// # Errors
+    /// If the source cannot be reached or refuses the request.
+    fn fetch(&self, key: &OsStr) -> Result<Option<OsString>, SecretError>;
+}
+
+impl<S: SecretSource + ?Sized> SecretSource for &S {
+    fn fetch(&self, key: &OsStr) -> Result<Option<OsString>, SecretError> {
+        (**self).fetch(key)
+    }
+}
+
+/// An error from fetching a secret from a [`SecretSource`](SecretSource).
+/// It holds the key and a description of the failure, never a value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SecretError {
+    key: OsString,
+    message: String,
+}
+
+impl SecretError {
+    /// The error for fetching `key`, which failed as `message` describes.
+    pub fn new(key: impl AsRef<OsStr>, message: impl Into<String>) -> Self {
+        SecretError {
+            key: key.as_ref().into(),
+            message: message.into(),
+        }
+    }
+
+    /// The key whose secret could not be fetched.
+    pub fn key(&self) -> &OsStr {
+        &self.key
+    }
+
+    /// A description of the failure.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot fetch secret {:?}: {}", self.key, self.message)
+    }
+}
+
+impl Error for SecretError {}
+
+impl From<SecretError> for EnvError {
+    fn from(error: SecretError) -> Self {
+        EnvError::Unavailable {
+            key: error.key,
+            message: error.message,
+        }
+    }
+}
+
+/// A [`SecretSource`](SecretSource) that holds its secrets in memory, for
+/// tests. It counts its fetches, and can be told to fail for some keys.
+///
+/// # Example
+/// ```rust
+/// # use std::ffi::OsStr;
+/// # use env_wrapper::{InMemorySecretSource, SecretSource};
+/// let source = InMemorySecretSource::new()
+///     .with_secret("DB_PASSWORD", "hunter2")
+///     .with_failure("API_KEY", "permission denied");
+///
+/// assert_eq!(source.fetch(OsStr::new("DB_PASSWORD")).unwrap().unwrap(), "hunter2");
+/// assert!(source.fetch(OsStr::new("API_KEY")).is_err());
+/// assert_eq!(source.fetches(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemorySecretSource {
+    secrets: HashMap<OsString, OsString>,
+    failures: HashMap<OsString, String>,
+    fetches: Cell<usize>,
+}
+
+impl InMemorySecretSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold `value` as the secret named `key`.
+    pub fn with_secret(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.secrets
+            .insert(key.as_ref().into(), value.as_ref().into());
+        self
+    }
+
+    /// Fail every fetch of `key` with `message`.
+    pub fn with_failure(mut self, key: impl AsRef<OsStr>, message: impl Into<String>) -> Self {
+        self.failures.insert(key.as_ref().into(), message.into());
+        self
+    }
+
+    /// How many times a secret has been fetched, including failed fetches.
+    pub fn fetches(&self) -> usize {
+        self.fetches.get()
+    }
+}
+
+impl SecretSource for InMemorySecretSource {
+    fn fetch(&self, key: &OsStr) -> Result<Option<OsString>, SecretError> {
+        self.fetches.set(self.fetches.get() + 1);
+        match self.failures.get(key) {
+            Some(message) => Err(SecretError::new(key, message.clone())),
+            None => Ok(self.secrets.get(key).cloned()),
+        }
+    }
+}
+
+/// A wrapper that reads a configured set of keys, such as `DB_PASSWORD`,
+/// from a [`SecretSource`](SecretSource), and every other key from the
+/// inner environment.
+///
+/// Each secret is fetched at most once: its answer, including that the
+/// source has no such secret, is cached. Failed fetches are not cached, so
+/// the next read tries again. `var` and `var_os` cannot return why a fetch
+/// failed, so they read such a secret as not set.
+/// [`var_redacted`](ReadEnvironment::var_redacted), which code generic over
+/// any environment can call, returns an
+/// [`EnvError::Unavailable`](EnvError::Unavailable) instead, and
+/// [`try_var_os`](SecretsOverlayEnvironment::try_var_os) the
+/// [`SecretError`](SecretError) itself.
+///
+/// Writes to secret keys are kept in the cache, where they shadow the
+/// source, which is never written to. Writes to other keys go to the inner
+/// environment, including through `try_set_var` and `try_remove_var`.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{
+/// #     Environment, FakeEnvironment, InMemorySecretSource, ReadEnvironment,
+/// #     SecretsOverlayEnvironment,
+/// # };
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("DB_HOST", "db.internal");
+/// fake_env.set_var("DB_PASSWORD", "not the real one");
+/// let source = InMemorySecretSource::new().with_secret("DB_PASSWORD", "hunter2");
+///
+/// let env = SecretsOverlayEnvironment::new(fake_env, source).secret("DB_PASSWORD");
+///
+/// assert_eq!(env.var("DB_HOST").unwrap(), "db.internal");
+/// assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+/// ```
+pub struct SecretsOverlayEnvironment<E, S> {
+    inner: E,
+    source: S,
+    keys: HashSet<OsString>,
+    cache: RefCell<HashMap<OsString, Option<OsString>>>,
+}
+
+impl<E: ReadEnvironment, S: SecretSource> SecretsOverlayEnvironment<E, S> {
+    /// Wrap `inner`, with no secret keys yet.
+    pub fn new(inner: E, source: S) -> Self {
+        SecretsOverlayEnvironment {
+            inner,
+            source,
+            keys: HashSet::new(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Read `key` from the source instead of the inner environment.
+    pub fn secret(mut self, key: impl AsRef<OsStr>) -> Self {
+        self.keys.insert(key.as_ref().into());
+        self
+    }
+
+    /// Read `key`, fetching it from the source if it is a secret key that
+    /// is not cached yet.
+    ///
+    /// # Errors
+    /// If the source fails to fetch the secret.
+    pub fn try_var_os(&self, key: impl AsRef<OsStr>) -> Result<Option<OsString>, SecretError> {
+        let key = key.as_ref();
+        if !self.keys.contains(key) {
+            return Ok(self.inner.var_os(key));
+        }
+        if let Some(value) = self.cache.borrow().get(key) {
+            return Ok(value.clone());
+        }
+        let value = self.source.fetch(key)?;
+        self.cache.borrow_mut().insert(key.into(), value.clone());
+        Ok(value)
+    }
+
+    /// Forget the cached secrets, so they are fetched again, including any
+    /// written locally.
+    pub fn clear_cache(&mut self) {
+        self.cache.get_mut().clear();
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: fmt::Debug, S> fmt::Debug for SecretsOverlayEnvironment<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the keys of cached secrets are shown, never their values.
+        let cached: Vec<_> = self.cache.borrow().keys().cloned().collect();
+        f.debug_struct("SecretsOverlayEnvironment")
+            .field("inner", &self.inner)
+            .field("keys", &self.keys)
+            .field("cached", &cached)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E: ReadEnvironment, S: SecretSource> ReadEnvironment for SecretsOverlayEnvironment<E, S> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        if self.keys.contains(key.as_ref()) {
+            var_from_os(self.var_os(key))
+        } else {
+            self.inner.var(key)
+        }
+    }
+
+    /// Reads a secret that could not be fetched as not set.
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.try_var_os(key).ok().flatten()
+    }
+
+    fn var_redacted(&self, key: impl AsRef<OsStr>) -> Result<String, EnvError> {
+        let key = key.as_ref();
+        let value = self.try_var_os(key)?;
+        var_from_os(value).map_err(|error| EnvError::from_var_error(key, error))
+    }
+}
+
+impl<E: Environment, S: SecretSource> Environment for SecretsOverlayEnvironment<E, S> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        if self.keys.contains(key) {
+            self.cache
+                .get_mut()
+                .insert(key.into(), Some(value.as_ref().into()));
+        } else {
+            self.inner.set_var(key, value);
+        }
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        if self.keys.contains(key) {
+            self.cache.get_mut().insert(key.into(), None);
+        } else {
+            self.inner.remove_var(key);
+        }
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let key = key.as_ref();
+        if self.keys.contains(key) {
+            self.set_var(key, value);
+            Ok(())
+        } else {
+            self.inner.try_set_var(key, value)
+        }
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        let key = key.as_ref();
+        if self.keys.contains(key) {
+            self.remove_var(key);
+            Ok(())
+        } else {
+            self.inner.try_remove_var(key)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::{InMemorySecretSource, SecretError, SecretsOverlayEnvironment};
+    use crate::{
+        test_helpers::fake_env, EnvError, Environment, FakeEnvironment, ReadEnvironment,
+        ReadOnlyEnvironment,
+    };
+
+    fn source() -> InMemorySecretSource {
+        InMemorySecretSource::new()
+            .with_secret("DB_PASSWORD", "hunter2")
+            .with_failure("API_KEY", "permission denied")
+    }
+
+    #[test]
+    fn given_repeated_reads_when_reading_secrets_then_each_is_fetched_once() {
+        // Arrange
+        let source = source();
+        let env = SecretsOverlayEnvironment::new(FakeEnvironment::new(), &source)
+            .secret("DB_PASSWORD")
+            .secret("MISSING_SECRET");
+
+        // Act
+        for _ in 0..3 {
+            assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+            assert!(env.var_os("MISSING_SECRET").is_none());
+        }
+
+        // Assert
+        assert_eq!(source.fetches(), 2);
+    }
+
+    #[test]
+    fn given_keys_that_are_not_secret_when_reading_then_the_inner_environment_answers() {
+        // Arrange
+        let source = source();
+        let env = SecretsOverlayEnvironment::new(
+            fake_env(&[("DB_HOST", "db.internal"), ("DB_PASSWORD", "from env")]),
+            &source,
+        );
+
+        // Act
+        let host = env.var("DB_HOST");
+        let password = env.var("DB_PASSWORD");
+
+        // Assert
+        assert_eq!(host.unwrap(), "db.internal");
+        assert_eq!(password.unwrap(), "from env");
+        assert_eq!(source.fetches(), 0);
+    }
+
+    #[test]
+    fn given_a_failing_source_when_reading_then_the_error_is_returned_and_not_cached() {
+        // Arrange
+        let source = source();
+        let env = SecretsOverlayEnvironment::new(fake_env(&[("API_KEY", "from env")]), &source)
+            .secret("API_KEY");
+
+        // Act
+        let first = env.try_var_os("API_KEY");
+        let second = env.try_var_os("API_KEY");
+
+        // Assert
+        let error = first.unwrap_err();
+        assert_eq!(error, SecretError::new("API_KEY", "permission denied"));
+        assert_eq!(error.key(), OsStr::new("API_KEY"));
+        assert_eq!(
+            error.to_string(),
+            "cannot fetch secret \"API_KEY\": permission denied"
+        );
+        assert_eq!(second.unwrap_err(), error);
+        assert_eq!(source.fetches(), 2);
+    }
+
+    #[test]
+    fn given_a_failing_source_when_reading_redacted_then_it_is_unavailable() {
+        // Arrange
+        let source = source();
+        let env = SecretsOverlayEnvironment::new(fake_env(&[("API_KEY", "from env")]), &source)
+            .secret("API_KEY")
+            .secret("DB_PASSWORD");
+
+        // Act
+        let infallible = env.var_os("API_KEY");
+        let redacted = env.var_redacted("API_KEY");
+        let fetched = env.var_redacted("DB_PASSWORD");
+
+        // Assert
+        assert!(infallible.is_none());
+        assert_eq!(
+            redacted.unwrap_err(),
+            EnvError::Unavailable {
+                key: "API_KEY".into(),
+                message: "permission denied".into(),
+            }
+        );
+        assert_eq!(fetched.unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn given_writes_to_secret_keys_when_reading_then_they_shadow_the_source() {
+        // Arrange
+        let source = source();
+        let mut env = SecretsOverlayEnvironment::new(FakeEnvironment::new(), &source)
+            .secret("DB_PASSWORD")
+            .secret("API_KEY");
+
+        // Act
+        env.set_var("API_KEY", "local");
+        env.remove_var("DB_PASSWORD");
+        env.set_var("LOG_LEVEL", "debug");
+
+        // Assert
+        assert_eq!(env.var("API_KEY").unwrap(), "local");
+        assert!(env.var_os("DB_PASSWORD").is_none());
+        assert_eq!(source.fetches(), 0);
+        env.clear_cache();
+        assert_eq!(env.var("DB_PASSWORD").unwrap(), "hunter2");
+        let inner = env.into_inner();
+        assert_eq!(inner.var("LOG_LEVEL").unwrap(), "debug");
+        assert!(inner.var_os("API_KEY").is_none());
+    }
+
+    #[test]
+    fn given_a_read_only_inner_environment_when_trying_writes_then_only_secret_keys_succeed() {
+        // Arrange
+        let source = source();
+        let mut env = SecretsOverlayEnvironment::new(
+            ReadOnlyEnvironment::new(FakeEnvironment::new()),
+            &source,
+        )
+        .secret("API_KEY");
+
+        // Act
+        let secret_set = env.try_set_var("API_KEY", "local");
+        let secret_removed = env.try_remove_var("API_KEY");
+        let other_set = env.try_set_var("LOG_LEVEL", "debug");
+        let other_removed = env.try_remove_var("LOG_LEVEL");
+
+        // Assert
+        assert_eq!(secret_set, Ok(()));
+        assert_eq!(secret_removed, Ok(()));
+        assert_eq!(other_set, Err(EnvError::ReadOnly("LOG_LEVEL".into())));
+        assert_eq!(other_removed, Err(EnvError::ReadOnly("LOG_LEVEL".into())));
+    }
+
+    #[test]
+    fn when_debug_formatting_then_cached_secret_values_are_not_shown() {
+        // Arrange
+        let source = source();
+        let env =
+            SecretsOverlayEnvironment::new(FakeEnvironment::new(), &source).secret("DB_PASSWORD");
+        env.var("DB_PASSWORD").unwrap();
+
+        // Act
+        let debug = format!("{env:?}");
+
+        // Assert
+        assert!(debug.contains("DB_PASSWORD"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{var_from_os, EnumerableEnvironment, EnvError, Environment, ReadEnvironment};
+
+/// What reading a key returns once its queued values have all been read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Exhausted {
+    /// Keep returning the last queued value.
+    #[default]
+    RepeatLast,
+    /// Read as not set.
+    Unset,
+    /// Panic, naming the key.
+    Panic,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Queue {
+    values: VecDeque<OsString>,
+    last: Option<OsString>,
+}
+
+/// A wrapper that returns queued values for successive reads of a key, for
+/// testing code that re-reads a variable, such as retry logic expecting a
+/// refreshed value.
+///
+/// Each `var`, `var_os`, or `contains` call on a key with queued values takes
+/// the next one. Once they run out, reads follow the [`Exhausted`](Exhausted)
+/// policy. Keys without queued values read from the wrapped environment.
+///
+/// Setting or removing a key through the wrapper discards its queue, so the
+/// written value is read from then on. Listing variables passes through to
+/// the wrapped environment and does not take queued values.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{FakeEnvironment, ReadEnvironment, SequenceEnvironment};
+/// let mut env = SequenceEnvironment::new(FakeEnvironment::new());
+/// env.queue_values("TOKEN", ["stale", "refreshed"]);
+///
+/// assert_eq!(env.var("TOKEN").unwrap(), "stale");
+/// assert_eq!(env.var("TOKEN").unwrap(), "refreshed");
+/// assert_eq!(env.var("TOKEN").unwrap(), "refreshed");
+/// ```
+#[derive(Clone, Debug)]
+pub struct SequenceEnvironment<E> {
+    inner: E,
+    queues: RefCell<HashMap<OsString, Queue>>,
+    exhausted: Exhausted,
+}
+
+impl<E: ReadEnvironment> SequenceEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        SequenceEnvironment {
+            inner,
+            queues: RefCell::new(HashMap::new()),
+            exhausted: Exhausted::default(),
+        }
+    }
+
+    /// Queue `values` to be returned by the next reads of `key`, after any
+    /// values already queued for it.
+    pub fn queue_values(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        values: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> &mut Self {
+        self.queues
+            .get_mut()
+            .entry(key.as_ref().into())
+            .or_default()
+            .values
+            .extend(values.into_iter().map(|value| value.as_ref().into()));
+        self
+    }
+
+    /// Choose what reads return once a key's queued values run out.
+    pub fn on_exhausted(&mut self, policy: Exhausted) -> &mut Self {
+        self.exhausted = policy;
+        self
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    /// The next value for `key`, or `None` if it has no queue.
+    fn next_value(&self, key: &OsStr) -> Option<Option<OsString>> {
+        let mut queues = self.queues.borrow_mut();
+        let queue = queues.get_mut(key)?;
+        if let Some(value) = queue.values.pop_front() {
+            queue.last = Some(value.clone());
+            return Some(Some(value));
+        }
+        match self.exhausted {
+            // A key queued with no values has nothing to repeat, so it reads
+            // from the wrapped environment.
+            Exhausted::RepeatLast => queue.last.clone().map(Some),
+            Exhausted::Unset => Some(None),
+            Exhausted::Panic => {
+                drop(queues);
+                panic!("no more values are queued for environment variable {key:?}")
+            }
+        }
+    }
+
+    fn discard_queue(&mut self, key: &OsStr) {
+        self.queues.get_mut().remove(key);
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for SequenceEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        match self.next_value(key.as_ref()) {
+            Some(value) => var_from_os(value),
+            None => self.inner.var(key),
+        }
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.next_value(key.as_ref()) {
+            Some(value) => value,
+            None => self.inner.var_os(key),
+        }
+    }
+}
+
+impl<E: Environment> Environment for SequenceEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.discard_queue(key.as_ref());
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.discard_queue(key.as_ref());
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(&key, value)?;
+        self.discard_queue(key.as_ref());
+        Ok(())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(&key)?;
+        self.discard_queue(key.as_ref());
+        Ok(())
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for SequenceEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::VarError;
+
+    use super::{Exhausted, SequenceEnvironment};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment};
+
+    #[test]
+    fn given_queued_values_when_reading_repeatedly_then_they_are_returned_in_order() {
+        // Arrange
+        let mut env = SequenceEnvironment::new(FakeEnvironment::new());
+        env.queue_values("TOKEN", ["one", "two"])
+            .queue_values("TOKEN", ["three"]);
+
+        // Act/Assert
+        assert_eq!(env.var("TOKEN").unwrap(), "one");
+        assert_eq!(env.var_os("TOKEN").unwrap(), "two");
+        assert_eq!(env.var("TOKEN").unwrap(), "three");
+    }
+
+    #[test]
+    fn given_exhausted_values_when_repeating_the_last_then_it_is_returned_again() {
+        // Arrange
+        let mut env = SequenceEnvironment::new(FakeEnvironment::new());
+        env.queue_values("TOKEN", ["stale", "fresh"]);
+        let _ = env.var("TOKEN");
+        let _ = env.var("TOKEN");
+
+        // Act/Assert
+        assert_eq!(env.var("TOKEN").unwrap(), "fresh");
+        assert_eq!(env.var("TOKEN").unwrap(), "fresh");
+    }
+
+    #[test]
+    fn given_exhausted_values_when_becoming_unset_then_the_key_is_not_present() {
+        // Arrange
+        let mut env = SequenceEnvironment::new(FakeEnvironment::new());
+        env.queue_values("TOKEN", ["only"])
+            .on_exhausted(Exhausted::Unset);
+        let _ = env.var("TOKEN");
+
+        // Act/Assert
+        assert_eq!(env.var("TOKEN").unwrap_err(), VarError::NotPresent);
+        assert!(!env.contains("TOKEN"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no more values are queued for environment variable \"TOKEN\"")]
+    fn given_exhausted_values_when_panicking_then_the_read_panics() {
+        // Arrange
+        let mut env = SequenceEnvironment::new(FakeEnvironment::new());
+        env.queue_values("TOKEN", ["only"])
+            .on_exhausted(Exhausted::Panic);
+        let _ = env.var("TOKEN");
+
+        // Act
+        let _ = env.var("TOKEN");
+    }
+
+    #[test]
+    fn given_queued_values_when_setting_the_key_then_the_set_value_overrides_the_queue() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("OTHER", "inner");
+        let mut env = SequenceEnvironment::new(inner);
+        env.queue_values("TOKEN", ["one", "two"]);
+        let _ = env.var("TOKEN");
+
+        // Act
+        env.set_var("TOKEN", "explicit");
+
+        // Assert
+        assert_eq!(env.var("TOKEN").unwrap(), "explicit");
+        assert_eq!(env.var("TOKEN").unwrap(), "explicit");
+        assert_eq!(env.var("OTHER").unwrap(), "inner");
+    }
+}
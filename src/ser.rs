@@ -0,0 +1,884 @@
+use std::{error::Error, fmt};
+
+use serde::ser::{
+    self, Impossible, Serialize, SerializeSeq, SerializeStruct, SerializeTuple,
+    SerializeTupleStruct, Serializer,
+};
+
+use crate::Environment;
+
+/// Serialize a configuration struct into variables in `env`, the reverse of
+/// [`from_env`](crate::from_env). Requires the `serde` feature.
+///
+/// Each field is written to the variable named after it, cased and prefixed
+/// according to `options`. Values are written as text:
+/// * numbers, `bool`s, and strings as they display,
+/// * `Option` fields as their value, or as configured when `None`,
+/// * sequences with their elements joined by commas, and
+/// * unit enum variants by name.
+///
+/// Nested structs are flattened, so with the default options the field
+/// `cert_path` of a field `tls` is written to `TLS_CERT_PATH`. Maps, bytes,
+/// and enum variants with data are not supported.
+///
+/// Every value is serialized before any variable is set, so on error `env`
+/// is left unchanged.
+///
+/// # Errors
+/// If `value` is not a struct, or a field's value cannot be written as text,
+/// it returns a [`SerError`](SerError).
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{to_env, FakeEnvironment, ReadEnvironment, ToEnvOptions};
+/// #[derive(serde::Serialize)]
+/// struct Config {
+///     port: u16,
+///     log_level: Option<String>,
+///     allowed_hosts: Vec<String>,
+/// }
+///
+/// let config = Config {
+///     port: 8080,
+///     log_level: None,
+///     allowed_hosts: vec!["example.com".into(), "example.org".into()],
+/// };
+/// let mut fake_env = FakeEnvironment::new();
+///
+/// to_env(&config, &mut fake_env, ToEnvOptions::new().prefix("MYAPP_")).unwrap();
+///
+/// assert_eq!(fake_env.var("MYAPP_PORT").unwrap(), "8080");
+/// assert!(!fake_env.contains("MYAPP_LOG_LEVEL"));
+/// assert_eq!(fake_env.var("MYAPP_ALLOWED_HOSTS").unwrap(), "example.com,example.org");
+/// ```
+pub fn to_env<T: Serialize + ?Sized>(
+    value: &T,
+    env: &mut impl Environment,
+    options: ToEnvOptions,
+) -> Result<(), SerError> {
+    let mut vars = Vec::new();
+    value.serialize(EnvSerializer {
+        options: &options,
+        vars: &mut vars,
+    })?;
+    for (key, value) in vars {
+        env.set_var(key, value);
+    }
+    Ok(())
+}
+
+/// How [`to_env`](to_env) names variables and writes `None`s.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{FieldCase, NoneValue, ToEnvOptions};
+/// let options = ToEnvOptions::new()
+///     .prefix("MYAPP_")
+///     .field_case(FieldCase::Unchanged)
+///     .none_values(NoneValue::Empty)
+///     .nested_separator("__");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToEnvOptions {
+    prefix: String,
+    case: FieldCase,
+    none: NoneValue,
+    separator: String,
+}
+
+impl ToEnvOptions {
+    /// Upper-case field names without a prefix, skip `None`s, and join
+    /// nested field names with `_`, matching [`from_env`](crate::from_env).
+    pub fn new() -> Self {
+        ToEnvOptions {
+            prefix: String::new(),
+            case: FieldCase::Upper,
+            none: NoneValue::Skip,
+            separator: "_".into(),
+        }
+    }
+
+    /// Start every variable name with `prefix`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// How to case field names. The prefix is kept as given.
+    pub fn field_case(mut self, case: FieldCase) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// How to write `Option` fields that are `None`.
+    pub fn none_values(mut self, policy: NoneValue) -> Self {
+        self.none = policy;
+        self
+    }
+
+    /// What to put between the name of a nested struct's field and the names
+    /// of its own fields.
+    pub fn nested_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    fn field_name(&self, field: &str) -> String {
+        match self.case {
+            FieldCase::Upper => field.to_ascii_uppercase(),
+            FieldCase::Lower => field.to_ascii_lowercase(),
+            FieldCase::Unchanged => field.into(),
+        }
+    }
+}
+
+impl Default for ToEnvOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How [`to_env`](to_env) cases field names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FieldCase {
+    /// `database_url` is written to `DATABASE_URL`.
+    Upper,
+    /// `databaseUrl` is written to `databaseurl`.
+    Lower,
+    /// Field names are used as they are, after any `#[serde(rename)]`.
+    Unchanged,
+}
+
+/// How [`to_env`](to_env) writes `Option` fields that are `None`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum NoneValue {
+    /// Leave the variable untouched.
+    Skip,
+    /// Set the variable to the empty string.
+    Empty,
+}
+
+/// An error from serializing a struct with [`to_env`](to_env).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SerError {
+    /// The value for the named variable cannot be written as text.
+    Invalid { variable: String, message: String },
+    /// Any other error, such as trying to serialize a type that is not a
+    /// struct.
+    Message(String),
+}
+
+impl SerError {
+    /// Attribute an error from serializing a value to the variable it was
+    /// for.
+    fn in_variable(self, variable: &str) -> Self {
+        match self {
+            SerError::Message(message) => SerError::Invalid {
+                variable: variable.into(),
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for SerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerError::Invalid { variable, message } => {
+                write!(f, "environment variable {variable:?} is invalid: {message}")
+            }
+            SerError::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for SerError {}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerError::Message(msg.to_string())
+    }
+}
+
+/// The variables collected so far, in the order they were serialized.
+type Vars = Vec<(String, String)>;
+
+/// Serializes a struct into the variables named after its fields.
+struct EnvSerializer<'a> {
+    options: &'a ToEnvOptions,
+    vars: &'a mut Vars,
+}
+
+impl EnvSerializer<'_> {
+    fn unsupported<T>(self) -> Result<T, SerError> {
+        Err(SerError::Message(
+            "only structs can be serialized into the environment".into(),
+        ))
+    }
+}
+
+/// Implement `Serializer`'s methods for scalars, which display as text,
+/// with `$body`.
+macro_rules! serialize_scalars {
+    ($self:ident, $value:ident => $body:expr) => {
+        serialize_scalars! { @each $self, $value => $body;
+            serialize_bool: bool,
+            serialize_i8: i8,
+            serialize_i16: i16,
+            serialize_i32: i32,
+            serialize_i64: i64,
+            serialize_i128: i128,
+            serialize_u8: u8,
+            serialize_u16: u16,
+            serialize_u32: u32,
+            serialize_u64: u64,
+            serialize_u128: u128,
+            serialize_f32: f32,
+            serialize_f64: f64,
+            serialize_char: char,
+            serialize_str: &str,
+        }
+    };
+    (@each $self:ident, $value:ident => $body:expr; $($serialize:ident: $ty:ty,)*) => {
+        $(
+            fn $serialize($self, $value: $ty) -> Result<Self::Ok, SerError> {
+                $body
+            }
+        )*
+    };
+}
+
+/// Implement the `Serializer` methods that every serializer here rejects or
+/// handles by forwarding to another method.
+macro_rules! serialize_rest {
+    ($ok:ty) => {
+        fn serialize_newtype_struct<T: Serialize + ?Sized>(
+            self,
+            _name: &'static str,
+            value: &T,
+        ) -> Result<$ok, SerError> {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            _value: &T,
+        ) -> Result<$ok, SerError> {
+            Err(SerError::Message(format!(
+                "cannot write enum variant {variant:?} with data as text"
+            )))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeTupleVariant, SerError> {
+            Err(SerError::Message(format!(
+                "cannot write enum variant {variant:?} with data as text"
+            )))
+        }
+
+        fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerError> {
+            Err(SerError::Message("cannot write a map as text".into()))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeStructVariant, SerError> {
+            Err(SerError::Message(format!(
+                "cannot write enum variant {variant:?} with data as text"
+            )))
+        }
+    };
+}
+
+impl<'a> Serializer for EnvSerializer<'a> {
+    type Ok = ();
+    type Error = SerError;
+    type SerializeSeq = Impossible<(), SerError>;
+    type SerializeTuple = Impossible<(), SerError>;
+    type SerializeTupleStruct = Impossible<(), SerError>;
+    type SerializeTupleVariant = Impossible<(), SerError>;
+    type SerializeMap = Impossible<(), SerError>;
+    type SerializeStruct = StructFields<'a>;
+    type SerializeStructVariant = Impossible<(), SerError>;
+
+    serialize_scalars!(self, _value => self.unsupported());
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_none(self) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_unit(self) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        self.unsupported()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerError> {
+        Ok(StructFields {
+            path: self.options.prefix.clone(),
+            options: self.options,
+            vars: self.vars,
+        })
+    }
+
+    serialize_rest!(());
+}
+
+/// Serializes each field of a struct into its own variable.
+struct StructFields<'a> {
+    /// What to start the name of each field's variable with.
+    path: String,
+    options: &'a ToEnvOptions,
+    vars: &'a mut Vars,
+}
+
+impl SerializeStruct for StructFields<'_> {
+    type Ok = ();
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerError> {
+        let variable = format!("{}{}", self.path, self.options.field_name(key));
+        value
+            .serialize(FieldSerializer {
+                variable: variable.clone(),
+                options: self.options,
+                vars: self.vars,
+            })
+            .map_err(|error| error.in_variable(&variable))
+    }
+
+    fn end(self) -> Result<(), SerError> {
+        Ok(())
+    }
+}
+
+/// Serializes one field into the named variable, or into one variable per
+/// field if it is a struct.
+struct FieldSerializer<'a> {
+    variable: String,
+    options: &'a ToEnvOptions,
+    vars: &'a mut Vars,
+}
+
+impl FieldSerializer<'_> {
+    fn push(self, value: String) -> Result<(), SerError> {
+        self.vars.push((self.variable, value));
+        Ok(())
+    }
+}
+
+impl<'a> Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = SerError;
+    type SerializeSeq = ListElements<'a>;
+    type SerializeTuple = ListElements<'a>;
+    type SerializeTupleStruct = ListElements<'a>;
+    type SerializeTupleVariant = Impossible<(), SerError>;
+    type SerializeMap = Impossible<(), SerError>;
+    type SerializeStruct = StructFields<'a>;
+    type SerializeStructVariant = Impossible<(), SerError>;
+
+    serialize_scalars!(self, value => self.push(value.to_string()));
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), SerError> {
+        self.push(ValueSerializer.serialize_bytes(value)?)
+    }
+
+    fn serialize_none(self) -> Result<(), SerError> {
+        match self.options.none {
+            NoneValue::Skip => Ok(()),
+            NoneValue::Empty => self.push(String::new()),
+        }
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerError> {
+        self.push(String::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerError> {
+        self.push(String::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerError> {
+        self.push(variant.into())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, SerError> {
+        Ok(ListElements {
+            elements: Vec::with_capacity(len.unwrap_or_default()),
+            field: self,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerError> {
+        Ok(StructFields {
+            path: self.variable + &self.options.separator,
+            options: self.options,
+            vars: self.vars,
+        })
+    }
+
+    serialize_rest!(());
+}
+
+/// Serializes the elements of a sequence into one comma-separated variable.
+struct ListElements<'a> {
+    field: FieldSerializer<'a>,
+    elements: Vec<String>,
+}
+
+impl ListElements<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.elements.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SerError> {
+        let value = self.elements.join(",");
+        self.field.push(value)
+    }
+}
+
+impl SerializeSeq for ListElements<'_> {
+    type Ok = ();
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), SerError> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for ListElements<'_> {
+    type Ok = ();
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), SerError> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for ListElements<'_> {
+    type Ok = ();
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), SerError> {
+        self.finish()
+    }
+}
+
+/// Serializes a single value, such as a list element, as text.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = String;
+    type Error = SerError;
+    type SerializeSeq = Impossible<String, SerError>;
+    type SerializeTuple = Impossible<String, SerError>;
+    type SerializeTupleStruct = Impossible<String, SerError>;
+    type SerializeTupleVariant = Impossible<String, SerError>;
+    type SerializeMap = Impossible<String, SerError>;
+    type SerializeStruct = Impossible<String, SerError>;
+    type SerializeStructVariant = Impossible<String, SerError>;
+
+    serialize_scalars!(self, value => Ok(value.to_string()));
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String, SerError> {
+        Err(SerError::Message("cannot write bytes as text".into()))
+    }
+
+    fn serialize_none(self) -> Result<String, SerError> {
+        Err(SerError::Message(
+            "cannot write a missing value in a list".into(),
+        ))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, SerError> {
+        Ok(String::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerError> {
+        Ok(String::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerError> {
+        Ok(variant.into())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerError> {
+        Err(SerError::Message(
+            "cannot write a nested list as text".into(),
+        ))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerError> {
+        Err(SerError::Message(
+            "cannot write a nested list as text".into(),
+        ))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        Err(SerError::Message(
+            "cannot write a nested list as text".into(),
+        ))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerError> {
+        Err(SerError::Message("cannot write a struct in a list".into()))
+    }
+
+    serialize_rest!(String);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{to_env, FieldCase, NoneValue, SerError, ToEnvOptions};
+    use crate::{
+        from_env, EnumerableEnvironment, FakeEnvironment, PrefixedEnvironment, ReadEnvironment,
+    };
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum LogLevel {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Port(u16);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Config {
+        database_url: String,
+        port: Port,
+        workers: Option<u32>,
+        timeout_secs: f64,
+        debug: bool,
+        log_level: LogLevel,
+        allowed_hosts: Vec<String>,
+        retry_delays_ms: Vec<u64>,
+        #[serde(rename = "MYAPP_REGION")]
+        region: Option<String>,
+    }
+
+    fn config() -> Config {
+        Config {
+            database_url: "postgres://localhost/app".into(),
+            port: Port(8080),
+            workers: None,
+            timeout_secs: 2.5,
+            debug: true,
+            log_level: LogLevel::Info,
+            allowed_hosts: vec!["example.com".into(), "example.org".into()],
+            retry_delays_ms: vec![],
+            region: Some("eu-west-1".into()),
+        }
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Server {
+        name: String,
+        tls: Tls,
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Tls {
+        cert_path: String,
+        key_path: Option<String>,
+    }
+
+    fn vars(env: &FakeEnvironment) -> Vec<(String, String)> {
+        let mut vars: Vec<_> = env
+            .vars_os()
+            .into_iter()
+            .map(|(key, value)| (key.into_string().unwrap(), value.into_string().unwrap()))
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    #[test]
+    fn given_a_config_when_serializing_then_it_deserializes_back_unchanged() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+
+        // Act
+        to_env(&config(), &mut env, ToEnvOptions::new()).unwrap();
+
+        // Assert
+        assert_eq!(env.var("PORT").unwrap(), "8080");
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+        assert_eq!(env.var("ALLOWED_HOSTS").unwrap(), "example.com,example.org");
+        assert!(!env.contains("WORKERS"));
+        assert_eq!(from_env::<Config>(&env).unwrap(), config());
+    }
+
+    #[test]
+    fn given_a_prefix_when_serializing_then_a_prefixed_view_deserializes_it_back() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        let options = ToEnvOptions::new().prefix("APP_");
+
+        // Act
+        to_env(&config(), &mut env, options).unwrap();
+
+        // Assert
+        assert_eq!(
+            env.var("APP_DATABASE_URL").unwrap(),
+            "postgres://localhost/app"
+        );
+        assert!(!env.contains("DATABASE_URL"));
+        let view = PrefixedEnvironment::new(env, "APP_");
+        assert_eq!(from_env::<Config>(&view).unwrap(), config());
+    }
+
+    #[test]
+    fn given_empty_none_values_when_serializing_then_none_fields_are_set_to_empty() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        let options = ToEnvOptions::new().none_values(NoneValue::Empty);
+
+        // Act
+        to_env(&config(), &mut env, options).unwrap();
+
+        // Assert
+        assert_eq!(env.var("WORKERS").unwrap(), "");
+        assert_eq!(env.var("MYAPP_REGION").unwrap(), "eu-west-1");
+    }
+
+    #[test]
+    fn given_a_nested_struct_when_serializing_then_its_fields_are_flattened() {
+        // Arrange
+        let server = Server {
+            name: "api".into(),
+            tls: Tls {
+                cert_path: "/etc/tls/cert.pem".into(),
+                key_path: None,
+            },
+        };
+        let mut default_env = FakeEnvironment::new();
+        let mut custom_env = FakeEnvironment::new();
+        let custom = ToEnvOptions::new()
+            .field_case(FieldCase::Lower)
+            .nested_separator("__");
+
+        // Act
+        to_env(&server, &mut default_env, ToEnvOptions::new()).unwrap();
+        to_env(&server, &mut custom_env, custom).unwrap();
+
+        // Assert
+        assert_eq!(
+            vars(&default_env),
+            [
+                ("NAME".into(), "api".into()),
+                ("TLS_CERT_PATH".into(), "/etc/tls/cert.pem".into()),
+            ]
+        );
+        assert_eq!(
+            vars(&custom_env),
+            [
+                ("name".into(), "api".into()),
+                ("tls__cert_path".into(), "/etc/tls/cert.pem".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_unchanged_case_when_serializing_then_field_names_are_kept() {
+        // Arrange
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Names {
+            database_url: &'static str,
+        }
+        let mut env = FakeEnvironment::new();
+        let options = ToEnvOptions::new().field_case(FieldCase::Unchanged);
+
+        // Act
+        to_env(&Names { database_url: "x" }, &mut env, options).unwrap();
+
+        // Assert
+        assert_eq!(vars(&env), [("databaseUrl".into(), "x".into())]);
+    }
+
+    #[test]
+    fn given_an_unsupported_field_when_serializing_then_nothing_is_set() {
+        // Arrange
+        #[derive(Serialize)]
+        struct WithMap {
+            name: &'static str,
+            labels: HashMap<String, String>,
+        }
+        let value = WithMap {
+            name: "api",
+            labels: HashMap::new(),
+        };
+        let mut env = FakeEnvironment::new();
+
+        // Act
+        let result = to_env(&value, &mut env, ToEnvOptions::new());
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            SerError::Invalid {
+                variable: "LABELS".into(),
+                message: "cannot write a map as text".into(),
+            }
+        );
+        assert!(!env.contains("NAME"));
+    }
+
+    #[test]
+    fn given_a_nested_list_when_serializing_then_the_error_names_the_variable() {
+        // Arrange
+        #[derive(Serialize)]
+        struct Grid {
+            rows: Vec<Vec<u8>>,
+        }
+        let mut env = FakeEnvironment::new();
+
+        // Act
+        let result = to_env(
+            &Grid {
+                rows: vec![vec![1]],
+            },
+            &mut env,
+            ToEnvOptions::new(),
+        );
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "environment variable \"ROWS\" is invalid: cannot write a nested list as text"
+        );
+    }
+
+    #[test]
+    fn when_serializing_something_other_than_a_struct_then_it_is_an_error() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+
+        // Act
+        let result = to_env(&42, &mut env, ToEnvOptions::new());
+
+        // Assert
+        assert!(matches!(result.unwrap_err(), SerError::Message(_)));
+    }
+}
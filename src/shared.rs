@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use crate::{
+    is_valid_key, EnumerableEnvironment, Environment, Provenance, ReadEnvironment,
+    SourcedEnvironment, VersionedEnvironment,
+};
+
+/// A fake process environment whose clones all share the same variables, for
+/// testing code that hands an environment to several threads.
+///
+/// Unlike [`FakeEnvironment`](crate::FakeEnvironment), cloning does not copy
+/// the variables: a variable set through one clone can be read through every
+/// other clone, on any thread.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, ReadEnvironment, SharedFakeEnvironment};
+/// let fake_env = SharedFakeEnvironment::new();
+///
+/// let mut worker_env = fake_env.clone();
+/// std::thread::spawn(move || worker_env.set_var("WORKER_STATE", "done"))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(fake_env.var("WORKER_STATE").unwrap(), "done");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SharedFakeEnvironment {
+    env_vars: Arc<RwLock<HashMap<OsString, OsString>>>,
+    // Bumped while the variables are locked for writing.
+    generation: Arc<AtomicU64>,
+}
+
+impl SharedFakeEnvironment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The variables are valid even if a panic interrupted another user.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<OsString, OsString>> {
+        self.env_vars.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<OsString, OsString>> {
+        self.env_vars
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Remove every variable, for every clone.
+    pub fn clear(&mut self) {
+        let mut vars = self.write();
+        if !vars.is_empty() {
+            vars.clear();
+            self.bump_generation();
+        }
+    }
+}
+
+impl ReadEnvironment for SharedFakeEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.read().get(key.as_ref()).cloned()
+    }
+}
+
+impl SourcedEnvironment for SharedFakeEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.var(key).map(|value| (value, Provenance::Fake))
+    }
+}
+
+impl Environment for SharedFakeEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        // As with FakeEnvironment, a key the platform never stores is ignored.
+        if !is_valid_key(key) {
+            return;
+        }
+        let mut vars = self.write();
+        vars.insert(key.into(), value.as_ref().into());
+        self.bump_generation();
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let mut vars = self.write();
+        if vars.remove(key.as_ref()).is_some() {
+            self.bump_generation();
+        }
+    }
+}
+
+/// Clones share their generation, as they share their variables.
+impl VersionedEnvironment for SharedFakeEnvironment {
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+}
+
+impl EnumerableEnvironment for SharedFakeEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.read()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod shared_fake_environment_conformance {
+    crate::conformance_tests!(super::SharedFakeEnvironment::new(), enumerable);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::SharedFakeEnvironment;
+    use crate::{EnumerableEnvironment, Environment, ReadEnvironment, VersionedEnvironment};
+
+    #[test]
+    fn given_clones_on_several_threads_when_each_writes_a_key_then_all_writes_are_merged() {
+        // Arrange
+        let fake_env = SharedFakeEnvironment::new();
+
+        // Act
+        let workers: Vec<_> = (0..8)
+            .map(|index| {
+                let mut worker_env = fake_env.clone();
+                thread::spawn(move || worker_env.set_var(format!("WORKER_{index}"), "done"))
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        // Assert
+        let mut vars = fake_env.vars_os();
+        vars.sort();
+        let expected: Vec<_> = (0..8)
+            .map(|index| (format!("WORKER_{index}").into(), "done".into()))
+            .collect();
+        assert_eq!(vars, expected);
+    }
+
+    #[test]
+    fn given_a_write_on_one_thread_when_reading_on_another_then_it_is_visible() {
+        // Arrange
+        let mut writer_env = SharedFakeEnvironment::new();
+        let reader_env = writer_env.clone();
+        let (written, was_written) = mpsc::channel();
+        let reader = thread::spawn(move || {
+            was_written.recv().unwrap();
+            reader_env.var("MODE")
+        });
+
+        // Act
+        writer_env.set_var("MODE", "fast");
+        written.send(()).unwrap();
+
+        // Assert
+        assert_eq!(reader.join().unwrap().unwrap(), "fast");
+    }
+
+    #[test]
+    fn given_a_clone_when_removing_through_it_then_the_original_no_longer_sees_the_key() {
+        // Arrange
+        let mut fake_env = SharedFakeEnvironment::new();
+        fake_env.set_var("TMP", "/tmp");
+        let mut clone = fake_env.clone();
+
+        // Act
+        clone.remove_var("TMP");
+
+        // Assert
+        assert!(fake_env.var_os("TMP").is_none());
+    }
+
+    #[test]
+    fn given_changes_through_several_clones_when_reading_the_generation_then_each_counts_once() {
+        // Arrange
+        let mut fake_env = SharedFakeEnvironment::new();
+        let mut clone = fake_env.clone();
+        let start = fake_env.generation();
+
+        // Act/Assert
+        fake_env.set_var("MODE", "fast");
+        clone.set_var("MODE", "fast");
+        assert_eq!(clone.generation(), start + 2);
+        let _ = fake_env.var("MODE");
+        let _ = clone.vars_os();
+        clone.remove_var("NEVER_SET");
+        assert!(!fake_env.changed_since(start + 2));
+        clone.remove_var("MODE");
+        fake_env.clear();
+        assert_eq!(fake_env.generation(), start + 3);
+        clone.set_var("TMP", "/tmp");
+        fake_env.clear();
+        assert_eq!(clone.generation(), start + 5);
+    }
+
+    #[test]
+    fn given_a_malformed_key_when_set_through_a_clone_then_no_clone_stores_it() {
+        // Arrange
+        let fake_env = SharedFakeEnvironment::new();
+        let mut clone = fake_env.clone();
+        let start = fake_env.generation();
+
+        // Act
+        clone.set_var("A=B", "1");
+        clone.set_var("", "1");
+
+        // Assert
+        assert!(fake_env.var_os("A=B").is_none());
+        assert!(fake_env.vars_os().is_empty());
+        assert_eq!(fake_env.generation(), start);
+    }
+
+    #[test]
+    fn shared_fake_environment_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedFakeEnvironment>();
+    }
+}
@@ -0,0 +1,287 @@
+use std::{error::Error, fmt};
+
+use crate::EnumerableEnvironment;
+
+/// The shell whose syntax [`ShellExportExt`](ShellExportExt) writes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Shell {
+    /// `export KEY='value'`, for `sh`, `bash`, `zsh`, and other POSIX shells.
+    Posix,
+    /// `set -gx KEY 'value'`, for `fish`.
+    Fish,
+    /// `$env:KEY = 'value'`, for PowerShell.
+    PowerShell,
+}
+
+/// An error from writing an environment as shell commands.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ShellExportError {
+    /// The name is not a valid shell variable name: ASCII letters, digits,
+    /// and `_`, not starting with a digit.
+    InvalidName(String),
+    /// The name or value of the named variable is not valid Unicode.
+    NotUnicode(String),
+    /// The value of the named variable contains a newline or NUL, which
+    /// cannot be written on one line.
+    Unrepresentable(String),
+}
+
+impl fmt::Display for ShellExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellExportError::InvalidName(name) => {
+                write!(f, "{name:?} is not a valid shell variable name")
+            }
+            ShellExportError::NotUnicode(name) => {
+                write!(f, "environment variable {name:?} is not valid Unicode")
+            }
+            ShellExportError::Unrepresentable(name) => write!(
+                f,
+                "environment variable {name:?} contains a newline or NUL, so cannot be exported \
+                 on one line"
+            ),
+        }
+    }
+}
+
+impl Error for ShellExportError {}
+
+/// Writing every variable of an environment as shell commands that set it,
+/// for bootstrapping shells and CI steps.
+pub trait ShellExportExt: EnumerableEnvironment {
+    /// Write one `export KEY='value'` line per variable, sorted by name, that
+    /// is safe to `eval` in POSIX shells. See
+    /// [`to_shell_exports_for`](ShellExportExt::to_shell_exports_for).
+    ///
+    /// # Errors
+    /// See [`to_shell_exports_for`](ShellExportExt::to_shell_exports_for).
+    fn to_shell_exports(&self) -> Result<String, ShellExportError> {
+        self.to_shell_exports_for(Shell::Posix)
+    }
+
+    /// Write one line per variable, sorted by name, that sets it in `shell`.
+    /// Values are single-quoted, so nothing in them is expanded:
+    /// * in POSIX shells, an embedded `'` is written as `'\''`,
+    /// * in fish, `'` and `\` are escaped with `\`, and
+    /// * in PowerShell, `'` and the other characters it treats as single
+    ///   quotes are doubled.
+    ///
+    /// # Errors
+    /// * [`ShellExportError::InvalidName`](ShellExportError::InvalidName) for
+    ///   a name that is not a valid shell variable name.
+    /// * [`ShellExportError::NotUnicode`](ShellExportError::NotUnicode) for a
+    ///   name or value that is not valid Unicode.
+    /// * [`ShellExportError::Unrepresentable`](ShellExportError::Unrepresentable)
+    ///   for a value containing a newline or NUL, rather than splitting it
+    ///   across lines.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use env_wrapper::{Environment, FakeEnvironment, Shell, ShellExportExt};
+    /// let mut fake_env = FakeEnvironment::new();
+    /// fake_env.set_var("GREETING", "it's $HOME");
+    ///
+    /// assert_eq!(
+    ///     fake_env.to_shell_exports().unwrap(),
+    ///     "export GREETING='it'\\''s $HOME'\n"
+    /// );
+    /// assert_eq!(
+    ///     fake_env.to_shell_exports_for(Shell::PowerShell).unwrap(),
+    ///     "$env:GREETING = 'it''s $HOME'\n"
+    /// );
+    /// ```
+    fn to_shell_exports_for(&self, shell: Shell) -> Result<String, ShellExportError> {
+        let mut vars = self
+            .vars_os()
+            .into_iter()
+            .map(|(name, value)| {
+                let name = name
+                    .into_string()
+                    .map_err(|name| ShellExportError::NotUnicode(name.to_string_lossy().into()))?;
+                let value = value
+                    .into_string()
+                    .map_err(|_| ShellExportError::NotUnicode(name.clone()))?;
+                Ok((name, value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        vars.sort();
+
+        let mut exports = String::new();
+        for (name, value) in vars {
+            if !is_shell_name(&name) {
+                return Err(ShellExportError::InvalidName(name));
+            }
+            if value.contains(['\n', '\r', '\0']) {
+                return Err(ShellExportError::Unrepresentable(name));
+            }
+            let line = match shell {
+                Shell::Posix => format!("export {name}='{}'\n", value.replace('\'', r"'\''")),
+                Shell::Fish => format!(
+                    "set -gx {name} '{}'\n",
+                    value.replace('\\', r"\\").replace('\'', r"\'")
+                ),
+                Shell::PowerShell => format!("$env:{name} = '{}'\n", double_quotes(&value)),
+            };
+            exports.push_str(&line);
+        }
+        Ok(exports)
+    }
+}
+
+impl<E: EnumerableEnvironment> ShellExportExt for E {}
+
+/// Whether `name` is a valid shell variable name.
+pub(crate) fn is_shell_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Double each character PowerShell treats as a single quote.
+fn double_quotes(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            escaped.push(c);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::{Shell, ShellExportError, ShellExportExt};
+    use crate::{Environment, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn tricky_env() -> FakeEnvironment {
+        let mut env = FakeEnvironment::new();
+        env.set_var("SPACES", "a b  c");
+        env.set_var("SINGLE", "it's");
+        env.set_var("DOUBLE", "say \"hi\"");
+        env.set_var("DOLLAR", "$HOME and ${PATH}");
+        env.set_var("BACKTICK", "`rm -rf /`");
+        env.set_var("BACKSLASH", r"C:\temp\");
+        env.set_var("EMPTY", "");
+        env
+    }
+
+    #[test]
+    fn given_tricky_values_when_exporting_for_posix_then_each_is_single_quoted() {
+        // Arrange
+        let env = tricky_env();
+
+        // Act
+        let exports = env.to_shell_exports().unwrap();
+
+        // Assert
+        assert_eq!(
+            exports,
+            "export BACKSLASH='C:\\temp\\'\n\
+             export BACKTICK='`rm -rf /`'\n\
+             export DOLLAR='$HOME and ${PATH}'\n\
+             export DOUBLE='say \"hi\"'\n\
+             export EMPTY=''\n\
+             export SINGLE='it'\\''s'\n\
+             export SPACES='a b  c'\n"
+        );
+    }
+
+    #[test]
+    fn given_tricky_values_when_exporting_for_fish_then_quotes_and_backslashes_are_escaped() {
+        // Arrange
+        let env = tricky_env();
+
+        // Act
+        let exports = env.to_shell_exports_for(Shell::Fish).unwrap();
+
+        // Assert
+        assert_eq!(
+            exports,
+            "set -gx BACKSLASH 'C:\\\\temp\\\\'\n\
+             set -gx BACKTICK '`rm -rf /`'\n\
+             set -gx DOLLAR '$HOME and ${PATH}'\n\
+             set -gx DOUBLE 'say \"hi\"'\n\
+             set -gx EMPTY ''\n\
+             set -gx SINGLE 'it\\'s'\n\
+             set -gx SPACES 'a b  c'\n"
+        );
+    }
+
+    #[test]
+    fn given_tricky_values_when_exporting_for_powershell_then_single_quotes_are_doubled() {
+        // Arrange
+        let mut env = tricky_env();
+        env.set_var("SMART", "it\u{2019}s");
+
+        // Act
+        let exports = env.to_shell_exports_for(Shell::PowerShell).unwrap();
+
+        // Assert
+        assert_eq!(
+            exports,
+            "$env:BACKSLASH = 'C:\\temp\\'\n\
+             $env:BACKTICK = '`rm -rf /`'\n\
+             $env:DOLLAR = '$HOME and ${PATH}'\n\
+             $env:DOUBLE = 'say \"hi\"'\n\
+             $env:EMPTY = ''\n\
+             $env:SINGLE = 'it''s'\n\
+             $env:SMART = 'it\u{2019}\u{2019}s'\n\
+             $env:SPACES = 'a b  c'\n"
+        );
+    }
+
+    #[test]
+    fn given_a_value_with_a_newline_when_exporting_then_it_is_refused() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("MOTD", "line one\nline two");
+
+        // Act
+        let result = env.to_shell_exports();
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            ShellExportError::Unrepresentable("MOTD".into())
+        );
+    }
+
+    #[test]
+    fn given_an_invalid_name_when_exporting_then_it_is_refused() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("1ST-VAR", "x");
+
+        // Act
+        let result = env.to_shell_exports();
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "\"1ST-VAR\" is not a valid shell variable name"
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_exporting_then_it_is_refused() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = env.to_shell_exports();
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            ShellExportError::NotUnicode("BINARY".into())
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::Arc,
+};
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    is_valid_key, EnumerableEnvironment, Environment, Provenance, ReadEnvironment,
+    SourcedEnvironment, VersionedEnvironment,
+};
+
+/// A fake process environment whose clones all share the same variables,
+/// like [`SharedFakeEnvironment`](crate::SharedFakeEnvironment), but whose
+/// reads never wait for a lock. Requires the `arc-swap` feature.
+///
+/// The variables are held as an immutable snapshot. Each read loads the
+/// current snapshot without locking, so readers on many threads do not slow
+/// each other down, which keeps timing-sensitive concurrency tests honest.
+/// Each write copies the snapshot, changes the copy, and swaps it in, so
+/// writes cost time in proportion to the number of variables.
+///
+/// # Concurrent writers
+/// * A write that races another is retried on the other's snapshot, so
+///   neither is lost. Updates built from a read and a later write, such as
+///   incrementing a counter, are not atomic, though: two threads can read
+///   the same value, and the second write overwrites the first.
+/// * A thread reads its own writes, unless another thread writes the same
+///   variable in between.
+/// * [`vars_os`](EnumerableEnvironment::vars_os) lists a single snapshot,
+///   but successive reads may each see a different one.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, ReadEnvironment, SnapshotSwapEnvironment};
+/// let fake_env = SnapshotSwapEnvironment::new();
+///
+/// let mut worker_env = fake_env.clone();
+/// std::thread::spawn(move || worker_env.set_var("WORKER_STATE", "done"))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(fake_env.var("WORKER_STATE").unwrap(), "done");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotSwapEnvironment {
+    snapshot: Arc<ArcSwap<Snapshot>>,
+}
+
+/// The variables at one point in time, and how many changes led to them.
+#[derive(Clone, Debug, Default)]
+struct Snapshot {
+    env_vars: HashMap<OsString, OsString>,
+    generation: u64,
+}
+
+impl SnapshotSwapEnvironment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove every variable, for every clone.
+    pub fn clear(&mut self) {
+        if !self.snapshot.load().env_vars.is_empty() {
+            self.update(|vars| {
+                let changed = !vars.is_empty();
+                vars.clear();
+                changed
+            });
+        }
+    }
+
+    /// Swap in a copy of the current snapshot changed by `change`, which
+    /// returns whether it changed anything, copying again if another write
+    /// swapped in a snapshot first.
+    fn update(&self, mut change: impl FnMut(&mut HashMap<OsString, OsString>) -> bool) {
+        self.snapshot.rcu(|current| {
+            let mut next = Snapshot::clone(current);
+            if change(&mut next.env_vars) {
+                next.generation += 1;
+            }
+            next
+        });
+    }
+}
+
+impl ReadEnvironment for SnapshotSwapEnvironment {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.snapshot.load().env_vars.get(key.as_ref()).cloned()
+    }
+}
+
+impl SourcedEnvironment for SnapshotSwapEnvironment {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        self.var(key).map(|value| (value, Provenance::Fake))
+    }
+}
+
+impl Environment for SnapshotSwapEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        // As with FakeEnvironment, a key the platform never stores is ignored.
+        if !is_valid_key(key) {
+            return;
+        }
+        self.update(|vars| {
+            vars.insert(key.into(), value.into());
+            true
+        });
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        if self.snapshot.load().env_vars.contains_key(key.as_ref()) {
+            self.update(|vars| vars.remove(key.as_ref()).is_some());
+        }
+    }
+}
+
+/// The generation is part of each snapshot, so it always matches the
+/// variables read with it.
+impl VersionedEnvironment for SnapshotSwapEnvironment {
+    fn generation(&self) -> u64 {
+        self.snapshot.load().generation
+    }
+}
+
+impl EnumerableEnvironment for SnapshotSwapEnvironment {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.snapshot
+            .load()
+            .env_vars
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod snapshot_swap_environment_conformance {
+    crate::conformance_tests!(super::SnapshotSwapEnvironment::new(), enumerable);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::SnapshotSwapEnvironment;
+    use crate::{EnumerableEnvironment, Environment, ReadEnvironment, VersionedEnvironment};
+
+    const WRITERS: usize = 4;
+    const READERS: usize = 8;
+    const WRITES: usize = 200;
+
+    #[test]
+    fn given_readers_on_many_threads_when_writers_write_concurrently_then_no_write_is_lost() {
+        // Arrange
+        let fake_env = SnapshotSwapEnvironment::new();
+
+        // Act
+        thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let mut writer_env = fake_env.clone();
+                scope.spawn(move || {
+                    for index in 0..WRITES {
+                        writer_env.set_var(format!("WRITER_{writer}_{index}"), index.to_string());
+                    }
+                });
+            }
+            for _ in 0..READERS {
+                let reader_env = fake_env.clone();
+                scope.spawn(move || {
+                    for _ in 0..WRITES {
+                        for writer in 0..WRITERS {
+                            // Each value is unset or the one its writer wrote.
+                            let key = format!("WRITER_{writer}_{}", WRITES / 2);
+                            if let Ok(value) = reader_env.var(key) {
+                                assert_eq!(value, (WRITES / 2).to_string());
+                            }
+                        }
+                        let listed = reader_env.vars_os().len();
+                        assert!(listed <= WRITERS * WRITES);
+                    }
+                });
+            }
+        });
+
+        // Assert
+        assert_eq!(fake_env.vars_os().len(), WRITERS * WRITES);
+        for writer in 0..WRITERS {
+            for index in 0..WRITES {
+                let value = fake_env.var(format!("WRITER_{writer}_{index}")).unwrap();
+                assert_eq!(value, index.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_clone_when_removing_through_it_then_the_original_no_longer_sees_the_key() {
+        // Arrange
+        let mut fake_env = SnapshotSwapEnvironment::new();
+        fake_env.set_var("TMP", "/tmp");
+        let mut clone = fake_env.clone();
+
+        // Act
+        clone.remove_var("TMP");
+        clone.remove_var("NEVER_SET");
+
+        // Assert
+        assert!(fake_env.var_os("TMP").is_none());
+        assert!(fake_env.vars_os().is_empty());
+    }
+
+    #[test]
+    fn given_changes_through_several_clones_when_reading_the_generation_then_each_counts_once() {
+        // Arrange
+        let mut fake_env = SnapshotSwapEnvironment::new();
+        let mut clone = fake_env.clone();
+        let start = fake_env.generation();
+
+        // Act/Assert
+        fake_env.set_var("MODE", "fast");
+        clone.set_var("MODE", "fast");
+        assert_eq!(clone.generation(), start + 2);
+        let _ = fake_env.var("MODE");
+        let _ = clone.vars_os();
+        clone.remove_var("NEVER_SET");
+        assert!(!fake_env.changed_since(start + 2));
+        clone.remove_var("MODE");
+        fake_env.clear();
+        assert_eq!(fake_env.generation(), start + 3);
+        clone.set_var("TMP", "/tmp");
+        fake_env.clear();
+        assert_eq!(clone.generation(), start + 5);
+    }
+
+    #[test]
+    fn given_concurrent_writers_when_they_finish_then_the_generation_counts_every_write() {
+        // Arrange
+        let fake_env = SnapshotSwapEnvironment::new();
+
+        // Act
+        thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let mut writer_env = fake_env.clone();
+                scope.spawn(move || {
+                    for index in 0..WRITES {
+                        writer_env.set_var(format!("WRITER_{writer}_{index}"), "1");
+                    }
+                });
+            }
+        });
+
+        // Assert
+        assert_eq!(fake_env.generation(), (WRITERS * WRITES) as u64);
+    }
+
+    #[test]
+    fn snapshot_swap_environment_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SnapshotSwapEnvironment>();
+    }
+}
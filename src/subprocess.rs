@@ -0,0 +1,164 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{self, Command, ExitStatus},
+    thread,
+};
+
+use crate::{CommandExt, EnumerableEnvironment};
+
+/// Set in the child to the name of the test it re-runs.
+const CHILD_MARKER: &str = "__ENV_WRAPPER_SUBPROCESS_TEST";
+
+/// Written by the child just before it runs the function, so the parent can
+/// drop the test harness's own output.
+const OUTPUT_START: &str = "\u{1e}env_wrapper subprocess output\u{1e}";
+
+/// What a function run with [`run_in_subprocess`](run_in_subprocess) did.
+#[derive(Clone, Debug)]
+pub struct SubprocessResult {
+    /// How the child exited. Its code is what the function returned, or 101
+    /// if the function panicked.
+    pub status: ExitStatus,
+    /// What the function wrote to stdout, converted lossily.
+    pub stdout: String,
+    /// What the function wrote to stderr, converted lossily.
+    pub stderr: String,
+}
+
+/// Run `f` in a separate process whose environment is exactly `env`, and
+/// wait for it, for code whose environment is only isolated by a process
+/// boundary, such as C libraries that read and cache it.
+///
+/// This must be called from a test run by the standard test harness. It
+/// re-runs the current test binary with only the calling test selected,
+/// output capture off, and `env` as its whole environment. When the test
+/// reaches this call again in the child, it runs `f` and exits with the code
+/// `f` returns, instead of starting another child. So:
+/// * call it at most once in each test, and
+/// * do the test's setup after the call where possible, since the child
+///   repeats everything before it.
+///
+/// Since the child inherits nothing, it has no `PATH`, and on Windows no
+/// `SystemRoot`, unless `env` sets them.
+///
+/// # Panics
+/// If it is not called from a test, or the test binary cannot be run.
+///
+/// # Example
+/// ```rust,no_run
+/// # use env_wrapper::{run_in_subprocess, Environment, FakeEnvironment};
+/// fn child() -> i32 {
+///     print!("{}", std::env::var("GREETING").unwrap());
+///     0
+/// }
+///
+/// // In a test module, annotate this with `#[test]`.
+/// fn given_a_fake_when_running_in_a_subprocess_then_the_child_sees_it() {
+///     let mut fake_env = FakeEnvironment::new();
+///     fake_env.set_var("GREETING", "hello");
+///
+///     let result = run_in_subprocess(&fake_env, child);
+///
+///     assert!(result.status.success());
+///     assert_eq!(result.stdout, "hello");
+/// }
+/// ```
+pub fn run_in_subprocess(env: &impl EnumerableEnvironment, f: fn() -> i32) -> SubprocessResult {
+    let current = thread::current();
+    let test_name = match current.name() {
+        Some(name) if name != "main" => name,
+        _ => panic!("run_in_subprocess must be called from a test"),
+    };
+    if env::var_os(CHILD_MARKER).is_some_and(|marker| marker == test_name) {
+        run_child(f);
+    }
+
+    let binary = env::current_exe().expect("the test binary can be found");
+    let output = Command::new(binary)
+        .args([test_name, "--exact", "--nocapture", "--test-threads=1"])
+        .with_exact_env(env)
+        .env(CHILD_MARKER, test_name)
+        .output()
+        .expect("the test binary can be run");
+    SubprocessResult {
+        status: output.status,
+        stdout: after_start(&output.stdout),
+        stderr: after_start(&output.stderr),
+    }
+}
+
+/// Run `f` as the child, and exit with its result.
+fn run_child(f: fn() -> i32) -> ! {
+    // Only the child's own test runs, so nothing else reads the environment.
+    env::remove_var(CHILD_MARKER);
+    print!("{OUTPUT_START}");
+    eprint!("{OUTPUT_START}");
+    let _ = io::stdout().flush();
+    let code = f();
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    process::exit(code)
+}
+
+/// The output written after the child's start marker.
+fn after_start(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(output);
+    match output.split_once(OUTPUT_START) {
+        Some((_, after)) => after.to_owned(),
+        None => output.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_in_subprocess;
+    use crate::{Environment, FakeEnvironment};
+
+    fn print_environment() -> i32 {
+        let mut vars: Vec<_> = std::env::vars().collect();
+        vars.sort();
+        for (key, value) in vars {
+            println!("{key}={value}");
+        }
+        eprint!("done");
+        7
+    }
+
+    fn panic_in_child() -> i32 {
+        panic!("the child failed");
+    }
+
+    #[test]
+    fn given_a_fake_when_running_in_a_subprocess_then_the_child_sees_only_its_variables() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("GREETING", "hello world");
+        env.set_var("EMPTY", "");
+
+        // Act
+        let result = run_in_subprocess(&env, print_environment);
+
+        // Assert
+        assert_eq!(result.status.code(), Some(7));
+        assert_eq!(result.stdout, "EMPTY=\nGREETING=hello world\n");
+        assert_eq!(result.stderr, "done");
+    }
+
+    #[test]
+    fn given_a_panicking_function_when_running_in_a_subprocess_then_the_child_fails() {
+        // Arrange
+        let env = FakeEnvironment::new();
+
+        // Act
+        let result = run_in_subprocess(&env, panic_in_child);
+
+        // Assert
+        assert_eq!(result.status.code(), Some(101));
+        assert!(
+            result.stderr.contains("the child failed"),
+            "{}",
+            result.stderr
+        );
+    }
+}
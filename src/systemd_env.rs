@@ -0,0 +1,431 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    str::Chars,
+};
+
+use crate::{shell::is_shell_name, EnumerableEnvironment, Environment, FakeEnvironment};
+
+/// The characters systemd escapes with `\` inside double-quoted values.
+const NEEDS_ESCAPE: [char; 4] = ['"', '\\', '`', '$'];
+
+impl FakeEnvironment {
+    /// A fake environment with the variables a systemd unit would get from
+    /// the file at `path` through `EnvironmentFile=`.
+    ///
+    /// Each assignment is `KEY=VALUE`. Lines starting with `#` or `;` are
+    /// comments, and later assignments to a key win. Values may be:
+    /// * unquoted, with trailing whitespace removed, and `\` taking the next
+    ///   character literally,
+    /// * single-quoted, taken literally, or
+    /// * double-quoted, with `\"`, `\\`, `` \` ``, and `\$` escapes, and any
+    ///   other `\` kept.
+    ///
+    /// Quoted values may span lines, and a `\` at the end of a line continues
+    /// the value, or comment, on the next one. Text after a closing quote is
+    /// joined to the value, as systemd does.
+    ///
+    /// # Errors
+    /// * If the file cannot be read, it returns the I/O error.
+    /// * If the file is not valid UTF-8, or has an assignment systemd would
+    ///   ignore with a warning, such as a line without `=`, an invalid name,
+    ///   or an unterminated quote, it returns an `ErrorKind::InvalidData`
+    ///   error naming the line.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use env_wrapper::{FakeEnvironment, ReadEnvironment};
+    /// let fake_env = FakeEnvironment::from_systemd_env_file("/etc/default/myapp")?;
+    ///
+    /// assert!(fake_env.var("MYAPP_PORT").is_ok());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_systemd_env_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let vars = parse_systemd_env(&contents).map_err(|message| {
+            io::Error::new(ErrorKind::InvalidData, format!("{path:?}: {message}"))
+        })?;
+        let mut env = FakeEnvironment::new();
+        for (key, value) in vars {
+            env.set_var(key, value);
+        }
+        Ok(env)
+    }
+}
+
+/// Export of an environment as a systemd environment file, for
+/// `EnvironmentFile=` in units and drop-ins.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, SystemdEnvFileExt};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+/// fake_env.set_var("GREETING", "hello \"world\"");
+///
+/// let file = fake_env.to_systemd_env_file().unwrap();
+///
+/// assert_eq!(file, "GREETING=\"hello \\\"world\\\"\"\nPORT=8080\n");
+/// ```
+pub trait SystemdEnvFileExt: EnumerableEnvironment {
+    /// Write every variable as a `KEY=VALUE` line, sorted by key. Values with
+    /// anything but letters, digits, and `_-.,:/@+%` are double-quoted, with
+    /// `"`, `\`, `` ` ``, and `$` escaped, and newlines kept inside the
+    /// quotes.
+    ///
+    /// # Errors
+    /// * If a key is not a valid variable name, of ASCII letters, digits, and
+    ///   `_`, not starting with a digit, or a value contains NUL, it returns
+    ///   an `ErrorKind::InvalidInput` error.
+    /// * If a key or value is not valid Unicode, it returns an
+    ///   `ErrorKind::InvalidData` error.
+    fn to_systemd_env_file(&self) -> io::Result<String> {
+        let mut vars = self
+            .vars_os()
+            .into_iter()
+            .map(
+                |(key, value)| match (key.into_string(), value.into_string()) {
+                    (Ok(key), Ok(value)) => Ok((key, value)),
+                    (key, _) => {
+                        let key = key.unwrap_or_else(|key| key.to_string_lossy().into_owned());
+                        Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("environment variable {key:?} is not valid Unicode"),
+                        ))
+                    }
+                },
+            )
+            .collect::<io::Result<Vec<_>>>()?;
+        vars.sort();
+
+        let mut file = String::new();
+        for (key, value) in vars {
+            if !is_shell_name(&key) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{key:?} is not a valid systemd variable name"),
+                ));
+            }
+            if value.contains('\0') {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("environment variable {key:?} contains NUL"),
+                ));
+            }
+            file.push_str(&key);
+            file.push('=');
+            file.push_str(&quote(&value));
+            file.push('\n');
+        }
+        Ok(file)
+    }
+}
+
+impl<E: EnumerableEnvironment> SystemdEnvFileExt for E {}
+
+/// Parse systemd environment file contents into assignments, in order.
+fn parse_systemd_env(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    let mut chars = contents.chars();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '#' | ';' => skip_comment(&mut chars, &mut line),
+            c if c.is_whitespace() => {}
+            c => {
+                let start = line;
+                let mut key = String::from(c);
+                loop {
+                    match chars.next() {
+                        Some('=') => break,
+                        Some('\n' | '\r') | None => {
+                            return Err(format!("line {start}: expected KEY=VALUE"))
+                        }
+                        Some(c) => key.push(c),
+                    }
+                }
+                let key = key.trim_end();
+                if !is_shell_name(key) {
+                    return Err(format!("line {start}: {key:?} is not a valid name"));
+                }
+                let value = parse_value(&mut chars, &mut line)
+                    .map_err(|message| format!("line {start}: {message}"))?;
+                vars.push((key.to_owned(), value));
+            }
+        }
+    }
+    Ok(vars)
+}
+
+/// Skip the rest of a comment, including any lines it continues onto.
+fn skip_comment(chars: &mut Chars<'_>, line: &mut usize) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next() == Some('\n') => *line += 1,
+            '\n' => {
+                *line += 1;
+                return;
+            }
+            '\r' => return,
+            _ => {}
+        }
+    }
+}
+
+enum ValueState {
+    /// Before the value, or after a closing quote.
+    Before,
+    Unquoted,
+    SingleQuoted,
+    DoubleQuoted,
+}
+
+/// Parse a value after its `=`, up to and including the end of its line.
+fn parse_value(chars: &mut Chars<'_>, line: &mut usize) -> Result<String, String> {
+    let mut value = String::new();
+    // Where the unquoted whitespace at the end of the value starts.
+    let mut trailing_whitespace = None;
+    let mut state = ValueState::Before;
+    while let Some(c) = chars.next() {
+        match state {
+            ValueState::Before | ValueState::Unquoted if matches!(c, '\n' | '\r') => {
+                if c == '\n' {
+                    *line += 1;
+                }
+                break;
+            }
+            ValueState::Before if c == '\'' => state = ValueState::SingleQuoted,
+            ValueState::Before if c == '"' => state = ValueState::DoubleQuoted,
+            ValueState::Before if c.is_whitespace() => {}
+            ValueState::Before | ValueState::Unquoted => {
+                state = ValueState::Unquoted;
+                if c == '\\' {
+                    trailing_whitespace = None;
+                    match chars.next() {
+                        Some('\n') => *line += 1,
+                        Some('\r') | None => {}
+                        Some(escaped) => value.push(escaped),
+                    }
+                } else {
+                    if !c.is_whitespace() {
+                        trailing_whitespace = None;
+                    } else if trailing_whitespace.is_none() {
+                        trailing_whitespace = Some(value.len());
+                    }
+                    value.push(c);
+                }
+            }
+            ValueState::SingleQuoted => match c {
+                '\'' => state = ValueState::Before,
+                c => {
+                    if c == '\n' {
+                        *line += 1;
+                    }
+                    value.push(c);
+                }
+            },
+            ValueState::DoubleQuoted => match c {
+                '"' => state = ValueState::Before,
+                '\\' => match chars.next() {
+                    Some(escaped) if NEEDS_ESCAPE.contains(&escaped) => value.push(escaped),
+                    Some('\n') => *line += 1,
+                    Some(other) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                c => {
+                    if c == '\n' {
+                        *line += 1;
+                    }
+                    value.push(c);
+                }
+            },
+        }
+    }
+    match state {
+        ValueState::SingleQuoted => Err("unterminated single-quoted value".into()),
+        ValueState::DoubleQuoted => Err("unterminated double-quoted value".into()),
+        ValueState::Before | ValueState::Unquoted => {
+            if let Some(end) = trailing_whitespace {
+                value.truncate(end);
+            }
+            Ok(value)
+        }
+    }
+}
+
+/// Write `value` so systemd reads it back unchanged.
+fn quote(value: &str) -> String {
+    let is_plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+%".contains(c));
+    if is_plain {
+        return value.to_owned();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if NEEDS_ESCAPE.contains(&c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::SystemdEnvFileExt;
+    use crate::{
+        test_helpers::fake_env, test_helpers::TempFile, EnumerableEnvironment, FakeEnvironment,
+        ReadEnvironment,
+    };
+
+    fn read(contents: &str) -> FakeEnvironment {
+        let file = TempFile::with_contents(contents);
+        FakeEnvironment::from_systemd_env_file(file.path()).unwrap()
+    }
+
+    #[test]
+    fn given_both_quote_styles_when_reading_then_each_follows_its_rules() {
+        // Act
+        let env = read(concat!(
+            "SINGLE='a \"b\" \\n $c'\n",
+            "DOUBLE=\"a \\\"b\\\" \\n \\$c \\\\ \\`d\\`\"\n",
+            "JOINED=\"left\" 'middle' right\n",
+        ));
+
+        // Assert
+        assert_eq!(env.var("SINGLE").unwrap(), "a \"b\" \\n $c");
+        assert_eq!(env.var("DOUBLE").unwrap(), "a \"b\" \\n $c \\ `d`");
+        assert_eq!(env.var("JOINED").unwrap(), "leftmiddleright");
+    }
+
+    #[test]
+    fn given_unquoted_values_with_spaces_when_reading_then_only_the_ends_are_trimmed() {
+        // Act
+        let env = read("  GREETING =   hello   world   \nESCAPED=a\\ \\ \nLITERAL=it's \"x\"\n");
+
+        // Assert
+        assert_eq!(env.var("GREETING").unwrap(), "hello   world");
+        assert_eq!(env.var("ESCAPED").unwrap(), "a  ");
+        assert_eq!(env.var("LITERAL").unwrap(), "it's \"x\"");
+    }
+
+    #[test]
+    fn given_continuations_when_reading_then_lines_are_joined() {
+        // Act
+        let env = read(concat!(
+            "UNQUOTED=one \\\n",
+            "two\n",
+            "DOUBLE=\"three \\\n",
+            "four\"\n",
+            "MULTILINE='five\n",
+            "six'\n",
+        ));
+
+        // Assert
+        assert_eq!(env.var("UNQUOTED").unwrap(), "one two");
+        assert_eq!(env.var("DOUBLE").unwrap(), "three four");
+        assert_eq!(env.var("MULTILINE").unwrap(), "five\nsix");
+    }
+
+    #[test]
+    fn given_comments_when_reading_then_they_are_ignored() {
+        // Act
+        let env = read(concat!(
+            "# a comment\n",
+            "; another comment \\\n",
+            "CONTINUED_COMMENT=ignored\n",
+            "\n",
+            "KEPT=value # not a comment\n",
+        ));
+
+        // Assert
+        assert_eq!(env.vars_os().len(), 1);
+        assert_eq!(env.var("KEPT").unwrap(), "value # not a comment");
+    }
+
+    #[test]
+    fn given_a_malformed_line_when_reading_then_it_names_the_line() {
+        // Arrange
+        let file = TempFile::with_contents("OK=1\n\nNO_EQUALS\n");
+
+        // Act
+        let result = FakeEnvironment::from_systemd_env_file(file.path());
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(
+            error.to_string().ends_with("line 3: expected KEY=VALUE"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn given_an_unterminated_quote_when_reading_then_it_is_an_error() {
+        // Arrange
+        let file = TempFile::with_contents("A=1\nB=\"open\nC=3\n");
+
+        // Act
+        let result = FakeEnvironment::from_systemd_env_file(file.path());
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .ends_with("line 2: unterminated double-quoted value"));
+    }
+
+    #[test]
+    fn given_tricky_values_when_written_and_read_back_then_they_are_unchanged() {
+        // Arrange
+        let env = fake_env(&[
+            ("PLAIN", "/usr/bin:/bin"),
+            ("SPACES", "  hello   world  "),
+            ("QUOTES", "it's \"quoted\""),
+            ("SHELLY", "$HOME `date` \\n"),
+            ("MULTILINE", "one\ntwo"),
+            ("EMPTY", ""),
+        ]);
+
+        // Act
+        let contents = env.to_systemd_env_file().unwrap();
+        let read_back = read(&contents);
+
+        // Assert
+        assert_eq!(
+            contents,
+            concat!(
+                "EMPTY=\n",
+                "MULTILINE=\"one\ntwo\"\n",
+                "PLAIN=/usr/bin:/bin\n",
+                "QUOTES=\"it's \\\"quoted\\\"\"\n",
+                "SHELLY=\"\\$HOME \\`date\\` \\\\n\"\n",
+                "SPACES=\"  hello   world  \"\n",
+            )
+        );
+        assert_eq!(read_back, env);
+    }
+
+    #[test]
+    fn given_an_invalid_name_when_writing_then_it_is_refused() {
+        // Arrange
+        let env = fake_env(&[("MY-VAR", "x")]);
+
+        // Act
+        let result = env.to_systemd_env_file();
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    cell::RefCell,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    future::Future,
+};
+
+use crate::{DynEnvironment, EnvError, Environment, ReadEnvironment, RealEnvironment};
+
+tokio::task_local! {
+    static SCOPED: RefCell<Box<dyn DynEnvironment + Send>>;
+}
+
+/// Run `future` with `env` as its environment: within it, including across
+/// `.await` points, [`current_env`](current_env) resolves to `env`. Requires
+/// the `tokio` feature.
+///
+/// Unlike [`with_ambient`](crate::with_ambient), this follows the future
+/// between worker threads and does not leak into other tasks on the same
+/// thread. Scopes nest: the innermost one is used.
+///
+/// Tasks spawned from within the future do not inherit the scope. To share
+/// one environment with a child task, scope both with clones of a
+/// [`SharedFakeEnvironment`](crate::SharedFakeEnvironment):
+///
+/// ```rust
+/// # use env_wrapper::{current_env, scope_env, Environment, ReadEnvironment, SharedFakeEnvironment};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let fake_env = SharedFakeEnvironment::new();
+///
+/// let child_env = fake_env.clone();
+/// scope_env(fake_env.clone(), async move {
+///     tokio::spawn(scope_env(child_env, async {
+///         current_env().set_var("CHILD_STATE", "done");
+///     }))
+///     .await
+///     .unwrap();
+/// })
+/// .await;
+///
+/// assert_eq!(fake_env.var("CHILD_STATE").unwrap(), "done");
+/// # }
+/// ```
+pub fn scope_env<F: Future>(
+    env: impl Environment + Send + 'static,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    SCOPED.scope(RefCell::new(Box::new(env)), future)
+}
+
+/// A handle to the current task's environment, as set by
+/// [`scope_env`](scope_env), or [`RealEnvironment`](RealEnvironment) outside
+/// any scope. Requires the `tokio` feature.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{current_env, scope_env, Environment, FakeEnvironment, ReadEnvironment};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// async fn config_location() -> String {
+///     current_env()
+///         .var("CONFIG_LOCATION")
+///         .unwrap_or_else(|_| "/etc/my_app/service.conf".into())
+/// }
+///
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("CONFIG_LOCATION", "/tmp/test.conf");
+///
+/// let location = scope_env(fake_env, config_location()).await;
+///
+/// assert_eq!(location, "/tmp/test.conf");
+/// # }
+/// ```
+pub fn current_env() -> TaskEnvironment {
+    TaskEnvironment
+}
+
+/// A handle to the current task's environment, returned by
+/// [`current_env`](current_env).
+///
+/// Every call goes to whichever environment is in scope for the task making
+/// the call.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TaskEnvironment;
+
+impl TaskEnvironment {
+    fn with_current<R>(&self, f: impl FnOnce(&dyn DynEnvironment) -> R) -> R {
+        let mut f = Some(f);
+        let scoped = SCOPED.try_with(|env| (f.take().unwrap())(&**env.borrow()));
+        match scoped {
+            Ok(result) => result,
+            Err(_) => (f.take().unwrap())(&RealEnvironment),
+        }
+    }
+
+    fn with_current_mut<R>(&mut self, f: impl FnOnce(&mut dyn DynEnvironment) -> R) -> R {
+        let mut f = Some(f);
+        let scoped = SCOPED.try_with(|env| (f.take().unwrap())(&mut **env.borrow_mut()));
+        match scoped {
+            Ok(result) => result,
+            Err(_) => (f.take().unwrap())(&mut RealEnvironment),
+        }
+    }
+}
+
+impl ReadEnvironment for TaskEnvironment {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.with_current(|env| env.dyn_var(key.as_ref()))
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.with_current(|env| env.dyn_var_os(key.as_ref()))
+    }
+}
+
+impl Environment for TaskEnvironment {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.with_current_mut(|env| env.dyn_set_var(key.as_ref(), value.as_ref()))
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.with_current_mut(|env| env.dyn_remove_var(key.as_ref()))
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.with_current_mut(|env| env.dyn_try_set_var(key.as_ref(), value.as_ref()))
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.with_current_mut(|env| env.dyn_try_remove_var(key.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::task::yield_now;
+
+    use super::{current_env, scope_env};
+    use crate::{
+        test_helpers::fake_env, test_helpers::random_upper, Environment, FakeEnvironment,
+        ReadEnvironment,
+    };
+
+    /// Read `MODE` repeatedly, yielding in between so the task can move
+    /// between worker threads and interleave with other tasks.
+    async fn read_mode_across_awaits() -> Vec<String> {
+        let mut modes = Vec::new();
+        for _ in 0..50 {
+            modes.push(current_env().var("MODE").unwrap());
+            yield_now().await;
+        }
+        modes
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn given_two_concurrent_scoped_tasks_when_reading_across_awaits_then_each_sees_its_own_environment(
+    ) {
+        // Arrange
+        let first = tokio::spawn(scope_env(
+            fake_env(&[("MODE", "first")]),
+            read_mode_across_awaits(),
+        ));
+        let second = tokio::spawn(scope_env(
+            fake_env(&[("MODE", "second")]),
+            read_mode_across_awaits(),
+        ));
+
+        // Act
+        let first_modes = first.await.unwrap();
+        let second_modes = second.await.unwrap();
+
+        // Assert
+        assert!(first_modes.iter().all(|mode| mode == "first"));
+        assert!(second_modes.iter().all(|mode| mode == "second"));
+    }
+
+    #[tokio::test]
+    async fn given_nested_scopes_when_reading_then_the_inner_scope_shadows_the_outer() {
+        // Arrange
+        let outer = fake_env(&[("MODE", "outer")]);
+        let inner = fake_env(&[("MODE", "inner")]);
+
+        // Act
+        let (during_inner, after_inner) = scope_env(outer, async {
+            let during_inner = scope_env(inner, async { current_env().var("MODE").unwrap() }).await;
+            (during_inner, current_env().var("MODE").unwrap())
+        })
+        .await;
+
+        // Assert
+        assert_eq!(during_inner, "inner");
+        assert_eq!(after_inner, "outer");
+    }
+
+    #[tokio::test]
+    async fn given_a_scope_when_writing_then_later_reads_in_the_scope_see_the_write() {
+        // Arrange
+        let fake_env = FakeEnvironment::new();
+
+        // Act
+        let state = scope_env(fake_env, async {
+            current_env().set_var("STATE", "done");
+            yield_now().await;
+            current_env().var("STATE").unwrap()
+        })
+        .await;
+
+        // Assert
+        assert_eq!(state, "done");
+    }
+
+    #[tokio::test]
+    async fn given_no_scope_when_reading_then_it_is_the_real_environment() {
+        // Arrange
+        let key = random_upper();
+        let value = random_upper();
+        std::env::set_var(&key, &value);
+
+        // Act
+        let result = current_env().var(&key);
+
+        // Assert
+        assert_eq!(result.unwrap(), value);
+    }
+}
@@ -1,7 +1,23 @@
 #![cfg(test)]
 
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
 use rand::{distributions::Uniform, Rng};
 
+use crate::{Environment, FakeEnvironment};
+
+/// A fake environment with `vars` set, in order.
+pub fn fake_env(vars: &[(&str, &str)]) -> FakeEnvironment {
+    let mut env = FakeEnvironment::new();
+    for (key, value) in vars {
+        env.set_var(key, value);
+    }
+    env
+}
+
 /// Random 12-character uppercase text.
 pub fn random_upper() -> String {
     let mut rng = rand::thread_rng();
@@ -9,6 +25,61 @@ pub fn random_upper() -> String {
     (0..11).map(|_| rng.sample(upper) as char).collect()
 }
 
+/// A uniquely named path in the temporary directory, deleted when dropped.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// A path with no file at it yet.
+    pub fn new() -> Self {
+        TempFile {
+            path: std::env::temp_dir().join(format!("env_wrapper_{}", random_upper())),
+        }
+    }
+
+    /// A file holding `contents`.
+    pub fn with_contents(contents: &str) -> Self {
+        let file = Self::new();
+        fs::write(&file.path, contents).unwrap();
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A uniquely named, empty directory in the temporary directory, deleted with
+/// its contents when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("env_wrapper_{}", random_upper()));
+        fs::create_dir(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::random_upper;
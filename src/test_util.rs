@@ -0,0 +1,163 @@
+use std::{
+    collections::hash_map::RandomState,
+    ffi::{OsStr, OsString},
+    hash::{BuildHasher, Hasher},
+    ops::{Deref, DerefMut},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::Environment;
+
+/// A variable name starting with `prefix` that no other call returns, in
+/// this process or another, for tests that must use the real environment
+/// without colliding with each other. Requires the `test-util` feature.
+///
+/// The name is `prefix` followed by the process ID, a counter, and random
+/// digits, all in uppercase hexadecimal, separated by `_`.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::unique_key;
+/// let key = unique_key("MY_APP_TEST");
+///
+/// assert!(key.starts_with("MY_APP_TEST_"));
+/// assert_ne!(key, unique_key("MY_APP_TEST"));
+/// ```
+pub fn unique_key(prefix: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let count = NEXT.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(count);
+    format!(
+        "{prefix}_{:X}_{count:X}_{:016X}",
+        process::id(),
+        hasher.finish()
+    )
+}
+
+/// A variable set in an environment for as long as this guard lives, and
+/// removed when it is dropped, including while a failing test unwinds.
+/// Requires the `test-util` feature.
+///
+/// The guard borrows the environment, and dereferences to it, so the
+/// environment can still be used through it.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{unique_key, ReadEnvironment, RealEnvironment, ScopedTestVar};
+/// let key = unique_key("MY_APP_TEST");
+/// {
+///     let mut env = RealEnvironment;
+///     let guard = ScopedTestVar::new(&mut env, &key, "on");
+///
+///     assert_eq!(guard.var(&key).unwrap(), "on");
+/// }
+/// assert!(std::env::var_os(&key).is_none());
+/// ```
+#[derive(Debug)]
+pub struct ScopedTestVar<'a, E: Environment> {
+    env: &'a mut E,
+    key: OsString,
+}
+
+impl<'a, E: Environment> ScopedTestVar<'a, E> {
+    /// Set `key` to `value` in `env` until the guard is dropped.
+    pub fn new(env: &'a mut E, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        let key = key.as_ref().to_owned();
+        env.set_var(&key, value);
+        ScopedTestVar { env, key }
+    }
+
+    /// The key of the variable the guard removes.
+    pub fn key(&self) -> &OsStr {
+        &self.key
+    }
+}
+
+impl<E: Environment> Deref for ScopedTestVar<'_, E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        self.env
+    }
+}
+
+impl<E: Environment> DerefMut for ScopedTestVar<'_, E> {
+    fn deref_mut(&mut self) -> &mut E {
+        self.env
+    }
+}
+
+impl<E: Environment> Drop for ScopedTestVar<'_, E> {
+    fn drop(&mut self) {
+        self.env.remove_var(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, panic, thread};
+
+    use super::{unique_key, ScopedTestVar};
+    use crate::{Environment, FakeEnvironment, ReadEnvironment, RealEnvironment};
+
+    #[test]
+    fn given_many_generations_across_threads_when_generating_keys_then_all_are_unique() {
+        // Act
+        let keys: Vec<String> = (0..4)
+            .map(|_| thread::spawn(|| (0..1000).map(|_| unique_key("TEST")).collect::<Vec<_>>()))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+
+        // Assert
+        let unique: HashSet<_> = keys.iter().collect();
+        assert_eq!(unique.len(), keys.len());
+        assert!(keys.iter().all(|key| key.starts_with("TEST_")));
+        assert!(keys.iter().all(|key| key
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')));
+    }
+
+    #[test]
+    fn given_a_scoped_variable_when_the_guard_is_dropped_then_it_is_removed() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("KEPT", "yes");
+
+        // Act
+        let during = {
+            let mut guard = ScopedTestVar::new(&mut env, "SCOPED", "on");
+            assert_eq!(guard.key(), "SCOPED");
+            guard.set_var("ALSO_SET", "through the guard");
+            guard.var("SCOPED")
+        };
+
+        // Assert
+        assert_eq!(during.unwrap(), "on");
+        assert!(!env.contains("SCOPED"));
+        assert_eq!(env.var("KEPT").unwrap(), "yes");
+        assert_eq!(env.var("ALSO_SET").unwrap(), "through the guard");
+    }
+
+    #[test]
+    fn given_a_scoped_real_variable_when_the_test_panics_then_it_is_still_removed() {
+        // Arrange
+        let key = unique_key("ENV_WRAPPER_TEST");
+        let panicking_key = key.clone();
+
+        // Act
+        let result = panic::catch_unwind(move || {
+            let mut env = RealEnvironment;
+            let guard = ScopedTestVar::new(&mut env, &panicking_key, "on");
+            assert_eq!(guard.var(&panicking_key).unwrap(), "on");
+            panic!("simulated test failure");
+        });
+
+        // Assert
+        assert!(result.is_err());
+        assert!(!RealEnvironment.contains(&key));
+    }
+}
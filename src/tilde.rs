@@ -0,0 +1,140 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
+
+use crate::{prefixed::strip_prefix, ReadEnvironment};
+
+/// The separators that may follow a leading `~`.
+#[cfg(windows)]
+const SEPARATORS: [&str; 2] = ["/", "\\"];
+#[cfg(not(windows))]
+const SEPARATORS: [&str; 1] = ["/"];
+
+/// Expand a leading `~` in `value` to the home directory of `env`, so
+/// `~/data` becomes `/home/me/data` when `HOME` is `/home/me`.
+///
+/// Only a `~` that is the whole value, or is followed by a separator, is
+/// expanded. `~user` forms and a `~` anywhere else are left as they are, and
+/// so is the whole value if the home directory is unknown. The home
+/// directory is `HOME`, or on Windows `USERPROFILE` if `HOME` is not set;
+/// empty values count as unset. The rest of the value is kept byte for
+/// byte, so it need not be valid Unicode.
+///
+/// # Example
+/// ```rust
+/// # use std::ffi::OsStr;
+/// # use env_wrapper::{expand_tilde, Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("HOME", "/home/me");
+///
+/// assert_eq!(expand_tilde(&fake_env, OsStr::new("~/data")), "/home/me/data");
+/// assert_eq!(expand_tilde(&fake_env, OsStr::new("~root/data")), "~root/data");
+/// assert_eq!(expand_tilde(&fake_env, OsStr::new("a/~/b")), "a/~/b");
+/// ```
+pub fn expand_tilde<E: ReadEnvironment + ?Sized>(env: &E, value: &OsStr) -> OsString {
+    let Some(rest) = strip_prefix(value, OsStr::new("~")) else {
+        return value.to_owned();
+    };
+    let path = if rest.is_empty() {
+        OsString::new()
+    } else {
+        match SEPARATORS
+            .iter()
+            .find_map(|separator| strip_prefix(&rest, OsStr::new(separator)))
+        {
+            Some(path) => path,
+            None => return value.to_owned(),
+        }
+    };
+    match home_dir(env) {
+        Some(home) if rest.is_empty() => home,
+        Some(home) => PathBuf::from(home).join(path).into_os_string(),
+        None => value.to_owned(),
+    }
+}
+
+/// The home directory of `env`, if it is set and not empty.
+fn home_dir<E: ReadEnvironment + ?Sized>(env: &E) -> Option<OsString> {
+    let home = env.var_os("HOME").filter(|home| !home.is_empty());
+    #[cfg(windows)]
+    let home = home.or_else(|| env.var_os("USERPROFILE").filter(|home| !home.is_empty()));
+    home
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+    use super::expand_tilde;
+    use crate::{test_helpers::fake_env, ExpandExt, FakeEnvironment};
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_a_leading_tilde_when_expanding_then_it_becomes_the_home_directory() {
+        // Arrange
+        let env = fake_env(&[("HOME", "/home/me")]);
+
+        // Act/Assert
+        assert_eq!(expand_tilde(&env, OsStr::new("~")), "/home/me");
+        assert_eq!(expand_tilde(&env, OsStr::new("~/")), "/home/me/");
+        assert_eq!(
+            expand_tilde(&env, OsStr::new("~/data/db")),
+            "/home/me/data/db"
+        );
+    }
+
+    #[test]
+    fn given_a_tilde_that_is_not_a_leading_home_reference_when_expanding_then_it_is_kept() {
+        // Arrange
+        let env = fake_env(&[("HOME", "/home/me")]);
+
+        // Act/Assert
+        assert_eq!(expand_tilde(&env, OsStr::new("data/~/db")), "data/~/db");
+        assert_eq!(expand_tilde(&env, OsStr::new("/~")), "/~");
+        assert_eq!(expand_tilde(&env, OsStr::new("~user/data")), "~user/data");
+        assert_eq!(expand_tilde(&env, OsStr::new("~~")), "~~");
+    }
+
+    #[test]
+    fn given_an_unset_or_empty_home_when_expanding_then_the_value_is_kept() {
+        // Arrange
+        let unset = FakeEnvironment::new();
+        let empty = fake_env(&[("HOME", "")]);
+
+        // Act/Assert
+        assert_eq!(expand_tilde(&unset, OsStr::new("~/data")), "~/data");
+        assert_eq!(expand_tilde(&empty, OsStr::new("~/data")), "~/data");
+    }
+
+    #[test]
+    fn given_a_non_unicode_remainder_when_expanding_then_it_is_kept_byte_for_byte() {
+        // Arrange
+        let env = fake_env(&[("HOME", "/home/me")]);
+        let mut value = b"~/".to_vec();
+        value.extend_from_slice(&INVALID_UTF8);
+
+        // Act
+        let expanded = expand_tilde(&env, OsStr::from_bytes(&value));
+
+        // Assert
+        let mut expected = b"/home/me/".to_vec();
+        expected.extend_from_slice(&INVALID_UTF8);
+        assert_eq!(expanded.as_bytes(), expected);
+    }
+
+    #[test]
+    fn given_a_path_variable_when_reading_it_expanded_then_its_tilde_is_expanded() {
+        // Arrange
+        let env = fake_env(&[("HOME", "/home/me"), ("MYAPP_DATA_DIR", "~/data")]);
+
+        // Act
+        let data_dir = env.var_path_expanded("MYAPP_DATA_DIR");
+        let unset = env.var_path_expanded("MYAPP_CACHE_DIR");
+
+        // Assert
+        assert_eq!(data_dir, Some(PathBuf::from("/home/me/data")));
+        assert_eq!(unset, None);
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use tracing::Level;
+
+use crate::{
+    EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment, RedactionPolicy,
+    SourcedEnvironment,
+};
+
+/// `tracing::event!` needs a constant level, so dispatch on the configured
+/// one.
+macro_rules! event_at {
+    ($level:expr, $($fields:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::event!(Level::ERROR, $($fields)+),
+            Level::WARN => tracing::event!(Level::WARN, $($fields)+),
+            Level::INFO => tracing::event!(Level::INFO, $($fields)+),
+            Level::DEBUG => tracing::event!(Level::DEBUG, $($fields)+),
+            Level::TRACE => tracing::event!(Level::TRACE, $($fields)+),
+        }
+    };
+}
+
+/// A wrapper that emits a [`tracing`](https://docs.rs/tracing) event for
+/// every read, write, and removal, for observing which variables a service
+/// uses and which were missing. Requires the `tracing` feature.
+///
+/// Each event has an `operation` field (`get`, `set`, or `remove`) and a
+/// `key` field. Reads also have a `hit` field saying whether the variable
+/// was set. Values are only included, in a `value` field, if enabled with
+/// [`record_values`](TracedEnvironment::record_values). Keys and values that
+/// are not valid Unicode are recorded lossily. Listing variables is not
+/// traced.
+///
+/// Events are emitted at `DEBUG` level unless configured otherwise.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{FakeEnvironment, ReadEnvironment, TracedEnvironment};
+/// let env = TracedEnvironment::new(FakeEnvironment::new()).level(tracing::Level::INFO);
+///
+/// // Emits an INFO event with operation = "get", key = "HOME", hit = false.
+/// let _ = env.var("HOME");
+/// ```
+#[derive(Clone, Debug)]
+pub struct TracedEnvironment<E> {
+    inner: E,
+    level: Level,
+    record_values: bool,
+    redaction_policy: RedactionPolicy,
+}
+
+impl<E: ReadEnvironment> TracedEnvironment<E> {
+    pub fn new(inner: E) -> Self {
+        TracedEnvironment {
+            inner,
+            level: Level::DEBUG,
+            record_values: false,
+            redaction_policy: RedactionPolicy::new(),
+        }
+    }
+
+    /// Emit events at `level` instead of `DEBUG`.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Whether to include values in events. Off by default, since values
+    /// often hold secrets.
+    pub fn record_values(mut self, record_values: bool) -> Self {
+        self.record_values = record_values;
+        self
+    }
+
+    /// When values are included, mask those `policy` covers.
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = policy;
+        self
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn trace_get(&self, key: &OsStr, value: Option<&OsStr>) {
+        let recorded_value = value
+            .filter(|_| self.record_values)
+            .map(|value| self.redaction_policy.redact(key, value).to_string_lossy());
+        event_at!(
+            self.level,
+            operation = "get",
+            key = %key.to_string_lossy(),
+            hit = value.is_some(),
+            value = recorded_value.as_deref(),
+            "read environment variable"
+        );
+    }
+
+    fn trace_set(&self, key: &OsStr, value: &OsStr) {
+        let recorded_value = Some(value)
+            .filter(|_| self.record_values)
+            .map(|value| self.redaction_policy.redact(key, value).to_string_lossy());
+        event_at!(
+            self.level,
+            operation = "set",
+            key = %key.to_string_lossy(),
+            value = recorded_value.as_deref(),
+            "set environment variable"
+        );
+    }
+
+    fn trace_remove(&self, key: &OsStr) {
+        event_at!(
+            self.level,
+            operation = "remove",
+            key = %key.to_string_lossy(),
+            "removed environment variable"
+        );
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for TracedEnvironment<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        let result = self.inner.var(&key);
+        match &result {
+            Ok(value) => self.trace_get(key.as_ref(), Some(value.as_ref())),
+            Err(VarError::NotUnicode(value)) => self.trace_get(key.as_ref(), Some(value)),
+            Err(VarError::NotPresent) => self.trace_get(key.as_ref(), None),
+        }
+        result
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let value = self.inner.var_os(&key);
+        self.trace_get(key.as_ref(), value.as_deref());
+        value
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for TracedEnvironment<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        let result = self.inner.var_with_source(&key);
+        match &result {
+            Ok((value, _)) => self.trace_get(key.as_ref(), Some(value.as_ref())),
+            Err(VarError::NotUnicode(value)) => self.trace_get(key.as_ref(), Some(value)),
+            Err(VarError::NotPresent) => self.trace_get(key.as_ref(), None),
+        }
+        result
+    }
+}
+
+impl<E: Environment> Environment for TracedEnvironment<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.trace_set(key.as_ref(), value.as_ref());
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.trace_remove(key.as_ref());
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.trace_set(key.as_ref(), value.as_ref());
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.trace_remove(key.as_ref());
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for TracedEnvironment<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.inner.vars_os()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fmt,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span, subscriber, Event, Level, Metadata, Subscriber,
+    };
+
+    use super::TracedEnvironment;
+    use crate::{Environment, FakeEnvironment, ReadEnvironment, RedactionPolicy};
+
+    #[derive(Debug, PartialEq)]
+    struct CapturedEvent {
+        level: Level,
+        fields: BTreeMap<String, String>,
+    }
+
+    /// Captures the fields of every event, formatted with `Debug`.
+    #[derive(Clone, Default)]
+    struct CapturingSubscriber {
+        events: Arc<Mutex<Vec<CapturedEvent>>>,
+    }
+
+    impl CapturingSubscriber {
+        fn events(&self) -> Vec<CapturedEvent> {
+            std::mem::take(&mut self.events.lock().unwrap())
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{value:?}"));
+        }
+    }
+
+    impl Subscriber for CapturingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = BTreeMap::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.events.lock().unwrap().push(CapturedEvent {
+                level: *event.metadata().level(),
+                fields,
+            });
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    fn event(level: Level, fields: &[(&str, &str)]) -> CapturedEvent {
+        CapturedEvent {
+            level,
+            fields: fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn given_set_and_unset_keys_when_reading_then_hit_and_miss_events_are_emitted_without_values() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("HOME", "/home/me");
+        let env = TracedEnvironment::new(inner);
+        let subscriber = CapturingSubscriber::default();
+
+        // Act
+        subscriber::with_default(subscriber.clone(), || {
+            let _ = env.var("HOME");
+            let _ = env.var_os("MISSING");
+        });
+
+        // Assert
+        assert_eq!(
+            subscriber.events(),
+            vec![
+                event(
+                    Level::DEBUG,
+                    &[
+                        ("message", "read environment variable"),
+                        ("operation", "\"get\""),
+                        ("key", "HOME"),
+                        ("hit", "true"),
+                    ]
+                ),
+                event(
+                    Level::DEBUG,
+                    &[
+                        ("message", "read environment variable"),
+                        ("operation", "\"get\""),
+                        ("key", "MISSING"),
+                        ("hit", "false"),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_values_recorded_at_a_custom_level_when_writing_then_events_include_values() {
+        // Arrange
+        let mut env = TracedEnvironment::new(FakeEnvironment::new())
+            .level(Level::INFO)
+            .record_values(true);
+        let subscriber = CapturingSubscriber::default();
+
+        // Act
+        subscriber::with_default(subscriber.clone(), || {
+            env.set_var("MODE", "fast");
+            let _ = env.var("MODE");
+            env.remove_var("MODE");
+        });
+
+        // Assert
+        assert_eq!(
+            subscriber.events(),
+            vec![
+                event(
+                    Level::INFO,
+                    &[
+                        ("message", "set environment variable"),
+                        ("operation", "\"set\""),
+                        ("key", "MODE"),
+                        ("value", "\"fast\""),
+                    ]
+                ),
+                event(
+                    Level::INFO,
+                    &[
+                        ("message", "read environment variable"),
+                        ("operation", "\"get\""),
+                        ("key", "MODE"),
+                        ("hit", "true"),
+                        ("value", "\"fast\""),
+                    ]
+                ),
+                event(
+                    Level::INFO,
+                    &[
+                        ("message", "removed environment variable"),
+                        ("operation", "\"remove\""),
+                        ("key", "MODE"),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn given_a_redaction_policy_when_recording_values_then_secret_values_are_masked() {
+        // Arrange
+        let mut env = TracedEnvironment::new(FakeEnvironment::new())
+            .record_values(true)
+            .redaction_policy(RedactionPolicy::new().secret("*_TOKEN"));
+        let subscriber = CapturingSubscriber::default();
+
+        // Act
+        subscriber::with_default(subscriber.clone(), || {
+            env.set_var("API_TOKEN", "tok_abc123");
+            let _ = env.var("API_TOKEN");
+            env.set_var("MODE", "fast");
+        });
+
+        // Assert
+        let values: Vec<_> = subscriber
+            .events()
+            .into_iter()
+            .map(|event| event.fields["value"].clone())
+            .collect();
+        assert_eq!(values, ["\"***\"", "\"***\"", "\"fast\""]);
+    }
+
+    #[test]
+    fn given_values_not_recorded_when_writing_then_the_set_event_has_no_value() {
+        // Arrange
+        let mut env = TracedEnvironment::new(FakeEnvironment::new());
+        let subscriber = CapturingSubscriber::default();
+
+        // Act
+        subscriber::with_default(subscriber.clone(), || {
+            env.set_var("TOKEN", "secret");
+        });
+
+        // Assert
+        let events = subscriber.events();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].fields.contains_key("value"));
+        assert_eq!(env.into_inner().var("TOKEN").unwrap(), "secret");
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{
+    is_valid_key, EnumerableEnvironment, EnvError, Environment, FakeEnvironment, ReadEnvironment,
+};
+
+impl FakeEnvironment {
+    /// Start staging changes, to apply them all at once with
+    /// [`commit`](Transaction::commit) or not at all. See
+    /// [`Transaction`](Transaction).
+    pub fn begin_transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            env: self,
+            staged: BTreeMap::new(),
+        }
+    }
+}
+
+/// Changes staged for a [`FakeEnvironment`](FakeEnvironment), created by
+/// [`begin_transaction`](FakeEnvironment::begin_transaction), so an arrange
+/// phase that fails halfway leaves the fixture as it was.
+///
+/// Reads through the transaction see the staged changes, over the
+/// environment's variables. [`commit`](Transaction::commit) applies them,
+/// in key order. Dropping the transaction without committing, e.g. while a
+/// failing test unwinds, or calling [`rollback`](Transaction::rollback),
+/// discards them.
+///
+/// Changing a [protected](FakeEnvironment::protect) key panics when it is
+/// staged, as it would on the environment, so committing never stops
+/// halfway. Setting a key that is empty or contains `=` or NUL is not
+/// staged, as the environment would not store it.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("LOG_LEVEL", "info");
+///
+/// let mut transaction = fake_env.begin_transaction();
+/// transaction.set_var("LOG_LEVEL", "debug");
+/// transaction.set_var("MODE", "fast");
+/// assert_eq!(transaction.var("LOG_LEVEL").unwrap(), "debug");
+/// transaction.commit();
+///
+/// assert_eq!(fake_env.var("LOG_LEVEL").unwrap(), "debug");
+/// assert_eq!(fake_env.var("MODE").unwrap(), "fast");
+/// ```
+///
+/// The transaction borrows the environment, so transactions cannot be
+/// nested, nor the environment used directly until the transaction ends:
+/// ```compile_fail
+/// # use env_wrapper::{Environment, FakeEnvironment};
+/// let mut fake_env = FakeEnvironment::new();
+///
+/// let mut outer = fake_env.begin_transaction();
+/// let mut inner = fake_env.begin_transaction();
+/// outer.set_var("MODE", "fast");
+/// ```
+pub struct Transaction<'a> {
+    env: &'a mut FakeEnvironment,
+    // `None` marks a variable removed in the transaction.
+    staged: BTreeMap<OsString, Option<OsString>>,
+}
+
+impl Transaction<'_> {
+    /// Apply the staged changes to the environment, in key order.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn commit(self) {
+        for (key, value) in self.staged {
+            match value {
+                Some(value) => self.env.set_var(key, value),
+                None => self.env.remove_var(key),
+            }
+        }
+    }
+
+    /// Discard the staged changes, leaving the environment as it was. The
+    /// same as dropping the transaction.
+    pub fn rollback(self) {}
+
+    /// The number of variables set or removed in the transaction.
+    pub fn change_count(&self) -> usize {
+        self.staged.len()
+    }
+}
+
+/// Lists the environment and the keys of the staged changes, but not their
+/// values, which the environment's redaction policy may cover.
+impl fmt::Debug for Transaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("env", &self.env)
+            .field("staged_keys", &self.staged.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ReadEnvironment for Transaction<'_> {
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        match self.staged.get(key.as_ref()) {
+            Some(value) => value.clone(),
+            None => self.env.var_os(key),
+        }
+    }
+}
+
+impl Environment for Transaction<'_> {
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        self.env.check_writable(key, Some(value));
+        if !is_valid_key(key) {
+            return;
+        }
+        self.staged.insert(key.into(), Some(value.into()));
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.env.check_writable(key, None);
+        self.staged.insert(key.into(), None);
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        if self.env.is_protected(&key) {
+            return Err(EnvError::ReadOnly(key.as_ref().into()));
+        }
+        self.set_var(key, value);
+        Ok(())
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        if self.env.is_protected(&key) {
+            return Err(EnvError::ReadOnly(key.as_ref().into()));
+        }
+        self.remove_var(key);
+        Ok(())
+    }
+}
+
+impl EnumerableEnvironment for Transaction<'_> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged: HashMap<_, _> = self.env.vars_os().into_iter().collect();
+        for (key, value) in &self.staged {
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+        merged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use crate::{
+        test_helpers::fake_env, EnumerableEnvironment, EnvError, Environment, ReadEnvironment,
+        VersionedEnvironment,
+    };
+
+    #[test]
+    fn given_staged_changes_when_committing_then_they_are_applied() {
+        // Arrange
+        let mut env = fake_env(&[("LOG_LEVEL", "info"), ("STALE", "1")]);
+        let mut transaction = env.begin_transaction();
+        transaction.set_var("LOG_LEVEL", "debug");
+        transaction.set_var("MODE", "fast");
+        transaction.remove_var("STALE");
+
+        // Act
+        transaction.commit();
+
+        // Assert
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "debug");
+        assert_eq!(env.var("MODE").unwrap(), "fast");
+        assert!(env.var_os("STALE").is_none());
+    }
+
+    #[test]
+    fn given_staged_changes_when_rolling_back_then_the_environment_is_untouched() {
+        // Arrange
+        let mut env = fake_env(&[("LOG_LEVEL", "info")]);
+        let mut transaction = env.begin_transaction();
+        transaction.set_var("LOG_LEVEL", "debug");
+        transaction.remove_var("LOG_LEVEL");
+        transaction.set_var("MODE", "fast");
+
+        // Act
+        transaction.rollback();
+
+        // Assert
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+        assert!(env.var_os("MODE").is_none());
+        assert_eq!(env.generation(), 1);
+    }
+
+    #[test]
+    fn given_an_arrange_phase_that_panics_when_unwinding_then_its_changes_are_discarded() {
+        // Arrange
+        let mut env = fake_env(&[("LOG_LEVEL", "info")]);
+
+        // Act
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut transaction = env.begin_transaction();
+            transaction.set_var("LOG_LEVEL", "debug");
+            transaction.set_var("DATABASE_URL", "postgres://localhost/test");
+            panic!("cannot start the test database");
+        }));
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+        assert!(env.var_os("DATABASE_URL").is_none());
+    }
+
+    #[test]
+    fn given_staged_changes_when_reading_then_only_the_transaction_sees_them() {
+        // Arrange
+        let mut env = fake_env(&[("LOG_LEVEL", "info"), ("REGION", "eu-west-1")]);
+        let mut transaction = env.begin_transaction();
+
+        // Act
+        transaction.set_var("MODE", "fast");
+        transaction.remove_var("LOG_LEVEL");
+
+        // Assert
+        assert_eq!(transaction.var("MODE").unwrap(), "fast");
+        assert!(transaction.var_os("LOG_LEVEL").is_none());
+        assert_eq!(transaction.var("REGION").unwrap(), "eu-west-1");
+        let mut vars = transaction.vars_os();
+        vars.sort();
+        assert_eq!(
+            vars,
+            [
+                ("MODE".into(), "fast".into()),
+                ("REGION".into(), "eu-west-1".into())
+            ]
+        );
+        assert_eq!(transaction.change_count(), 2);
+        transaction.rollback();
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+        assert!(env.var_os("MODE").is_none());
+    }
+
+    #[test]
+    fn given_a_malformed_key_when_staging_it_then_it_is_never_present() {
+        // Arrange
+        let mut env = fake_env(&[]);
+        let mut transaction = env.begin_transaction();
+
+        // Act
+        transaction.set_var("A=B", "1");
+        transaction.set_var("", "1");
+
+        // Assert
+        assert!(transaction.var_os("A=B").is_none());
+        assert!(transaction.vars_os().is_empty());
+        assert_eq!(transaction.change_count(), 0);
+        transaction.commit();
+        assert!(env.var_os("A=B").is_none());
+        assert!(env.vars_os().is_empty());
+    }
+
+    #[test]
+    fn given_a_protected_key_when_staging_a_change_then_it_is_rejected_when_staged() {
+        // Arrange
+        let mut env = fake_env(&[("HOME", "/home/test")]);
+        env.protect("HOME");
+        let mut transaction = env.begin_transaction();
+
+        // Act
+        let result = transaction.try_set_var("HOME", "/root");
+
+        // Assert
+        assert_eq!(result, Err(EnvError::ReadOnly("HOME".into())));
+        assert_eq!(transaction.change_count(), 0);
+    }
+}
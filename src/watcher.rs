@@ -0,0 +1,341 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fmt, panic,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::ReadEnvironment;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+type ChangeCallback = Box<dyn FnMut(&OsStr, Option<&OsStr>, Option<&OsStr>) + Send>;
+
+/// What the polling thread owns, and hands back when it stops.
+struct Poller<E> {
+    env: E,
+    /// The last value seen for each watched key, `None` if it was not set.
+    values: BTreeMap<OsString, Option<OsString>>,
+}
+
+/// Polls variables on a background thread, and calls callbacks when their
+/// values change, so a long-running program can react when an operator
+/// changes a setting, e.g. in a
+/// [`SharedFakeEnvironment`](crate::SharedFakeEnvironment) another part of
+/// the program writes to.
+///
+/// Callbacks receive the key, the previous value, and the new value, with
+/// `None` for a variable that is not set. They run on the polling thread, in
+/// the order they were registered. Changes that are undone between two polls
+/// are not seen.
+///
+/// Polling starts with [`watch`](EnvWatcher::watch), and stops with
+/// [`stop`](EnvWatcher::stop) or when the watcher is dropped. Either waits
+/// for a poll in progress to finish.
+///
+/// # Example
+/// ```rust
+/// # use std::{sync::mpsc, time::Duration};
+/// # use env_wrapper::{EnvWatcher, Environment, SharedFakeEnvironment};
+/// let mut fake_env = SharedFakeEnvironment::new();
+/// fake_env.set_var("LOG_LEVEL", "info");
+/// let (changes, changed) = mpsc::channel();
+///
+/// let mut watcher = EnvWatcher::new(fake_env.clone()).interval(Duration::from_millis(10));
+/// watcher.on_change(move |key, old, new| {
+///     let new = new.map(|new| new.to_string_lossy().into_owned());
+///     changes.send((key.to_owned(), new)).unwrap();
+/// });
+/// watcher.watch(["LOG_LEVEL"]);
+///
+/// fake_env.set_var("LOG_LEVEL", "debug");
+///
+/// let (key, new) = changed.recv().unwrap();
+/// assert_eq!(key, "LOG_LEVEL");
+/// assert_eq!(new.as_deref(), Some("debug"));
+/// ```
+pub struct EnvWatcher<E> {
+    interval: Duration,
+    callbacks: Arc<Mutex<Vec<ChangeCallback>>>,
+    // `None` while the polling thread owns it.
+    poller: Option<Poller<E>>,
+    running: Option<(Sender<()>, JoinHandle<Poller<E>>)>,
+}
+
+impl<E: ReadEnvironment + Send + 'static> EnvWatcher<E> {
+    /// Watch `env`, polling every second once keys are watched.
+    pub fn new(env: E) -> Self {
+        EnvWatcher {
+            interval: DEFAULT_INTERVAL,
+            callbacks: Arc::new(Mutex::new(Vec::new())),
+            poller: Some(Poller {
+                env,
+                values: BTreeMap::new(),
+            }),
+            running: None,
+        }
+    }
+
+    /// Poll every `interval`. This applies the next time polling starts.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Call `callback` with the key, previous value, and new value whenever
+    /// a watched variable changes. Callbacks can be registered at any time.
+    pub fn on_change(
+        &mut self,
+        callback: impl FnMut(&OsStr, Option<&OsStr>, Option<&OsStr>) + Send + 'static,
+    ) {
+        self.callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(callback));
+    }
+
+    /// Start watching `keys`, in addition to any already watched, and start
+    /// polling if it is stopped. Changes are reported relative to the values
+    /// the keys have now.
+    ///
+    /// # Panics
+    /// If a callback panicked on the polling thread, the panic is resumed
+    /// here.
+    pub fn watch(&mut self, keys: impl IntoIterator<Item = impl AsRef<OsStr>>) {
+        self.stop();
+        let mut poller = self.poller.take().expect("the watcher is stopped");
+        for key in keys {
+            let key = key.as_ref();
+            if !poller.values.contains_key(key) {
+                let value = poller.env.var_os(key);
+                poller.values.insert(key.into(), value);
+            }
+        }
+
+        let (stop, stopped) = mpsc::channel();
+        let callbacks = Arc::clone(&self.callbacks);
+        let interval = self.interval;
+        let thread = thread::spawn(move || {
+            // Both a stop message and a dropped sender stop polling.
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                poll(&mut poller, &callbacks);
+            }
+            poller
+        });
+        self.running = Some((stop, thread));
+    }
+
+    /// Stop polling, waiting for a poll in progress to finish. Does nothing
+    /// if polling is already stopped.
+    ///
+    /// # Panics
+    /// If a callback panicked on the polling thread, the panic is resumed
+    /// here.
+    pub fn stop(&mut self) {
+        if let Some(poller) = self.join() {
+            self.poller = Some(poller);
+        }
+    }
+
+    /// Whether the watcher is polling.
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Stop polling and return the watched environment.
+    ///
+    /// # Panics
+    /// If a callback panicked on the polling thread, the panic is resumed
+    /// here.
+    pub fn into_inner(mut self) -> E {
+        self.stop();
+        self.poller.take().expect("the watcher is stopped").env
+    }
+}
+
+impl<E> EnvWatcher<E> {
+    /// Stop the polling thread, if there is one, and take back what it owns.
+    fn join(&mut self) -> Option<Poller<E>> {
+        let (stop, thread) = self.running.take()?;
+        // The thread is gone if a callback panicked, so sending can fail.
+        let _ = stop.send(());
+        match thread.join() {
+            Ok(poller) => Some(poller),
+            Err(payload) if !thread::panicking() => panic::resume_unwind(payload),
+            Err(_) => None,
+        }
+    }
+}
+
+impl<E> Drop for EnvWatcher<E> {
+    fn drop(&mut self) {
+        if let Some((stop, thread)) = self.running.take() {
+            let _ = stop.send(());
+            // A callback's panic was already reported on the polling thread.
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for EnvWatcher<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvWatcher")
+            .field("interval", &self.interval)
+            .field("running", &self.running.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read each watched key of `poller`, and call `callbacks` for those that
+/// changed.
+fn poll<E: ReadEnvironment>(poller: &mut Poller<E>, callbacks: &Mutex<Vec<ChangeCallback>>) {
+    for (key, old) in &mut poller.values {
+        let new = poller.env.var_os(key);
+        if new != *old {
+            let mut callbacks = callbacks.lock().unwrap_or_else(PoisonError::into_inner);
+            for callback in callbacks.iter_mut() {
+                callback(key, old.as_deref(), new.as_deref());
+            }
+            *old = new;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        sync::mpsc::{self, Receiver, RecvTimeoutError},
+        thread,
+        time::Duration,
+    };
+
+    use super::EnvWatcher;
+    use crate::{Environment, ReadEnvironment, SharedFakeEnvironment};
+
+    const INTERVAL: Duration = Duration::from_millis(5);
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    type Change = (OsString, Option<OsString>, Option<OsString>);
+
+    fn recording_watcher(
+        env: &SharedFakeEnvironment,
+    ) -> (EnvWatcher<SharedFakeEnvironment>, Receiver<Change>) {
+        let (changes, changed) = mpsc::channel();
+        let mut watcher = EnvWatcher::new(env.clone()).interval(INTERVAL);
+        watcher.on_change(move |key, old, new| {
+            let _ = changes.send((key.into(), old.map(Into::into), new.map(Into::into)));
+        });
+        (watcher, changed)
+    }
+
+    fn change(key: &str, old: Option<&str>, new: Option<&str>) -> Change {
+        (key.into(), old.map(Into::into), new.map(Into::into))
+    }
+
+    #[test]
+    fn given_watched_keys_when_they_change_then_callbacks_receive_old_and_new_values() {
+        // Arrange
+        let mut env = SharedFakeEnvironment::new();
+        env.set_var("LOG_LEVEL", "info");
+        let (mut watcher, changed) = recording_watcher(&env);
+        watcher.watch(["LOG_LEVEL", "FEATURES"]);
+
+        // Act/Assert
+        env.set_var("LOG_LEVEL", "debug");
+        assert_eq!(
+            changed.recv_timeout(TIMEOUT).unwrap(),
+            change("LOG_LEVEL", Some("info"), Some("debug"))
+        );
+        env.set_var("FEATURES", "beta");
+        assert_eq!(
+            changed.recv_timeout(TIMEOUT).unwrap(),
+            change("FEATURES", None, Some("beta"))
+        );
+        env.remove_var("LOG_LEVEL");
+        assert_eq!(
+            changed.recv_timeout(TIMEOUT).unwrap(),
+            change("LOG_LEVEL", Some("debug"), None)
+        );
+    }
+
+    #[test]
+    fn given_unwatched_or_unchanged_keys_when_polling_then_callbacks_are_not_called() {
+        // Arrange
+        let mut env = SharedFakeEnvironment::new();
+        env.set_var("LOG_LEVEL", "info");
+        let (mut watcher, changed) = recording_watcher(&env);
+        watcher.watch(["LOG_LEVEL"]);
+
+        // Act
+        env.set_var("OTHER", "changed");
+        env.set_var("LOG_LEVEL", "info");
+        thread::sleep(INTERVAL * 10);
+
+        // Assert
+        assert_eq!(changed.try_recv(), Err(mpsc::TryRecvError::Empty));
+        assert!(watcher.is_running());
+    }
+
+    #[test]
+    fn given_a_stopped_watcher_when_keys_change_then_callbacks_stop_firing() {
+        // Arrange
+        let mut env = SharedFakeEnvironment::new();
+        let (mut watcher, changed) = recording_watcher(&env);
+        watcher.watch(["MODE"]);
+        env.set_var("MODE", "a");
+        changed.recv_timeout(TIMEOUT).unwrap();
+
+        // Act
+        watcher.stop();
+        env.set_var("MODE", "b");
+        thread::sleep(INTERVAL * 10);
+
+        // Assert
+        assert!(!watcher.is_running());
+        assert_eq!(changed.try_recv(), Err(mpsc::TryRecvError::Empty));
+        watcher.watch(["OTHER"]);
+        assert_eq!(
+            changed.recv_timeout(TIMEOUT).unwrap(),
+            change("MODE", Some("a"), Some("b"))
+        );
+    }
+
+    #[test]
+    fn when_dropping_a_watcher_then_its_thread_stops_and_releases_the_callbacks() {
+        // Arrange
+        let mut env = SharedFakeEnvironment::new();
+        let (mut watcher, changed) = recording_watcher(&env);
+        watcher.watch(["MODE"]);
+
+        // Act
+        drop(watcher);
+        env.set_var("MODE", "changed");
+
+        // Assert
+        assert_eq!(
+            changed.recv_timeout(TIMEOUT),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn when_unwrapping_a_watcher_then_the_environment_is_returned() {
+        // Arrange
+        let env = SharedFakeEnvironment::new();
+        let (mut watcher, _changed) = recording_watcher(&env);
+        watcher.watch(["MODE"]);
+
+        // Act
+        let mut inner = watcher.into_inner();
+
+        // Assert
+        inner.set_var("MODE", "b");
+        assert_eq!(env.var("MODE").unwrap(), "b");
+    }
+}
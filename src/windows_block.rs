@@ -0,0 +1,235 @@
+use std::{
+    cmp::Ordering,
+    ffi::OsStr,
+    io::{self, ErrorKind},
+};
+
+use crate::EnumerableEnvironment;
+
+/// Export of an environment as a Windows environment block, suitable for the
+/// `lpEnvironment` argument of
+/// [`CreateProcessW`](https://learn.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-createprocessw)
+/// when `CREATE_UNICODE_ENVIRONMENT` is set.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, WindowsEnvironmentBlockExt};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("b", "2");
+/// fake_env.set_var("A", "1");
+///
+/// let block = fake_env.to_windows_environment_block().unwrap();
+///
+/// let expected: Vec<u16> = "A=1\0b=2\0\0".encode_utf16().collect();
+/// assert_eq!(block, expected);
+/// ```
+pub trait WindowsEnvironmentBlockExt: EnumerableEnvironment {
+    /// Encode every variable as UTF-16 `KEY=VALUE` entries, each terminated by
+    /// a NUL, followed by a final NUL. Entries are sorted by key,
+    /// case-insensitively, as Windows requires.
+    ///
+    /// On Windows, keys and values are converted without loss, including
+    /// unpaired surrogates. On other platforms, they must be valid Unicode.
+    ///
+    /// # Errors
+    /// * If a key or value contains the NUL character, it returns an
+    ///   `ErrorKind::InvalidInput` error.
+    /// * If a key or value cannot be represented as UTF-16 on this platform,
+    ///   it returns an `ErrorKind::InvalidData` error.
+    fn to_windows_environment_block(&self) -> io::Result<Vec<u16>> {
+        let vars = self
+            .vars_os()
+            .iter()
+            .map(|(key, value)| Ok((to_wide(key)?, to_wide(value)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        encode_block(vars)
+    }
+}
+
+impl<E: EnumerableEnvironment> WindowsEnvironmentBlockExt for E {}
+
+#[cfg(windows)]
+fn to_wide(s: &OsStr) -> io::Result<Vec<u16>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    Ok(s.encode_wide().collect())
+}
+
+#[cfg(not(windows))]
+fn to_wide(s: &OsStr) -> io::Result<Vec<u16>> {
+    match s.to_str() {
+        Some(valid_utf8) => Ok(valid_utf8.encode_utf16().collect()),
+        None => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "environment variables must be valid Unicode to encode as UTF-16",
+        )),
+    }
+}
+
+/// Build a block from already-encoded UTF-16 keys and values.
+fn encode_block(mut vars: Vec<(Vec<u16>, Vec<u16>)>) -> io::Result<Vec<u16>> {
+    for (key, value) in &vars {
+        if key.contains(&0) || value.contains(&0) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "nul byte found in environment variable",
+            ));
+        }
+    }
+    vars.sort_by(|(a, _), (b, _)| compare_ignore_case(a, b));
+
+    let mut block = Vec::new();
+    for (key, value) in vars {
+        block.extend(key);
+        block.push(u16::from(b'='));
+        block.extend(value);
+        block.push(0);
+    }
+    // An empty block still needs a terminator for its (absent) first entry.
+    if block.is_empty() {
+        block.push(0);
+    }
+    block.push(0);
+    Ok(block)
+}
+
+/// Compare UTF-16 text the way Windows orders environment blocks: unit by
+/// unit, after upper-casing each unit that is not a surrogate.
+fn compare_ignore_case(a: &[u16], b: &[u16]) -> Ordering {
+    a.iter()
+        .map(|&unit| upper_unit(unit))
+        .cmp(b.iter().map(|&unit| upper_unit(unit)))
+}
+
+fn upper_unit(unit: u16) -> u16 {
+    let Some(c) = char::from_u32(u32::from(unit)) else {
+        // Surrogates have no case.
+        return unit;
+    };
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(single), None) => u16::try_from(u32::from(single)).unwrap_or(unit),
+        _ => unit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::{encode_block, WindowsEnvironmentBlockExt};
+    use crate::{Environment, FakeEnvironment};
+
+    const UNPAIRED_SURROGATE: u16 = 0xD800;
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    /// Split a block back into its entries, checking the terminators.
+    fn decode(block: &[u16]) -> Vec<Vec<u16>> {
+        assert_eq!(&block[block.len() - 2..], &[0, 0]);
+        let entries = &block[..block.len() - 2];
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        entries
+            .split(|&unit| unit == 0)
+            .map(<[u16]>::to_vec)
+            .collect()
+    }
+
+    #[test]
+    fn when_encoding_a_block_then_entries_are_sorted_case_insensitively() {
+        // Arrange
+        let vars = vec![
+            (wide("path"), wide("a")),
+            (wide("Zeta"), wide("b")),
+            (wide("ALPHA"), wide("c")),
+            (wide("beta"), wide("d")),
+        ];
+
+        // Act
+        let block = encode_block(vars).unwrap();
+
+        // Assert
+        assert_eq!(
+            decode(&block),
+            vec![
+                wide("ALPHA=c"),
+                wide("beta=d"),
+                wide("path=a"),
+                wide("Zeta=b")
+            ]
+        );
+    }
+
+    #[test]
+    fn when_encoding_a_block_then_each_entry_is_nul_terminated_and_the_block_is_double_nul_terminated(
+    ) {
+        // Act
+        let block = encode_block(vec![(wide("A"), wide("1")), (wide("B"), wide("2"))]).unwrap();
+
+        // Assert
+        assert_eq!(block, wide("A=1\0B=2\0\0"));
+    }
+
+    #[test]
+    fn given_no_variables_when_encoding_a_block_then_it_is_two_nuls() {
+        // Act
+        let block = encode_block(Vec::new()).unwrap();
+
+        // Assert
+        assert_eq!(block, vec![0, 0]);
+        assert!(decode(&block).is_empty());
+    }
+
+    #[test]
+    fn given_unpaired_surrogates_when_encoding_a_block_then_they_are_preserved() {
+        // Arrange
+        let key = vec![u16::from(b'K'), UNPAIRED_SURROGATE];
+        let value = vec![UNPAIRED_SURROGATE, u16::from(b'V')];
+
+        // Act
+        let block = encode_block(vec![(key, value)]).unwrap();
+
+        // Assert
+        assert_eq!(
+            decode(&block),
+            vec![vec![
+                u16::from(b'K'),
+                UNPAIRED_SURROGATE,
+                u16::from(b'='),
+                UNPAIRED_SURROGATE,
+                u16::from(b'V'),
+            ]]
+        );
+    }
+
+    #[test]
+    fn given_a_key_with_an_embedded_nul_when_encoding_a_block_then_it_is_an_invalid_input_error() {
+        // Act
+        let result = encode_block(vec![(wide("BAD\0KEY"), wide("value"))]);
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn given_a_fake_environment_when_exporting_a_windows_block_then_it_decodes_back_to_the_same_variables(
+    ) {
+        // Arrange
+        let mut fake_env = FakeEnvironment::new();
+        fake_env.set_var("windir", "C:\\Windows");
+        fake_env.set_var("Path", "C:\\bin;C:\\tools");
+
+        // Act
+        let block = fake_env.to_windows_environment_block().unwrap();
+
+        // Assert
+        assert_eq!(
+            decode(&block),
+            vec![wide("Path=C:\\bin;C:\\tools"), wide("windir=C:\\Windows")]
+        );
+    }
+}
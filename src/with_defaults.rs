@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    var_from_os, EnumerableEnvironment, EnvError, Environment, Provenance, ReadEnvironment,
+    SourcedEnvironment,
+};
+
+/// A wrapper that falls back to registered default values for variables the
+/// wrapped environment does not have.
+///
+/// Reads, `contains`, and listings reflect the merged view, with the wrapped
+/// environment's values taking precedence. Writes and removals go to the
+/// wrapped environment; the defaults themselves never change, so removing a
+/// variable exposes its default again.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, ReadEnvironment, WithDefaults};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("WORKERS", "8");
+///
+/// let env = WithDefaults::new(fake_env)
+///     .default("LOG_LEVEL", "info")
+///     .default("WORKERS", "4");
+///
+/// assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+/// assert_eq!(env.var("WORKERS").unwrap(), "8");
+/// ```
+#[derive(Clone, Debug)]
+pub struct WithDefaults<E> {
+    inner: E,
+    defaults: HashMap<OsString, OsString>,
+}
+
+impl<E: ReadEnvironment> WithDefaults<E> {
+    pub fn new(inner: E) -> Self {
+        WithDefaults {
+            inner,
+            defaults: HashMap::new(),
+        }
+    }
+
+    /// Register `value` as the default for `key`, replacing any previous
+    /// default.
+    pub fn default(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.defaults
+            .insert(key.as_ref().into(), value.as_ref().into());
+        self
+    }
+
+    /// The registered default for `key`, whether or not it is in use.
+    pub fn default_for(&self, key: impl AsRef<OsStr>) -> Option<&OsStr> {
+        self.defaults.get(key.as_ref()).map(OsString::as_os_str)
+    }
+
+    /// Unwrap the environment, returning the underlying environment.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: ReadEnvironment> ReadEnvironment for WithDefaults<E> {
+    fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        match self.inner.var(&key) {
+            Err(VarError::NotPresent) => var_from_os(self.defaults.get(key.as_ref()).cloned()),
+            result => result,
+        }
+    }
+
+    fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        self.inner
+            .var_os(&key)
+            .or_else(|| self.defaults.get(key.as_ref()).cloned())
+    }
+}
+
+impl<E: SourcedEnvironment> SourcedEnvironment for WithDefaults<E> {
+    fn var_with_source(&self, key: impl AsRef<OsStr>) -> Result<(String, Provenance), VarError> {
+        match self.inner.var_with_source(&key) {
+            Err(VarError::NotPresent) => {
+                let value = var_from_os(self.defaults.get(key.as_ref()).cloned())?;
+                Ok((value.clone(), Provenance::Default(value)))
+            }
+            result => result,
+        }
+    }
+}
+
+impl<E: Environment> Environment for WithDefaults<E> {
+    fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.inner.set_var(key, value)
+    }
+
+    fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        self.inner.remove_var(key)
+    }
+
+    fn try_set_var(
+        &mut self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        self.inner.try_set_var(key, value)
+    }
+
+    fn try_remove_var(&mut self, key: impl AsRef<OsStr>) -> Result<(), EnvError> {
+        self.inner.try_remove_var(key)
+    }
+}
+
+impl<E: EnumerableEnvironment> EnumerableEnvironment for WithDefaults<E> {
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut merged = self.defaults.clone();
+        merged.extend(self.inner.vars_os());
+        merged.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::VarError, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::WithDefaults;
+    use crate::{
+        EnumerableEnvironment, Environment, FakeEnvironment, Provenance, ReadEnvironment,
+        SourcedEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    #[test]
+    fn given_an_unset_key_with_a_default_when_reading_then_the_default_is_returned() {
+        // Arrange
+        let env = WithDefaults::new(FakeEnvironment::new()).default("LOG_LEVEL", "info");
+
+        // Act/Assert
+        assert_eq!(env.var("LOG_LEVEL").unwrap(), "info");
+        assert_eq!(env.var_os("LOG_LEVEL").unwrap(), "info");
+        assert!(env.contains("LOG_LEVEL"));
+        assert_eq!(env.var("OTHER").unwrap_err(), VarError::NotPresent);
+        assert!(!env.contains("OTHER"));
+    }
+
+    #[test]
+    fn given_a_set_key_with_a_default_when_reading_then_the_real_value_shadows_the_default() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("WORKERS", "8");
+        let env = WithDefaults::new(inner).default("WORKERS", "4");
+
+        // Act/Assert
+        assert_eq!(env.var("WORKERS").unwrap(), "8");
+    }
+
+    #[test]
+    fn given_a_fake_with_defaults_when_reading_with_source_then_each_value_reports_its_origin() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("WORKERS", "8");
+        let env = WithDefaults::new(inner)
+            .default("WORKERS", "4")
+            .default("LOG_LEVEL", "info");
+
+        // Act/Assert
+        assert_eq!(
+            env.var_with_source("WORKERS").unwrap(),
+            ("8".into(), Provenance::Fake)
+        );
+        assert_eq!(
+            env.var_with_source("LOG_LEVEL").unwrap(),
+            ("info".into(), Provenance::Default("info".into()))
+        );
+        assert_eq!(
+            env.var_with_source("OTHER").unwrap_err(),
+            VarError::NotPresent
+        );
+    }
+
+    #[test]
+    fn given_a_shadowed_default_when_removing_the_real_value_then_the_default_is_exposed_again() {
+        // Arrange
+        let mut env = WithDefaults::new(FakeEnvironment::new()).default("WORKERS", "4");
+        env.set_var("WORKERS", "8");
+
+        // Act
+        env.remove_var("WORKERS");
+
+        // Assert
+        assert_eq!(env.var("WORKERS").unwrap(), "4");
+        assert!(env.into_inner().var_os("WORKERS").is_none());
+    }
+
+    #[test]
+    fn given_a_non_unicode_default_when_reading_then_it_behaves_like_a_non_unicode_value() {
+        // Arrange
+        let value = OsStr::from_bytes(&INVALID_UTF8);
+        let env = WithDefaults::new(FakeEnvironment::new()).default("BIN", value);
+
+        // Act/Assert
+        assert_eq!(env.var_os("BIN").unwrap(), value);
+        assert_eq!(
+            env.var("BIN").unwrap_err(),
+            VarError::NotUnicode(value.into())
+        );
+    }
+
+    #[test]
+    fn given_defaults_and_real_values_when_listing_variables_then_the_merged_view_is_returned() {
+        // Arrange
+        let mut inner = FakeEnvironment::new();
+        inner.set_var("WORKERS", "8");
+        inner.set_var("HOME", "/home/me");
+        let env = WithDefaults::new(inner)
+            .default("WORKERS", "4")
+            .default("LOG_LEVEL", "info");
+
+        // Act
+        let mut vars = env.vars_os();
+
+        // Assert
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                ("HOME".into(), "/home/me".into()),
+                ("LOG_LEVEL".into(), "info".into()),
+                ("WORKERS".into(), "8".into()),
+            ]
+        );
+    }
+}
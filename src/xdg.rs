@@ -0,0 +1,237 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::ReadEnvironment;
+
+/// The directories of the
+/// [XDG Base Directory Specification](https://specifications.freedesktop.org/basedir-spec/latest/),
+/// resolved from the variables of an environment.
+///
+/// Each `XDG_*` variable is used if it holds an absolute path. Following the
+/// specification, variables that are unset, empty, or hold a relative path
+/// are ignored, and their directory falls back to its default: one under
+/// `HOME` for the `_HOME` directories, which is `None` if `HOME` is not an
+/// absolute path either, and fixed system directories for the `_DIRS`
+/// lists. `XDG_RUNTIME_DIR` has no default.
+///
+/// The `_DIRS` lists are separated as `PATH` is, by `:` on Unix, and relative
+/// entries are dropped from them.
+///
+/// # Example
+/// ```rust
+/// # use std::path::Path;
+/// # use env_wrapper::{Environment, FakeEnvironment, XdgDirs};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("HOME", "/home/me");
+/// fake_env.set_var("XDG_CONFIG_HOME", "relative/config");
+/// fake_env.set_var("XDG_CACHE_HOME", "/var/cache/me");
+///
+/// let dirs = XdgDirs::from_env(&fake_env);
+///
+/// assert_eq!(dirs.config_home(), Some(Path::new("/home/me/.config")));
+/// assert_eq!(dirs.cache_home(), Some(Path::new("/var/cache/me")));
+/// assert_eq!(dirs.runtime_dir(), None);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XdgDirs {
+    config_home: Option<PathBuf>,
+    data_home: Option<PathBuf>,
+    state_home: Option<PathBuf>,
+    cache_home: Option<PathBuf>,
+    runtime_dir: Option<PathBuf>,
+    config_dirs: Vec<PathBuf>,
+    data_dirs: Vec<PathBuf>,
+}
+
+impl XdgDirs {
+    /// Resolve the directories from the variables of `env`.
+    pub fn from_env(env: &impl ReadEnvironment) -> Self {
+        let home = absolute(env.var_os("HOME"));
+        let home_dir = |name: &str, default: &str| {
+            absolute(env.var_os(name)).or_else(|| Some(home.as_ref()?.join(default)))
+        };
+        XdgDirs {
+            config_home: home_dir("XDG_CONFIG_HOME", ".config"),
+            data_home: home_dir("XDG_DATA_HOME", ".local/share"),
+            state_home: home_dir("XDG_STATE_HOME", ".local/state"),
+            cache_home: home_dir("XDG_CACHE_HOME", ".cache"),
+            runtime_dir: absolute(env.var_os("XDG_RUNTIME_DIR")),
+            config_dirs: dir_list(env.var_os("XDG_CONFIG_DIRS"), &["/etc/xdg"]),
+            data_dirs: dir_list(
+                env.var_os("XDG_DATA_DIRS"),
+                &["/usr/local/share", "/usr/share"],
+            ),
+        }
+    }
+
+    /// Where user-specific configuration is written, `XDG_CONFIG_HOME` or
+    /// `$HOME/.config`.
+    pub fn config_home(&self) -> Option<&Path> {
+        self.config_home.as_deref()
+    }
+
+    /// Where user-specific data is written, `XDG_DATA_HOME` or
+    /// `$HOME/.local/share`.
+    pub fn data_home(&self) -> Option<&Path> {
+        self.data_home.as_deref()
+    }
+
+    /// Where user-specific state, such as history, is written,
+    /// `XDG_STATE_HOME` or `$HOME/.local/state`.
+    pub fn state_home(&self) -> Option<&Path> {
+        self.state_home.as_deref()
+    }
+
+    /// Where user-specific cached data is written, `XDG_CACHE_HOME` or
+    /// `$HOME/.cache`.
+    pub fn cache_home(&self) -> Option<&Path> {
+        self.cache_home.as_deref()
+    }
+
+    /// Where user-specific sockets and other runtime files are written,
+    /// `XDG_RUNTIME_DIR`, which has no default.
+    pub fn runtime_dir(&self) -> Option<&Path> {
+        self.runtime_dir.as_deref()
+    }
+
+    /// Where configuration is searched for after
+    /// [`config_home`](XdgDirs::config_home), most important first,
+    /// `XDG_CONFIG_DIRS` or `/etc/xdg`.
+    pub fn config_dirs(&self) -> &[PathBuf] {
+        &self.config_dirs
+    }
+
+    /// Where data is searched for after [`data_home`](XdgDirs::data_home),
+    /// most important first, `XDG_DATA_DIRS` or
+    /// `/usr/local/share:/usr/share`.
+    pub fn data_dirs(&self) -> &[PathBuf] {
+        &self.data_dirs
+    }
+}
+
+/// `value` as a path, if it is an absolute one.
+fn absolute(value: Option<OsString>) -> Option<PathBuf> {
+    Some(PathBuf::from(value?)).filter(|path| path.is_absolute())
+}
+
+/// The absolute paths listed in `value`, or `defaults` if there are none.
+fn dir_list(value: Option<OsString>, defaults: &[&str]) -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = value
+        .map(|value| {
+            env::split_paths(&value)
+                .filter(|path| path.is_absolute())
+                .collect()
+        })
+        .unwrap_or_default();
+    if dirs.is_empty() {
+        defaults.iter().map(PathBuf::from).collect()
+    } else {
+        dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::XdgDirs;
+    use crate::test_helpers::fake_env;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn given_explicit_values_when_resolving_then_they_are_used() {
+        // Arrange
+        let env = fake_env(&[
+            ("HOME", "/home/me"),
+            ("XDG_CONFIG_HOME", "/cfg"),
+            ("XDG_DATA_HOME", "/data"),
+            ("XDG_STATE_HOME", "/state"),
+            ("XDG_CACHE_HOME", "/cache"),
+            ("XDG_RUNTIME_DIR", "/run/user/1000"),
+        ]);
+
+        // Act
+        let dirs = XdgDirs::from_env(&env);
+
+        // Assert
+        assert_eq!(dirs.config_home(), Some(Path::new("/cfg")));
+        assert_eq!(dirs.data_home(), Some(Path::new("/data")));
+        assert_eq!(dirs.state_home(), Some(Path::new("/state")));
+        assert_eq!(dirs.cache_home(), Some(Path::new("/cache")));
+        assert_eq!(dirs.runtime_dir(), Some(Path::new("/run/user/1000")));
+    }
+
+    #[test]
+    fn given_only_home_when_resolving_then_home_directories_fall_back_under_it() {
+        // Arrange
+        let env = fake_env(&[("HOME", "/home/me")]);
+
+        // Act
+        let dirs = XdgDirs::from_env(&env);
+
+        // Assert
+        assert_eq!(dirs.config_home(), Some(Path::new("/home/me/.config")));
+        assert_eq!(dirs.data_home(), Some(Path::new("/home/me/.local/share")));
+        assert_eq!(dirs.state_home(), Some(Path::new("/home/me/.local/state")));
+        assert_eq!(dirs.cache_home(), Some(Path::new("/home/me/.cache")));
+        assert_eq!(dirs.runtime_dir(), None);
+        assert_eq!(dirs.config_dirs(), paths(&["/etc/xdg"]));
+        assert_eq!(dirs.data_dirs(), paths(&["/usr/local/share", "/usr/share"]));
+    }
+
+    #[test]
+    fn given_relative_or_empty_values_when_resolving_then_they_are_ignored() {
+        // Arrange
+        let env = fake_env(&[
+            ("HOME", "/home/me"),
+            ("XDG_CONFIG_HOME", "./config"),
+            ("XDG_DATA_HOME", ""),
+            ("XDG_RUNTIME_DIR", "run"),
+            ("XDG_CONFIG_DIRS", "etc/xdg:relative"),
+        ]);
+
+        // Act
+        let dirs = XdgDirs::from_env(&env);
+
+        // Assert
+        assert_eq!(dirs.config_home(), Some(Path::new("/home/me/.config")));
+        assert_eq!(dirs.data_home(), Some(Path::new("/home/me/.local/share")));
+        assert_eq!(dirs.runtime_dir(), None);
+        assert_eq!(dirs.config_dirs(), paths(&["/etc/xdg"]));
+    }
+
+    #[test]
+    fn given_no_usable_home_when_resolving_then_home_directories_are_unknown() {
+        // Arrange
+        let env = fake_env(&[("HOME", "home/me"), ("XDG_CACHE_HOME", "/cache")]);
+
+        // Act
+        let dirs = XdgDirs::from_env(&env);
+
+        // Assert
+        assert_eq!(dirs.config_home(), None);
+        assert_eq!(dirs.cache_home(), Some(Path::new("/cache")));
+    }
+
+    #[test]
+    fn given_dirs_lists_when_resolving_then_they_are_split_in_order_without_relative_entries() {
+        // Arrange
+        let env = fake_env(&[
+            ("XDG_CONFIG_DIRS", "/etc/myapp/xdg:/etc/xdg"),
+            ("XDG_DATA_DIRS", "/opt/share::share:/usr/share"),
+        ]);
+
+        // Act
+        let dirs = XdgDirs::from_env(&env);
+
+        // Assert
+        assert_eq!(dirs.config_dirs(), paths(&["/etc/myapp/xdg", "/etc/xdg"]));
+        assert_eq!(dirs.data_dirs(), paths(&["/opt/share", "/usr/share"]));
+    }
+}
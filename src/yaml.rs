@@ -0,0 +1,182 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    document::{decode_value, encode_vars, Encoded, BASE64_TAG},
+    DocumentError, EnumerableEnvironment, Environment, FakeEnvironment, NonUnicode,
+};
+
+/// Export of an environment as a YAML map. Requires the `yaml` feature.
+///
+/// # Example
+/// ```rust
+/// # use env_wrapper::{Environment, FakeEnvironment, NonUnicode, YamlExt};
+/// let mut fake_env = FakeEnvironment::new();
+/// fake_env.set_var("PORT", "8080");
+///
+/// let yaml = fake_env.to_yaml_value(NonUnicode::Error).unwrap();
+///
+/// assert_eq!(serde_yaml::to_string(&yaml).unwrap(), "PORT: '8080'\n");
+/// assert_eq!(FakeEnvironment::from_yaml_value(&yaml, NonUnicode::Error).unwrap(), fake_env);
+/// ```
+pub trait YamlExt: EnumerableEnvironment {
+    /// Write every variable as an entry of a YAML map, with its value as a
+    /// string, treating names and values that are not valid Unicode as
+    /// `policy` says. Entries are sorted by name.
+    ///
+    /// # Errors
+    /// With [`NonUnicode::Error`](NonUnicode::Error), or with
+    /// [`NonUnicode::Base64`](NonUnicode::Base64) for a name, it returns
+    /// [`DocumentError::NotUnicode`](DocumentError::NotUnicode) for a name or
+    /// value that is not valid Unicode.
+    fn to_yaml_value(&self, policy: NonUnicode) -> Result<Value, DocumentError> {
+        let mapping = encode_vars(self.vars_os(), policy)?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Encoded::Text(text) => Value::String(text),
+                    Encoded::Base64(encoded) => {
+                        Value::Mapping(Mapping::from_iter([(BASE64_TAG.into(), encoded.into())]))
+                    }
+                };
+                (Value::String(name), value)
+            })
+            .collect();
+        Ok(Value::Mapping(mapping))
+    }
+}
+
+impl<E: EnumerableEnvironment> YamlExt for E {}
+
+impl FakeEnvironment {
+    /// A fake environment with a variable for each entry of a YAML map,
+    /// reading values as [`YamlExt::to_yaml_value`](YamlExt::to_yaml_value)
+    /// writes them with `policy`. Requires the `yaml` feature.
+    ///
+    /// # Errors
+    /// * [`DocumentError::NotAnObject`](DocumentError::NotAnObject) if `yaml`
+    ///   is not a map.
+    /// * [`DocumentError::InvalidKey`](DocumentError::InvalidKey) for a key
+    ///   that is not a string.
+    /// * [`DocumentError::InvalidValue`](DocumentError::InvalidValue) for a
+    ///   value that is not a string, or, with
+    ///   [`NonUnicode::Base64`](NonUnicode::Base64), a base64-encoded map.
+    /// * [`DocumentError::InvalidBase64`](DocumentError::InvalidBase64) or
+    ///   [`DocumentError::NotUnicode`](DocumentError::NotUnicode) for a
+    ///   base64-encoded value that cannot be decoded on this platform.
+    pub fn from_yaml_value(yaml: &Value, policy: NonUnicode) -> Result<Self, DocumentError> {
+        let Value::Mapping(mapping) = yaml else {
+            return Err(DocumentError::NotAnObject);
+        };
+        let mut env = FakeEnvironment::new();
+        for (key, value) in mapping {
+            let Value::String(name) = key else {
+                let key = serde_yaml::to_string(key).unwrap_or_default();
+                return Err(DocumentError::InvalidKey(key.trim_end().into()));
+            };
+            let encoded = match value {
+                Value::String(text) => Encoded::Text(text.clone()),
+                Value::Mapping(tagged) => match (tagged.len(), tagged.get(BASE64_TAG)) {
+                    (1, Some(Value::String(encoded))) => Encoded::Base64(encoded.clone()),
+                    _ => return Err(DocumentError::InvalidValue(name.clone())),
+                },
+                _ => return Err(DocumentError::InvalidValue(name.clone())),
+            };
+            env.set_var(name, decode_value(name, encoded, policy)?);
+        }
+        Ok(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use serde_yaml::Value;
+
+    use super::YamlExt;
+    use crate::{
+        test_helpers::fake_env, DocumentError, Environment, FakeEnvironment, NonUnicode,
+        ReadEnvironment,
+    };
+
+    const INVALID_UTF8: [u8; 4] = [0x66, 0x6f, 0x80, 0x6f];
+
+    fn parse(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn given_an_environment_when_round_tripped_through_yaml_then_it_is_unchanged() {
+        // Arrange
+        let env = fake_env(&[("PORT", "8080"), ("FLAG", "true"), ("NAME", "api")]);
+
+        // Act
+        let yaml = env.to_yaml_value(NonUnicode::Error).unwrap();
+        let text = serde_yaml::to_string(&yaml).unwrap();
+        let imported = FakeEnvironment::from_yaml_value(&parse(&text), NonUnicode::Error).unwrap();
+
+        // Assert
+        assert_eq!(text, "FLAG: 'true'\nNAME: api\nPORT: '8080'\n");
+        assert_eq!(imported, env);
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_when_round_tripped_with_base64_then_the_bytes_are_kept() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let yaml = env.to_yaml_value(NonUnicode::Base64).unwrap();
+        let imported = FakeEnvironment::from_yaml_value(&yaml, NonUnicode::Base64).unwrap();
+
+        // Assert
+        assert_eq!(yaml, parse("BINARY: { base64: Zm+Abw== }"));
+        assert_eq!(
+            imported.var_os("BINARY").unwrap(),
+            OsStr::from_bytes(&INVALID_UTF8)
+        );
+    }
+
+    #[test]
+    fn given_a_non_unicode_value_with_the_error_policy_when_exporting_then_it_is_refused() {
+        // Arrange
+        let mut env = FakeEnvironment::new();
+        env.set_var("BINARY", OsStr::from_bytes(&INVALID_UTF8));
+
+        // Act
+        let result = env.to_yaml_value(NonUnicode::Error);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DocumentError::NotUnicode("BINARY".into())
+        );
+    }
+
+    #[test]
+    fn given_a_non_map_document_when_importing_then_it_is_rejected() {
+        for yaml in ["- PORT\n- '8080'", "PORT=8080", "~"] {
+            // Act
+            let result = FakeEnvironment::from_yaml_value(&parse(yaml), NonUnicode::Error);
+
+            // Assert
+            assert_eq!(result.unwrap_err(), DocumentError::NotAnObject, "{yaml}");
+        }
+    }
+
+    #[test]
+    fn given_a_non_string_key_when_importing_then_it_is_rejected() {
+        // Arrange
+        let yaml = parse("8080: PORT");
+
+        // Act
+        let result = FakeEnvironment::from_yaml_value(&yaml, NonUnicode::Error);
+
+        // Assert
+        assert_eq!(
+            result.unwrap_err(),
+            DocumentError::InvalidKey("8080".into())
+        );
+    }
+}